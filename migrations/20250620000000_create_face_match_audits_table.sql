@@ -0,0 +1,22 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS face_match_audits (
+    id BIGSERIAL PRIMARY KEY,
+    submission_id UUID NOT NULL,
+    submission_type TEXT NOT NULL,
+    similarity_score DOUBLE PRECISION NOT NULL,
+    threshold DOUBLE PRECISION NOT NULL,
+    is_match BOOLEAN NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS face_match_audits_submission_id_idx ON face_match_audits(submission_id);
+CREATE INDEX IF NOT EXISTS face_match_audits_created_at_idx ON face_match_audits(created_at);
+
+-- Per-day aggregates kept after raw audit rows are purged by the retention job
+CREATE TABLE IF NOT EXISTS face_match_audit_daily_stats (
+    day DATE PRIMARY KEY,
+    total_count BIGINT NOT NULL,
+    match_count BIGINT NOT NULL,
+    similarity_score_sum DOUBLE PRECISION NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,3 @@
+-- Add migration script here
+CREATE INDEX IF NOT EXISTS submissions_nfc_identifier_type_created_at_idx
+    ON submissions(nfc_identifier, submission_type, created_at DESC);
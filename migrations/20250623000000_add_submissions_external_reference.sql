@@ -0,0 +1,6 @@
+-- Add migration script here
+ALTER TABLE submissions ADD COLUMN IF NOT EXISTS external_reference TEXT;
+
+CREATE INDEX IF NOT EXISTS submissions_external_reference_idx
+    ON submissions(external_reference)
+    WHERE external_reference IS NOT NULL;
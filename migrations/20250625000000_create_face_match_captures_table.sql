@@ -0,0 +1,14 @@
+-- Add migration script here
+-- Sampled face match inputs and provider responses kept for offline model evaluation
+CREATE TABLE IF NOT EXISTS face_match_captures (
+    id BIGSERIAL PRIMARY KEY,
+    submission_id UUID NOT NULL,
+    submission_type TEXT NOT NULL,
+    reference_object_url TEXT NOT NULL,
+    selfie_object_url TEXT NOT NULL,
+    threshold DOUBLE PRECISION NOT NULL,
+    response TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS face_match_captures_created_at_idx ON face_match_captures(created_at);
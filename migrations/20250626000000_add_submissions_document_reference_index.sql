@@ -0,0 +1,3 @@
+-- Add migration script here
+CREATE INDEX IF NOT EXISTS submissions_document_references_idx
+    ON submissions USING GIN ((jsonb_path_query_array(submission_data::jsonb, '$.*.documentReference')));
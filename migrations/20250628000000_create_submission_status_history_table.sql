@@ -0,0 +1,13 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS submission_status_history (
+    id BIGSERIAL PRIMARY KEY,
+    submission_id UUID NOT NULL,
+    previous_status TEXT NOT NULL,
+    status TEXT NOT NULL,
+    reason TEXT NOT NULL,
+    actor TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS submission_status_history_submission_id_idx
+    ON submission_status_history(submission_id, created_at);
@@ -0,0 +1,2 @@
+-- Add migration script here
+ALTER TABLE submissions ADD COLUMN IF NOT EXISTS face_match_result TEXT;
@@ -0,0 +1,2 @@
+-- Add migration script here
+ALTER TABLE submissions ADD COLUMN IF NOT EXISTS risk_tier TEXT;
@@ -0,0 +1,7 @@
+-- Add migration script here
+ALTER TABLE submissions ADD COLUMN IF NOT EXISTS idempotency_key TEXT;
+ALTER TABLE submissions ADD COLUMN IF NOT EXISTS idempotency_request_hash TEXT;
+
+CREATE UNIQUE INDEX IF NOT EXISTS submissions_user_idempotency_key_idx
+    ON submissions(user_id, idempotency_key)
+    WHERE idempotency_key IS NOT NULL;
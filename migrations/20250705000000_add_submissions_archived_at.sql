@@ -0,0 +1,6 @@
+-- Add migration script here
+ALTER TABLE submissions ADD COLUMN IF NOT EXISTS archived_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_submissions_archivable
+    ON submissions (updated_at)
+    WHERE archived_at IS NULL AND deleted_at IS NULL AND status IN ('APPROVED', 'REJECTED');
@@ -0,0 +1,97 @@
+use std::fmt;
+
+use actix_web::{
+    http::{header, StatusCode},
+    HttpResponse, ResponseError,
+};
+
+use crate::models::user::{ApiError, ApiResponse};
+
+// Error a handler returns with `?`. The variant picks the HTTP status; the errors go out in the
+// usual ApiResponse shape, so which codes map to which status stays the handler's decision.
+#[derive(Debug)]
+pub enum AppError {
+    // 400
+    Validation(Vec<ApiError>),
+    // 401
+    Unauthorized(Vec<ApiError>),
+    // 403
+    Forbidden(Vec<ApiError>),
+    // 404
+    NotFound(Vec<ApiError>),
+    // 409
+    Conflict(Vec<ApiError>),
+    // 413
+    PayloadTooLarge(Vec<ApiError>),
+    // 422
+    Unprocessable(Vec<ApiError>),
+    // 503; a dependency refused the call, with the seconds until retrying is worth it when known
+    Upstream { errors: Vec<ApiError>, retry_after_secs: Option<u64> },
+    // 500
+    Internal(Vec<ApiError>),
+}
+
+// The single error most responses carry
+pub fn errors(code: &str, cause: impl Into<String>) -> Vec<ApiError> {
+    vec![ApiError {
+        entity: "SOCIO_ECHO_BE".to_string(),
+        code: code.to_string(),
+        cause: cause.into(),
+    }]
+}
+
+impl AppError {
+    fn errors(&self) -> &[ApiError] {
+        match self {
+            AppError::Validation(errors)
+            | AppError::Unauthorized(errors)
+            | AppError::Forbidden(errors)
+            | AppError::NotFound(errors)
+            | AppError::Conflict(errors)
+            | AppError::PayloadTooLarge(errors)
+            | AppError::Unprocessable(errors)
+            | AppError::Upstream { errors, .. }
+            | AppError::Internal(errors) => errors,
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let causes = self
+            .errors()
+            .iter()
+            .map(|e| format!("{} {}", e.code, e.cause))
+            .collect::<Vec<String>>()
+            .join("; ");
+        write!(f, "{}", causes)
+    }
+}
+
+impl ResponseError for AppError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::Validation(_) => StatusCode::BAD_REQUEST,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            AppError::Unprocessable(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::Upstream { .. } => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut response = HttpResponse::build(self.status_code());
+        if let AppError::Upstream { retry_after_secs: Some(secs), .. } = self {
+            response.insert_header((header::RETRY_AFTER, secs.to_string()));
+        }
+        response.json(ApiResponse::<()> {
+            success: false,
+            data: None,
+            errors: Some(self.errors().to_vec()),
+        })
+    }
+}
@@ -0,0 +1,101 @@
+use std::time::Duration;
+
+use sqlx::{postgres::PgPoolOptions, PgPool};
+
+// Pool used for read-only queries; points at the replica when one is configured,
+// otherwise it is a handle to the primary pool
+#[derive(Clone)]
+pub struct ReadPool(pub PgPool);
+
+// Connects with up to `attempts` tries, doubling the delay after each failure, so the
+// app tolerates a database that comes up shortly after it does. `min_connections` are opened
+// up front and kept open, so a burst doesn't pay connection setup on top of its queries.
+pub async fn connect_with_retry(
+    url: &str,
+    max_connections: u32,
+    min_connections: u32,
+    attempts: u32,
+    initial_delay: Duration,
+) -> Result<PgPool, sqlx::Error> {
+    let mut delay = initial_delay;
+    let mut attempt = 1;
+
+    loop {
+        let options = PgPoolOptions::new()
+            .max_connections(max_connections)
+            .min_connections(min_connections);
+        match options.connect(url).await {
+            Ok(pool) => return Ok(pool),
+            Err(e) if attempt < attempts => {
+                log::warn!(
+                    "Database connection attempt {}/{} failed: {}; retrying in {:?}",
+                    attempt, attempts, e, delay
+                );
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    };
+
+    use tokio::net::{TcpListener, TcpStream};
+
+    use super::*;
+
+    // A proxy in front of the test database that drops its first `refused` connections, the
+    // way a database that is still starting would. Returns its URL and the connection count.
+    async fn starting_database(pool: &PgPool, refused: u32) -> (String, Arc<AtomicU32>) {
+        let options = pool.connect_options();
+        let upstream = format!("{}:{}", options.get_host(), options.get_port());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+
+        let mut url = reqwest::Url::parse(&std::env::var("DATABASE_URL").unwrap()).unwrap();
+        url.set_port(Some(listener.local_addr().unwrap().port())).unwrap();
+        url.set_path(options.get_database().unwrap());
+
+        let connections = Arc::new(AtomicU32::new(0));
+        let counted = connections.clone();
+        tokio::spawn(async move {
+            while let Ok((mut client, _)) = listener.accept().await {
+                if counted.fetch_add(1, Ordering::SeqCst) < refused {
+                    continue;
+                }
+                let upstream = upstream.clone();
+                tokio::spawn(async move {
+                    let mut server = TcpStream::connect(upstream).await.unwrap();
+                    let _ = tokio::io::copy_bidirectional(&mut client, &mut server).await;
+                });
+            }
+        });
+
+        (url.to_string(), connections)
+    }
+
+    #[sqlx::test]
+    async fn retries_until_the_database_accepts(pool: PgPool) {
+        let (url, connections) = starting_database(&pool, 2).await;
+
+        let connected = connect_with_retry(&url, 1, 0, 3, Duration::from_millis(10)).await.unwrap();
+
+        assert_eq!(connections.load(Ordering::SeqCst), 3);
+        let one: i32 = sqlx::query_scalar("SELECT 1").fetch_one(&connected).await.unwrap();
+        assert_eq!(one, 1);
+    }
+
+    #[sqlx::test]
+    async fn gives_up_after_the_configured_attempts(pool: PgPool) {
+        let (url, connections) = starting_database(&pool, 2).await;
+
+        assert!(connect_with_retry(&url, 1, 0, 2, Duration::from_millis(10)).await.is_err());
+        assert_eq!(connections.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,83 @@
+use std::borrow::Cow;
+use std::sync::OnceLock;
+
+// Codes whose cause can be a raw underlying error (sqlx, reqwest, MinIO) rather than one of ours
+const INTERNAL_CODES: &[&str] = &["1000", "1001", "1002", "1006"];
+
+const GENERIC_CAUSE: &str = "INTERNAL_ERROR";
+
+struct CausePolicy {
+    max_length: usize,
+    production_mode: bool,
+}
+
+static POLICY: OnceLock<CausePolicy> = OnceLock::new();
+
+// Until this is called causes are sent to clients unchanged
+pub fn init(max_length: usize, production_mode: bool) {
+    let _ = POLICY.set(CausePolicy { max_length, production_mode });
+}
+
+// Applied when an ApiError is serialized for a client. Anything dropped or cut is logged in full.
+pub fn sanitize<'a>(code: &str, cause: &'a str) -> Cow<'a, str> {
+    match POLICY.get() {
+        Some(policy) => policy.apply(code, cause),
+        None => Cow::Borrowed(cause),
+    }
+}
+
+impl CausePolicy {
+    fn apply<'a>(&self, code: &str, cause: &'a str) -> Cow<'a, str> {
+        if self.production_mode && INTERNAL_CODES.contains(&code) && !is_identifier(cause) {
+            log::error!("Hiding internal error cause for code {}: {}", code, cause);
+            return Cow::Borrowed(GENERIC_CAUSE);
+        }
+
+        if self.max_length > 0 && cause.chars().count() > self.max_length {
+            log::warn!("Truncating error cause for code {}: {}", code, cause);
+            let truncated: String = cause.chars().take(self.max_length).collect();
+            return Cow::Owned(format!("{}...", truncated));
+        }
+
+        Cow::Borrowed(cause)
+    }
+}
+
+// Causes we write ourselves are identifiers like INVALID_EMAIL_OR_PASSWORD
+fn is_identifier(cause: &str) -> bool {
+    !cause.is_empty() && cause.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commons::logging::tests::{capture_logs_blocking, logged};
+
+    #[test]
+    fn production_mode_hides_internal_causes_and_logs_them_in_full() {
+        let _logs = capture_logs_blocking();
+        let policy = CausePolicy { max_length: 40, production_mode: true };
+        let raw = "error returned from database: relation \"users_pk\" at db-primary.internal:5432";
+
+        assert_eq!(policy.apply("1002", raw), GENERIC_CAUSE);
+        assert!(logged(raw));
+        // Our own identifiers are safe to show
+        assert_eq!(policy.apply("1002", "SUBMISSION_NOT_FOUND"), "SUBMISSION_NOT_FOUND");
+
+        // Client errors aren't hidden, only cut to the maximum length
+        let long = format!("INVALID_REQUEST_BODY: {}", "x".repeat(40));
+        let truncated = policy.apply("1003", &long);
+        assert_eq!(truncated, format!("{}...", &long[..40]));
+        assert!(logged(&long));
+    }
+
+    #[test]
+    fn outside_production_causes_are_left_alone_up_to_the_limit() {
+        let policy = CausePolicy { max_length: 0, production_mode: false };
+        let raw = "error returned from database: relation \"users_pk\"";
+
+        assert_eq!(policy.apply("1002", raw), raw);
+        assert_eq!(policy.apply("1003", &"x".repeat(1_000)), "x".repeat(1_000));
+    }
+}
+
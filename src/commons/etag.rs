@@ -0,0 +1,19 @@
+use actix_web::{http::header, HttpRequest};
+use chrono::{DateTime, Utc};
+
+// Strong validator derived from the row's last update, so it changes whenever the resource does
+pub fn from_updated_at(updated_at: DateTime<Utc>) -> String {
+    format!("\"{:x}\"", updated_at.timestamp_micros())
+}
+
+// Whether the request's If-None-Match already holds `etag`, i.e. a 304 can be returned
+pub fn matches_if_none_match(req: &HttpRequest, etag: &str) -> bool {
+    let Some(value) = req.headers().get(header::IF_NONE_MATCH).and_then(|v| v.to_str().ok()) else {
+        return false;
+    };
+
+    value
+        .split(',')
+        .map(|candidate| candidate.trim().trim_start_matches("W/"))
+        .any(|candidate| candidate == "*" || candidate == etag)
+}
@@ -0,0 +1,46 @@
+use actix_web::{
+    error::{InternalError, JsonPayloadError},
+    http::StatusCode,
+    web::JsonConfig,
+    HttpResponse,
+};
+
+use crate::models::user::{ApiError, ApiResponse};
+
+// JSON extractor config capping bodies at `limit` bytes. Oversized bodies get a structured 413.
+pub fn config(limit: usize) -> JsonConfig {
+    JsonConfig::default()
+        .limit(limit)
+        .error_handler(move |err, _| match err {
+            JsonPayloadError::OverflowKnownLength { .. } | JsonPayloadError::Overflow { .. } => {
+                let response = HttpResponse::PayloadTooLarge().json(ApiResponse::<()> {
+                    success: false,
+                    data: None,
+                    errors: Some(vec![ApiError {
+                        entity: "SOCIO_ECHO_BE".to_string(),
+                        code: "1003".to_string(),
+                        cause: format!("PAYLOAD_TOO_LARGE: max {} bytes", limit),
+                    }]),
+                });
+                InternalError::from_response(err, response).into()
+            }
+            err => err.into(),
+        })
+}
+
+// Response for a body that failed to extract: the 413 from `config` as is, otherwise a 400
+pub fn rejection(e: actix_web::Error) -> HttpResponse {
+    if e.as_response_error().status_code() == StatusCode::PAYLOAD_TOO_LARGE {
+        return e.error_response();
+    }
+
+    HttpResponse::BadRequest().json(ApiResponse::<()> {
+        success: false,
+        data: None,
+        errors: Some(vec![ApiError {
+            entity: "SOCIO_ECHO_BE".to_string(),
+            code: "1003".to_string(),
+            cause: format!("INVALID_REQUEST_BODY: {}", e),
+        }]),
+    })
+}
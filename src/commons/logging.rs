@@ -0,0 +1,240 @@
+use tracing::{level_filters::LevelFilter, Subscriber};
+use tracing_subscriber::{
+    fmt::MakeWriter, layer::SubscriberExt, reload, util::SubscriberInitExt, EnvFilter, Registry,
+};
+
+// Handle to the active log filter, so its directives can be swapped without a restart
+#[derive(Clone)]
+pub struct LogLevelHandle(reload::Handle<EnvFilter, Registry>);
+
+// Initializes tracing with JSON output, filtered by RUST_LOG until changed at runtime
+pub fn init() -> LogLevelHandle {
+    let (subscriber, handle) = subscriber(EnvFilter::from_default_env(), std::io::stdout);
+    subscriber.init();
+
+    install_panic_hook();
+
+    handle
+}
+
+// JSON lines to `writer`, filtered by `filter` until changed through the returned handle
+fn subscriber<W>(filter: EnvFilter, writer: W) -> (impl Subscriber + Send + Sync, LogLevelHandle)
+where
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let (filter, handle) = reload::Layer::new(filter);
+    let subscriber = tracing_subscriber::registry()
+        .with(filter)
+        .with(tracing_subscriber::fmt::layer().json().with_writer(writer));
+
+    (subscriber, LogLevelHandle(handle))
+}
+
+// Replaces the default plain-text panic output with a JSON event like every other log line.
+// The backtrace is only captured when RUST_BACKTRACE asks for it, as with the default hook.
+fn install_panic_hook() {
+    std::panic::set_hook(Box::new(|info| {
+        let message = if let Some(message) = info.payload().downcast_ref::<&str>() {
+            message.to_string()
+        } else if let Some(message) = info.payload().downcast_ref::<String>() {
+            message.clone()
+        } else {
+            "Box<dyn Any>".to_string()
+        };
+        let location = info
+            .location()
+            .map(|location| format!("{}:{}:{}", location.file(), location.line(), location.column()))
+            .unwrap_or_default();
+        let thread = std::thread::current();
+        let backtrace = std::backtrace::Backtrace::capture();
+
+        let thread_name = thread.name().unwrap_or("<unnamed>");
+
+        // Logged under this crate's target so filters like RUST_LOG=socio_echo_be=info keep it.
+        // A filter that still drops it must not make the panic disappear.
+        if tracing::enabled!(tracing::Level::ERROR) {
+            tracing::error!(
+                panic_message = %message,
+                location = %location,
+                thread = thread_name,
+                backtrace = %backtrace,
+                "thread panicked"
+            );
+        } else {
+            eprintln!("thread '{}' panicked at {}: {}\n{}", thread_name, location, message, backtrace);
+        }
+    }));
+}
+
+impl LogLevelHandle {
+    pub fn current(&self) -> String {
+        self.0
+            .with_current(|filter| filter.to_string())
+            .unwrap_or_default()
+    }
+
+    // Replaces the filter with `directives` (RUST_LOG syntax) and returns the applied filter
+    pub fn set(&self, directives: &str) -> Result<String, String> {
+        let filter = EnvFilter::try_new(directives).map_err(|e| e.to_string())?;
+        self.0.reload(filter).map_err(|e| e.to_string())?;
+
+        // Records from the `log` macros are bridged into tracing, but `log` keeps its own
+        // max level from startup, so raise or lower it to match the new filter
+        log::set_max_level(as_log_level(LevelFilter::current()));
+
+        Ok(self.current())
+    }
+}
+
+fn as_log_level(level: LevelFilter) -> log::LevelFilter {
+    if level == LevelFilter::OFF {
+        log::LevelFilter::Off
+    } else if level == LevelFilter::ERROR {
+        log::LevelFilter::Error
+    } else if level == LevelFilter::WARN {
+        log::LevelFilter::Warn
+    } else if level == LevelFilter::INFO {
+        log::LevelFilter::Info
+    } else if level == LevelFilter::DEBUG {
+        log::LevelFilter::Debug
+    } else {
+        log::LevelFilter::Trace
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{
+        io,
+        sync::{Arc, Mutex, Once},
+    };
+
+    use tokio::sync::MutexGuard;
+
+    use super::*;
+
+    // Keeps every message logged through the `log` macros; tests look for their own among them
+    struct Captured(Mutex<Vec<String>>);
+
+    impl log::Log for Captured {
+        fn enabled(&self, _: &log::Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &log::Record) {
+            self.0.lock().unwrap().push(record.args().to_string());
+        }
+
+        fn flush(&self) {}
+    }
+
+    static LOGGED: Captured = Captured(Mutex::new(Vec::new()));
+
+    // Held by tests that rely on or change the global `log` max level, so they don't race
+    static LOG_LEVEL: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+    // Captures `log` messages at info and above until the guard is dropped
+    pub(crate) async fn capture_logs() -> MutexGuard<'static, ()> {
+        let guard = LOG_LEVEL.lock().await;
+        install_capture();
+        guard
+    }
+
+    pub(crate) fn capture_logs_blocking() -> MutexGuard<'static, ()> {
+        let guard = LOG_LEVEL.blocking_lock();
+        install_capture();
+        guard
+    }
+
+    fn install_capture() {
+        static INIT: Once = Once::new();
+        INIT.call_once(|| log::set_logger(&LOGGED).unwrap());
+        log::set_max_level(log::LevelFilter::Info);
+    }
+
+    pub(crate) fn logged(needle: &str) -> bool {
+        logged_messages().iter().any(|message| message.contains(needle))
+    }
+
+    pub(crate) fn logged_messages() -> Vec<String> {
+        LOGGED.0.lock().unwrap().clone()
+    }
+
+    // Collects everything written to it
+    #[derive(Clone, Default)]
+    struct Output(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Output {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Output {
+        fn messages(&self) -> Vec<String> {
+            String::from_utf8(self.0.lock().unwrap().clone())
+                .unwrap()
+                .lines()
+                .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()["fields"]["message"].as_str().unwrap().to_string())
+                .collect()
+        }
+    }
+
+    #[test]
+    fn a_changed_filter_applies_to_later_events() {
+        // Setting the filter also sets the `log` max level
+        let _log_level = capture_logs_blocking();
+        let output = Output::default();
+        let writer = output.clone();
+        let (subscriber, handle) = subscriber(EnvFilter::new("info"), move || writer.clone());
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug!("hidden at info");
+            tracing::info!("shown at info");
+
+            assert_eq!(handle.set("debug").unwrap(), "debug");
+            tracing::debug!("shown at debug");
+
+            assert!(handle.set("not a = filter").is_err());
+            assert_eq!(handle.current(), "debug");
+
+            handle.set("warn").unwrap();
+            tracing::info!("hidden at warn");
+        });
+
+        assert_eq!(output.messages(), ["shown at info", "shown at debug"]);
+    }
+
+    #[test]
+    fn panics_are_logged_as_structured_events() {
+        let output = Output::default();
+        let writer = output.clone();
+
+        let previous = std::panic::take_hook();
+        install_panic_hook();
+        let panicked = std::thread::Builder::new()
+            .name("worker-7".to_string())
+            .spawn(move || {
+                let (subscriber, _) = subscriber(EnvFilter::new("info"), move || writer.clone());
+                tracing::subscriber::with_default(subscriber, || panic!("submission {} vanished", 42));
+            })
+            .unwrap()
+            .join();
+        std::panic::set_hook(previous);
+        assert!(panicked.is_err());
+
+        let lines = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        let event: serde_json::Value = serde_json::from_str(lines.lines().next().unwrap()).unwrap();
+        assert_eq!(event["level"], "ERROR");
+        assert_eq!(event["fields"]["message"], "thread panicked");
+        assert_eq!(event["fields"]["panic_message"], "submission 42 vanished");
+        assert_eq!(event["fields"]["thread"], "worker-7");
+        assert!(event["fields"]["location"].as_str().unwrap().starts_with("src/commons/logging.rs:"), "{event}");
+    }
+}
+
@@ -1,26 +1,149 @@
 use aws_sdk_s3::{
     config::{Credentials, Region},
+    error::{ProvideErrorMetadata, SdkError},
+    operation::head_object::HeadObjectError,
     Client,
     presigning::PresigningConfig,
     primitives::ByteStream,
 };
-use std::time::Duration;
+use std::{fmt::Debug, time::Duration};
 use anyhow::Result;
+use uuid::Uuid;
+
+use crate::{config::Config, submissions::document_type::ImageFormat};
+
+#[derive(Debug, thiserror::Error)]
+pub enum MinioError {
+    #[error("NO_SUCH_BUCKET: bucket '{0}' does not exist")]
+    NoSuchBucket(String),
+    #[error("OBJECT_KEY_NOT_ALLOWED: '{0}' is not a document key")]
+    KeyNotAllowed(String),
+}
+
+pub fn is_no_such_bucket(err: &anyhow::Error) -> bool {
+    matches!(err.downcast_ref::<MinioError>(), Some(MinioError::NoSuchBucket(_)))
+}
+
+// How a browser should treat a file opened through a view URL
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContentDisposition {
+    Inline,
+    // Downloaded and saved under the given file name, extended with the stored file's format
+    Attachment(String),
+}
+
+impl ContentDisposition {
+    pub fn header_value(&self, format: ImageFormat) -> String {
+        match self {
+            ContentDisposition::Inline => "inline".to_string(),
+            ContentDisposition::Attachment(filename) => {
+                // Quotes, backslashes and control characters would break out of the quoted filename
+                let filename: String = filename
+                    .chars()
+                    .filter(|c| !c.is_control() && *c != '"' && *c != '\\')
+                    .collect();
+                format!("attachment; filename=\"{}.{}\"", filename, format.extension())
+            }
+        }
+    }
+}
+
+
+// Lifetime of the view URL returned by the upload helpers
+const UPLOADED_FILE_VIEW_URL_TTL: Duration = Duration::from_secs(3600);
+
+// How to reach the bucket, taken from the MINIO_* settings
+#[derive(Clone)]
+pub struct MinioSettings {
+    pub endpoint: String,
+    pub access_key: String,
+    pub secret_key: String,
+    pub bucket_name: String,
+    pub view_key_suffixes: Vec<String>,
+    pub create_bucket: bool,
+    pub connect_attempts: u32,
+    pub connect_retry_delay: Duration,
+}
+
+impl MinioSettings {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            endpoint: config.minio_endpoint.clone(),
+            access_key: config.minio_access_key.clone(),
+            secret_key: config.minio_secret_key.clone(),
+            bucket_name: config.minio_bucket_name.clone(),
+            view_key_suffixes: config.minio_view_key_suffixes.clone(),
+            create_bucket: config.minio_create_bucket,
+            connect_attempts: config.minio_connect_attempts,
+            connect_retry_delay: Duration::from_millis(config.minio_connect_retry_delay_millis),
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct MinioService {
     client: Client,
     bucket_name: String,
+    // Document suffixes of the `{uuid}_{SUFFIX}` keys view URLs may be generated for
+    view_key_suffixes: Vec<String>,
 }
 
 impl MinioService {
-    pub async fn new(endpoint: &str, access_key: &str, secret_key: &str, bucket_name: &str) -> Result<Self> {
+    // Fails unless the bucket is reachable, so a misconfigured bucket stops the service at startup
+    // instead of on the first upload. A missing bucket is created when `create_bucket` is set;
+    // other failures are retried with a doubling delay while MinIO may still be starting.
+    pub async fn new(settings: MinioSettings) -> Result<Self> {
+        let MinioSettings {
+            endpoint,
+            access_key,
+            secret_key,
+            bucket_name,
+            view_key_suffixes,
+            create_bucket,
+            connect_attempts: attempts,
+            connect_retry_delay,
+        } = settings;
+
         // Ensure endpoint doesn't end with slash
         let endpoint = endpoint.trim_end_matches('/');
 
-        println!("Initializing MinIO service with endpoint: {}", endpoint);
-        println!("Bucket name: {}", bucket_name);
-        
+        log::info!("Initializing MinIO service with endpoint {} and bucket {}", endpoint, bucket_name);
+
+        let service = Self::unchecked(endpoint, &access_key, &secret_key, &bucket_name, view_key_suffixes);
+
+        let mut delay = connect_retry_delay;
+        let mut attempt = 1;
+
+        loop {
+            match service.check_bucket().await {
+                Ok(()) => break,
+                Err(e) if is_no_such_bucket(&e) && create_bucket => {
+                    service.create_bucket().await?;
+                    log::info!("Created MinIO bucket {}", bucket_name);
+                    break;
+                }
+                // Retrying won't make a missing bucket appear
+                Err(e) if is_no_such_bucket(&e) => return Err(e),
+                Err(e) if attempt < attempts => {
+                    log::warn!(
+                        "MinIO bucket check attempt {}/{} failed: {}; retrying in {:?}",
+                        attempt, attempts, e, delay
+                    );
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e.context(format!("MinIO bucket '{}' is not accessible", bucket_name))),
+            }
+        }
+
+        log::info!("MinIO connection successful");
+        Ok(service)
+    }
+
+    // Builds the client without contacting MinIO. Presigning works offline, so tests use it for
+    // services that never get to an actual request.
+    pub(crate) fn unchecked(endpoint: &str, access_key: &str, secret_key: &str, bucket_name: &str, view_key_suffixes: Vec<String>) -> Self {
         let config = aws_sdk_s3::config::Builder::new()
             .endpoint_url(endpoint)
             .region(Region::new("us-east-1"))
@@ -35,59 +158,100 @@ impl MinioService {
             .behavior_version_latest()
             .build();
 
-        let client = Client::from_conf(config);
+        Self {
+            client: Client::from_conf(config),
+            bucket_name: bucket_name.to_string(),
+            view_key_suffixes,
+        }
+    }
 
-        // Test the connection by listing buckets
-        match client.list_buckets().send().await {
-            Ok(_) => println!("MinIO connection successful"),
-            Err(e) => println!("MinIO connection test failed: {:?}", e),
+    // Another instance creating the bucket at the same time counts as success
+    async fn create_bucket(&self) -> Result<()> {
+        match self.client.create_bucket().bucket(&self.bucket_name).send().await {
+            Ok(_) => Ok(()),
+            Err(e) if e
+                .as_service_error()
+                .is_some_and(|e| e.is_bucket_already_owned_by_you() || e.is_bucket_already_exists()) =>
+            {
+                Ok(())
+            }
+            Err(e) => Err(anyhow::Error::from(e).context(format!("Failed to create MinIO bucket '{}'", self.bucket_name))),
         }
+    }
 
-        Ok(Self {
-            client,
-            bucket_name: bucket_name.to_string(),
-        })
+    // Served with the content type the object was stored with, so a PNG isn't presented as a JPEG.
+    // Keys are read back from stored submission data, so anything but a document key is refused
+    // rather than trusted.
+    pub async fn generate_view_url(
+        &self,
+        file_name: String,
+        disposition: ContentDisposition,
+        expires_in: Duration,
+    ) -> Result<String> {
+        let bucket = self.bucket_name.clone();
+        self.generate_view_url_in(&bucket, file_name, disposition, expires_in).await
     }
 
-    pub async fn generate_presigned_url(&self, file_name: String, expires_in: Duration) -> Result<String> {
-        let object_key = format!("{}", file_name);
+    // generate_view_url for an object in another bucket on the same endpoint, e.g. an archived
+    // document
+    pub async fn generate_view_url_in(
+        &self,
+        bucket: &str,
+        file_name: String,
+        disposition: ContentDisposition,
+        expires_in: Duration,
+    ) -> Result<String> {
+        if !self.is_view_key_allowed(&file_name) {
+            log::error!("Refused to generate a view URL for unexpected key {}", file_name);
+            return Err(MinioError::KeyNotAllowed(file_name).into());
+        }
+
         let presigned_config = PresigningConfig::builder()
             .expires_in(expires_in)
             .build()?;
 
+        let format = self.stored_format(bucket, &file_name).await;
         let presigned_request = self
             .client
             .get_object()
-            .bucket(&self.bucket_name)
-            .key(&object_key)
+            .bucket(bucket)
+            .key(&file_name)
+            .response_content_type(format.content_type())
+            .response_content_disposition(disposition.header_value(format))
             .presigned(presigned_config)
-            .await?;
+            .await
+            .map_err(|e| classify_for(bucket, e))?;
+
+        // The URL carries the presigned credentials, so only the object is logged
+        log::info!("Generated view URL for {}", file_name);
 
         Ok(presigned_request.uri().to_string())
     }
 
-    pub async fn generate_view_url(&self, file_name: String) -> Result<String> {
-        let presigned_config = PresigningConfig::builder()
-            .expires_in(Duration::from_secs(3600))
-            .build()?;
-    
-        let presigned_request = self
-            .client
-            .get_object()
-            .bucket(&self.bucket_name)
-            .key(&file_name)
-            .response_content_type("image/jpg")
-            .presigned(presigned_config)
-            .await?;
+    // `{uuid}_{SUFFIX}` with a hyphenated UUID, as document keys are generated
+    fn is_view_key_allowed(&self, file_name: &str) -> bool {
+        let Some((prefix, suffix)) = file_name.split_once('_') else {
+            return false;
+        };
+        prefix.len() == 36
+            && Uuid::parse_str(prefix).is_ok()
+            && self.view_key_suffixes.iter().any(|allowed| allowed == suffix)
+    }
 
-        let url = presigned_request.uri().to_string();
-        log::info!("Generated view URL: {}", url);
-        
-        Ok(url)
+    // Falls back to JPEG, the only format stored before others were accepted, when the object
+    // has no content type or can't be read; a missing object fails once the URL is used anyway
+    async fn stored_format(&self, bucket: &str, file_name: &str) -> ImageFormat {
+        match self.client.head_object().bucket(bucket).key(file_name).send().await {
+            Ok(head) => served_format(head.content_type.as_deref()),
+            Err(e) => {
+                log::warn!("Failed to read the content type of {}: {}", file_name, e);
+                ImageFormat::Jpeg
+            }
+        }
     }
 
     pub async fn generate_upload_url(&self, file_name: String, expires_in: Duration) -> Result<String> {
-        let object_key = format!("{}", file_name);
+        let object_key = file_name;
         let presigned_config = PresigningConfig::builder()
             .expires_in(expires_in)
             .build()?;
@@ -99,7 +263,8 @@ impl MinioService {
             .key(&object_key)
             .content_type("image/jpeg")
             .presigned(presigned_config)
-            .await?;
+            .await
+            .map_err(|e| self.classify(e))?;
 
         // Log the generated URL for debugging
         println!("Generated presigned URL: {}", presigned_request.uri());
@@ -108,7 +273,7 @@ impl MinioService {
     }
 
     pub async fn upload_file(&self, file_name: String, content: Vec<u8>, content_type: Option<String>) -> Result<String> {
-        let object_key = format!("{}", file_name);
+        let object_key = file_name;
         let byte_stream = ByteStream::from(content);
 
         let mut put_object = self
@@ -123,65 +288,83 @@ impl MinioService {
             put_object = put_object.content_type(ct);
         }
 
-        put_object.send().await?;
+        put_object.send().await.map_err(|e| self.classify(e))?;
 
         // Generate a view URL for the uploaded file
-        let view_url = self.generate_view_url(file_name).await?;
+        let view_url = self.generate_view_url(object_key, ContentDisposition::Inline, UPLOADED_FILE_VIEW_URL_TTL).await?;
         
         Ok(view_url)
     }
 
-    pub async fn upload_file_with_metadata(
-        &self, 
-        file_name: String, 
-        content: Vec<u8>, 
-        content_type: Option<String>,
-        metadata: std::collections::HashMap<String, String>
-    ) -> Result<String> {
-        let object_key = format!("{}", file_name);
-        let byte_stream = ByteStream::from(content);
-
-        let mut put_object = self
+    pub async fn delete_file(&self, file_name: String) -> Result<()> {
+        let object_key = file_name;
+        
+        self
             .client
-            .put_object()
+            .delete_object()
             .bucket(&self.bucket_name)
             .key(&object_key)
-            .body(byte_stream);
-
-        // Set content type if provided
-        if let Some(ct) = content_type {
-            put_object = put_object.content_type(ct);
-        }
+            .send()
+            .await
+            .map_err(|e| self.classify(e))?;
 
-        // Add metadata
-        for (key, value) in metadata {
-            put_object = put_object.metadata(key, value);
-        }
+        Ok(())
+    }
 
-        put_object.send().await?;
+    // Deletes an object that was moved to another bucket, e.g. by archival
+    pub async fn delete_file_in(&self, bucket: &str, file_name: String) -> Result<()> {
+        self.client
+            .delete_object()
+            .bucket(bucket)
+            .key(&file_name)
+            .send()
+            .await
+            .map_err(|e| classify_for(bucket, e))?;
 
-        // Generate a view URL for the uploaded file
-        let view_url = self.generate_view_url(file_name).await?;
-        
-        Ok(view_url)
+        Ok(())
     }
 
-    pub async fn delete_file(&self, file_name: String) -> Result<()> {
-        let object_key = format!("{}", file_name);
-        
-        self
-            .client
-            .delete_object()
-            .bucket(&self.bucket_name)
-            .key(&object_key)
+    // Server-side copy into another bucket on the same endpoint; the object never passes through
+    // this service. The source is left in place.
+    pub async fn copy_object(&self, src_key: &str, dst_bucket: &str, dst_key: &str) -> Result<()> {
+        self.client
+            .copy_object()
+            .copy_source(format!("{}/{}", self.bucket_name, src_key))
+            .bucket(dst_bucket)
+            .key(dst_key)
             .send()
-            .await?;
+            .await
+            .map_err(|e| classify_for(dst_bucket, e))?;
 
         Ok(())
     }
 
+    // Cheap reachability check of the endpoint, credentials and bucket
+    pub async fn check_bucket(&self) -> Result<()> {
+        match self.client.head_bucket().bucket(&self.bucket_name).send().await {
+            Ok(_) => Ok(()),
+            // HEAD responses have no body, so a missing bucket shows up as a bare 404
+            Err(e) if e.as_service_error().is_some_and(|e| e.is_not_found()) => {
+                Err(MinioError::NoSuchBucket(self.bucket_name.clone()).into())
+            }
+            Err(e) => Err(self.classify(e)),
+        }
+    }
+
+    // A missing or misnamed bucket gets its own error so it isn't mistaken for a
+    // credentials or network problem
+    fn classify<E, R>(&self, err: SdkError<E, R>) -> anyhow::Error
+    where
+        E: ProvideErrorMetadata + std::error::Error + Send + Sync + 'static,
+        R: Debug + Send + Sync + 'static,
+    {
+        classify_for(&self.bucket_name, err)
+    }
+
+    // Only a 404 means the object is missing; anything else is an error, so an unreachable
+    // MinIO isn't taken for a document the client never uploaded
     pub async fn file_exists(&self, file_name: String) -> Result<bool> {
-        let object_key = format!("{}", file_name);
+        let object_key = file_name;
         
         match self
             .client
@@ -192,7 +375,126 @@ impl MinioService {
             .await
         {
             Ok(_) => Ok(true),
-            Err(_) => Ok(false),
+            Err(e) if e.as_service_error().is_some_and(HeadObjectError::is_not_found) => Ok(false),
+            Err(e) => Err(self.classify(e)),
         }
     }
 }
+
+// View URLs are only ever served as one of the accepted image formats, so a content type set on
+// the object by whoever uploaded it (text/html, say) can't make the browser render it as such
+fn served_format(content_type: Option<&str>) -> ImageFormat {
+    content_type.and_then(ImageFormat::from_content_type).unwrap_or(ImageFormat::Jpeg)
+}
+
+fn classify_for<E, R>(bucket: &str, err: SdkError<E, R>) -> anyhow::Error
+where
+    E: ProvideErrorMetadata + std::error::Error + Send + Sync + 'static,
+    R: Debug + Send + Sync + 'static,
+{
+    if err.code() == Some("NoSuchBucket") {
+        log::error!("MinIO bucket '{}' does not exist", bucket);
+        return MinioError::NoSuchBucket(bucket.to_string()).into();
+    }
+    err.into()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+
+    use super::*;
+
+    // Stands in for MinIO: every object exists and every request succeeds. Keeps the method
+    // and path of each request.
+    #[derive(Default)]
+    pub(crate) struct FakeS3 {
+        pub(crate) requests: Mutex<Vec<(String, String)>>,
+    }
+
+    impl FakeS3 {
+        pub(crate) fn requests(&self, method: &str) -> Vec<String> {
+            let requests = self.requests.lock().unwrap();
+            requests.iter().filter(|(m, _)| m == method).map(|(_, path)| path.clone()).collect()
+        }
+    }
+
+    // Endpoint of a server answering for `s3`
+    pub(crate) fn fake_s3(s3: Arc<FakeS3>) -> String {
+        let server = HttpServer::new(move || {
+            let s3 = s3.clone();
+            App::new().default_service(web::to(move |request: HttpRequest| {
+                let s3 = s3.clone();
+                async move {
+                    s3.requests.lock().unwrap().push((request.method().to_string(), request.path().to_string()));
+                    HttpResponse::Ok().finish()
+                }
+            }))
+        })
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+        let addr = server.addrs()[0];
+        tokio::spawn(server.run());
+        format!("http://{}", addr)
+    }
+
+    fn service() -> MinioService {
+        let suffixes = ["SELFIE", "NFC", "KTP"].map(String::from).to_vec();
+        MinioService::unchecked("http://127.0.0.1:1", "minio", "minio123", "documents", suffixes)
+    }
+
+    #[test]
+    fn only_document_keys_are_viewable() {
+        let service = service();
+        let id = "0b7e6c1e-8f4a-4c43-9d55-2f0f4b1f6a3e";
+
+        assert!(service.is_view_key_allowed(&format!("{}_SELFIE", id)));
+        assert!(service.is_view_key_allowed(&format!("{}_KTP", id)));
+        assert!(!service.is_view_key_allowed(&format!("{}_PASSPORT", id)));
+        assert!(!service.is_view_key_allowed(&format!("{}_SELFIE", id.replace('-', ""))));
+        assert!(!service.is_view_key_allowed("not-a-uuid_SELFIE"));
+        assert!(!service.is_view_key_allowed("../secrets/config.json"));
+    }
+
+    // Refused before anything is asked of MinIO, which isn't running here
+    #[tokio::test]
+    async fn unexpected_keys_get_no_view_url() {
+        let err = service()
+            .generate_view_url("exports/users.csv".to_string(), ContentDisposition::Inline, Duration::from_secs(60))
+            .await
+            .unwrap_err();
+        assert!(matches!(err.downcast_ref::<MinioError>(), Some(MinioError::KeyNotAllowed(key)) if key == "exports/users.csv"));
+    }
+
+    // A connection failure is not a missing object
+    #[tokio::test]
+    async fn unreachable_minio_is_an_error_not_a_missing_file() {
+        assert!(service().file_exists(format!("{}_SELFIE", Uuid::new_v4())).await.is_err());
+    }
+
+    #[test]
+    fn serves_stored_png_as_png() {
+        assert_eq!(served_format(Some("image/png")), ImageFormat::Png);
+        assert_eq!(served_format(Some("image/PNG; charset=binary")), ImageFormat::Png);
+        assert_eq!(served_format(Some("image/jpeg")), ImageFormat::Jpeg);
+    }
+
+    #[test]
+    fn serves_anything_else_as_jpeg() {
+        assert_eq!(served_format(None), ImageFormat::Jpeg);
+        assert_eq!(served_format(Some("")), ImageFormat::Jpeg);
+        assert_eq!(served_format(Some("text/html")), ImageFormat::Jpeg);
+        assert_eq!(served_format(Some("image/svg+xml")), ImageFormat::Jpeg);
+    }
+
+    #[test]
+    fn downloads_are_named_after_the_stored_format() {
+        let download = ContentDisposition::Attachment("0b7e6c1e_SELFIE".to_string());
+        assert_eq!(download.header_value(ImageFormat::Png), "attachment; filename=\"0b7e6c1e_SELFIE.png\"");
+        assert_eq!(download.header_value(ImageFormat::Jpeg), "attachment; filename=\"0b7e6c1e_SELFIE.jpg\"");
+        assert_eq!(ContentDisposition::Inline.header_value(ImageFormat::Png), "inline");
+    }
+}
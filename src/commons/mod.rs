@@ -1 +1,8 @@
-pub mod minio_service; 
+pub mod app_error;
+pub mod database;
+pub mod error_cause;
+pub mod etag;
+pub mod json_body;
+pub mod logging;
+pub mod minio_service;
+pub mod pii;
@@ -0,0 +1,51 @@
+use std::borrow::Cow;
+use std::sync::OnceLock;
+
+use sha2::{Digest, Sha256};
+
+static ANONYMIZE_NFC_IDENTIFIER: OnceLock<bool> = OnceLock::new();
+
+// Until this is called identifiers are shown unchanged
+pub fn init(anonymize_nfc_identifier: bool) {
+    let _ = ANONYMIZE_NFC_IDENTIFIER.set(anonymize_nfc_identifier);
+}
+
+// How an nfc_identifier may appear in logs and error causes. When anonymized it becomes a
+// short SHA-256 prefix, so the same identifier can still be followed across log lines without
+// the value ever leaving the database.
+pub fn nfc_identifier(nfc_identifier: &str) -> Cow<'_, str> {
+    masked(nfc_identifier, ANONYMIZE_NFC_IDENTIFIER.get().copied().unwrap_or(false))
+}
+
+// The anonymized form whatever the configuration, for events that must never carry the value
+pub fn hashed_nfc_identifier(nfc_identifier: &str) -> String {
+    let digest = format!("{:x}", Sha256::digest(nfc_identifier.as_bytes()));
+    format!("sha256:{}", &digest[..16])
+}
+
+fn masked(nfc_identifier: &str, anonymize: bool) -> Cow<'_, str> {
+    if anonymize {
+        Cow::Owned(hashed_nfc_identifier(nfc_identifier))
+    } else {
+        Cow::Borrowed(nfc_identifier)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn masks_the_identifier_when_anonymized() {
+        let masked = masked("04A1B2C3D4E5F6", true);
+        assert!(masked.starts_with("sha256:"));
+        assert!(!masked.contains("04A1B2C3D4E5F6"));
+        assert_eq!(masked, hashed_nfc_identifier("04A1B2C3D4E5F6"));
+        assert_ne!(masked, hashed_nfc_identifier("04A1B2C3D4E5F7"));
+    }
+
+    #[test]
+    fn shows_the_identifier_unless_anonymized() {
+        assert_eq!(masked("04A1B2C3D4E5F6", false), "04A1B2C3D4E5F6");
+    }
+}
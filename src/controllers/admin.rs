@@ -0,0 +1,1094 @@
+use actix_web::{web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::PgPool;
+
+use crate::{
+    commons::{database::ReadPool, json_body, logging::LogLevelHandle},
+    config::Config,
+    jobs::{submission_archival, submission_reprocessing},
+    submissions::{
+        document_type::DocumentType,
+        submission_service::{DEFAULT_RISK_TIER, DEFAULT_STUCK_PROCESSING_SECS, MAX_BULK_STATUS_SIZE},
+    },
+    middleware::admin::AdminGuard,
+    models::{
+        pagination::{Page, PaginatedResponse},
+        user::{ApiError, ApiResponse},
+    },
+    repositories::{audit_log_repository::AuditLogRepository, user_repository::UserRepository},
+    services::{app_services::AppServices, feature_flags_service::FeatureFlagsService},
+};
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetFeatureFlagBody {
+    pub enabled: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetRiskTierBody {
+    // One of RISK_TIER_THRESHOLDS, or null/DEFAULT for the global threshold
+    pub risk_tier: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RiskTierResponse {
+    pub user_id: i32,
+    pub risk_tier: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecomputeStatusQuery {
+    // Required to overwrite an existing APPROVED/REJECTED/REQUIRES_REVIEW decision
+    #[serde(default)]
+    pub confirm: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkStatusBody {
+    pub submission_ids: Vec<String>,
+    pub status: String,
+    // Recorded in the status history of every updated submission
+    pub reason: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetLogLevelBody {
+    // RUST_LOG syntax, e.g. "info,socio_echo_be=debug"
+    pub filter: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogLevelResponse {
+    pub filter: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentViewUrlQuery {
+    // Served as an attachment with a file name instead of for inline display
+    #[serde(default)]
+    pub download: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NfcSummaryQuery {
+    pub nfc_identifier: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StuckSubmissionsQuery {
+    // Defaults to DEFAULT_STUCK_PROCESSING_SECS
+    pub older_than_secs: Option<u64>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListSubmissionsQuery {
+    pub status: Option<String>,
+    pub submission_type: Option<String>,
+    pub user_id: Option<String>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentViewUrlResponse {
+    pub url: String,
+}
+
+#[actix_web::delete("/users/{id}")]
+async fn erase_user(
+    _admin: AdminGuard,
+    services: web::Data<AppServices>,
+    path: web::Path<i32>,
+) -> HttpResponse {
+    let user_id = path.into_inner();
+
+    let erasure_service = services.erasure_service();
+
+    match erasure_service.erase_user(user_id, "admin").await {
+        Ok(Some(summary)) => HttpResponse::Ok().json(ApiResponse {
+            success: true,
+            data: Some(summary),
+            errors: None,
+        }),
+        Ok(None) => HttpResponse::NotFound().json(ApiResponse::<()> {
+            success: false,
+            data: None,
+            errors: Some(vec![ApiError {
+                entity: "SOCIO_ECHO_BE".to_string(),
+                code: "1004".to_string(),
+                cause: "USER_NOT_FOUND".to_string(),
+            }]),
+        }),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::<()> {
+            success: false,
+            data: None,
+            errors: Some(vec![ApiError {
+                entity: "SOCIO_ECHO_BE".to_string(),
+                code: "1002".to_string(),
+                cause: e.to_string(),
+            }]),
+        }),
+    }
+}
+
+// Submissions the user creates afterwards are matched against the tier's threshold; existing
+// ones keep the tier they were created with
+#[actix_web::put("/users/{id}/risk-tier")]
+async fn set_user_risk_tier(
+    _admin: AdminGuard,
+    config: web::Data<Config>,
+    pool: web::Data<PgPool>,
+    read_pool: web::Data<ReadPool>,
+    path: web::Path<i32>,
+    body: Result<web::Json<SetRiskTierBody>, actix_web::Error>,
+) -> HttpResponse {
+    let body = match body {
+        Ok(b) => b.into_inner(),
+        Err(e) => return json_body::rejection(e),
+    };
+    let user_id = path.into_inner();
+
+    let risk_tier = body
+        .risk_tier
+        .map(|tier| tier.trim().to_uppercase())
+        .filter(|tier| tier != DEFAULT_RISK_TIER);
+    if let Some(tier) = risk_tier.as_ref().filter(|tier| !config.risk_tier_thresholds.contains_key(*tier)) {
+        return HttpResponse::BadRequest().json(ApiResponse::<()> {
+            success: false,
+            data: None,
+            errors: Some(vec![ApiError {
+                entity: "SOCIO_ECHO_BE".to_string(),
+                code: "1003".to_string(),
+                cause: format!("INVALID_RISK_TIER: {}", tier),
+            }]),
+        });
+    }
+
+    let db_error = |e: sqlx::Error| {
+        HttpResponse::InternalServerError().json(ApiResponse::<()> {
+            success: false,
+            data: None,
+            errors: Some(vec![ApiError {
+                entity: "SOCIO_ECHO_BE".to_string(),
+                code: "1002".to_string(),
+                cause: e.to_string(),
+            }]),
+        })
+    };
+
+    let user_repository = UserRepository::new(pool.get_ref().clone(), read_pool.0.clone());
+    let mut tx = match user_repository.begin().await {
+        Ok(tx) => tx,
+        Err(e) => return db_error(e),
+    };
+    match UserRepository::set_risk_tier(&mut tx, user_id, risk_tier.as_deref()).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return HttpResponse::NotFound().json(ApiResponse::<()> {
+                success: false,
+                data: None,
+                errors: Some(vec![ApiError {
+                    entity: "SOCIO_ECHO_BE".to_string(),
+                    code: "1004".to_string(),
+                    cause: "USER_NOT_FOUND".to_string(),
+                }]),
+            });
+        }
+        Err(e) => return db_error(e),
+    }
+    let audited = AuditLogRepository::create(
+        &mut tx,
+        "USER_RISK_TIER_SET",
+        "USER",
+        &user_id.to_string(),
+        "admin",
+        json!({ "riskTier": risk_tier }),
+    )
+    .await;
+    if let Err(e) = audited {
+        return db_error(e);
+    }
+    if let Err(e) = tx.commit().await {
+        return db_error(e);
+    }
+
+    HttpResponse::Ok().json(ApiResponse {
+        success: true,
+        data: Some(RiskTierResponse { user_id, risk_tier }),
+        errors: None,
+    })
+}
+
+#[actix_web::get("/debug/config")]
+async fn get_effective_config(
+    _admin: AdminGuard,
+    config: web::Data<Config>,
+) -> HttpResponse {
+    HttpResponse::Ok().json(ApiResponse {
+        success: true,
+        data: Some(config.get_ref()),
+        errors: None,
+    })
+}
+
+#[actix_web::get("/debug/log-level")]
+async fn get_log_level(
+    _admin: AdminGuard,
+    log_level: web::Data<LogLevelHandle>,
+) -> HttpResponse {
+    HttpResponse::Ok().json(ApiResponse {
+        success: true,
+        data: Some(LogLevelResponse { filter: log_level.current() }),
+        errors: None,
+    })
+}
+
+#[actix_web::put("/debug/log-level")]
+async fn set_log_level(
+    _admin: AdminGuard,
+    log_level: web::Data<LogLevelHandle>,
+    body: Result<web::Json<SetLogLevelBody>, actix_web::Error>,
+) -> HttpResponse {
+    let body = match body {
+        Ok(b) => b,
+        Err(e) => return json_body::rejection(e),
+    };
+
+    match log_level.set(&body.filter) {
+        Ok(filter) => {
+            log::warn!("Log filter changed to '{}'", filter);
+            HttpResponse::Ok().json(ApiResponse {
+                success: true,
+                data: Some(LogLevelResponse { filter }),
+                errors: None,
+            })
+        }
+        Err(cause) => HttpResponse::BadRequest().json(ApiResponse::<()> {
+            success: false,
+            data: None,
+            errors: Some(vec![ApiError {
+                entity: "SOCIO_ECHO_BE".to_string(),
+                code: "1003".to_string(),
+                cause: format!("INVALID_LOG_FILTER: {}", cause),
+            }]),
+        }),
+    }
+}
+
+#[actix_web::post("/submissions/reprocess")]
+async fn reprocess_submissions(
+    _admin: AdminGuard,
+    services: web::Data<AppServices>,
+) -> HttpResponse {
+    let started = submission_reprocessing::spawn(
+        services.pool.clone(),
+        services.minio.clone(),
+        services.face_match.clone(),
+        services.webhooks.clone(),
+        services.metrics.clone(),
+        services.config.clone(),
+        services.feature_flags.clone(),
+    );
+
+    if !started {
+        return HttpResponse::Conflict().json(ApiResponse::<()> {
+            success: false,
+            data: None,
+            errors: Some(vec![ApiError {
+                entity: "SOCIO_ECHO_BE".to_string(),
+                code: "1012".to_string(),
+                cause: "REPROCESS_ALREADY_RUNNING".to_string(),
+            }]),
+        });
+    }
+
+    HttpResponse::Accepted().json(ApiResponse::<()> {
+        success: true,
+        data: None,
+        errors: None,
+    })
+}
+
+#[actix_web::post("/submissions/archive")]
+async fn archive_submissions(
+    _admin: AdminGuard,
+    services: web::Data<AppServices>,
+) -> HttpResponse {
+    if services.config.minio_archive_bucket.is_none() {
+        return HttpResponse::Conflict().json(ApiResponse::<()> {
+            success: false,
+            data: None,
+            errors: Some(vec![ApiError {
+                entity: "SOCIO_ECHO_BE".to_string(),
+                code: "1022".to_string(),
+                cause: "ARCHIVE_BUCKET_NOT_CONFIGURED".to_string(),
+            }]),
+        });
+    }
+
+    let started = submission_archival::spawn(
+        services.pool.clone(),
+        services.minio.clone(),
+        services.metrics.clone(),
+        services.config.clone(),
+    );
+
+    if !started {
+        return HttpResponse::Conflict().json(ApiResponse::<()> {
+            success: false,
+            data: None,
+            errors: Some(vec![ApiError {
+                entity: "SOCIO_ECHO_BE".to_string(),
+                code: "1012".to_string(),
+                cause: "ARCHIVE_ALREADY_RUNNING".to_string(),
+            }]),
+        });
+    }
+
+    HttpResponse::Accepted().json(ApiResponse::<()> {
+        success: true,
+        data: None,
+        errors: None,
+    })
+}
+
+#[actix_web::get("/feature-flags")]
+async fn list_feature_flags(
+    _admin: AdminGuard,
+    feature_flags: web::Data<FeatureFlagsService>,
+) -> HttpResponse {
+    match feature_flags.list().await {
+        Ok(flags) => HttpResponse::Ok().json(ApiResponse {
+            success: true,
+            data: Some(flags),
+            errors: None,
+        }),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::<()> {
+            success: false,
+            data: None,
+            errors: Some(vec![ApiError {
+                entity: "SOCIO_ECHO_BE".to_string(),
+                code: "1002".to_string(),
+                cause: e.to_string(),
+            }]),
+        }),
+    }
+}
+
+#[actix_web::put("/feature-flags/{name}")]
+async fn set_feature_flag(
+    _admin: AdminGuard,
+    feature_flags: web::Data<FeatureFlagsService>,
+    path: web::Path<String>,
+    body: Result<web::Json<SetFeatureFlagBody>, actix_web::Error>,
+) -> HttpResponse {
+    let body = match body {
+        Ok(b) => b,
+        Err(e) => return json_body::rejection(e),
+    };
+
+    let name = path.into_inner();
+    let valid_name = !name.is_empty()
+        && name.len() <= 64
+        && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    if !valid_name {
+        return HttpResponse::BadRequest().json(ApiResponse::<()> {
+            success: false,
+            data: None,
+            errors: Some(vec![ApiError {
+                entity: "SOCIO_ECHO_BE".to_string(),
+                code: "1003".to_string(),
+                cause: "INVALID_FEATURE_FLAG_NAME".to_string(),
+            }]),
+        });
+    }
+
+    match feature_flags.set(&name, body.enabled, "admin").await {
+        Ok(flag) => HttpResponse::Ok().json(ApiResponse {
+            success: true,
+            data: Some(flag),
+            errors: None,
+        }),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::<()> {
+            success: false,
+            data: None,
+            errors: Some(vec![ApiError {
+                entity: "SOCIO_ECHO_BE".to_string(),
+                code: "1002".to_string(),
+                cause: e.to_string(),
+            }]),
+        }),
+    }
+}
+
+#[actix_web::post("/submissions/{id}/recompute-status")]
+async fn recompute_submission_status(
+    _admin: AdminGuard,
+    services: web::Data<AppServices>,
+    path: web::Path<String>,
+    query: web::Query<RecomputeStatusQuery>,
+) -> HttpResponse {
+    let submission_service = services.submission_service();
+
+    match submission_service.recompute_status(&path.into_inner(), query.confirm, "admin", &services.webhooks).await {
+        Ok(response) => HttpResponse::Ok().json(ApiResponse {
+            success: true,
+            data: Some(response),
+            errors: None,
+        }),
+        Err(errors) => {
+            let status_code = if errors.iter().any(|e| e.code == "1003") {
+                HttpResponse::BadRequest
+            } else if errors.iter().any(|e| e.code == "1004") {
+                HttpResponse::NotFound
+            } else if errors.iter().any(|e| e.code == "1015") {
+                HttpResponse::Conflict
+            } else {
+                HttpResponse::InternalServerError
+            };
+
+            status_code().json(ApiResponse::<()> {
+                success: false,
+                data: None,
+                errors: Some(errors),
+            })
+        }
+    }
+}
+
+#[actix_web::get("/submissions/by-document/{reference}")]
+async fn find_by_document_reference(
+    _admin: AdminGuard,
+    services: web::Data<AppServices>,
+    path: web::Path<String>,
+) -> HttpResponse {
+    let submission_service = services.submission_service();
+
+    match submission_service.find_by_document_reference(&path.into_inner()).await {
+        Ok(submission) => HttpResponse::Ok().json(ApiResponse {
+            success: true,
+            data: Some(submission),
+            errors: None,
+        }),
+        Err(errors) => {
+            let status_code = if errors.iter().any(|e| e.code == "1003") {
+                HttpResponse::BadRequest
+            } else if errors.iter().any(|e| e.code == "1004") {
+                HttpResponse::NotFound
+            } else {
+                HttpResponse::InternalServerError
+            };
+
+            status_code().json(ApiResponse::<()> {
+                success: false,
+                data: None,
+                errors: Some(errors),
+            })
+        }
+    }
+}
+
+#[actix_web::get("/submissions/nfc-summary")]
+async fn get_nfc_summary(
+    _admin: AdminGuard,
+    services: web::Data<AppServices>,
+    query: Result<web::Query<NfcSummaryQuery>, actix_web::Error>,
+) -> HttpResponse {
+    let query = match query {
+        Ok(q) => q,
+        Err(e) => {
+            return HttpResponse::BadRequest().json(ApiResponse::<()> {
+                success: false,
+                data: None,
+                errors: Some(vec![ApiError {
+                    entity: "SOCIO_ECHO_BE".to_string(),
+                    code: "1003".to_string(),
+                    cause: format!("INVALID_QUERY_PARAMS: {}", e),
+                }]),
+            });
+        }
+    };
+
+    let submission_service = services.submission_service();
+
+    match submission_service.nfc_summary(&query.nfc_identifier).await {
+        Ok(summary) => HttpResponse::Ok().json(ApiResponse {
+            success: true,
+            data: Some(summary),
+            errors: None,
+        }),
+        Err(errors) => {
+            let status_code = if errors.iter().any(|e| e.code == "1004") {
+                HttpResponse::NotFound
+            } else {
+                HttpResponse::InternalServerError
+            };
+
+            status_code().json(ApiResponse::<()> {
+                success: false,
+                data: None,
+                errors: Some(errors),
+            })
+        }
+    }
+}
+
+// Submissions across all users, each filter applying only when given
+#[actix_web::get("/submissions")]
+async fn list_submissions(
+    _admin: AdminGuard,
+    services: web::Data<AppServices>,
+    query: Result<web::Query<ListSubmissionsQuery>, actix_web::Error>,
+) -> HttpResponse {
+    let query = match query {
+        Ok(q) => q,
+        Err(e) => {
+            return HttpResponse::BadRequest().json(ApiResponse::<()> {
+                success: false,
+                data: None,
+                errors: Some(vec![ApiError {
+                    entity: "SOCIO_ECHO_BE".to_string(),
+                    code: "1003".to_string(),
+                    cause: format!("INVALID_QUERY_PARAMS: {}", e),
+                }]),
+            });
+        }
+    };
+
+    let page = match Page::from_query(query.limit, query.offset) {
+        Ok(page) => page,
+        Err(cause) => {
+            return HttpResponse::BadRequest().json(ApiResponse::<()> {
+                success: false,
+                data: None,
+                errors: Some(vec![ApiError {
+                    entity: "SOCIO_ECHO_BE".to_string(),
+                    code: "1003".to_string(),
+                    cause,
+                }]),
+            });
+        }
+    };
+
+    let submission_service = services.submission_service();
+
+    let result = submission_service
+        .list_submissions(query.status.as_deref(), query.submission_type.as_deref(), query.user_id.as_deref(), page)
+        .await;
+    match result {
+        Ok((submissions, total)) => HttpResponse::Ok().json(PaginatedResponse {
+            success: true,
+            meta: Some(page.meta(total, submissions.len())),
+            data: Some(submissions),
+            errors: None,
+        }),
+        Err(errors) => {
+            let status_code = if errors.iter().any(|e| e.code == "1003") {
+                HttpResponse::BadRequest
+            } else {
+                HttpResponse::InternalServerError
+            };
+
+            status_code().json(PaginatedResponse::<()> {
+                success: false,
+                data: None,
+                errors: Some(errors),
+                meta: None,
+            })
+        }
+    }
+}
+
+// Submissions a crash left in PROCESSING, to be reprocessed or failed through bulk-status
+#[actix_web::get("/submissions/stuck")]
+async fn list_stuck_submissions(
+    _admin: AdminGuard,
+    services: web::Data<AppServices>,
+    query: Result<web::Query<StuckSubmissionsQuery>, actix_web::Error>,
+) -> HttpResponse {
+    let query = match query {
+        Ok(q) => q,
+        Err(e) => {
+            return HttpResponse::BadRequest().json(ApiResponse::<()> {
+                success: false,
+                data: None,
+                errors: Some(vec![ApiError {
+                    entity: "SOCIO_ECHO_BE".to_string(),
+                    code: "1003".to_string(),
+                    cause: format!("INVALID_QUERY_PARAMS: {}", e),
+                }]),
+            });
+        }
+    };
+
+    let page = match Page::from_query(query.limit, query.offset) {
+        Ok(page) => page,
+        Err(cause) => {
+            return HttpResponse::BadRequest().json(ApiResponse::<()> {
+                success: false,
+                data: None,
+                errors: Some(vec![ApiError {
+                    entity: "SOCIO_ECHO_BE".to_string(),
+                    code: "1003".to_string(),
+                    cause,
+                }]),
+            });
+        }
+    };
+
+    let submission_service = services.submission_service();
+
+    let older_than_secs = query.older_than_secs.unwrap_or(DEFAULT_STUCK_PROCESSING_SECS);
+    match submission_service.find_stuck_processing(older_than_secs, page).await {
+        Ok((submissions, total)) => HttpResponse::Ok().json(PaginatedResponse {
+            success: true,
+            meta: Some(page.meta(total, submissions.len())),
+            data: Some(submissions),
+            errors: None,
+        }),
+        Err(errors) => {
+            let status_code = if errors.iter().any(|e| e.code == "1003") {
+                HttpResponse::BadRequest
+            } else {
+                HttpResponse::InternalServerError
+            };
+
+            status_code().json(PaginatedResponse::<()> {
+                success: false,
+                data: None,
+                errors: Some(errors),
+                meta: None,
+            })
+        }
+    }
+}
+
+#[actix_web::post("/submissions/bulk-status")]
+async fn bulk_update_submission_status(
+    _admin: AdminGuard,
+    services: web::Data<AppServices>,
+    body: Result<web::Json<BulkStatusBody>, actix_web::Error>,
+) -> HttpResponse {
+    let body = match body {
+        Ok(b) => b.into_inner(),
+        Err(e) => return json_body::rejection(e),
+    };
+
+    let cause = if body.submission_ids.is_empty() {
+        Some("EMPTY_SUBMISSION_IDS".to_string())
+    } else if body.submission_ids.len() > MAX_BULK_STATUS_SIZE {
+        Some(format!("BATCH_SIZE_EXCEEDED: max {}", MAX_BULK_STATUS_SIZE))
+    } else if body.reason.trim().is_empty() {
+        Some("REASON_REQUIRED".to_string())
+    } else {
+        None
+    };
+    if let Some(cause) = cause {
+        return HttpResponse::BadRequest().json(ApiResponse::<()> {
+            success: false,
+            data: None,
+            errors: Some(vec![ApiError {
+                entity: "SOCIO_ECHO_BE".to_string(),
+                code: "1003".to_string(),
+                cause,
+            }]),
+        });
+    }
+
+    let submission_service = services.submission_service();
+
+    match submission_service
+        .bulk_update_status(&body.submission_ids, &body.status, body.reason.trim(), "admin", &services.webhooks)
+        .await
+    {
+        Ok(response) => HttpResponse::Ok().json(ApiResponse {
+            success: true,
+            data: Some(response),
+            errors: None,
+        }),
+        Err(errors) => {
+            let status_code = if errors.iter().any(|e| e.code == "1003") {
+                HttpResponse::BadRequest
+            } else if errors.iter().any(|e| e.code == "1004") {
+                HttpResponse::NotFound
+            } else if errors.iter().any(|e| e.code == "1017") {
+                HttpResponse::Conflict
+            } else {
+                HttpResponse::InternalServerError
+            };
+
+            status_code().json(ApiResponse::<()> {
+                success: false,
+                data: None,
+                errors: Some(errors),
+            })
+        }
+    }
+}
+
+#[actix_web::get("/submissions/{id}/documents/{document_type}/view-url")]
+async fn get_document_view_url(
+    _admin: AdminGuard,
+    services: web::Data<AppServices>,
+    path: web::Path<(String, String)>,
+    query: web::Query<DocumentViewUrlQuery>,
+) -> HttpResponse {
+    let (submission_id, document_type) = path.into_inner();
+    let document_type = match document_type.parse::<DocumentType>() {
+        Ok(document_type) => document_type,
+        Err(cause) => {
+            return HttpResponse::BadRequest().json(ApiResponse::<()> {
+                success: false,
+                data: None,
+                errors: Some(vec![ApiError {
+                    entity: "SOCIO_ECHO_BE".to_string(),
+                    code: "1003".to_string(),
+                    cause,
+                }]),
+            });
+        }
+    };
+
+    let submission_service = services.submission_service();
+
+    match submission_service.document_view_url(&submission_id, document_type, query.download).await {
+        Ok(url) => HttpResponse::Ok().json(ApiResponse {
+            success: true,
+            data: Some(DocumentViewUrlResponse { url }),
+            errors: None,
+        }),
+        Err(errors) => {
+            let status_code = if errors.iter().any(|e| e.code == "1003") {
+                HttpResponse::BadRequest
+            } else if errors.iter().any(|e| e.code == "1004") {
+                HttpResponse::NotFound
+            } else {
+                HttpResponse::InternalServerError
+            };
+
+            status_code().json(ApiResponse::<()> {
+                success: false,
+                data: None,
+                errors: Some(errors),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        dev::HttpServiceFactory,
+        http::StatusCode,
+        test::{call_service, init_service, read_body_json, TestRequest},
+        App,
+    };
+    use serde_json::Value;
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::{
+        commons::minio_service::tests::{fake_s3, FakeS3},
+        config::tests::from_env_with,
+        repositories::user_repository::UserRepository,
+        services::app_services,
+        submissions::submission_repository::{NewSubmission, SubmissionRepository},
+    };
+
+    const ADMIN_KEY: &str = "admin-key";
+
+    // Status and JSON body of `request`, sent with the admin key, against `service`
+    async fn call<F: HttpServiceFactory + 'static>(pool: PgPool, service: F, request: TestRequest) -> (StatusCode, Value) {
+        call_with_minio(pool, "http://127.0.0.1:1", service, request).await
+    }
+
+    async fn call_with_minio<F: HttpServiceFactory + 'static>(
+        pool: PgPool,
+        minio_endpoint: &str,
+        service: F,
+        request: TestRequest,
+    ) -> (StatusCode, Value) {
+        let services = app_services::tests::services_with_minio(pool, &[("ADMIN_API_KEY", ADMIN_KEY)], minio_endpoint);
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(services.config.clone()))
+                .app_data(web::Data::new(services.pool.clone()))
+                .app_data(web::Data::new(services.read_pool.clone()))
+                .app_data(web::Data::new(services))
+                .service(service),
+        )
+        .await;
+        let response = call_service(&app, request.insert_header(("x-admin-key", ADMIN_KEY)).to_request()).await;
+        let status = response.status();
+        (status, read_body_json(response).await)
+    }
+
+    async fn seed(pool: &PgPool, user_id: &str, status: &str) -> Uuid {
+        let submission_id = Uuid::new_v4();
+        SubmissionRepository::new(pool.clone(), pool.clone())
+            .create(NewSubmission {
+                submission_id,
+                submission_type: "KYC",
+                session_id: &submission_id.to_string(),
+                user_id,
+                status,
+                submission_data: json!({}),
+                request_data: json!({}),
+                nfc_identifier: submission_id.to_string(),
+                external_reference: None,
+                risk_tier: None,
+                idempotency_key: None,
+                idempotency_request_hash: None,
+                callback_url: None,
+            })
+            .await
+            .unwrap();
+        submission_id
+    }
+
+    fn submission_ids(body: &Value) -> Vec<String> {
+        let mut ids: Vec<_> = body["data"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|submission| submission["submissionId"].as_str().unwrap().to_string())
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    #[sqlx::test]
+    async fn lists_submissions_across_users_by_filter(pool: PgPool) {
+        let approved_1 = seed(&pool, "1", "APPROVED").await;
+        let approved_2 = seed(&pool, "2", "APPROVED").await;
+        let rejected_2 = seed(&pool, "2", "REJECTED").await;
+        let list = |query: &str| TestRequest::get().uri(&format!("/submissions{}", query));
+
+        let (status, body) = call(pool.clone(), list_submissions, list("")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["meta"]["total"], 3);
+
+        let (_, body) = call(pool.clone(), list_submissions, list("?status=APPROVED")).await;
+        let mut expected = vec![approved_1.to_string(), approved_2.to_string()];
+        expected.sort();
+        assert_eq!(submission_ids(&body), expected);
+
+        let (_, body) = call(pool.clone(), list_submissions, list("?userId=2")).await;
+        let mut expected = vec![approved_2.to_string(), rejected_2.to_string()];
+        expected.sort();
+        assert_eq!(submission_ids(&body), expected);
+
+        let (_, body) = call(pool.clone(), list_submissions, list("?userId=2&status=REJECTED&submissionType=KYC")).await;
+        assert_eq!(submission_ids(&body), [rejected_2.to_string()]);
+
+        let (status, body) = call(pool, list_submissions, list("?status=UNKNOWN")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["errors"][0]["cause"], "INVALID_STATUS: UNKNOWN");
+    }
+
+    #[sqlx::test]
+    async fn pages_report_where_they_are_in_the_list(pool: PgPool) {
+        for _ in 0..5 {
+            seed(&pool, "1", "APPROVED").await;
+        }
+        let list = |query: &str| TestRequest::get().uri(&format!("/submissions{}", query));
+
+        let (_, body) = call(pool.clone(), list_submissions, list("?limit=2")).await;
+        assert_eq!(body["data"].as_array().unwrap().len(), 2);
+        assert_eq!(body["meta"], json!({ "total": 5, "limit": 2, "offset": 0, "hasMore": true }));
+
+        let (_, body) = call(pool.clone(), list_submissions, list("?limit=2&offset=4")).await;
+        assert_eq!(body["data"].as_array().unwrap().len(), 1);
+        assert_eq!(body["meta"], json!({ "total": 5, "limit": 2, "offset": 4, "hasMore": false }));
+
+        let (_, body) = call(pool.clone(), list_submissions, list("")).await;
+        assert_eq!(body["meta"], json!({ "total": 5, "limit": 20, "offset": 0, "hasMore": false }));
+
+        let (status, body) = call(pool, list_submissions, list("?limit=101")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["errors"][0]["cause"], "INVALID_LIMIT: must be between 1 and 100");
+    }
+
+    #[actix_web::test]
+    async fn listing_submissions_requires_the_admin_key() {
+        let config = from_env_with(&[("ADMIN_API_KEY", ADMIN_KEY)]).unwrap();
+        let app = init_service(App::new().app_data(web::Data::new(config)).service(list_submissions)).await;
+        let response = call_service(&app, TestRequest::get().uri("/submissions").to_request()).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[sqlx::test]
+    async fn erasing_a_user_removes_their_submissions_and_objects(pool: PgPool) {
+        let s3 = Arc::new(FakeS3::default());
+        let endpoint = fake_s3(s3.clone());
+        let user = UserRepository::new(pool.clone(), pool.clone()).create("Ana", "ana@example.com", "hash").await.unwrap();
+        let user_id = user.id.to_string();
+        let live = seed(&pool, &user_id, "APPROVED").await;
+        let archived = seed(&pool, &user_id, "REJECTED").await;
+        let other = seed(&pool, "999", "APPROVED").await;
+        for (submission_id, data) in [
+            (live, json!({ "SELFIE": { "documentName": "live_SELFIE" }, "NFC": { "documentName": "live_NFC" } })),
+            (archived, json!({ "SELFIE": { "documentName": "old_SELFIE", "archivedBucket": "archive" } })),
+            (other, json!({ "SELFIE": { "documentName": "other_SELFIE" } })),
+        ] {
+            sqlx::query("UPDATE submissions SET submission_data = $2 WHERE submission_id = $1")
+                .bind(submission_id)
+                .bind(data.to_string())
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+        let erase = || TestRequest::delete().uri(&format!("/users/{}", user.id));
+
+        let (status, body) = call_with_minio(pool.clone(), &endpoint, erase_user, erase()).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"]["submissionsDeleted"], 2);
+        assert_eq!(body["data"]["objectsDeleted"], 3);
+        let deleted = s3.requests("DELETE");
+        assert_eq!(deleted.len(), 3, "{deleted:?}");
+        assert!(deleted.iter().any(|path| path == "/archive/old_SELFIE"), "{deleted:?}");
+        assert!(deleted.iter().any(|path| path.ends_with("/live_NFC")), "{deleted:?}");
+        assert!(!deleted.iter().any(|path| path.ends_with("/other_SELFIE")), "{deleted:?}");
+
+        let users: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM users WHERE id = $1").bind(user.id).fetch_one(&pool).await.unwrap();
+        assert_eq!(users, 0);
+        let statuses: Vec<(Uuid, String, Option<String>)> =
+            sqlx::query_as("SELECT submission_id, status, submission_data FROM submissions ORDER BY id")
+                .fetch_all(&pool)
+                .await
+                .unwrap();
+        assert_eq!(statuses[0], (live, "DELETED".to_string(), None));
+        assert_eq!(statuses[1], (archived, "DELETED".to_string(), None));
+        assert_eq!((statuses[2].0, statuses[2].1.as_str()), (other, "APPROVED"));
+
+        let (status, body) = call_with_minio(pool, &endpoint, erase_user, erase()).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["errors"][0]["cause"], "USER_NOT_FOUND");
+    }
+
+    #[sqlx::test]
+    async fn finds_a_submission_by_one_of_its_document_references(pool: PgPool) {
+        let submission_id = seed(&pool, "1", "APPROVED").await;
+        seed(&pool, "2", "APPROVED").await;
+        let (selfie, nfc) = (Uuid::new_v4(), Uuid::new_v4());
+        sqlx::query("UPDATE submissions SET submission_data = $2 WHERE submission_id = $1")
+            .bind(submission_id)
+            .bind(
+                json!({
+                    "SELFIE": { "documentName": "a_SELFIE", "documentReference": selfie },
+                    "NFC": { "documentName": "a_NFC", "documentReference": nfc },
+                })
+                .to_string(),
+            )
+            .execute(&pool)
+            .await
+            .unwrap();
+        let find = |reference: String| TestRequest::get().uri(&format!("/submissions/by-document/{}", reference));
+
+        let (status, body) = call(pool.clone(), find_by_document_reference, find(nfc.to_string())).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"]["submissionId"], submission_id.to_string());
+        assert_eq!(body["data"]["status"], "APPROVED");
+
+        let (status, _) = call(pool.clone(), find_by_document_reference, find(Uuid::new_v4().to_string())).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = call(pool, find_by_document_reference, find("not-a-reference".to_string())).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    async fn status_of(pool: &PgPool, submission_id: Uuid) -> String {
+        sqlx::query_scalar("SELECT status FROM submissions WHERE submission_id = $1")
+            .bind(submission_id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn bulk_status_updates_record_history_and_refuse_illegal_transitions(pool: PgPool) {
+        let approved = [seed(&pool, "1", "APPROVED").await, seed(&pool, "2", "APPROVED").await];
+        let rejected = seed(&pool, "3", "REJECTED").await;
+        let initiated = seed(&pool, "4", "INITIATED").await;
+        let bulk = |ids: &[Uuid], status: &str| {
+            TestRequest::post()
+                .uri("/submissions/bulk-status")
+                .set_json(json!({ "submissionIds": ids, "status": status, "reason": "threshold misconfigured" }))
+        };
+
+        let (status, body) = call(pool.clone(), bulk_update_submission_status, bulk(&[approved[0], approved[1], rejected], "REJECTED")).await;
+        assert_eq!(status, StatusCode::OK);
+        let changed: Vec<bool> = body["data"]["submissions"].as_array().unwrap().iter().map(|s| s["changed"].as_bool().unwrap()).collect();
+        assert_eq!(changed, [true, true, false]);
+        for submission_id in approved {
+            assert_eq!(status_of(&pool, submission_id).await, "REJECTED");
+        }
+        let history: Vec<(Uuid, String, String, String, String)> = sqlx::query_as(
+            "SELECT submission_id, previous_status, status, reason, actor FROM submission_status_history ORDER BY id",
+        )
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        let recorded = |submission_id| {
+            let row = (submission_id, "APPROVED", "REJECTED", "threshold misconfigured", "admin");
+            (row.0, row.1.to_string(), row.2.to_string(), row.3.to_string(), row.4.to_string())
+        };
+        assert_eq!(history, [recorded(approved[0]), recorded(approved[1])]);
+
+        // One illegal transition fails the whole batch
+        let (status, body) = call(pool.clone(), bulk_update_submission_status, bulk(&[rejected, initiated], "APPROVED")).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert!(body["errors"][0]["cause"].as_str().unwrap().starts_with("ILLEGAL_STATUS_TRANSITION"));
+        assert_eq!(status_of(&pool, rejected).await, "REJECTED");
+        assert_eq!(status_of(&pool, initiated).await, "INITIATED");
+
+        let too_many: Vec<Uuid> = (0..=MAX_BULK_STATUS_SIZE).map(|_| Uuid::new_v4()).collect();
+        let (status, body) = call(pool.clone(), bulk_update_submission_status, bulk(&too_many, "REJECTED")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body["errors"][0]["cause"].as_str().unwrap().starts_with("BATCH_SIZE_EXCEEDED"));
+        let (status, _) = call(pool, bulk_update_submission_status, bulk(&[rejected], "PROCESSING")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[sqlx::test]
+    async fn nfc_summary_counts_the_identifier_submissions_per_status(pool: PgPool) {
+        for status in ["INITIATED", "REJECTED", "INITIATED", "REJECTED", "APPROVED"] {
+            let submission_id = seed(&pool, "1", status).await;
+            sqlx::query("UPDATE submissions SET nfc_identifier = 'nfc-under-review' WHERE submission_id = $1")
+                .bind(submission_id)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+        // Another identifier's submission
+        seed(&pool, "2", "REJECTED").await;
+        let summary = |query: &str| TestRequest::get().uri(&format!("/submissions/nfc-summary{}", query));
+
+        let (status, body) = call(pool.clone(), get_nfc_summary, summary("?nfcIdentifier=nfc-under-review")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"]["total"], 5);
+        assert_eq!(body["data"]["statusCounts"], json!({ "APPROVED": 1, "INITIATED": 2, "REJECTED": 2 }));
+        assert_eq!(body["data"]["latestStatus"], "APPROVED");
+
+        let (status, _) = call(pool.clone(), get_nfc_summary, summary("?nfcIdentifier=nfc-unknown")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = call(pool, get_nfc_summary, summary("")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
@@ -1,18 +1,29 @@
-use actix_web::{web, HttpResponse};
+use actix_web::{http::header::RETRY_AFTER, web, HttpResponse};
+use chrono::Utc;
 use sqlx::PgPool;
 use tracing::{info, info_span};
 use validator::Validate;
 use std::collections::HashMap;
 
 use crate::{
-    models::user::{ApiError, ApiResponse, AuthResponse, LoginRequest, RegisterRequest},
-    services::{auth_service::AuthService, metrics_service::MetricsService},
+    commons::database::ReadPool,
+    config::Config,
+    middleware::auth::AuthenticatedUser,
+    models::user::{ApiError, ApiResponse, AuthResponse, ChangePasswordRequest, ForgotPasswordRequest, LoginRequest, LogoutRequest, RefreshTokenRequest, RegisterRequest, ResetPasswordRequest, User, VerifyEmailRequest},
+    services::{
+        auth_service::{AccountLocked, AuthService, VerificationThrottled},
+        email_sender::EmailSender,
+        metrics_service::MetricsService,
+    },
 };
 
 #[actix_web::post("/register")]
 async fn register(
+    config: web::Data<Config>,
     pool: web::Data<PgPool>,
+    read_pool: web::Data<ReadPool>,
     metrics: web::Data<MetricsService>,
+    email_sender: web::Data<dyn EmailSender>,
     request: web::Json<RegisterRequest>,
 ) -> HttpResponse {
     let start = std::time::Instant::now();
@@ -20,7 +31,7 @@ async fn register(
     tags.insert("endpoint".to_string(), "register".to_string());
 
     // Validate request
-    if let Err(_) = request.validate() {
+    if request.validate().is_err() {
         metrics.increment("auth.validation.failed", Some(tags.clone()));
         return HttpResponse::UnprocessableEntity().json(ApiResponse::<AuthResponse> {
             success: false,
@@ -33,14 +44,11 @@ async fn register(
         });
     }
 
-    // Get JWT secret from environment
-    let jwt_secret = std::env::var("JWT_SECRET").expect("JWT_SECRET must be set");
-
     // Create auth service
-    let auth_service = AuthService::new(pool.get_ref().clone(), jwt_secret);
+    let auth_service = AuthService::new(pool.get_ref().clone(), read_pool.0.clone(), &config);
 
     // Handle registration
-    match auth_service.register(request.into_inner()).await {
+    match auth_service.register(request.into_inner(), email_sender.get_ref()).await {
         Ok(response) => {
             metrics.increment("auth.register.success", Some(tags.clone()));
             metrics.timing("auth.register.duration", start.elapsed(), Some(tags));
@@ -84,18 +92,19 @@ async fn register(
 
 #[actix_web::post("/login")]
 async fn login(
+    config: web::Data<Config>,
     pool: web::Data<PgPool>,
+    read_pool: web::Data<ReadPool>,
     metrics: web::Data<MetricsService>,
     request: web::Json<LoginRequest>,
 ) -> HttpResponse {
     let _span = info_span!("login-api", correlation_id = uuid::Uuid::new_v4().to_string()).entered();
-    let start = std::time::Instant::now();
     let mut tags = HashMap::new();
     tags.insert("endpoint".to_string(), "login".to_string());
 
     let start = std::time::Instant::now();
     // Validate request
-    if let Err(_) = request.validate() {
+    if request.validate().is_err() {
         metrics.increment("auth.validation.failed", Some(tags.clone()));
         return HttpResponse::UnprocessableEntity().json(ApiResponse::<AuthResponse> {
             success: false,
@@ -113,16 +122,9 @@ async fn login(
     let duration = start.elapsed();
     info!("Validation process took: {:?}", duration);
 
-    let start = std::time::Instant::now();
-    // Get JWT secret from environment
-    let jwt_secret = std::env::var("JWT_SECRET").expect("JWT_SECRET must be set");
-
-    let duration = start.elapsed();
-    info!("JWT secret process took: {:?}", duration);
-
     let start = std::time::Instant::now();
     // Create auth service
-    let auth_service = AuthService::new(pool.get_ref().clone(), jwt_secret);
+    let auth_service = AuthService::new(pool.get_ref().clone(), read_pool.0.clone(), &config);
 
     let duration = start.elapsed();
     info!("Auth service process took: {:?}", duration);
@@ -140,7 +142,24 @@ async fn login(
             })
         },
         Err(e) => {
-            if e.to_string() == "Invalid email or password" {
+            if let Some(locked) = e.downcast_ref::<AccountLocked>() {
+                tags.insert("error".to_string(), "account_locked".to_string());
+                metrics.increment("auth.login.failed", Some(tags.clone()));
+                metrics.timing("auth.login.duration", start.elapsed(), Some(tags));
+                // Rounded up so clients retrying on the header don't land just before the unlock
+                let retry_after = (locked.until - Utc::now()).num_seconds().max(0) + 1;
+                HttpResponse::TooManyRequests()
+                    .insert_header((RETRY_AFTER, retry_after.to_string()))
+                    .json(ApiResponse::<AuthResponse> {
+                        success: false,
+                        data: None,
+                        errors: Some(vec![ApiError {
+                            entity: "SOCIO_ECHO_BE".to_string(),
+                            code: "1019".to_string(),
+                            cause: "ACCOUNT_LOCKED".to_string(),
+                        }]),
+                    })
+            } else if e.to_string() == "Invalid email or password" {
                 tags.insert("error".to_string(), "invalid_credentials".to_string());
                 metrics.increment("auth.login.failed", Some(tags.clone()));
                 metrics.timing("auth.login.duration", start.elapsed(), Some(tags));
@@ -169,4 +188,530 @@ async fn login(
             }
         }
     }
-} 
\ No newline at end of file
+}
+
+// The signed in user's account, including when it was created and last updated
+#[actix_web::get("/me")]
+async fn me(
+    user: AuthenticatedUser,
+    config: web::Data<Config>,
+    pool: web::Data<PgPool>,
+    read_pool: web::Data<ReadPool>,
+    metrics: web::Data<MetricsService>,
+) -> HttpResponse {
+    let start = std::time::Instant::now();
+    let mut tags = HashMap::new();
+    tags.insert("endpoint".to_string(), "me".to_string());
+
+    let auth_service = AuthService::new(pool.get_ref().clone(), read_pool.0.clone(), &config);
+
+    match auth_service.find_user(user.user_id).await {
+        Ok(Some(user)) => {
+            metrics.increment("auth.me.success", Some(tags.clone()));
+            metrics.timing("auth.me.duration", start.elapsed(), Some(tags));
+            HttpResponse::Ok().json(ApiResponse {
+                success: true,
+                data: Some(user),
+                errors: None,
+            })
+        },
+        Ok(None) => {
+            tags.insert("error".to_string(), "user_not_found".to_string());
+            metrics.increment("auth.me.failed", Some(tags.clone()));
+            metrics.timing("auth.me.duration", start.elapsed(), Some(tags));
+            HttpResponse::NotFound().json(ApiResponse::<User> {
+                success: false,
+                data: None,
+                errors: Some(vec![ApiError {
+                    entity: "SOCIO_ECHO_BE".to_string(),
+                    code: "1004".to_string(),
+                    cause: "USER_NOT_FOUND".to_string(),
+                }]),
+            })
+        },
+        Err(e) => {
+            log::error!("Failed to load user {}: {}", user.user_id, e);
+            tags.insert("error".to_string(), "system_error".to_string());
+            metrics.increment("auth.me.failed", Some(tags.clone()));
+            metrics.timing("auth.me.duration", start.elapsed(), Some(tags));
+            HttpResponse::InternalServerError().json(ApiResponse::<User> {
+                success: false,
+                data: None,
+                errors: Some(vec![ApiError {
+                    entity: "SOCIO_ECHO_BE".to_string(),
+                    code: "1000".to_string(),
+                    cause: "SYSTEM_ERROR".to_string(),
+                }]),
+            })
+        }
+    }
+}
+
+#[actix_web::post("/me/password")]
+async fn change_password(
+    user: AuthenticatedUser,
+    config: web::Data<Config>,
+    pool: web::Data<PgPool>,
+    read_pool: web::Data<ReadPool>,
+    metrics: web::Data<MetricsService>,
+    request: web::Json<ChangePasswordRequest>,
+) -> HttpResponse {
+    let start = std::time::Instant::now();
+    let mut tags = HashMap::new();
+    tags.insert("endpoint".to_string(), "change_password".to_string());
+
+    // Validate request
+    if request.validate().is_err() {
+        metrics.increment("auth.validation.failed", Some(tags.clone()));
+        return HttpResponse::UnprocessableEntity().json(ApiResponse::<()> {
+            success: false,
+            data: None,
+            errors: Some(vec![ApiError {
+                entity: "SOCIO_ECHO_BE".to_string(),
+                code: "1001".to_string(),
+                cause: "INVALID_PASSWORD".to_string(),
+            }]),
+        });
+    }
+
+    if request.new_password == request.current_password {
+        tags.insert("error".to_string(), "password_unchanged".to_string());
+        metrics.increment("auth.change_password.failed", Some(tags.clone()));
+        metrics.timing("auth.change_password.duration", start.elapsed(), Some(tags));
+        return HttpResponse::UnprocessableEntity().json(ApiResponse::<()> {
+            success: false,
+            data: None,
+            errors: Some(vec![ApiError {
+                entity: "SOCIO_ECHO_BE".to_string(),
+                code: "1001".to_string(),
+                cause: "PASSWORD_UNCHANGED".to_string(),
+            }]),
+        });
+    }
+
+    let auth_service = AuthService::new(pool.get_ref().clone(), read_pool.0.clone(), &config);
+
+    match auth_service.change_password(user.user_id, request.into_inner()).await {
+        Ok(()) => {
+            metrics.increment("auth.change_password.success", Some(tags.clone()));
+            metrics.timing("auth.change_password.duration", start.elapsed(), Some(tags));
+            HttpResponse::Ok().json(ApiResponse::<()> {
+                success: true,
+                data: None,
+                errors: None,
+            })
+        },
+        Err(e) => {
+            if e.to_string() == "Invalid current password" {
+                tags.insert("error".to_string(), "invalid_current_password".to_string());
+                metrics.increment("auth.change_password.failed", Some(tags.clone()));
+                metrics.timing("auth.change_password.duration", start.elapsed(), Some(tags));
+                HttpResponse::UnprocessableEntity().json(ApiResponse::<()> {
+                    success: false,
+                    data: None,
+                    errors: Some(vec![ApiError {
+                        entity: "SOCIO_ECHO_BE".to_string(),
+                        code: "1001".to_string(),
+                        cause: "INVALID_CURRENT_PASSWORD".to_string(),
+                    }]),
+                })
+            } else {
+                tags.insert("error".to_string(), "system_error".to_string());
+                metrics.increment("auth.change_password.failed", Some(tags.clone()));
+                metrics.timing("auth.change_password.duration", start.elapsed(), Some(tags));
+                HttpResponse::InternalServerError().json(ApiResponse::<()> {
+                    success: false,
+                    data: None,
+                    errors: Some(vec![ApiError {
+                        entity: "SOCIO_ECHO_BE".to_string(),
+                        code: "1000".to_string(),
+                        cause: "SYSTEM_ERROR".to_string(),
+                    }]),
+                })
+            }
+        }
+    }
+}
+
+#[actix_web::post("/refresh")]
+async fn refresh(
+    config: web::Data<Config>,
+    pool: web::Data<PgPool>,
+    read_pool: web::Data<ReadPool>,
+    metrics: web::Data<MetricsService>,
+    request: web::Json<RefreshTokenRequest>,
+) -> HttpResponse {
+    let start = std::time::Instant::now();
+    let mut tags = HashMap::new();
+    tags.insert("endpoint".to_string(), "refresh".to_string());
+
+    // Validate request
+    if request.validate().is_err() {
+        metrics.increment("auth.validation.failed", Some(tags.clone()));
+        return HttpResponse::UnprocessableEntity().json(ApiResponse::<AuthResponse> {
+            success: false,
+            data: None,
+            errors: Some(vec![ApiError {
+                entity: "SOCIO_ECHO_BE".to_string(),
+                code: "1008".to_string(),
+                cause: "INVALID_REFRESH_TOKEN".to_string(),
+            }]),
+        });
+    }
+
+    let auth_service = AuthService::new(pool.get_ref().clone(), read_pool.0.clone(), &config);
+
+    match auth_service.refresh(&request.refresh_token).await {
+        Ok(response) => {
+            metrics.increment("auth.refresh.success", Some(tags.clone()));
+            metrics.timing("auth.refresh.duration", start.elapsed(), Some(tags));
+            HttpResponse::Ok().json(ApiResponse {
+                success: true,
+                data: Some(response),
+                errors: None,
+            })
+        },
+        Err(e) => {
+            if e.to_string() == "Invalid refresh token" {
+                tags.insert("error".to_string(), "invalid_refresh_token".to_string());
+                metrics.increment("auth.refresh.failed", Some(tags.clone()));
+                metrics.timing("auth.refresh.duration", start.elapsed(), Some(tags));
+                HttpResponse::Unauthorized().json(ApiResponse::<AuthResponse> {
+                    success: false,
+                    data: None,
+                    errors: Some(vec![ApiError {
+                        entity: "SOCIO_ECHO_BE".to_string(),
+                        code: "1008".to_string(),
+                        cause: "INVALID_REFRESH_TOKEN".to_string(),
+                    }]),
+                })
+            } else {
+                tags.insert("error".to_string(), "system_error".to_string());
+                metrics.increment("auth.refresh.failed", Some(tags.clone()));
+                metrics.timing("auth.refresh.duration", start.elapsed(), Some(tags));
+                HttpResponse::InternalServerError().json(ApiResponse::<AuthResponse> {
+                    success: false,
+                    data: None,
+                    errors: Some(vec![ApiError {
+                        entity: "SOCIO_ECHO_BE".to_string(),
+                        code: "1000".to_string(),
+                        cause: "SYSTEM_ERROR".to_string(),
+                    }]),
+                })
+            }
+        }
+    }
+}
+
+#[actix_web::post("/logout")]
+async fn logout(
+    user: AuthenticatedUser,
+    config: web::Data<Config>,
+    pool: web::Data<PgPool>,
+    read_pool: web::Data<ReadPool>,
+    metrics: web::Data<MetricsService>,
+    request: Option<web::Json<LogoutRequest>>,
+) -> HttpResponse {
+    let start = std::time::Instant::now();
+    let mut tags = HashMap::new();
+    tags.insert("endpoint".to_string(), "logout".to_string());
+
+    // The body is optional; without it only the access token is revoked
+    let request = request.map(|r| r.into_inner()).unwrap_or_default();
+    let auth_service = AuthService::new(pool.get_ref().clone(), read_pool.0.clone(), &config);
+
+    match auth_service
+        .revoke_token(user.user_id, &user.jti, user.expires_at, request.refresh_token.as_deref())
+        .await
+    {
+        Ok(()) => {
+            metrics.increment("auth.logout.success", Some(tags.clone()));
+            metrics.timing("auth.logout.duration", start.elapsed(), Some(tags));
+            HttpResponse::Ok().json(ApiResponse::<()> {
+                success: true,
+                data: None,
+                errors: None,
+            })
+        },
+        Err(e) => {
+            log::error!("Failed to revoke token: {}", e);
+            tags.insert("error".to_string(), "system_error".to_string());
+            metrics.increment("auth.logout.failed", Some(tags.clone()));
+            metrics.timing("auth.logout.duration", start.elapsed(), Some(tags));
+            HttpResponse::InternalServerError().json(ApiResponse::<()> {
+                success: false,
+                data: None,
+                errors: Some(vec![ApiError {
+                    entity: "SOCIO_ECHO_BE".to_string(),
+                    code: "1000".to_string(),
+                    cause: "SYSTEM_ERROR".to_string(),
+                }]),
+            })
+        }
+    }
+}
+
+#[actix_web::post("/verify-email")]
+async fn verify_email(
+    config: web::Data<Config>,
+    pool: web::Data<PgPool>,
+    read_pool: web::Data<ReadPool>,
+    metrics: web::Data<MetricsService>,
+    request: web::Json<VerifyEmailRequest>,
+) -> HttpResponse {
+    let start = std::time::Instant::now();
+    let mut tags = HashMap::new();
+    tags.insert("endpoint".to_string(), "verify_email".to_string());
+
+    // Validate request
+    if request.validate().is_err() {
+        metrics.increment("auth.validation.failed", Some(tags.clone()));
+        return HttpResponse::UnprocessableEntity().json(ApiResponse::<()> {
+            success: false,
+            data: None,
+            errors: Some(vec![ApiError {
+                entity: "SOCIO_ECHO_BE".to_string(),
+                code: "1023".to_string(),
+                cause: "INVALID_VERIFICATION_TOKEN".to_string(),
+            }]),
+        });
+    }
+
+    let auth_service = AuthService::new(pool.get_ref().clone(), read_pool.0.clone(), &config);
+
+    match auth_service.verify_email(&request.token).await {
+        Ok(()) => {
+            metrics.increment("auth.verify_email.success", Some(tags.clone()));
+            metrics.timing("auth.verify_email.duration", start.elapsed(), Some(tags));
+            HttpResponse::Ok().json(ApiResponse::<()> {
+                success: true,
+                data: None,
+                errors: None,
+            })
+        },
+        Err(e) => {
+            if e.to_string() == "Invalid verification token" {
+                tags.insert("error".to_string(), "invalid_verification_token".to_string());
+                metrics.increment("auth.verify_email.failed", Some(tags.clone()));
+                metrics.timing("auth.verify_email.duration", start.elapsed(), Some(tags));
+                HttpResponse::UnprocessableEntity().json(ApiResponse::<()> {
+                    success: false,
+                    data: None,
+                    errors: Some(vec![ApiError {
+                        entity: "SOCIO_ECHO_BE".to_string(),
+                        code: "1023".to_string(),
+                        cause: "INVALID_VERIFICATION_TOKEN".to_string(),
+                    }]),
+                })
+            } else {
+                log::error!("Failed to verify email: {}", e);
+                tags.insert("error".to_string(), "system_error".to_string());
+                metrics.increment("auth.verify_email.failed", Some(tags.clone()));
+                metrics.timing("auth.verify_email.duration", start.elapsed(), Some(tags));
+                HttpResponse::InternalServerError().json(ApiResponse::<()> {
+                    success: false,
+                    data: None,
+                    errors: Some(vec![ApiError {
+                        entity: "SOCIO_ECHO_BE".to_string(),
+                        code: "1000".to_string(),
+                        cause: "SYSTEM_ERROR".to_string(),
+                    }]),
+                })
+            }
+        }
+    }
+}
+
+#[actix_web::post("/resend-verification")]
+async fn resend_verification(
+    user: AuthenticatedUser,
+    config: web::Data<Config>,
+    pool: web::Data<PgPool>,
+    read_pool: web::Data<ReadPool>,
+    metrics: web::Data<MetricsService>,
+    email_sender: web::Data<dyn EmailSender>,
+) -> HttpResponse {
+    let start = std::time::Instant::now();
+    let mut tags = HashMap::new();
+    tags.insert("endpoint".to_string(), "resend_verification".to_string());
+
+    let auth_service = AuthService::new(pool.get_ref().clone(), read_pool.0.clone(), &config);
+
+    match auth_service.resend_verification(user.user_id, email_sender.get_ref()).await {
+        Ok(()) => {
+            metrics.increment("auth.resend_verification.success", Some(tags.clone()));
+            metrics.timing("auth.resend_verification.duration", start.elapsed(), Some(tags));
+            HttpResponse::Accepted().json(ApiResponse::<()> {
+                success: true,
+                data: None,
+                errors: None,
+            })
+        },
+        Err(e) => {
+            if let Some(throttled) = e.downcast_ref::<VerificationThrottled>() {
+                tags.insert("error".to_string(), "throttled".to_string());
+                metrics.increment("auth.resend_verification.failed", Some(tags.clone()));
+                metrics.timing("auth.resend_verification.duration", start.elapsed(), Some(tags));
+                HttpResponse::TooManyRequests()
+                    .insert_header((RETRY_AFTER, throttled.retry_after_secs.to_string()))
+                    .json(ApiResponse::<()> {
+                        success: false,
+                        data: None,
+                        errors: Some(vec![ApiError {
+                            entity: "SOCIO_ECHO_BE".to_string(),
+                            code: "1026".to_string(),
+                            cause: "VERIFICATION_RESEND_TOO_SOON".to_string(),
+                        }]),
+                    })
+            } else if e.to_string() == "Email already verified" {
+                tags.insert("error".to_string(), "already_verified".to_string());
+                metrics.increment("auth.resend_verification.failed", Some(tags.clone()));
+                metrics.timing("auth.resend_verification.duration", start.elapsed(), Some(tags));
+                HttpResponse::Conflict().json(ApiResponse::<()> {
+                    success: false,
+                    data: None,
+                    errors: Some(vec![ApiError {
+                        entity: "SOCIO_ECHO_BE".to_string(),
+                        code: "1025".to_string(),
+                        cause: "EMAIL_ALREADY_VERIFIED".to_string(),
+                    }]),
+                })
+            } else {
+                log::error!("Failed to resend verification email: {}", e);
+                tags.insert("error".to_string(), "system_error".to_string());
+                metrics.increment("auth.resend_verification.failed", Some(tags.clone()));
+                metrics.timing("auth.resend_verification.duration", start.elapsed(), Some(tags));
+                HttpResponse::InternalServerError().json(ApiResponse::<()> {
+                    success: false,
+                    data: None,
+                    errors: Some(vec![ApiError {
+                        entity: "SOCIO_ECHO_BE".to_string(),
+                        code: "1000".to_string(),
+                        cause: "SYSTEM_ERROR".to_string(),
+                    }]),
+                })
+            }
+        }
+    }
+}
+
+// Answers the same whether or not the email is registered, and sends the mail in the
+// background so the response time doesn't tell either
+#[actix_web::post("/password/forgot")]
+async fn forgot_password(
+    config: web::Data<Config>,
+    pool: web::Data<PgPool>,
+    read_pool: web::Data<ReadPool>,
+    metrics: web::Data<MetricsService>,
+    email_sender: web::Data<dyn EmailSender>,
+    request: web::Json<ForgotPasswordRequest>,
+) -> HttpResponse {
+    let mut tags = HashMap::new();
+    tags.insert("endpoint".to_string(), "forgot_password".to_string());
+
+    // Validate request
+    if request.validate().is_err() {
+        metrics.increment("auth.validation.failed", Some(tags.clone()));
+        return HttpResponse::UnprocessableEntity().json(ApiResponse::<()> {
+            success: false,
+            data: None,
+            errors: Some(vec![ApiError {
+                entity: "SOCIO_ECHO_BE".to_string(),
+                code: "1001".to_string(),
+                cause: "INVALID_EMAIL".to_string(),
+            }]),
+        });
+    }
+
+    let auth_service = AuthService::new(pool.get_ref().clone(), read_pool.0.clone(), &config);
+    let email = request.into_inner().email;
+
+    tokio::spawn(async move {
+        if let Err(e) = auth_service.forgot_password(&email, email_sender.get_ref()).await {
+            log::error!("Failed to send password reset email: {}", e);
+            metrics.increment("auth.forgot_password.failed", Some(tags));
+        }
+    });
+
+    HttpResponse::Accepted().json(ApiResponse::<()> {
+        success: true,
+        data: None,
+        errors: None,
+    })
+}
+
+#[actix_web::post("/password/reset")]
+async fn reset_password(
+    config: web::Data<Config>,
+    pool: web::Data<PgPool>,
+    read_pool: web::Data<ReadPool>,
+    metrics: web::Data<MetricsService>,
+    request: web::Json<ResetPasswordRequest>,
+) -> HttpResponse {
+    let start = std::time::Instant::now();
+    let mut tags = HashMap::new();
+    tags.insert("endpoint".to_string(), "reset_password".to_string());
+
+    // Validate request
+    if let Err(e) = request.validate() {
+        metrics.increment("auth.validation.failed", Some(tags.clone()));
+        let (code, cause) = if e.field_errors().contains_key("token") {
+            ("1027", "INVALID_RESET_TOKEN")
+        } else {
+            ("1001", "INVALID_PASSWORD")
+        };
+        return HttpResponse::UnprocessableEntity().json(ApiResponse::<()> {
+            success: false,
+            data: None,
+            errors: Some(vec![ApiError {
+                entity: "SOCIO_ECHO_BE".to_string(),
+                code: code.to_string(),
+                cause: cause.to_string(),
+            }]),
+        });
+    }
+
+    let auth_service = AuthService::new(pool.get_ref().clone(), read_pool.0.clone(), &config);
+
+    match auth_service.reset_password(request.into_inner()).await {
+        Ok(()) => {
+            metrics.increment("auth.reset_password.success", Some(tags.clone()));
+            metrics.timing("auth.reset_password.duration", start.elapsed(), Some(tags));
+            HttpResponse::Ok().json(ApiResponse::<()> {
+                success: true,
+                data: None,
+                errors: None,
+            })
+        },
+        Err(e) => {
+            if e.to_string() == "Invalid reset token" {
+                tags.insert("error".to_string(), "invalid_reset_token".to_string());
+                metrics.increment("auth.reset_password.failed", Some(tags.clone()));
+                metrics.timing("auth.reset_password.duration", start.elapsed(), Some(tags));
+                HttpResponse::UnprocessableEntity().json(ApiResponse::<()> {
+                    success: false,
+                    data: None,
+                    errors: Some(vec![ApiError {
+                        entity: "SOCIO_ECHO_BE".to_string(),
+                        code: "1027".to_string(),
+                        cause: "INVALID_RESET_TOKEN".to_string(),
+                    }]),
+                })
+            } else {
+                log::error!("Failed to reset password: {}", e);
+                tags.insert("error".to_string(), "system_error".to_string());
+                metrics.increment("auth.reset_password.failed", Some(tags.clone()));
+                metrics.timing("auth.reset_password.duration", start.elapsed(), Some(tags));
+                HttpResponse::InternalServerError().json(ApiResponse::<()> {
+                    success: false,
+                    data: None,
+                    errors: Some(vec![ApiError {
+                        entity: "SOCIO_ECHO_BE".to_string(),
+                        code: "1000".to_string(),
+                        cause: "SYSTEM_ERROR".to_string(),
+                    }]),
+                })
+            }
+        }
+    }
+}
@@ -1,7 +1,11 @@
-use actix_web::{get, HttpRequest, HttpResponse};
+use actix_web::{get, http::header::RETRY_AFTER, web, HttpResponse};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Timelike, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use crate::utils::validate_token;
+use crate::{
+    middleware::auth::AuthenticatedUser,
+    services::dashboard_service::{CityCountsError, DashboardService, DEFAULT_RANGE_FROM, DEFAULT_RANGE_TO},
+};
 
 #[derive(Debug, Serialize)]
 pub struct DashboardCityCountResponse {
@@ -13,6 +17,10 @@ pub struct DashboardCityCountResponse {
 #[derive(Debug, Serialize)]
 pub struct DashboardCityCountData {
     pub cities: HashMap<String, i64>,
+    // When the counts were computed; older than the request when served from the cache
+    pub cached_at: DateTime<Utc>,
+    // Elasticsearch is unavailable and the counts are older than the cache TTL
+    pub degraded: bool,
 }
 
 #[derive(Debug, Serialize)]
@@ -25,143 +33,297 @@ pub struct DashboardError {
 #[derive(Debug, Deserialize)]
 pub struct DashboardCityCountQuery {
     pub cities: String, // comma separated
+    // ES date math (e.g. now-4w/w) or ISO dates, both or neither
+    pub from: Option<String>,
+    pub to: Option<String>,
+    // Recompute instead of serving cached counts
+    #[serde(default)]
+    pub refresh: bool,
 }
 
 #[get("/summary/city")]
 pub async fn get_city_count(
-    req: HttpRequest,
-    query: actix_web::web::Query<std::collections::HashMap<String, String>>,
+    _user: AuthenticatedUser,
+    dashboard: web::Data<DashboardService>,
+    query: Result<actix_web::web::Query<DashboardCityCountQuery>, actix_web::Error>,
 ) -> HttpResponse {
-    // JWT validation
-    let jwt_secret = std::env::var("JWT_SECRET").unwrap_or_default();
-    let token = req.headers().get("x-user-token").and_then(|v| v.to_str().ok());
-    if token.is_none() {
-        return HttpResponse::Unauthorized().json(DashboardCityCountResponse {
-            success: false,
-            data: None,
-            errors: Some(vec![DashboardError {
-                code: "401".to_string(),
-                entity: "SOCIO_ECHO".to_string(),
-                message: "MISSING_OR_INVALID_TOKEN".to_string(),
-            }]),
-        });
-    }
-    if validate_token(token.unwrap(), &jwt_secret).is_err() {
-        return HttpResponse::Unauthorized().json(DashboardCityCountResponse {
-            success: false,
-            data: None,
-            errors: Some(vec![DashboardError {
-                code: "401".to_string(),
-                entity: "SOCIO_ECHO".to_string(),
-                message: "MISSING_OR_INVALID_TOKEN".to_string(),
-            }]),
-        });
-    }
-
-    // Check for 'cities' param
-    let cities_param = query.get("cities");
-    if cities_param.is_none() {
-        return HttpResponse::BadRequest().json(DashboardCityCountResponse {
-            success: false,
-            data: None,
-            errors: Some(vec![DashboardError {
-                code: "1003".to_string(),
-                entity: "SOCIO_ECHO".to_string(),
-                message: "Query param 'cities' is required".to_string(),
-            }]),
-        });
-    }
-    let city_list: Vec<String> = cities_param.unwrap().split(',').map(|c| c.trim().to_string()).collect();
-
-    // Prepare ElasticSearch query
-
-    // ELASTICSEARCH_URL="https://100.95.170.73:9200"
-    // ELASTICSEARCH_USER='elastic'
-    // ELASTICSEARCH_PASS='wWEVEfUXVVnIyz8=a62k'
-    let es_url = "https://100.95.170.73:9200";
-    let es_user = "elastic";
-    let es_pass = "wWEVEfUXVVnIyz8=a62k";
-    let client = reqwest::Client::builder()
-        .danger_accept_invalid_certs(true)
-        .build()
-        .unwrap();
-
-    let es_url = format!("{}/media-online-*/_search?pretty", es_url.clone());
-    let es_body = serde_json::json!({
-        "size": 0,
-        "query": {
-            "bool": {
-                "filter": [
-                    { "range": { "published_at": { "gte": "now-100w/w", "lt": "now/w" } } }
-                ]
-            }
-        },
-        "aggs": {
-            "cities_count": {
-                "terms": {
-                    "field": "cities.keyword",
-                    "include": city_list,
-                    "size": 10
-                }
-            }
-        }
-    });
-
-    let res = client
-        .get(&es_url)
-        .basic_auth(es_user.clone(), Some(es_pass.clone()))
-        .header("Content-Type", "application/json")
-        .json(&es_body)
-        .send()
-        .await;
-
-    let res_json = match res {
-        Ok(r) => r,
+    // 'cities' is required, so a failed extraction means it is missing or malformed
+    let query = match query {
+        Ok(q) => q.into_inner(),
         Err(e) => {
-            return HttpResponse::InternalServerError().json(DashboardCityCountResponse {
+            return HttpResponse::BadRequest().json(DashboardCityCountResponse {
                 success: false,
                 data: None,
                 errors: Some(vec![DashboardError {
-                    code: "100".to_string(),
+                    code: "1003".to_string(),
                     entity: "SOCIO_ECHO".to_string(),
-                    message: format!("ELASTIC_REQUEST_ERROR: {}", e),
+                    message: format!("INVALID_QUERY_PARAMS: {}", e),
+                }]),
+            });
+        }
+    };
+    let city_list: Vec<String> = query.cities.split(',').map(|c| c.trim().to_string()).collect();
+    let (range_from, range_to) = match validate_range(query.from, query.to) {
+        Ok(range) => range,
+        Err(message) => {
+            return HttpResponse::BadRequest().json(DashboardCityCountResponse {
+                success: false,
+                data: None,
+                errors: Some(vec![DashboardError {
+                    code: "1003".to_string(),
+                    entity: "SOCIO_ECHO".to_string(),
+                    message,
                 }]),
             });
         }
     };
 
-    log::info!("tes: {:?}", res_json);
-
-    let val = match res_json.json::<serde_json::Value>().await {
-        Ok(val) => val,
-        Err(e) => {
+    let counts = match dashboard.city_counts(&city_list, &range_from, &range_to, query.refresh).await {
+        Ok(counts) => counts,
+        Err(CityCountsError::Unavailable { retry_after }) => {
+            return HttpResponse::ServiceUnavailable()
+                .insert_header((RETRY_AFTER, (retry_after.as_secs() + 1).to_string()))
+                .json(DashboardCityCountResponse {
+                    success: false,
+                    data: None,
+                    errors: Some(vec![DashboardError {
+                        code: "1029".to_string(),
+                        entity: "SOCIO_ECHO".to_string(),
+                        message: "ELASTICSEARCH_UNAVAILABLE".to_string(),
+                    }]),
+                });
+        }
+        Err(CityCountsError::NotConfigured) => {
+            return HttpResponse::ServiceUnavailable().json(DashboardCityCountResponse {
+                success: false,
+                data: None,
+                errors: Some(vec![DashboardError {
+                    code: "1029".to_string(),
+                    entity: "SOCIO_ECHO".to_string(),
+                    message: "ELASTICSEARCH_UNAVAILABLE".to_string(),
+                }]),
+            });
+        }
+        Err(CityCountsError::Request(e)) => {
+            let message = if e.is_decode() {
+                format!("ELASTIC_PARSE_ERROR: {}", e)
+            } else {
+                format!("ELASTIC_REQUEST_ERROR: {}", e)
+            };
             return HttpResponse::InternalServerError().json(DashboardCityCountResponse {
                 success: false,
                 data: None,
                 errors: Some(vec![DashboardError {
                     code: "100".to_string(),
                     entity: "SOCIO_ECHO".to_string(),
-                    message: format!("ELASTIC_PARSE_ERROR: {}", e),
+                    message,
                 }]),
             });
         }
     };
-    let mut cities = HashMap::new();
-    if let Some(buckets) = val["aggregations"]["cities_count"]["buckets"].as_array() {
-        for bucket in buckets {
-            if let (Some(key), Some(doc_count)) = (bucket["key"].as_str(), bucket["doc_count"].as_i64()) {
-                cities.insert(key.to_string(), doc_count);
-            }
-        }
-    }
-    let mut response_cities = HashMap::new();
-    for city in &city_list {
-        response_cities.insert(city.clone(), *cities.get(city).unwrap_or(&0));
-    }
-    let data = DashboardCityCountData { cities: response_cities };
+    let data = DashboardCityCountData {
+        cities: counts.cities,
+        cached_at: counts.cached_at,
+        degraded: counts.degraded,
+    };
     HttpResponse::Ok().json(DashboardCityCountResponse {
         success: true,
         data: Some(data),
         errors: None,
     })
-} 
\ No newline at end of file
+} 
+// Defaults to the last 100 weeks. Bounds are passed to ES as given; they are only resolved
+// here (date math arithmetic approximately) to reject malformed or inverted ranges.
+fn validate_range(from: Option<String>, to: Option<String>) -> Result<(String, String), String> {
+    let (from, to) = match (from, to) {
+        (Some(from), Some(to)) => (from, to),
+        (None, None) => return Ok((DEFAULT_RANGE_FROM.to_string(), DEFAULT_RANGE_TO.to_string())),
+        _ => return Err("INVALID_QUERY_PARAMS: from and to must be given together".to_string()),
+    };
+
+    let now = Utc::now();
+    let from_at = resolve_date(&from, now).ok_or_else(|| format!("INVALID_QUERY_PARAMS: invalid from '{}'", from))?;
+    let to_at = resolve_date(&to, now).ok_or_else(|| format!("INVALID_QUERY_PARAMS: invalid to '{}'", to))?;
+    if from_at >= to_at {
+        return Err("INVALID_QUERY_PARAMS: from must be before to".to_string());
+    }
+
+    Ok((from, to))
+}
+
+fn resolve_date(value: &str, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    if let Some(math) = value.strip_prefix("now") {
+        return apply_date_math(now, math);
+    }
+    if let Ok(at) = DateTime::parse_from_rfc3339(value) {
+        return Some(at.with_timezone(&Utc));
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|at| at.and_utc())
+}
+
+// Applies ES date math like `-100w/w` or `+1d`
+fn apply_date_math(mut at: DateTime<Utc>, math: &str) -> Option<DateTime<Utc>> {
+    let mut chars = math.chars().peekable();
+    while let Some(op) = chars.next() {
+        match op {
+            '+' | '-' => {
+                let mut amount = String::new();
+                while let Some(c) = chars.peek().filter(|c| c.is_ascii_digit()) {
+                    amount.push(*c);
+                    chars.next();
+                }
+                let step = date_math_unit(chars.next()?)?.checked_mul(amount.parse::<i32>().ok()?)?;
+                at = if op == '+' { at.checked_add_signed(step)? } else { at.checked_sub_signed(step)? };
+            }
+            '/' => {
+                at = round_down(at, chars.next()?)?;
+            }
+            _ => return None,
+        }
+    }
+    Some(at)
+}
+
+fn date_math_unit(unit: char) -> Option<Duration> {
+    match unit {
+        'y' => Some(Duration::days(365)),
+        'M' => Some(Duration::days(30)),
+        'w' => Some(Duration::weeks(1)),
+        'd' => Some(Duration::days(1)),
+        'h' | 'H' => Some(Duration::hours(1)),
+        'm' => Some(Duration::minutes(1)),
+        's' => Some(Duration::seconds(1)),
+        _ => None,
+    }
+}
+
+// Start of the unit `at` falls in, as ES rounds both gte and lt bounds; weeks start on Monday
+fn round_down(at: DateTime<Utc>, unit: char) -> Option<DateTime<Utc>> {
+    let date = at.date_naive();
+    let start = match unit {
+        'y' => date.with_ordinal(1)?.and_hms_opt(0, 0, 0)?,
+        'M' => date.with_day(1)?.and_hms_opt(0, 0, 0)?,
+        'w' => (date - Duration::days(date.weekday().num_days_from_monday() as i64)).and_hms_opt(0, 0, 0)?,
+        'd' => date.and_hms_opt(0, 0, 0)?,
+        'h' | 'H' => date.and_hms_opt(at.hour(), 0, 0)?,
+        'm' => date.and_hms_opt(at.hour(), at.minute(), 0)?,
+        's' => date.and_hms_opt(at.hour(), at.minute(), at.second())?,
+        _ => return None,
+    };
+    Some(start.and_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration as StdDuration;
+
+    use actix_web::{http::StatusCode, test::{call_service, init_service, read_body_json, TestRequest}, App};
+    use serde_json::Value;
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::{
+        middleware::auth::tests::bearer,
+        services::{
+            app_services,
+            circuit_breaker::CircuitBreaker,
+            dashboard_service::{self, tests::fake_cluster},
+            metrics_service::MetricsService,
+        },
+    };
+
+    fn at(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn date_math_rounds_down_to_the_unit() {
+        // A Wednesday
+        let now = at("2025-07-09T13:45:30Z");
+
+        assert_eq!(apply_date_math(now, "/w"), Some(at("2025-07-07T00:00:00Z")));
+        assert_eq!(apply_date_math(now, "/d"), Some(at("2025-07-09T00:00:00Z")));
+        assert_eq!(apply_date_math(now, "/M"), Some(at("2025-07-01T00:00:00Z")));
+        assert_eq!(apply_date_math(now, "/h"), Some(at("2025-07-09T13:00:00Z")));
+        assert_eq!(apply_date_math(now, "-1w/w"), Some(at("2025-06-30T00:00:00Z")));
+        assert_eq!(apply_date_math(now, "/x"), None);
+    }
+
+    #[test]
+    fn rounded_ranges_are_compared_after_rounding() {
+        assert!(validate_range(Some("now/w".to_string()), Some("now".to_string())).is_ok());
+        assert!(validate_range(Some("now".to_string()), Some("now/w".to_string())).is_err());
+        assert!(validate_range(Some("now-1w/w".to_string()), Some("now/w".to_string())).is_ok());
+        assert!(validate_range(Some("now/w".to_string()), Some("now-1w/w".to_string())).is_err());
+        assert!(validate_range(Some("now/y".to_string()), Some("now/y".to_string())).is_err());
+    }
+
+    async fn call(pool: PgPool, dashboard: DashboardService, uri: &str) -> actix_web::dev::ServiceResponse {
+        let services = app_services::tests::services(pool, &[]);
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(services.config.clone()))
+                .app_data(web::Data::new(services.pool.clone()))
+                .app_data(web::Data::new(services.read_pool.clone()))
+                .app_data(web::Data::new(dashboard))
+                .service(get_city_count),
+        )
+        .await;
+
+        let request = TestRequest::get()
+            .uri(uri)
+            .insert_header(("Authorization", bearer(&services.config, 1)))
+            .to_request();
+        call_service(&app, request).await
+    }
+
+    #[sqlx::test]
+    async fn without_elasticsearch_the_dashboard_is_unavailable(pool: PgPool) {
+        let breaker = CircuitBreaker::new(
+            "elasticsearch",
+            2,
+            StdDuration::from_secs(60),
+            StdDuration::from_secs(60),
+            MetricsService::noop(),
+        );
+        let dashboard = DashboardService::new(None, breaker, StdDuration::from_secs(60));
+        let response = call(pool, dashboard, "/summary/city?cities=Jakarta").await;
+
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert!(response.headers().get(RETRY_AFTER).is_none());
+        let body: Value = read_body_json(response).await;
+        assert_eq!(body["errors"][0]["code"], "1029");
+        assert_eq!(body["errors"][0]["message"], "ELASTICSEARCH_UNAVAILABLE");
+    }
+
+    #[sqlx::test]
+    async fn city_counts_need_the_cities_param(pool: PgPool) {
+        let cluster = fake_cluster(200).await;
+        let dashboard = dashboard_service::tests::service(&cluster.url, StdDuration::from_secs(60));
+
+        let response = call(pool, dashboard, "/summary/city?from=now-1w").await;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body: Value = read_body_json(response).await;
+        assert_eq!(body["success"], false);
+        assert_eq!(body["errors"][0]["code"], "1003");
+        assert!(body["errors"][0]["message"].as_str().unwrap().starts_with("INVALID_QUERY_PARAMS"));
+    }
+
+    #[sqlx::test]
+    async fn city_counts_are_returned_for_the_given_params(pool: PgPool) {
+        let cluster = fake_cluster(200).await;
+        let dashboard = dashboard_service::tests::service(&cluster.url, StdDuration::from_secs(60));
+
+        let response = call(pool, dashboard, "/summary/city?cities=Jakarta,%20Bandung&from=now-1w&to=now&refresh=true").await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: Value = read_body_json(response).await;
+        assert_eq!(body["success"], true);
+        assert_eq!(body["data"]["cities"]["Jakarta"], 3);
+        assert_eq!(body["data"]["degraded"], false);
+    }
+}
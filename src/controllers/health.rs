@@ -0,0 +1,101 @@
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::time::Duration;
+
+use actix_web::{web, HttpResponse};
+use serde::Serialize;
+use sqlx::PgPool;
+
+use crate::{
+    commons::minio_service::MinioService,
+    config::Config,
+    models::user::{ApiError, ApiResponse},
+    services::face_match_service::{FaceMatchHealth, FaceMatchService},
+};
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadinessResponse {
+    // "ok", "failed" or "skipped" per dependency
+    pub checks: BTreeMap<&'static str, &'static str>,
+}
+
+// Liveness: the process is up and serving requests
+#[actix_web::get("/health")]
+async fn health() -> HttpResponse {
+    HttpResponse::Ok().json(ApiResponse::<()> {
+        success: true,
+        data: None,
+        errors: None,
+    })
+}
+
+// Readiness: every dependency needed to serve traffic is reachable
+#[actix_web::get("/ready")]
+async fn ready(
+    config: web::Data<Config>,
+    pool: web::Data<PgPool>,
+    minio_service: web::Data<MinioService>,
+    face_match_service: web::Data<FaceMatchService>,
+) -> HttpResponse {
+    let timeout = Duration::from_millis(config.ready_check_timeout_millis);
+
+    let database = check(timeout, async {
+        sqlx::query("SELECT 1").execute(pool.get_ref()).await.map(|_| ()).map_err(anyhow::Error::from)
+    });
+    let storage = check(timeout, minio_service.check_bucket());
+    let face_match = async {
+        if config.ready_check_face_match {
+            let probe = async {
+                match face_match_service.health_check().await {
+                    FaceMatchHealth::Healthy => Ok(()),
+                    unhealthy => Err(anyhow::anyhow!("{}", unhealthy)),
+                }
+            };
+            Some(check(timeout, probe).await)
+        } else {
+            None
+        }
+    };
+    let (database, storage, face_match) = tokio::join!(database, storage, face_match);
+
+    let mut checks = BTreeMap::new();
+    let mut errors = Vec::new();
+    for (name, cause, result) in [
+        ("database", "DATABASE_UNREACHABLE", Some(database)),
+        ("storage", "STORAGE_UNREACHABLE", Some(storage)),
+        ("faceMatch", "FACE_MATCH_UNREACHABLE", face_match),
+    ] {
+        let state = match result {
+            None => "skipped",
+            Some(Ok(())) => "ok",
+            Some(Err(e)) => {
+                log::warn!("Readiness check {} failed: {}", name, e);
+                errors.push(ApiError {
+                    entity: "SOCIO_ECHO_BE".to_string(),
+                    code: "1000".to_string(),
+                    cause: cause.to_string(),
+                });
+                "failed"
+            }
+        };
+        checks.insert(name, state);
+    }
+
+    let ready = errors.is_empty();
+    let status_code = if ready { HttpResponse::Ok } else { HttpResponse::ServiceUnavailable };
+    status_code().json(ApiResponse {
+        success: ready,
+        data: Some(ReadinessResponse { checks }),
+        errors: (!ready).then_some(errors),
+    })
+}
+
+async fn check<F>(timeout: Duration, probe: F) -> anyhow::Result<()>
+where
+    F: Future<Output = anyhow::Result<()>>,
+{
+    tokio::time::timeout(timeout, probe)
+        .await
+        .unwrap_or_else(|_| Err(anyhow::anyhow!("timed out after {:?}", timeout)))
+}
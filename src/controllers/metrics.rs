@@ -0,0 +1,14 @@
+use actix_web::{web, HttpResponse};
+
+use crate::services::metrics_service::MetricsService;
+
+// Prometheus scrape target; 404 unless METRICS_PROMETHEUS_ENABLED is set
+#[actix_web::get("/metrics")]
+async fn prometheus_metrics(metrics: web::Data<MetricsService>) -> HttpResponse {
+    match metrics.render_prometheus() {
+        Some(body) => HttpResponse::Ok()
+            .content_type("text/plain; version=0.0.4")
+            .body(body),
+        None => HttpResponse::NotFound().finish(),
+    }
+}
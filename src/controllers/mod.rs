@@ -1,2 +1,5 @@
+pub mod admin;
 pub mod auth;
-pub mod dashboard; 
\ No newline at end of file
+pub mod dashboard;
+pub mod health;
+pub mod metrics;
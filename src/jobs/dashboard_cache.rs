@@ -0,0 +1,27 @@
+use std::time::Duration;
+
+use crate::services::dashboard_service::{DashboardService, DEFAULT_RANGE_FROM, DEFAULT_RANGE_TO};
+
+// Recomputes the default-range city counts for the configured cities on a fixed interval, so
+// dashboard requests for them are served from the cache. Requests must list exactly these
+// cities (in any order) to hit the precomputed entry. An interval of 0, no cities or no
+// Elasticsearch cluster disables it.
+pub fn spawn(dashboard: DashboardService, cities: Vec<String>, interval: Duration) {
+    if interval.is_zero() || cities.is_empty() || !dashboard.is_configured() {
+        log::info!("Dashboard cache refresh disabled");
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+
+        loop {
+            ticker.tick().await;
+
+            // The cache keeps serving the previous counts until its TTL runs out
+            if let Err(e) = dashboard.city_counts(&cities, DEFAULT_RANGE_FROM, DEFAULT_RANGE_TO, true).await {
+                log::warn!("Failed to refresh dashboard city counts: {}", e);
+            }
+        }
+    });
+}
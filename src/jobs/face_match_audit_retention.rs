@@ -0,0 +1,53 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use sqlx::PgPool;
+
+use crate::{services::metrics_service::MetricsService, submissions::submission_repository::SubmissionRepository};
+
+// Periodically purges face match audits and sampled captures older than `retention_days`.
+// A retention of 0 keeps them forever.
+pub fn spawn(
+    pool: PgPool,
+    metrics: MetricsService,
+    retention_days: u32,
+    archive_stats: bool,
+    interval: Duration,
+) {
+    if retention_days == 0 {
+        log::info!("Face match audit retention disabled");
+        return;
+    }
+
+    tokio::spawn(async move {
+        let repository = SubmissionRepository::new(pool.clone(), pool);
+        let mut ticker = tokio::time::interval(interval);
+
+        loop {
+            ticker.tick().await;
+
+            let cutoff = Utc::now() - chrono::Duration::days(retention_days as i64);
+            match repository.purge_face_match_audits(cutoff, archive_stats).await {
+                Ok(deleted) => {
+                    log::info!("Purged {} face match audits older than {}", deleted, cutoff);
+                    metrics.gauge("face_match_audit.purged", deleted as f64, None);
+                }
+                Err(e) => {
+                    metrics.increment("face_match_audit.purge_error", None);
+                    log::error!("Failed to purge face match audits: {}", e);
+                }
+            }
+
+            match repository.purge_face_match_captures(cutoff).await {
+                Ok(deleted) => {
+                    log::info!("Purged {} face match captures older than {}", deleted, cutoff);
+                    metrics.gauge("face_match_capture.purged", deleted as f64, None);
+                }
+                Err(e) => {
+                    metrics.increment("face_match_capture.purge_error", None);
+                    log::error!("Failed to purge face match captures: {}", e);
+                }
+            }
+        }
+    });
+}
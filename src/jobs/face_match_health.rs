@@ -0,0 +1,32 @@
+use std::time::Duration;
+
+use crate::services::face_match_service::FaceMatchService;
+
+// Checks the face match provider on a fixed interval so the face_match.health gauge keeps
+// reporting while there is no user traffic. An interval of 0 disables the check.
+pub fn spawn(face_match: FaceMatchService, interval: Duration) {
+    if interval.is_zero() {
+        log::info!("Face match health check disabled");
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        let mut was_healthy = true;
+
+        loop {
+            ticker.tick().await;
+
+            // Only changes are logged, so a long outage doesn't flood the log
+            let health = face_match.health_check().await;
+            if health.is_healthy() != was_healthy {
+                if health.is_healthy() {
+                    log::info!("Face match provider is healthy again");
+                } else {
+                    log::warn!("Face match provider is {}", health);
+                }
+                was_healthy = health.is_healthy();
+            }
+        }
+    });
+}
@@ -0,0 +1,8 @@
+pub mod dashboard_cache;
+pub mod face_match_audit_retention;
+pub mod face_match_health;
+pub mod submission_archival;
+pub mod submission_reprocessing;
+pub mod pool_stats;
+pub mod revoked_token_cleanup;
+pub mod warmup;
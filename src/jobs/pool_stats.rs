@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use sqlx::PgPool;
+
+use crate::services::metrics_service::MetricsService;
+
+// Periodically reports size, idle connections and acquire wait time of each named pool.
+// An interval of 0 disables sampling.
+pub fn spawn(pools: Vec<(&'static str, PgPool)>, metrics: MetricsService, interval: Duration) {
+    if interval.is_zero() {
+        log::info!("DB pool stats sampling disabled");
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+
+        loop {
+            ticker.tick().await;
+
+            for (name, pool) in &pools {
+                sample(name, pool, &metrics).await;
+            }
+        }
+    });
+}
+
+async fn sample(name: &str, pool: &PgPool, metrics: &MetricsService) {
+    let mut tags = HashMap::new();
+    tags.insert("pool".to_string(), name.to_string());
+
+    let size = pool.size();
+    let idle = pool.num_idle();
+    metrics.gauge("db.pool.size", size as f64, Some(tags.clone()));
+    metrics.gauge("db.pool.idle", idle as f64, Some(tags.clone()));
+    metrics.gauge("db.pool.in_use", size.saturating_sub(idle as u32) as f64, Some(tags.clone()));
+    metrics.gauge("db.pool.max", pool.options().get_max_connections() as f64, Some(tags.clone()));
+
+    // Time a probe acquire, which waits behind requests when the pool is exhausted
+    let start = Instant::now();
+    match pool.acquire().await {
+        Ok(_conn) => metrics.timing("db.pool.acquire_wait", start.elapsed(), Some(tags)),
+        Err(e) => {
+            metrics.increment("db.pool.acquire_error", Some(tags));
+            log::error!("Failed to acquire a connection from the {} pool: {}", name, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::metrics_service::tests::{agent, dogstatsd, received};
+
+    #[sqlx::test]
+    async fn the_sampler_reports_each_pool(pool: PgPool) {
+        let agent = agent();
+        spawn(vec![("primary", pool)], dogstatsd(&agent, None, HashMap::new()), Duration::from_millis(50));
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let lines = received(&agent);
+        for gauge in ["db.pool.size", "db.pool.idle", "db.pool.in_use", "db.pool.max"] {
+            assert!(
+                lines.iter().any(|line| line.starts_with(&format!("{}:", gauge)) && line.ends_with("|g|#pool:primary")),
+                "{gauge} in {lines:?}"
+            );
+        }
+        assert!(lines.iter().any(|line| line.starts_with("db.pool.acquire_wait:")), "{lines:?}");
+    }
+
+    #[sqlx::test]
+    async fn a_zero_interval_samples_nothing(pool: PgPool) {
+        let agent = agent();
+        spawn(vec![("primary", pool)], dogstatsd(&agent, None, HashMap::new()), Duration::ZERO);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        assert!(received(&agent).is_empty());
+    }
+}
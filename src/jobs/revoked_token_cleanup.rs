@@ -0,0 +1,25 @@
+use std::time::Duration;
+
+use crate::services::{auth_service::AuthService, metrics_service::MetricsService};
+
+// Periodically drops denylisted tokens that have expired anyway
+pub fn spawn(auth_service: AuthService, metrics: MetricsService, interval: Duration) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+
+        loop {
+            ticker.tick().await;
+
+            match auth_service.cleanup_expired().await {
+                Ok(deleted) => {
+                    log::info!("Removed {} expired revoked tokens", deleted);
+                    metrics.gauge("revoked_token.purged", deleted as f64, None);
+                }
+                Err(e) => {
+                    metrics.increment("revoked_token.purge_error", None);
+                    log::error!("Failed to remove expired revoked tokens: {}", e);
+                }
+            }
+        }
+    });
+}
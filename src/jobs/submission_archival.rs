@@ -0,0 +1,312 @@
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use chrono::Utc;
+use futures::future::BoxFuture;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    commons::minio_service::MinioService,
+    config::Config,
+    services::metrics_service::MetricsService,
+    submissions::submission_repository::SubmissionRepository,
+};
+
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+// Clears the running flag however the run ends
+struct RunningGuard;
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        RUNNING.store(false, Ordering::SeqCst);
+    }
+}
+
+// Runs archival every `interval`, skipping a tick while a run is still in progress.
+// An interval of 0 or no archive bucket disables it.
+pub fn schedule(pool: PgPool, minio: MinioService, metrics: MetricsService, config: Config, interval: Duration) {
+    if interval.is_zero() || config.minio_archive_bucket.is_none() {
+        log::info!("Scheduled submission archival disabled");
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+
+        loop {
+            ticker.tick().await;
+
+            if !spawn(pool.clone(), minio.clone(), metrics.clone(), config.clone()) {
+                log::info!("Submission archival still running, skipping this run");
+            }
+        }
+    });
+}
+
+// Moves the documents of submissions decided more than ARCHIVE_AFTER_DAYS ago to the archive
+// bucket. Returns false when a run is already in progress.
+pub fn spawn(pool: PgPool, minio: MinioService, metrics: MetricsService, config: Config) -> bool {
+    let Some(bucket) = config.minio_archive_bucket.clone() else {
+        return false;
+    };
+
+    if RUNNING.swap(true, Ordering::SeqCst) {
+        return false;
+    }
+
+    tokio::spawn(async move {
+        let _guard = RunningGuard;
+        run(pool, minio, metrics, config, bucket).await;
+    });
+
+    true
+}
+
+async fn run(pool: PgPool, minio: MinioService, metrics: MetricsService, config: Config, bucket: String) {
+    let repository = SubmissionRepository::new(pool.clone(), pool);
+    let batch_size = config.archive_batch_size;
+    let cutoff = Utc::now() - chrono::Duration::days(config.archive_after_days as i64);
+
+    let mut after_id = 0;
+    let mut archived = 0u64;
+    let mut failed = 0u64;
+
+    log::info!("Archiving submissions decided before {} to bucket {}", cutoff, bucket);
+
+    loop {
+        let batch = match repository.find_archivable_submissions(cutoff, after_id, batch_size).await {
+            Ok(batch) => batch,
+            Err(e) => {
+                metrics.increment("archive.error", None);
+                log::error!("Failed to fetch submissions to archive: {}", e);
+                break;
+            }
+        };
+
+        let Some((last_id, _, _)) = batch.last() else {
+            break;
+        };
+        after_id = *last_id;
+        let batch_len = batch.len() as i64;
+
+        for (_, submission_id, submission_data) in batch {
+            let mark = |data: Value| {
+                let repository = &repository;
+                async move { repository.mark_archived(submission_id, &data).await }
+            };
+            if archive_submission(&minio, &bucket, submission_id, submission_data, mark).await {
+                archived += 1;
+            } else {
+                failed += 1;
+            }
+        }
+
+        metrics.increment("archive.batch", None);
+        metrics.gauge("archive.archived", archived as f64, None);
+        metrics.gauge("archive.failed", failed as f64, None);
+
+        if batch_len < batch_size {
+            break;
+        }
+    }
+
+    log::info!("Archival finished: {} archived, {} failed", archived, failed);
+}
+
+// The object operations archival needs, implemented by MinioService
+pub trait ArchiveStore: Send + Sync {
+    fn exists<'a>(&'a self, name: &'a str) -> BoxFuture<'a, bool>;
+    fn copy_to<'a>(&'a self, name: &'a str, bucket: &'a str) -> BoxFuture<'a, anyhow::Result<()>>;
+    fn delete<'a>(&'a self, name: &'a str) -> BoxFuture<'a, anyhow::Result<()>>;
+    fn delete_in<'a>(&'a self, bucket: &'a str, name: &'a str) -> BoxFuture<'a, anyhow::Result<()>>;
+}
+
+impl ArchiveStore for MinioService {
+    fn exists<'a>(&'a self, name: &'a str) -> BoxFuture<'a, bool> {
+        Box::pin(async move { self.file_exists(name.to_string()).await.unwrap_or(false) })
+    }
+
+    fn copy_to<'a>(&'a self, name: &'a str, bucket: &'a str) -> BoxFuture<'a, anyhow::Result<()>> {
+        Box::pin(self.copy_object(name, bucket, name))
+    }
+
+    fn delete<'a>(&'a self, name: &'a str) -> BoxFuture<'a, anyhow::Result<()>> {
+        Box::pin(self.delete_file(name.to_string()))
+    }
+
+    fn delete_in<'a>(&'a self, bucket: &'a str, name: &'a str) -> BoxFuture<'a, anyhow::Result<()>> {
+        Box::pin(self.delete_file_in(bucket, name.to_string()))
+    }
+}
+
+// Copies every stored document first and only deletes the originals once the submission points
+// at the copies, so a failure part way leaves the submission as it was for the next run.
+// `mark` stores the updated submission data and reports whether the submission was still
+// archivable; when it wasn't, the copies are removed again and the originals kept.
+async fn archive_submission<S, F, Fut>(
+    store: &S,
+    bucket: &str,
+    submission_id: Uuid,
+    mut submission_data: Value,
+    mark: F,
+) -> bool
+where
+    S: ArchiveStore,
+    F: FnOnce(Value) -> Fut,
+    Fut: Future<Output = Result<bool, sqlx::Error>>,
+{
+    let mut moved = Vec::new();
+
+    if let Some(documents) = submission_data.as_object_mut() {
+        for entry in documents.values_mut().filter_map(Value::as_object_mut) {
+            if entry.contains_key("archivedBucket") {
+                continue;
+            }
+            let Some(name) = entry.get("documentName").and_then(Value::as_str).filter(|name| !name.is_empty()) else {
+                continue;
+            };
+            let name = name.to_string();
+
+            // Documents the client never uploaded have nothing to move
+            if !store.exists(&name).await {
+                continue;
+            }
+
+            if let Err(e) = store.copy_to(&name, bucket).await {
+                log::error!("Failed to copy {} of submission {} to {}: {}", name, submission_id, bucket, e);
+                return false;
+            }
+
+            entry.insert("archivedBucket".to_string(), json!(bucket));
+            moved.push(name);
+        }
+    }
+
+    match mark(submission_data).await {
+        Ok(true) => {}
+        Ok(false) => {
+            log::info!("Submission {} changed while archiving, discarding the copies", submission_id);
+            remove_copies(store, bucket, submission_id, &moved).await;
+            return false;
+        }
+        Err(e) => {
+            log::error!("Failed to mark submission {} archived: {}", submission_id, e);
+            remove_copies(store, bucket, submission_id, &moved).await;
+            return false;
+        }
+    }
+
+    // The copies are already referenced, so a leftover original is only logged
+    for name in moved {
+        if let Err(e) = store.delete(&name).await {
+            log::warn!("Failed to delete archived original {} of submission {}: {}", name, submission_id, e);
+        }
+    }
+
+    true
+}
+
+async fn remove_copies<S: ArchiveStore>(store: &S, bucket: &str, submission_id: Uuid, moved: &[String]) {
+    for name in moved {
+        if let Err(e) = store.delete_in(bucket, name).await {
+            log::warn!("Failed to delete unused archive copy {} of submission {}: {}", name, submission_id, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::sync::Mutex;
+
+    use super::*;
+
+    const ARCHIVE: &str = "archive";
+    const LIVE: &str = "live";
+
+    // Objects as (bucket, name) pairs
+    #[derive(Default)]
+    struct MemoryStore {
+        objects: Mutex<HashSet<(String, String)>>,
+    }
+
+    impl MemoryStore {
+        fn with(names: &[&str]) -> Self {
+            let objects = names.iter().map(|name| (LIVE.to_string(), name.to_string())).collect();
+            Self { objects: Mutex::new(objects) }
+        }
+
+        fn has(&self, bucket: &str, name: &str) -> bool {
+            self.objects.lock().unwrap().contains(&(bucket.to_string(), name.to_string()))
+        }
+    }
+
+    impl ArchiveStore for MemoryStore {
+        fn exists<'a>(&'a self, name: &'a str) -> BoxFuture<'a, bool> {
+            Box::pin(async move { self.has(LIVE, name) })
+        }
+
+        fn copy_to<'a>(&'a self, name: &'a str, bucket: &'a str) -> BoxFuture<'a, anyhow::Result<()>> {
+            Box::pin(async move {
+                self.objects.lock().unwrap().insert((bucket.to_string(), name.to_string()));
+                Ok(())
+            })
+        }
+
+        fn delete<'a>(&'a self, name: &'a str) -> BoxFuture<'a, anyhow::Result<()>> {
+            self.delete_in(LIVE, name)
+        }
+
+        fn delete_in<'a>(&'a self, bucket: &'a str, name: &'a str) -> BoxFuture<'a, anyhow::Result<()>> {
+            Box::pin(async move {
+                self.objects.lock().unwrap().remove(&(bucket.to_string(), name.to_string()));
+                Ok(())
+            })
+        }
+    }
+
+    fn submission_data() -> Value {
+        json!({
+            "SELFIE": { "documentName": "selfie.jpg" },
+            "ID_CARD": { "documentName": "id.jpg" },
+            "PASSPORT": { "documentName": "never-uploaded.jpg" },
+        })
+    }
+
+    #[tokio::test]
+    async fn moves_documents_to_the_archive_bucket() {
+        let store = MemoryStore::with(&["selfie.jpg", "id.jpg"]);
+        let stored = Mutex::new(None);
+
+        let archived = archive_submission(&store, ARCHIVE, Uuid::new_v4(), submission_data(), |data| {
+            *stored.lock().unwrap() = Some(data);
+            async { Ok(true) }
+        })
+        .await;
+
+        assert!(archived);
+        assert!(store.has(ARCHIVE, "selfie.jpg") && store.has(ARCHIVE, "id.jpg"));
+        assert!(!store.has(LIVE, "selfie.jpg") && !store.has(LIVE, "id.jpg"));
+
+        let stored = stored.lock().unwrap().take().unwrap();
+        assert_eq!(stored["SELFIE"]["archivedBucket"], ARCHIVE);
+        assert_eq!(stored["ID_CARD"]["archivedBucket"], ARCHIVE);
+        assert!(stored["PASSPORT"].get("archivedBucket").is_none());
+    }
+
+    #[tokio::test]
+    async fn keeps_originals_when_the_submission_changed() {
+        let store = MemoryStore::with(&["selfie.jpg", "id.jpg"]);
+
+        let archived =
+            archive_submission(&store, ARCHIVE, Uuid::new_v4(), submission_data(), |_| async { Ok(false) }).await;
+
+        assert!(!archived);
+        assert!(store.has(LIVE, "selfie.jpg") && store.has(LIVE, "id.jpg"));
+        assert!(!store.has(ARCHIVE, "selfie.jpg") && !store.has(ARCHIVE, "id.jpg"));
+    }
+}
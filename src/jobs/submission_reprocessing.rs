@@ -0,0 +1,235 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use chrono::Utc;
+use futures::{stream, StreamExt};
+use rand::Rng;
+use sqlx::PgPool;
+
+use crate::{
+    commons::minio_service::MinioService,
+    config::Config,
+    services::{
+        face_match_service::FaceMatchService,
+        feature_flags_service::FeatureFlagsService,
+        metrics_service::MetricsService,
+        webhook_service::WebhookService,
+    },
+    submissions::{submission_repository::SubmissionRepository, submission_service::SubmissionService},
+};
+
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+// Clears the running flag however the run ends
+struct RunningGuard;
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        RUNNING.store(false, Ordering::SeqCst);
+    }
+}
+
+// Reprocesses PENDING_RETRY/PROCESSING submissions in spaced batches so a recovering
+// face match provider isn't flooded. Returns false when a run is already in progress.
+pub fn spawn(
+    pool: PgPool,
+    minio: MinioService,
+    face_match: FaceMatchService,
+    webhooks: WebhookService,
+    metrics: MetricsService,
+    config: Config,
+    feature_flags: FeatureFlagsService,
+) -> bool {
+    if RUNNING.swap(true, Ordering::SeqCst) {
+        return false;
+    }
+
+    tokio::spawn(async move {
+        let _guard = RunningGuard;
+        run(pool, minio, face_match, webhooks, metrics, config, feature_flags).await;
+    });
+
+    true
+}
+
+async fn run(
+    pool: PgPool,
+    minio: MinioService,
+    face_match: FaceMatchService,
+    webhooks: WebhookService,
+    metrics: MetricsService,
+    config: Config,
+    feature_flags: FeatureFlagsService,
+) {
+    let repository = SubmissionRepository::new(pool.clone(), pool.clone());
+    let batch_size = config.reprocess_batch_size;
+    let concurrency = config.reprocess_concurrency;
+    let batch_delay = Duration::from_millis(config.reprocess_batch_delay_millis);
+    let jitter_millis = config.reprocess_jitter_millis;
+
+    // Fixed for the whole run, so submissions failing again now aren't retried until the next one
+    let cutoff = Utc::now() - chrono::Duration::seconds(config.reprocess_grace_secs as i64);
+    let submission_service = SubmissionService::new(
+        minio,
+        SubmissionRepository::new(pool.clone(), pool),
+        metrics.clone(),
+        config,
+        feature_flags,
+    );
+
+    let mut after_id = 0;
+    let mut succeeded = 0u64;
+    let mut failed = 0u64;
+
+    log::info!("Reprocessing submissions last updated before {}", cutoff);
+
+    loop {
+        let batch = match repository.find_reprocessable_submissions(cutoff, after_id, batch_size).await {
+            Ok(batch) => batch,
+            Err(e) => {
+                metrics.increment("reprocess.error", None);
+                log::error!("Failed to fetch submissions to reprocess: {}", e);
+                break;
+            }
+        };
+
+        let Some((last_id, _)) = batch.last() else {
+            break;
+        };
+        after_id = *last_id;
+        let batch_len = batch.len() as i64;
+
+        let service = &submission_service;
+        let results: Vec<bool> = stream::iter(batch)
+            .map(|(_, submission_id)| {
+                let face_match = face_match.clone();
+                let webhooks = webhooks.clone();
+                async move {
+                    service
+                        .process_submission(None, submission_id.to_string(), face_match, webhooks)
+                        .await
+                        .is_ok()
+                }
+            })
+            .buffer_unordered(concurrency)
+            .collect()
+            .await;
+
+        let batch_succeeded = results.iter().filter(|ok| **ok).count() as u64;
+        succeeded += batch_succeeded;
+        failed += results.len() as u64 - batch_succeeded;
+
+        metrics.increment("reprocess.batch", None);
+        metrics.gauge("reprocess.succeeded", succeeded as f64, None);
+        metrics.gauge("reprocess.failed", failed as f64, None);
+
+        if batch_len < batch_size {
+            break;
+        }
+
+        let jitter = rand::thread_rng().gen_range(0..=jitter_millis);
+        tokio::time::sleep(batch_delay + Duration::from_millis(jitter)).await;
+    }
+
+    log::info!("Reprocessing finished: {} succeeded, {} failed", succeeded, failed);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use serde_json::json;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::{
+        commons::minio_service::tests::{fake_s3, FakeS3},
+        services::{
+            app_services,
+            circuit_breaker::CircuitBreaker,
+            face_match_service::{self, tests::{fake_provider, matched, Provider}},
+        },
+        submissions::submission_repository::NewSubmission,
+    };
+
+    async fn seed_pending_retry(repository: &SubmissionRepository) {
+        let submission_id = Uuid::new_v4();
+        let mut documents = json!({});
+        for document in ["KTP", "SELFIE", "NFC"] {
+            documents[document] = json!({ "documentName": format!("{}_{}", submission_id, document), "uploadStatus": "UPLOADED" });
+        }
+        repository
+            .create(NewSubmission {
+                submission_id,
+                submission_type: "KYC",
+                session_id: &submission_id.to_string(),
+                user_id: "1",
+                status: "PENDING_RETRY",
+                submission_data: documents,
+                request_data: json!({}),
+                nfc_identifier: submission_id.to_string(),
+                external_reference: None,
+                risk_tier: None,
+                idempotency_key: None,
+                idempotency_request_hash: None,
+                callback_url: None,
+            })
+            .await
+            .unwrap();
+    }
+
+    #[sqlx::test]
+    async fn reprocesses_in_spaced_batches_of_the_configured_size(pool: PgPool) {
+        let repository = SubmissionRepository::new(pool.clone(), pool.clone());
+        for _ in 0..5 {
+            seed_pending_retry(&repository).await;
+        }
+        let services = app_services::tests::services_with_minio(
+            pool.clone(),
+            &[
+                ("REPROCESS_GRACE_SECS", "0"),
+                ("REPROCESS_BATCH_SIZE", "2"),
+                ("REPROCESS_CONCURRENCY", "4"),
+                ("REPROCESS_BATCH_DELAY_MILLIS", "300"),
+                ("REPROCESS_JITTER_MILLIS", "0"),
+            ],
+            &fake_s3(Arc::new(FakeS3::default())),
+        );
+        let provider = Arc::new(Provider::default());
+        let breaker = CircuitBreaker::new("face_match", 0, Duration::from_secs(60), Duration::from_secs(30), services.metrics.clone());
+        let face_match = FaceMatchService::new(
+            face_match_service::tests::settings(fake_provider(provider.clone(), Duration::ZERO, matched).await, 1),
+            breaker,
+            services.metrics.clone(),
+        )
+        .unwrap();
+
+        run(
+            pool.clone(),
+            services.minio,
+            face_match,
+            services.webhooks,
+            services.metrics,
+            services.config,
+            services.feature_flags,
+        )
+        .await;
+
+        // Batches of 2, 2 and 1, each at least the delay after the one before
+        let arrivals = provider.arrivals();
+        assert_eq!(arrivals.len(), 5);
+        for (earlier, later) in [(0, 1), (2, 3)] {
+            assert!(arrivals[later] - arrivals[earlier] < Duration::from_millis(150), "{arrivals:?}");
+        }
+        for (last_of_batch, first_of_next) in [(1, 2), (3, 4)] {
+            assert!(arrivals[first_of_next] - arrivals[last_of_batch] >= Duration::from_millis(300), "{arrivals:?}");
+        }
+
+        let left: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM submissions WHERE status = 'PENDING_RETRY'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(left, 0);
+    }
+}
+
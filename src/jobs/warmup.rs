@@ -0,0 +1,93 @@
+use std::future::Future;
+use std::time::Duration;
+
+use crate::{commons::minio_service::MinioService, services::face_match_service::FaceMatchService};
+
+// Opens connections to MinIO and the face match host right after startup, so the first
+// requests don't pay for connection setup. Failures are retried with a doubling delay and
+// never stop the service. 0 attempts disables the warmup.
+pub fn spawn(minio: MinioService, face_match: FaceMatchService, attempts: u32, retry_delay: Duration) {
+    if attempts == 0 {
+        log::info!("Connection warmup disabled");
+        return;
+    }
+
+    tokio::spawn(async move {
+        tokio::join!(
+            warm("MinIO", attempts, retry_delay, || minio.check_bucket()),
+            warm("face match", attempts, retry_delay, || face_match.ping()),
+        );
+    });
+}
+
+async fn warm<F, Fut>(name: &str, attempts: u32, retry_delay: Duration, call: F)
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = anyhow::Result<()>>,
+{
+    let mut delay = retry_delay;
+
+    for attempt in 1..=attempts {
+        match call().await {
+            Ok(()) => {
+                log::info!("Warmed up {} connection", name);
+                return;
+            }
+            Err(e) if attempt < attempts => {
+                log::warn!("Warming up {} failed (attempt {}/{}), retrying in {:?}: {}", name, attempt, attempts, delay, e);
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+            }
+            Err(e) => log::warn!("Giving up warming up {} after {} attempts: {}", name, attempts, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    };
+
+    use super::*;
+    use crate::{
+        commons::minio_service::tests::{fake_s3, FakeS3},
+        services::{circuit_breaker::CircuitBreaker, face_match_service, metrics_service::MetricsService},
+    };
+
+    // Counts the calls, failing the first `failures`
+    fn flaky(calls: &AtomicU32, failures: u32) -> impl Fn() -> std::future::Ready<anyhow::Result<()>> + '_ {
+        move || {
+            let call = calls.fetch_add(1, Ordering::SeqCst);
+            std::future::ready(if call < failures { Err(anyhow::anyhow!("not ready")) } else { Ok(()) })
+        }
+    }
+
+    #[tokio::test]
+    async fn retries_until_the_dependency_answers_and_gives_up_quietly() {
+        let calls = AtomicU32::new(0);
+        warm("recovering", 5, Duration::from_millis(1), flaky(&calls, 2)).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        let calls = AtomicU32::new(0);
+        warm("down", 3, Duration::from_millis(1), flaky(&calls, u32::MAX)).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[actix_web::test]
+    async fn warms_minio_even_when_face_match_is_down() {
+        let s3 = Arc::new(FakeS3::default());
+        let minio = MinioService::unchecked(&fake_s3(s3.clone()), "minio", "minio123", "documents", Vec::new());
+        let metrics = MetricsService::noop();
+        let breaker = CircuitBreaker::new("face_match", 0, Duration::from_secs(60), Duration::from_secs(30), metrics.clone());
+        // Nothing listens on the face match host
+        let face_match =
+            FaceMatchService::new(face_match_service::tests::settings("http://127.0.0.1:1".to_string(), 1), breaker, metrics).unwrap();
+
+        spawn(minio, face_match, 2, Duration::from_millis(10));
+        tokio::time::sleep(Duration::from_millis(300)).await;
+
+        assert_eq!(s3.requests("HEAD").len(), 1);
+    }
+}
@@ -1,12 +1,28 @@
-use actix_web::{web, App, HttpServer};
+use actix_web::{guard, middleware::Condition, web, App, HttpServer};
 use actix_cors::Cors;
-use std::env;
-use sqlx::postgres::PgPoolOptions;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
-use crate::services::{metrics_service::MetricsService, face_match_service::FaceMatchService};
+use crate::commons::{
+    database::{self, ReadPool},
+    minio_service::{MinioService, MinioSettings},
+};
+use crate::config::Config;
+use crate::middleware::{accept_json::AcceptJson, load_shedding::LoadShedding, require_https::RequireHttps};
+use crate::services::{
+    app_services::AppServices,
+    auth_service::AuthService,
+    circuit_breaker::CircuitBreaker,
+    dashboard_service::DashboardService,
+    elasticsearch_client::ElasticsearchClient,
+    metrics_service::MetricsService,
+    face_match_service::{FaceMatchService, FaceMatchSettings},
+    feature_flags_service::FeatureFlagsService,
+    webhook_service::{WebhookService, WebhookSettings},
+};
 
 mod commons;
+mod config;
 mod controllers;
+mod jobs;
+mod middleware;
 mod models;
 mod repositories;
 mod services;
@@ -18,62 +34,265 @@ async fn main() -> std::io::Result<()> {
     dotenv::dotenv().ok();
     
     // Initialize tracing with JSON format
-    tracing_subscriber::registry()
-        .with(EnvFilter::from_default_env())
-        .with(tracing_subscriber::fmt::layer().json())
-        .init();
-
-    let host = std::env::var("HOST").expect("HOST must be set");
-    let port = std::env::var("PORT").expect("PORT must be set");
-
-    let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-    let pool = PgPoolOptions::new()
-        .max_connections(5)
-        .connect(&database_url)
+    let log_level = web::Data::new(commons::logging::init());
+
+    let config = Config::from_env().unwrap_or_else(|e| {
+        tracing::error!("{}", e);
+        std::process::exit(1);
+    });
+
+    commons::error_cause::init(config.error_cause_max_length, config.production_mode);
+    commons::pii::init(config.anonymize_nfc_identifier);
+
+    let db_retry_delay = std::time::Duration::from_millis(config.db_connect_retry_delay_millis);
+
+    let pool = database::connect_with_retry(
+        &config.database_url,
+        config.db_max_connections,
+        config.db_min_connections,
+        config.db_connect_attempts,
+        db_retry_delay,
+    )
+    .await
+    .expect("Failed to create pool");
+
+    let read_pool = match &config.database_replica_url {
+        Some(replica_url) => database::connect_with_retry(
+            replica_url,
+            config.db_max_connections,
+            config.db_min_connections,
+            config.db_connect_attempts,
+            db_retry_delay,
+        )
         .await
-        .expect("Failed to create pool");
+        .expect("Failed to create replica pool"),
+        None => pool.clone(),
+    };
 
     let pool = web::Data::new(pool);
+    let read_pool = web::Data::new(ReadPool(read_pool));
 
-    let metrics_service = web::Data::new(MetricsService::new(
-        &std::env::var("STATSD_HOST").expect("STATSD_HOST must be set"),
-        std::env::var("STATSD_PORT").expect("STATSD_PORT must be set").parse::<u16>().unwrap(),
-        &std::env::var("STATSD_PREFIX").expect("STATSD_PREFIX must be set")
-    ));
+    // Without a collector (e.g. local development) statsd calls are no-ops
+    let metrics_service = match (&config.statsd_host, config.metrics_enabled) {
+        (Some(statsd_host), true) => MetricsService::new(
+            statsd_host,
+            config.statsd_port,
+            config.statsd_prefix.as_deref(),
+            config.metrics_tag_format,
+            config.metrics_sample_rate,
+            config.metrics_sample_rate_overrides.clone(),
+        ),
+        _ => {
+            log::warn!("Statsd metrics disabled: METRICS_ENABLED is false or STATSD_HOST is not set");
+            MetricsService::noop()
+        }
+    };
+    let metrics_service = web::Data::new(if config.metrics_prometheus_enabled {
+        metrics_service.with_prometheus()
+    } else {
+        metrics_service
+    });
 
     let face_match_service = web::Data::new(FaceMatchService::new(
-        std::env::var("FACE_MATCH_HOST").expect("FACE_MATCH_HOST must be set"),
-        std::env::var("FACE_MATCH_THRESHOLD").expect("FACE_MATCH_THRESHOLD must be set").parse::<f64>().unwrap(),
-        std::env::var("FACE_MATCH_TIMEOUT_MILLIS").expect("FACE_MATCH_TIMEOUT_MILLIS must be set").parse::<u64>().unwrap(),
+        FaceMatchSettings::from_config(&config),
+        CircuitBreaker::new(
+            "face_match",
+            config.face_match_circuit_failure_threshold,
+            std::time::Duration::from_secs(config.face_match_circuit_window_secs),
+            std::time::Duration::from_secs(config.face_match_circuit_cooldown_secs),
+            metrics_service.as_ref().clone(),
+        ),
+        metrics_service.as_ref().clone(),
+    ).expect("Failed to initialize face match service"));
+
+    let webhooks = WebhookService::new(
+        pool.get_ref().clone(),
+        WebhookSettings::from_config(&config),
         metrics_service.as_ref().clone(),
+    );
+
+    let dashboard = web::Data::new(DashboardService::new(
+        config.elasticsearch_url.clone().map(|url| {
+            ElasticsearchClient::new(
+                url,
+                config.elasticsearch_user.clone(),
+                config.elasticsearch_pass.clone(),
+                config.elasticsearch_accept_invalid_certs,
+                config.elasticsearch_timeout_millis,
+            )
+        }),
+        CircuitBreaker::new(
+            "elasticsearch",
+            config.elasticsearch_circuit_failure_threshold,
+            std::time::Duration::from_secs(config.elasticsearch_circuit_window_secs),
+            std::time::Duration::from_secs(config.elasticsearch_circuit_cooldown_secs),
+            metrics_service.as_ref().clone(),
+        ),
+        std::time::Duration::from_secs(config.dashboard_cache_ttl_secs),
+    ));
+
+    let feature_flags = web::Data::new(FeatureFlagsService::new(
+        pool.get_ref().clone(),
+        std::time::Duration::from_secs(config.feature_flags_cache_ttl_secs),
     ));
 
-    let minio_service = commons::minio_service::MinioService::new(
-        &env::var("MINIO_ENDPOINT").expect("MINIO_ENDPOINT must be set"),
-        &env::var("MINIO_ACCESS_KEY").expect("MINIO_ACCESS_KEY must be set"),
-        &env::var("MINIO_SECRET_KEY").expect("MINIO_SECRET_KEY must be set"),
-        &env::var("MINIO_BUCKET_NAME").expect("MINIO_BUCKET_NAME must be set"),
-    ).await.expect("Failed to initialize MinIO service");
+    let minio_service = MinioService::new(MinioSettings::from_config(&config))
+        .await
+        .expect("Failed to initialize MinIO service");
+
+    jobs::warmup::spawn(
+        minio_service.clone(),
+        face_match_service.get_ref().clone(),
+        config.warmup_attempts,
+        std::time::Duration::from_millis(config.warmup_retry_delay_millis),
+    );
+
+    jobs::face_match_audit_retention::spawn(
+        pool.get_ref().clone(),
+        metrics_service.as_ref().clone(),
+        config.face_match_audit_retention_days,
+        config.face_match_audit_archive_stats,
+        std::time::Duration::from_secs(config.face_match_audit_purge_interval_secs),
+    );
+
+    jobs::submission_archival::schedule(
+        pool.get_ref().clone(),
+        minio_service.clone(),
+        metrics_service.as_ref().clone(),
+        config.clone(),
+        std::time::Duration::from_secs(config.archive_interval_secs),
+    );
+
+    jobs::revoked_token_cleanup::spawn(
+        AuthService::new(pool.get_ref().clone(), read_pool.0.clone(), &config),
+        metrics_service.as_ref().clone(),
+        std::time::Duration::from_secs(config.revoked_token_cleanup_interval_secs),
+    );
+
+    jobs::dashboard_cache::spawn(
+        dashboard.get_ref().clone(),
+        config.dashboard_cache_cities.clone(),
+        std::time::Duration::from_secs(config.dashboard_cache_refresh_interval_secs),
+    );
+
+    jobs::face_match_health::spawn(
+        face_match_service.get_ref().clone(),
+        std::time::Duration::from_secs(config.face_match_health_interval_secs),
+    );
+
+    // Without a replica the read pool is the primary, so it's only sampled once
+    let mut sampled_pools = vec![("primary", pool.get_ref().clone())];
+    if config.database_replica_url.is_some() {
+        sampled_pools.push(("replica", read_pool.0.clone()));
+    }
+    jobs::pool_stats::spawn(
+        sampled_pools,
+        metrics_service.as_ref().clone(),
+        std::time::Duration::from_secs(config.pool_stats_interval_secs),
+    );
+
+    let load_shedding = LoadShedding::new(
+        config.load_shed_max_in_flight,
+        config.load_shed_retry_after_secs,
+        config.load_shed_paths.clone(),
+        metrics_service.as_ref().clone(),
+    );
+
+    let require_https = config.require_https;
+    let hsts_max_age_secs = config.hsts_max_age_secs;
+    let reject_unacceptable = config.reject_unacceptable;
+    let json_body_limit_bytes = config.json_body_limit_bytes;
+    let submission_body_limit_bytes = config.submission_body_limit_bytes;
+
+    let email_sender = web::Data::from(services::email_sender::from_config(&config));
+
+    let services = web::Data::new(AppServices {
+        config: config.clone(),
+        pool: pool.get_ref().clone(),
+        read_pool: read_pool.get_ref().clone(),
+        minio: minio_service.clone(),
+        metrics: metrics_service.get_ref().clone(),
+        feature_flags: feature_flags.get_ref().clone(),
+        face_match: face_match_service.get_ref().clone(),
+        webhooks,
+    });
+
+    let bind_address = format!("{}:{}", config.host, config.port);
+    let config = web::Data::new(config);
 
     HttpServer::new(move || {
         App::new()
+            .wrap(load_shedding.clone())
             .wrap(Cors::permissive())
+            .app_data(config.clone())
             .app_data(pool.clone())
+            .app_data(read_pool.clone())
             .app_data(metrics_service.clone())
             .app_data(face_match_service.clone())
+            .app_data(services.clone())
+            .app_data(dashboard.clone())
+            .app_data(feature_flags.clone())
+            .app_data(log_level.clone())
+            .app_data(email_sender.clone())
             .app_data(web::Data::new(minio_service.clone()))
+            // Probes stay outside /v1 so they skip auth and the HTTPS redirect
+            .service(controllers::health::health)
+            .service(controllers::health::ready)
+            .service(controllers::metrics::prometheus_metrics)
             .service(
                 web::scope("/v1")
+                    .wrap(Condition::new(reject_unacceptable, AcceptJson))
+                    .wrap(Condition::new(require_https, RequireHttps::new(hsts_max_age_secs)))
+                    .app_data(commons::json_body::config(json_body_limit_bytes))
                     .service(controllers::auth::register)
                     .service(controllers::auth::login)
-                    .service(submissions::submission_controller::presigned_urls)
+                    .service(controllers::auth::refresh)
+                    .service(controllers::auth::logout)
+                    .service(controllers::auth::me)
+                    .service(controllers::auth::change_password)
+                    .service(controllers::auth::verify_email)
+                    .service(controllers::auth::resend_verification)
+                    .service(controllers::auth::forgot_password)
+                    .service(controllers::auth::reset_password)
+                    .service(
+                        // Guarded so PUT falls through to process_submission on the same path
+                        web::resource("/submissions/urls")
+                            .guard(guard::Post())
+                            .app_data(commons::json_body::config(submission_body_limit_bytes))
+                            .route(web::post().to(submissions::submission_controller::presigned_urls)),
+                    )
                     .service(submissions::submission_controller::face_match)
+                    .service(
+                        web::resource("/submissions/face-match/batch")
+                            .app_data(commons::json_body::config(submission_body_limit_bytes))
+                            .route(web::post().to(submissions::submission_controller::face_match_batch)),
+                    )
                     .service(submissions::submission_controller::process_submission)
                     .service(submissions::submission_controller::get_submission_status)
+                    .service(submissions::submission_controller::find_by_external_reference)
+                    .service(submissions::submission_controller::erase_submission)
+                    .service(submissions::submission_controller::upload_document)
+                    .service(submissions::submission_controller::get_submission_type_documents)
                     .service(controllers::dashboard::get_city_count)
+                    .service(controllers::admin::erase_user)
+                    .service(controllers::admin::set_user_risk_tier)
+                    .service(controllers::admin::get_effective_config)
+                    .service(controllers::admin::get_log_level)
+                    .service(controllers::admin::set_log_level)
+                    .service(controllers::admin::reprocess_submissions)
+                    .service(controllers::admin::archive_submissions)
+                    .service(controllers::admin::recompute_submission_status)
+                    .service(controllers::admin::bulk_update_submission_status)
+                    .service(controllers::admin::find_by_document_reference)
+                    .service(controllers::admin::get_nfc_summary)
+                    .service(controllers::admin::list_submissions)
+                    .service(controllers::admin::list_stuck_submissions)
+                    .service(controllers::admin::get_document_view_url)
+                    .service(controllers::admin::list_feature_flags)
+                    .service(controllers::admin::set_feature_flag)
             )
     })
-    .bind(format!("{}:{}", host, port))?
+    .bind(bind_address)?
     .run()
     .await
 }
@@ -0,0 +1,147 @@
+use std::future::{ready, Ready};
+
+use actix_web::{
+    body::EitherBody,
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::header::ACCEPT,
+    Error, HttpResponse,
+};
+use futures::future::LocalBoxFuture;
+
+use crate::models::user::{ApiError, ApiResponse};
+
+// Answers 406 when the Accept header rules out JSON, the only type the API produces. A
+// missing header, `*/*`, `application/*` and `application/json` are accepted; ranges with
+// q=0 count as excluded. The 406 body is JSON anyway since there's nothing else to send.
+#[derive(Clone)]
+pub struct AcceptJson;
+
+impl<S, B> Transform<S, ServiceRequest> for AcceptJson
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = AcceptJsonMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(AcceptJsonMiddleware { service }))
+    }
+}
+
+pub struct AcceptJsonMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for AcceptJsonMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        // Every Accept header counts, as if they were one comma-separated list
+        let accept: Vec<String> = req
+            .headers()
+            .get_all(ACCEPT)
+            .filter_map(|v| v.to_str().ok())
+            .map(str::to_string)
+            .collect();
+
+        if !accept.is_empty() && !accepts_json(&accept.join(",")) {
+            let response = HttpResponse::NotAcceptable().json(ApiResponse::<()> {
+                success: false,
+                data: None,
+                errors: Some(vec![ApiError {
+                    entity: "SOCIO_ECHO_BE".to_string(),
+                    code: "1028".to_string(),
+                    cause: "NOT_ACCEPTABLE: only application/json is available".to_string(),
+                }]),
+            });
+            return Box::pin(async move { Ok(req.into_response(response).map_into_right_body()) });
+        }
+
+        let fut = self.service.call(req);
+        Box::pin(async move { Ok(fut.await?.map_into_left_body()) })
+    }
+}
+
+fn accepts_json(accept: &str) -> bool {
+    // A header with no usable ranges at all is treated like a missing one
+    let mut ranges = accept.split(',').map(str::trim).filter(|r| !r.is_empty()).peekable();
+    if ranges.peek().is_none() {
+        return true;
+    }
+
+    ranges.any(|range| {
+        let mut parts = range.split(';').map(str::trim);
+        let media_type = parts.next().unwrap_or("").to_ascii_lowercase();
+        let excluded = parts.filter_map(|param| param.split_once('=')).any(|(name, value)| {
+            name.trim().eq_ignore_ascii_case("q") && value.trim().parse::<f32>().is_ok_and(|q| q <= 0.0)
+        });
+
+        !excluded && matches!(media_type.as_str(), "*/*" | "*" | "application/*" | "application/json")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        http::StatusCode,
+        test::{call_service, init_service, TestRequest},
+        web, App,
+    };
+
+    use super::*;
+
+    #[test]
+    fn json_compatible_ranges_are_accepted() {
+        for accept in ["application/json", "*/*", "application/*", "text/html, application/json;q=0.5", " , "] {
+            assert!(accepts_json(accept), "{accept}");
+        }
+    }
+
+    #[test]
+    fn ranges_without_json_are_refused() {
+        for accept in ["text/html", "application/xml, text/plain", "application/json;q=0", "*/*; q=0.0"] {
+            assert!(!accepts_json(accept), "{accept}");
+        }
+    }
+
+    async fn status_for(accept: &[&str]) -> StatusCode {
+        let app = init_service(
+            App::new().wrap(AcceptJson).route("/", web::get().to(|| async { HttpResponse::Ok().json(()) })),
+        )
+        .await;
+
+        let mut request = TestRequest::get().uri("/");
+        for value in accept {
+            request = request.append_header((ACCEPT, *value));
+        }
+        call_service(&app, request.to_request()).await.status()
+    }
+
+    #[actix_web::test]
+    async fn answers_406_when_json_is_ruled_out() {
+        assert_eq!(status_for(&["text/html"]).await, StatusCode::NOT_ACCEPTABLE);
+        assert_eq!(status_for(&["application/json;q=0"]).await, StatusCode::NOT_ACCEPTABLE);
+    }
+
+    #[actix_web::test]
+    async fn passes_requests_that_take_json() {
+        assert_eq!(status_for(&[]).await, StatusCode::OK);
+        assert_eq!(status_for(&["*/*"]).await, StatusCode::OK);
+        // Separate headers count as one list
+        assert_eq!(status_for(&["text/html", "application/json"]).await, StatusCode::OK);
+    }
+}
@@ -0,0 +1,48 @@
+use std::future::{ready, Ready};
+
+use actix_web::{dev::Payload, error::InternalError, web, FromRequest, HttpRequest, HttpResponse};
+
+use crate::{
+    config::Config,
+    models::user::{ApiError, ApiResponse},
+};
+
+// Extractor for admin-only routes: requires the `x-admin-key` header to match ADMIN_API_KEY.
+// When no key is configured every admin route is refused.
+pub struct AdminGuard;
+
+impl FromRequest for AdminGuard {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let expected = req
+            .app_data::<web::Data<Config>>()
+            .and_then(|config| config.admin_api_key.clone());
+        let provided = req.headers().get("x-admin-key").and_then(|v| v.to_str().ok());
+
+        let authorized = match (expected.as_deref(), provided) {
+            (Some(expected), Some(provided)) => constant_time_eq(expected.as_bytes(), provided.as_bytes()),
+            _ => false,
+        };
+
+        if authorized {
+            return ready(Ok(AdminGuard));
+        }
+
+        let response = HttpResponse::Forbidden().json(ApiResponse::<()> {
+            success: false,
+            data: None,
+            errors: Some(vec![ApiError {
+                entity: "SOCIO_ECHO_BE".to_string(),
+                code: "1011".to_string(),
+                cause: "ADMIN_ACCESS_REQUIRED".to_string(),
+            }]),
+        });
+        ready(Err(InternalError::from_response("admin access required", response).into()))
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
@@ -0,0 +1,142 @@
+use std::ops::Deref;
+
+use actix_web::{dev::Payload, http::header, web, FromRequest, HttpRequest};
+use chrono::{DateTime, Utc};
+use futures::future::LocalBoxFuture;
+use sqlx::PgPool;
+
+use crate::{
+    commons::{
+        app_error::{errors, AppError},
+        database::ReadPool,
+    },
+    config::Config,
+    repositories::user_repository::UserRepository,
+    services::auth_service::AuthService,
+    utils::validate_token,
+};
+
+// Extractor for routes that need a logged-in user. Reads the JWT from `Authorization: Bearer`,
+// falling back to the `x-user-token` header existing clients send. Revoked tokens are rejected.
+pub struct AuthenticatedUser {
+    pub user_id: i32,
+    pub jti: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl FromRequest for AuthenticatedUser {
+    type Error = AppError;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let config = req.app_data::<web::Data<Config>>().cloned();
+        let pool = req.app_data::<web::Data<PgPool>>().cloned();
+        let read_pool = req.app_data::<web::Data<ReadPool>>().cloned();
+        let token = bearer_token(req).map(str::to_string);
+
+        Box::pin(async move {
+            let (Some(config), Some(pool), Some(read_pool), Some(token)) = (config, pool, read_pool, token) else {
+                return Err(unauthorized());
+            };
+            let Ok(claims) = validate_token(&token, &config.jwt_secret) else {
+                return Err(unauthorized());
+            };
+
+            // Checked against the primary so a logout takes effect immediately
+            let auth_service = AuthService::new(pool.get_ref().clone(), read_pool.0.clone(), &config);
+            match auth_service.is_revoked(&claims.jti).await {
+                Ok(false) => {}
+                Ok(true) => return Err(unauthorized()),
+                Err(e) => {
+                    log::error!("Failed to check token revocation: {}", e);
+                    return Err(AppError::Internal(errors("1000", "SYSTEM_ERROR")));
+                }
+            }
+
+            Ok(AuthenticatedUser {
+                user_id: claims.sub,
+                jti: claims.jti,
+                expires_at: DateTime::from_timestamp(claims.exp, 0).unwrap_or_else(Utc::now),
+            })
+        })
+    }
+}
+
+// AuthenticatedUser that, when REQUIRE_EMAIL_VERIFICATION is on, has also verified their email
+pub struct VerifiedUser(pub AuthenticatedUser);
+
+impl Deref for VerifiedUser {
+    type Target = AuthenticatedUser;
+
+    fn deref(&self) -> &AuthenticatedUser {
+        &self.0
+    }
+}
+
+impl FromRequest for VerifiedUser {
+    type Error = AppError;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let config = req.app_data::<web::Data<Config>>().cloned();
+        let pool = req.app_data::<web::Data<PgPool>>().cloned();
+        let read_pool = req.app_data::<web::Data<ReadPool>>().cloned();
+        let authenticated = AuthenticatedUser::from_request(req, payload);
+
+        Box::pin(async move {
+            let user = authenticated.await?;
+            let (Some(config), Some(pool), Some(read_pool)) = (config, pool, read_pool) else {
+                return Err(unauthorized());
+            };
+            if !config.require_email_verification {
+                return Ok(VerifiedUser(user));
+            }
+
+            // The primary, so a verification that just happened counts
+            let user_repository = UserRepository::new(pool.get_ref().clone(), read_pool.0.clone());
+            match user_repository.is_email_verified(user.user_id).await {
+                Ok(Some(true)) => Ok(VerifiedUser(user)),
+                Ok(Some(false)) => Err(AppError::Forbidden(errors("1024", "EMAIL_NOT_VERIFIED"))),
+                Ok(None) => Err(unauthorized()),
+                Err(e) => {
+                    log::error!("Failed to check email verification: {}", e);
+                    Err(AppError::Internal(errors("1000", "SYSTEM_ERROR")))
+                }
+            }
+        })
+    }
+}
+
+fn unauthorized() -> AppError {
+    AppError::Unauthorized(errors("1008", "MISSING_OR_INVALID_TOKEN"))
+}
+
+fn bearer_token(req: &HttpRequest) -> Option<&str> {
+    let authorization = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+
+    authorization.or_else(|| req.headers().get("x-user-token").and_then(|v| v.to_str().ok()))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::utils::Claims;
+
+    // An `Authorization` header value for `user_id`, valid for an hour
+    pub(crate) fn bearer(config: &Config, user_id: i32) -> String {
+        let claims = Claims {
+            sub: user_id,
+            exp: (Utc::now() + chrono::Duration::hours(1)).timestamp(),
+            jti: Uuid::new_v4().to_string(),
+        };
+        let token = encode(&Header::default(), &claims, &EncodingKey::from_secret(config.jwt_secret.as_bytes())).unwrap();
+        format!("Bearer {}", token)
+    }
+}
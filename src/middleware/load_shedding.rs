@@ -0,0 +1,187 @@
+use std::{
+    future::{ready, Ready},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use actix_web::{
+    body::EitherBody,
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::header::RETRY_AFTER,
+    Error, HttpResponse,
+};
+use futures::future::LocalBoxFuture;
+
+use crate::{
+    models::user::{ApiError, ApiResponse},
+    services::metrics_service::MetricsService,
+};
+
+// Counts requests currently being handled and rejects non-critical ones with 503
+// once the count goes over `max_in_flight`. A limit of 0 disables shedding.
+#[derive(Clone)]
+pub struct LoadShedding {
+    in_flight: Arc<AtomicUsize>,
+    max_in_flight: usize,
+    retry_after_secs: u64,
+    sheddable_paths: Arc<Vec<String>>,
+    metrics: MetricsService,
+}
+
+impl LoadShedding {
+    pub fn new(
+        max_in_flight: usize,
+        retry_after_secs: u64,
+        sheddable_paths: Vec<String>,
+        metrics: MetricsService,
+    ) -> Self {
+        Self {
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            max_in_flight,
+            retry_after_secs,
+            sheddable_paths: Arc::new(sheddable_paths),
+            metrics,
+        }
+    }
+
+    fn should_shed(&self, path: &str, in_flight: usize) -> bool {
+        self.max_in_flight > 0
+            && in_flight > self.max_in_flight
+            && self.sheddable_paths.iter().any(|prefix| path.starts_with(prefix.as_str()))
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for LoadShedding
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = LoadSheddingMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(LoadSheddingMiddleware {
+            service,
+            state: self.clone(),
+        }))
+    }
+}
+
+pub struct LoadSheddingMiddleware<S> {
+    service: S,
+    state: LoadShedding,
+}
+
+impl<S, B> Service<ServiceRequest> for LoadSheddingMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let in_flight = self.state.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        let guard = InFlightGuard(self.state.in_flight.clone());
+        self.state.metrics.gauge("http.in_flight", in_flight as f64, None);
+
+        if self.state.should_shed(req.path(), in_flight) {
+            drop(guard);
+            self.state.metrics.increment("http.load_shed", None);
+
+            let response = HttpResponse::ServiceUnavailable()
+                .insert_header((RETRY_AFTER, self.state.retry_after_secs.to_string()))
+                .json(ApiResponse::<()> {
+                    success: false,
+                    data: None,
+                    errors: Some(vec![ApiError {
+                        entity: "SOCIO_ECHO_BE".to_string(),
+                        code: "1009".to_string(),
+                        cause: "SERVICE_OVERLOADED".to_string(),
+                    }]),
+                });
+
+            return Box::pin(async move { Ok(req.into_response(response).map_into_right_body()) });
+        }
+
+        let fut = self.service.call(req);
+        Box::pin(async move {
+            let res = fut.await;
+            drop(guard);
+            res.map(ServiceResponse::map_into_left_body)
+        })
+    }
+}
+
+// Decrements the in-flight counter however the request finishes
+struct InFlightGuard(Arc<AtomicUsize>);
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        http::StatusCode,
+        test::{call_service, init_service, TestRequest},
+        web, App,
+    };
+
+    use super::*;
+
+    #[actix_web::test]
+    async fn sheds_only_non_critical_paths_over_the_limit() {
+        let shedding = LoadShedding::new(2, 7, vec!["/v1/summary".to_string()], MetricsService::noop());
+        let app = init_service(
+            App::new()
+                .wrap(shedding.clone())
+                .route("/v1/summary", web::get().to(|| async { HttpResponse::Ok().json(()) }))
+                .route("/v1/auth/login", web::post().to(|| async { HttpResponse::Ok().json(()) })),
+        )
+        .await;
+
+        let dashboard = || TestRequest::get().uri("/v1/summary").to_request();
+        let login = || TestRequest::post().uri("/v1/auth/login").to_request();
+        assert_eq!(call_service(&app, dashboard()).await.status(), StatusCode::OK);
+
+        // Two requests already in flight, so the next one goes over the limit
+        shedding.in_flight.store(2, Ordering::SeqCst);
+
+        let shed = call_service(&app, dashboard()).await;
+        assert_eq!(shed.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(shed.headers().get(RETRY_AFTER).unwrap(), "7");
+        assert_eq!(call_service(&app, login()).await.status(), StatusCode::OK);
+        assert_eq!(shedding.in_flight.load(Ordering::SeqCst), 2);
+
+        shedding.in_flight.store(0, Ordering::SeqCst);
+        assert_eq!(call_service(&app, dashboard()).await.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn a_zero_limit_never_sheds() {
+        let shedding = LoadShedding::new(0, 7, vec!["/v1/summary".to_string()], MetricsService::noop());
+        let app = init_service(
+            App::new()
+                .wrap(shedding.clone())
+                .route("/v1/summary", web::get().to(|| async { HttpResponse::Ok().json(()) })),
+        )
+        .await;
+
+        shedding.in_flight.store(1_000, Ordering::SeqCst);
+        let response = call_service(&app, TestRequest::get().uri("/v1/summary").to_request()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,5 @@
+pub mod accept_json;
+pub mod admin;
+pub mod auth;
+pub mod load_shedding;
+pub mod require_https;
@@ -0,0 +1,139 @@
+use std::future::{ready, Ready};
+
+use actix_web::{
+    body::EitherBody,
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::{
+        header::{HeaderValue, LOCATION, STRICT_TRANSPORT_SECURITY},
+        Method,
+    },
+    Error, HttpResponse,
+};
+use futures::future::LocalBoxFuture;
+
+use crate::models::user::{ApiError, ApiResponse};
+
+// Rejects plain HTTP requests (as reported by the TLS-terminating proxy through
+// X-Forwarded-Proto/Forwarded) and adds HSTS to every response it lets through.
+// Safe requests are redirected to https, anything else is refused.
+#[derive(Clone)]
+pub struct RequireHttps {
+    hsts_max_age_secs: u64,
+}
+
+impl RequireHttps {
+    pub fn new(hsts_max_age_secs: u64) -> Self {
+        Self { hsts_max_age_secs }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RequireHttps
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = RequireHttpsMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequireHttpsMiddleware {
+            service,
+            hsts: HeaderValue::from_str(&format!(
+                "max-age={}; includeSubDomains",
+                self.hsts_max_age_secs
+            ))
+            .expect("HSTS header value is always valid"),
+        }))
+    }
+}
+
+pub struct RequireHttpsMiddleware<S> {
+    service: S,
+    hsts: HeaderValue,
+}
+
+impl<S, B> Service<ServiceRequest> for RequireHttpsMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let connection_info = req.connection_info().clone();
+
+        if connection_info.scheme() != "https" {
+            let response = if req.method() == Method::GET || req.method() == Method::HEAD {
+                let location = format!("https://{}{}", connection_info.host(), req.uri());
+                HttpResponse::PermanentRedirect()
+                    .insert_header((LOCATION, location))
+                    .finish()
+            } else {
+                HttpResponse::Forbidden().json(ApiResponse::<()> {
+                    success: false,
+                    data: None,
+                    errors: Some(vec![ApiError {
+                        entity: "SOCIO_ECHO_BE".to_string(),
+                        code: "1010".to_string(),
+                        cause: "HTTPS_REQUIRED".to_string(),
+                    }]),
+                })
+            };
+
+            return Box::pin(async move { Ok(req.into_response(response).map_into_right_body()) });
+        }
+
+        let hsts = self.hsts.clone();
+        let fut = self.service.call(req);
+        Box::pin(async move {
+            let mut res = fut.await?;
+            res.headers_mut().insert(STRICT_TRANSPORT_SECURITY, hsts);
+            Ok(res.map_into_left_body())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        http::StatusCode,
+        test::{call_service, init_service, TestRequest},
+        web, App,
+    };
+
+    use super::*;
+
+    #[actix_web::test]
+    async fn plain_http_is_blocked_and_forwarded_https_gets_hsts() {
+        let app = init_service(
+            App::new()
+                .wrap(RequireHttps::new(600))
+                .route("/v1/me", web::get().to(|| async { HttpResponse::Ok().json(()) }))
+                .route("/v1/auth/login", web::post().to(|| async { HttpResponse::Ok().json(()) })),
+        )
+        .await;
+
+        let redirected = call_service(&app, TestRequest::get().uri("/v1/me?x=1").insert_header(("Host", "api.example.com")).to_request()).await;
+        assert_eq!(redirected.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(redirected.headers().get(LOCATION).unwrap(), "https://api.example.com/v1/me?x=1");
+        assert!(redirected.headers().get(STRICT_TRANSPORT_SECURITY).is_none());
+
+        let refused = call_service(&app, TestRequest::post().uri("/v1/auth/login").to_request()).await;
+        assert_eq!(refused.status(), StatusCode::FORBIDDEN);
+
+        for forwarded in [("X-Forwarded-Proto", "https"), ("Forwarded", "proto=https")] {
+            let response = call_service(&app, TestRequest::post().uri("/v1/auth/login").insert_header(forwarded).to_request()).await;
+            assert_eq!(response.status(), StatusCode::OK, "{forwarded:?}");
+            assert_eq!(response.headers().get(STRICT_TRANSPORT_SECURITY).unwrap(), "max-age=600; includeSubDomains");
+        }
+    }
+}
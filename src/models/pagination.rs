@@ -0,0 +1,56 @@
+use serde::Serialize;
+
+use crate::models::user::ApiError;
+
+pub const DEFAULT_PAGE_SIZE: i64 = 20;
+pub const MAX_PAGE_SIZE: i64 = 100;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Page {
+    pub limit: i64,
+    pub offset: i64,
+}
+
+impl Page {
+    // Validates the optional limit/offset query params against MAX_PAGE_SIZE
+    pub fn from_query(limit: Option<i64>, offset: Option<i64>) -> Result<Self, String> {
+        let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE);
+        let offset = offset.unwrap_or(0);
+
+        if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+            return Err(format!("INVALID_LIMIT: must be between 1 and {}", MAX_PAGE_SIZE));
+        }
+        if offset < 0 {
+            return Err("INVALID_OFFSET: must not be negative".to_string());
+        }
+
+        Ok(Self { limit, offset })
+    }
+
+    pub fn meta(&self, total: i64, returned: usize) -> PaginationMeta {
+        PaginationMeta {
+            total,
+            limit: self.limit,
+            offset: self.offset,
+            has_more: self.offset + (returned as i64) < total,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PaginationMeta {
+    pub total: i64,
+    pub limit: i64,
+    pub offset: i64,
+    pub has_more: bool,
+}
+
+// ApiResponse for list endpoints, with the pagination of the returned page
+#[derive(Debug, Serialize)]
+pub struct PaginatedResponse<T> {
+    pub success: bool,
+    pub data: Option<Vec<T>>,
+    pub errors: Option<Vec<ApiError>>,
+    pub meta: Option<PaginationMeta>,
+}
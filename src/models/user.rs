@@ -1,16 +1,20 @@
 use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize};
+use serde::{ser::SerializeStruct, Deserialize, Serialize, Serializer};
 use validator::Validate;
 
+use crate::commons::error_cause;
+
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct User {
     pub id: i32,
     pub name: String,
     pub email: String,
     #[serde(skip_serializing)]
     pub password_hash: String,
-    // pub created_at: Option<DateTime<Utc>>,
-    // pub updated_at: Option<DateTime<Utc>>,
+    pub email_verified: bool,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, Deserialize, Validate)]
@@ -31,10 +35,50 @@ pub struct LoginRequest {
     pub password: String,
 }
 
+#[derive(Debug, Deserialize, Validate)]
+pub struct ChangePasswordRequest {
+    pub current_password: String,
+    #[validate(length(min = 6, message = "Password must be at least 6 characters"))]
+    pub new_password: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ForgotPasswordRequest {
+    #[validate(email(message = "Invalid email format"))]
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ResetPasswordRequest {
+    #[validate(length(min = 1, message = "Reset token cannot be empty"))]
+    pub token: String,
+    #[validate(length(min = 6, message = "Password must be at least 6 characters"))]
+    pub new_password: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct VerifyEmailRequest {
+    #[validate(length(min = 1, message = "Verification token cannot be empty"))]
+    pub token: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct RefreshTokenRequest {
+    #[validate(length(min = 1, message = "Refresh token cannot be empty"))]
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct LogoutRequest {
+    // Revoked together with the access token when given
+    pub refresh_token: Option<String>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct AuthResponse {
     pub token: String,
     pub expired_at: DateTime<Utc>,
+    pub refresh_token: String,
 }
 
 #[derive(Debug, Serialize)]
@@ -44,9 +88,20 @@ pub struct ApiResponse<T> {
     pub errors: Option<Vec<ApiError>>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone)]
 pub struct ApiError {
     pub entity: String,
     pub code: String,
     pub cause: String,
+}
+
+// The cause goes through the configured cause policy on its way to the client
+impl Serialize for ApiError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("ApiError", 3)?;
+        state.serialize_field("entity", &self.entity)?;
+        state.serialize_field("code", &self.code)?;
+        state.serialize_field("cause", &error_cause::sanitize(&self.code, &self.cause))?;
+        state.end()
+    }
 } 
\ No newline at end of file
@@ -0,0 +1,32 @@
+use serde_json::Value;
+use sqlx::PgConnection;
+
+pub struct AuditLogRepository;
+
+impl AuditLogRepository {
+    // Takes a connection so the entry commits or rolls back with the audited change
+    pub async fn create(
+        conn: &mut PgConnection,
+        action: &str,
+        entity_type: &str,
+        entity_id: &str,
+        actor: &str,
+        details: Value,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO audit_logs (action, entity_type, entity_id, actor, details)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+            action,
+            entity_type,
+            entity_id,
+            actor,
+            details.to_string()
+        )
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,67 @@
+use chrono::{DateTime, Utc};
+use sqlx::{PgConnection, PgPool, Postgres, Transaction};
+
+// Like refresh tokens, only SHA-256 hashes of verification tokens are stored
+pub struct EmailVerificationTokenRepository {
+    pool: PgPool,
+}
+
+impl EmailVerificationTokenRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn begin(&self) -> Result<Transaction<'static, Postgres>, sqlx::Error> {
+        self.pool.begin().await
+    }
+
+    pub async fn create(
+        conn: &mut PgConnection,
+        user_id: i32,
+        token_hash: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO email_verification_tokens (user_id, token_hash, expires_at)
+            VALUES ($1, $2, $3)
+            "#,
+            user_id,
+            token_hash,
+            expires_at
+        )
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    // When the user was last sent a token, to throttle resends
+    pub async fn find_last_created_at(conn: &mut PgConnection, user_id: i32) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"
+            SELECT MAX(created_at)
+            FROM email_verification_tokens
+            WHERE user_id = $1
+            "#,
+            user_id
+        )
+        .fetch_one(conn)
+        .await
+    }
+
+    // Marks the token used and returns its user, or None when it is unknown, used or expired
+    pub async fn consume(conn: &mut PgConnection, token_hash: &str) -> Result<Option<i32>, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"
+            UPDATE email_verification_tokens
+            SET used_at = NOW()
+            WHERE token_hash = $1 AND used_at IS NULL AND expires_at > NOW()
+            RETURNING user_id
+            "#,
+            token_hash
+        )
+        .fetch_optional(conn)
+        .await
+    }
+}
@@ -0,0 +1,61 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{PgConnection, PgPool};
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FeatureFlag {
+    pub name: String,
+    pub enabled: bool,
+    pub updated_by: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Clone)]
+pub struct FeatureFlagRepository {
+    pool: PgPool,
+}
+
+impl FeatureFlagRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn list(&self) -> Result<Vec<FeatureFlag>, sqlx::Error> {
+        sqlx::query_as!(
+            FeatureFlag,
+            r#"
+            SELECT name, enabled, updated_by, updated_at
+            FROM feature_flags
+            order by name
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn upsert(
+        conn: &mut PgConnection,
+        name: &str,
+        enabled: bool,
+        updated_by: &str,
+    ) -> Result<FeatureFlag, sqlx::Error> {
+        sqlx::query_as!(
+            FeatureFlag,
+            r#"
+            INSERT INTO feature_flags (name, enabled, updated_by)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (name) DO UPDATE
+            SET enabled = EXCLUDED.enabled,
+                updated_by = EXCLUDED.updated_by,
+                updated_at = NOW()
+            RETURNING name, enabled, updated_by, updated_at
+            "#,
+            name,
+            enabled,
+            updated_by
+        )
+        .fetch_one(conn)
+        .await
+    }
+}
@@ -0,0 +1,82 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+// Consecutive failed logins per email, and the lock they put on the account
+pub struct LoginAttemptRepository {
+    pool: PgPool,
+}
+
+impl LoginAttemptRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn find_locked_until(&self, email: &str) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+        let locked_until = sqlx::query_scalar!(
+            r#"
+            SELECT locked_until
+            FROM login_attempts
+            WHERE email = $1 AND locked_until > NOW()
+            "#,
+            email
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(locked_until.flatten())
+    }
+
+    // Counts one more failure and locks the account once `threshold` is reached. The count
+    // starts over after an expired lock, so the next lock again takes `threshold` failures.
+    pub async fn record_failure(
+        &self,
+        email: &str,
+        threshold: i32,
+        lock_secs: f64,
+    ) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"
+            INSERT INTO login_attempts (email, failed_count, locked_until, updated_at)
+            VALUES (
+                $1,
+                1,
+                CASE WHEN 1 >= $2 THEN NOW() + make_interval(secs => $3) END,
+                NOW()
+            )
+            ON CONFLICT (email) DO UPDATE SET
+                failed_count = CASE
+                    WHEN login_attempts.locked_until <= NOW() THEN 1
+                    ELSE login_attempts.failed_count + 1
+                END,
+                locked_until = CASE
+                    WHEN login_attempts.locked_until > NOW() THEN login_attempts.locked_until
+                    WHEN (CASE
+                        WHEN login_attempts.locked_until <= NOW() THEN 1
+                        ELSE login_attempts.failed_count + 1
+                    END) >= $2 THEN NOW() + make_interval(secs => $3)
+                END,
+                updated_at = NOW()
+            RETURNING locked_until
+            "#,
+            email,
+            threshold,
+            lock_secs
+        )
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    pub async fn reset(&self, email: &str) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            DELETE FROM login_attempts
+            WHERE email = $1
+            "#,
+            email
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
@@ -1 +1,8 @@
-pub mod user_repository; 
\ No newline at end of file
+pub mod audit_log_repository;
+pub mod email_verification_token_repository;
+pub mod feature_flag_repository;
+pub mod login_attempt_repository;
+pub mod refresh_token_repository;
+pub mod revoked_token_repository;
+pub mod user_repository;
+pub mod webhook_delivery_repository;
@@ -0,0 +1,109 @@
+use chrono::{DateTime, Utc};
+use sqlx::{PgConnection, PgPool, Postgres, Transaction};
+
+#[derive(Debug)]
+pub struct RefreshToken {
+    pub id: i64,
+    pub user_id: i32,
+    pub expires_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+// Only SHA-256 hashes of refresh tokens are stored, never the tokens themselves
+pub struct RefreshTokenRepository {
+    pool: PgPool,
+}
+
+impl RefreshTokenRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn begin(&self) -> Result<Transaction<'static, Postgres>, sqlx::Error> {
+        self.pool.begin().await
+    }
+
+    pub async fn create(
+        conn: &mut PgConnection,
+        user_id: i32,
+        token_hash: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"
+            INSERT INTO refresh_tokens (user_id, token_hash, expires_at)
+            VALUES ($1, $2, $3)
+            RETURNING id
+            "#,
+            user_id,
+            token_hash,
+            expires_at
+        )
+        .fetch_one(conn)
+        .await
+    }
+
+    // Locks the row so two concurrent refreshes with the same token can't both rotate it
+    pub async fn lock_by_hash(conn: &mut PgConnection, token_hash: &str) -> Result<Option<RefreshToken>, sqlx::Error> {
+        sqlx::query_as!(
+            RefreshToken,
+            r#"
+            SELECT id, user_id, expires_at, revoked_at
+            FROM refresh_tokens
+            WHERE token_hash = $1
+            FOR UPDATE
+            "#,
+            token_hash
+        )
+        .fetch_optional(conn)
+        .await
+    }
+
+    pub async fn revoke(conn: &mut PgConnection, id: i64, replaced_by: Option<i64>) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE refresh_tokens
+            SET revoked_at = NOW(), replaced_by = $2
+            WHERE id = $1
+            "#,
+            id,
+            replaced_by
+        )
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    // Scoped to the user so one user can't revoke another's token
+    pub async fn revoke_by_hash(conn: &mut PgConnection, user_id: i32, token_hash: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE refresh_tokens
+            SET revoked_at = NOW()
+            WHERE user_id = $1 AND token_hash = $2 AND revoked_at IS NULL
+            "#,
+            user_id,
+            token_hash
+        )
+        .execute(conn)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn revoke_all_for_user(conn: &mut PgConnection, user_id: i32) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE refresh_tokens
+            SET revoked_at = NOW()
+            WHERE user_id = $1 AND revoked_at IS NULL
+            "#,
+            user_id
+        )
+        .execute(conn)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+}
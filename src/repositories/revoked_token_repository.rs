@@ -0,0 +1,54 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+// Denylist of access token ids revoked before their expiry
+pub struct RevokedTokenRepository {
+    pool: PgPool,
+}
+
+impl RevokedTokenRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn create(&self, jti: &str, expires_at: DateTime<Utc>) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO revoked_tokens (jti, expires_at)
+            VALUES ($1, $2)
+            ON CONFLICT (jti) DO NOTHING
+            "#,
+            jti,
+            expires_at
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn exists(&self, jti: &str) -> Result<bool, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS(SELECT 1 FROM revoked_tokens WHERE jti = $1) as "exists!"
+            "#,
+            jti
+        )
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    pub async fn delete_expired(&self, now: DateTime<Utc>) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM revoked_tokens
+            WHERE expires_at < $1
+            "#,
+            now
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+}
@@ -1,16 +1,31 @@
-use sqlx::PgPool;
+use chrono::{DateTime, Utc};
+use sqlx::{PgConnection, PgPool, Postgres, Transaction};
 use crate::models::user::User;
 
 pub struct UserRepository {
     pool: PgPool,
+    read_pool: PgPool,
 }
 
 impl UserRepository {
-    pub fn new(pool: PgPool) -> Self {
-        Self { pool }
+    pub fn new(pool: PgPool, read_pool: PgPool) -> Self {
+        Self { pool, read_pool }
+    }
+
+    pub async fn begin(&self) -> Result<Transaction<'static, Postgres>, sqlx::Error> {
+        self.pool.begin().await
     }
 
     pub async fn find_by_email(&self, email: &str) -> Result<Option<User>, sqlx::Error> {
+        Self::fetch_by_email(&self.pool, email).await
+    }
+
+    // Login only reads, so it can tolerate replica lag
+    pub async fn find_by_email_from_replica(&self, email: &str) -> Result<Option<User>, sqlx::Error> {
+        Self::fetch_by_email(&self.read_pool, email).await
+    }
+
+    async fn fetch_by_email(pool: &PgPool, email: &str) -> Result<Option<User>, sqlx::Error> {
         sqlx::query_as!(
             User,
             r#"
@@ -18,13 +33,16 @@ impl UserRepository {
                 id, 
                 name, 
                 email, 
-                password_hash
+                password_hash,
+                email_verified,
+                created_at,
+                updated_at
             FROM users
             WHERE email = $1
             "#,
             email
         )
-        .fetch_optional(&self.pool)
+        .fetch_optional(pool)
         .await
     }
 
@@ -38,7 +56,10 @@ impl UserRepository {
                 id, 
                 name, 
                 email, 
-                password_hash
+                password_hash,
+                email_verified,
+                created_at,
+                updated_at
             "#,
             name,
             email,
@@ -47,4 +68,198 @@ impl UserRepository {
         .fetch_one(&self.pool)
         .await
     }
-} 
\ No newline at end of file
+
+    pub async fn find_by_id(&self, id: i32) -> Result<Option<User>, sqlx::Error> {
+        sqlx::query_as!(
+            User,
+            r#"
+            SELECT 
+                id, 
+                name, 
+                email, 
+                password_hash,
+                email_verified,
+                created_at,
+                updated_at
+            FROM users
+            WHERE id = $1
+            "#,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    // Holds the user's row until the transaction ends, serializing per-user throttles
+    pub async fn lock_by_id(conn: &mut PgConnection, id: i32) -> Result<Option<User>, sqlx::Error> {
+        sqlx::query_as!(
+            User,
+            r#"
+            SELECT 
+                id, 
+                name, 
+                email, 
+                password_hash,
+                email_verified,
+                created_at,
+                updated_at
+            FROM users
+            WHERE id = $1
+            FOR UPDATE
+            "#,
+            id
+        )
+        .fetch_optional(conn)
+        .await
+    }
+
+    // None for a missing user as well as for one on the default tier
+    pub async fn find_risk_tier(&self, id: i32) -> Result<Option<String>, sqlx::Error> {
+        let risk_tier = sqlx::query_scalar!(
+            r#"
+            SELECT risk_tier
+            FROM users
+            WHERE id = $1
+            "#,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(risk_tier.flatten())
+    }
+
+    // None puts the user back on the default tier. False when there is no such user.
+    pub async fn set_risk_tier(conn: &mut PgConnection, id: i32, risk_tier: Option<&str>) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET risk_tier = $2, updated_at = NOW()
+            WHERE id = $1
+            "#,
+            id,
+            risk_tier
+        )
+        .execute(conn)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    // Only the SHA-256 hash of a reset token is stored
+    pub async fn create_password_reset(
+        &self,
+        user_id: i32,
+        token_hash: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO password_resets (user_id, token_hash, expires_at)
+            VALUES ($1, $2, $3)
+            "#,
+            user_id,
+            token_hash,
+            expires_at
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    // When the user last requested a reset, to throttle reset mails
+    pub async fn find_last_password_reset_at(&self, user_id: i32) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"
+            SELECT MAX(created_at)
+            FROM password_resets
+            WHERE user_id = $1
+            "#,
+            user_id
+        )
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    // Uses up the token along with every other outstanding reset of the same user, and returns
+    // the user. None when the token is unknown, used or expired.
+    pub async fn consume_password_reset(conn: &mut PgConnection, token_hash: &str) -> Result<Option<i32>, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"
+            UPDATE password_resets
+            SET used_at = NOW()
+            WHERE used_at IS NULL AND user_id = (
+                SELECT user_id
+                FROM password_resets
+                WHERE token_hash = $1 AND used_at IS NULL AND expires_at > NOW()
+                FOR UPDATE
+            )
+            RETURNING user_id
+            "#,
+            token_hash
+        )
+        .fetch_all(conn)
+        .await
+        .map(|user_ids| user_ids.into_iter().next())
+    }
+
+    pub async fn set_password(conn: &mut PgConnection, id: i32, password_hash: &str) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET password_hash = $2, updated_at = NOW()
+            WHERE id = $1
+            "#,
+            id,
+            password_hash
+        )
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn mark_email_verified(conn: &mut PgConnection, id: i32) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET email_verified = TRUE, updated_at = NOW()
+            WHERE id = $1
+            "#,
+            id
+        )
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    // None when the user no longer exists
+    pub async fn is_email_verified(&self, id: i32) -> Result<Option<bool>, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"
+            SELECT email_verified
+            FROM users
+            WHERE id = $1
+            "#,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    pub async fn delete(conn: &mut PgConnection, id: i32) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM users
+            WHERE id = $1
+            "#,
+            id
+        )
+        .execute(conn)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
@@ -0,0 +1,45 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+
+// One attempt to deliver a webhook; `status_code` is absent when no response arrived
+pub struct NewWebhookDelivery<'a> {
+    pub submission_id: Uuid,
+    pub url: &'a str,
+    pub event: &'a str,
+    pub attempt: i32,
+    pub status_code: Option<i32>,
+    pub error: Option<&'a str>,
+    pub delivered: bool,
+}
+
+// One row per attempt to deliver a webhook, successful or not
+#[derive(Clone)]
+pub struct WebhookDeliveryRepository {
+    pool: PgPool,
+}
+
+impl WebhookDeliveryRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn create(&self, delivery: NewWebhookDelivery<'_>) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO webhook_deliveries (submission_id, url, event, attempt, status_code, error, delivered)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+            delivery.submission_id,
+            delivery.url,
+            delivery.event,
+            delivery.attempt,
+            delivery.status_code,
+            delivery.error,
+            delivery.delivered
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,89 @@
+use sqlx::PgPool;
+
+use crate::{
+    commons::{database::ReadPool, minio_service::MinioService},
+    config::Config,
+    services::{
+        face_match_service::FaceMatchService,
+        feature_flags_service::FeatureFlagsService,
+        metrics_service::MetricsService,
+        user_erasure_service::UserErasureService,
+        webhook_service::WebhookService,
+    },
+    submissions::{submission_repository::SubmissionRepository, submission_service::SubmissionService},
+};
+
+// The services submission and admin handlers share, registered once in main so a handler
+// takes one `web::Data<AppServices>` instead of each piece on its own
+#[derive(Clone)]
+pub struct AppServices {
+    pub config: Config,
+    pub pool: PgPool,
+    pub read_pool: ReadPool,
+    pub minio: MinioService,
+    pub metrics: MetricsService,
+    pub feature_flags: FeatureFlagsService,
+    pub face_match: FaceMatchService,
+    pub webhooks: WebhookService,
+}
+
+impl AppServices {
+    pub fn submission_service(&self) -> SubmissionService {
+        SubmissionService::new(
+            self.minio.clone(),
+            SubmissionRepository::new(self.pool.clone(), self.read_pool.0.clone()),
+            self.metrics.clone(),
+            self.config.clone(),
+            self.feature_flags.clone(),
+        )
+    }
+
+    pub fn erasure_service(&self) -> UserErasureService {
+        UserErasureService::new(self.pool.clone(), self.minio.clone(), self.metrics.clone())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{
+        config::tests::from_env_with,
+        services::{circuit_breaker::CircuitBreaker, face_match_service, webhook_service::WebhookSettings},
+    };
+
+    // Services over `pool` and a config read from `vars`. MinIO and the face match provider
+    // are unreachable, and webhooks are disabled unless `vars` set a secret.
+    pub(crate) fn services(pool: PgPool, vars: &[(&str, &str)]) -> AppServices {
+        services_with_minio(pool, vars, "http://127.0.0.1:1")
+    }
+
+    pub(crate) fn services_with_minio(pool: PgPool, vars: &[(&str, &str)], minio_endpoint: &str) -> AppServices {
+        let config = from_env_with(vars).unwrap();
+        let metrics = MetricsService::noop();
+        let breaker = CircuitBreaker::new("face_match", 0, Duration::from_secs(60), Duration::from_secs(30), metrics.clone());
+
+        AppServices {
+            minio: MinioService::unchecked(
+                minio_endpoint,
+                "minio",
+                "minio123",
+                &config.minio_bucket_name,
+                config.minio_view_key_suffixes.clone(),
+            ),
+            face_match: FaceMatchService::new(
+                face_match_service::tests::settings("http://127.0.0.1:1".to_string(), 1),
+                breaker,
+                metrics.clone(),
+            )
+            .unwrap(),
+            webhooks: WebhookService::new(pool.clone(), WebhookSettings::from_config(&config), metrics.clone()),
+            feature_flags: FeatureFlagsService::new(pool.clone(), Duration::ZERO),
+            read_pool: ReadPool(pool.clone()),
+            pool,
+            metrics,
+            config,
+        }
+    }
+}
@@ -1,37 +1,105 @@
 use argon2::{self, password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString}};
-use chrono::{Duration, Utc};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use chrono::{DateTime, Duration, Utc};
 use jsonwebtoken::{encode, EncodingKey, Header};
+use rand::RngCore;
 use serde::{Deserialize, Serialize};
-use sqlx::PgPool;
+use sha2::{Digest, Sha256};
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
 
 use crate::{
-    models::user::{AuthResponse, LoginRequest, RegisterRequest},
-    repositories::user_repository::UserRepository,
+    config::Config,
+    models::user::{AuthResponse, ChangePasswordRequest, LoginRequest, RegisterRequest, ResetPasswordRequest, User},
+    repositories::{
+        email_verification_token_repository::EmailVerificationTokenRepository,
+        login_attempt_repository::LoginAttemptRepository,
+        refresh_token_repository::RefreshTokenRepository,
+        revoked_token_repository::RevokedTokenRepository,
+        user_repository::UserRepository,
+    },
+    services::email_sender::{EmailMessage, EmailSender},
 };
 
 #[derive(Debug, Serialize, Deserialize)]
 struct Claims {
     sub: i32,
     exp: i64,
+    jti: String,
+}
+
+// Returned by login while too many consecutive failures keep the account locked
+#[derive(Debug, thiserror::Error)]
+#[error("Account locked until {until}")]
+pub struct AccountLocked {
+    pub until: DateTime<Utc>,
+}
+
+// Returned by resend_verification while the previous mail is too recent
+#[derive(Debug, thiserror::Error)]
+#[error("Verification email sent too recently, retry in {retry_after_secs}s")]
+pub struct VerificationThrottled {
+    pub retry_after_secs: i64,
 }
 
 pub struct AuthService {
     user_repository: UserRepository,
+    login_attempt_repository: LoginAttemptRepository,
+    refresh_token_repository: RefreshTokenRepository,
+    revoked_token_repository: RevokedTokenRepository,
+    email_verification_token_repository: EmailVerificationTokenRepository,
+    // Configured cost for new hashes; existing hashes verify with the params encoded in them
+    argon2: argon2::Argon2<'static>,
     jwt_secret: String,
+    access_token_ttl: Duration,
+    refresh_token_ttl: Duration,
+    login_lockout_threshold: i32,
+    login_lockout_secs: u64,
+    email_verification_url: String,
+    email_verification_token_ttl: Duration,
+    email_verification_resend_cooldown: Duration,
+    password_reset_url: String,
+    password_reset_token_ttl: Duration,
+    password_reset_cooldown: Duration,
 }
 
 impl AuthService {
-    pub fn new(pool: PgPool, jwt_secret: String) -> Self {
+    pub fn new(pool: PgPool, read_pool: PgPool, config: &Config) -> Self {
         Self {
-            user_repository: UserRepository::new(pool),
-            jwt_secret,
+            refresh_token_repository: RefreshTokenRepository::new(pool.clone()),
+            revoked_token_repository: RevokedTokenRepository::new(pool.clone()),
+            login_attempt_repository: LoginAttemptRepository::new(pool.clone()),
+            email_verification_token_repository: EmailVerificationTokenRepository::new(pool.clone()),
+            user_repository: UserRepository::new(pool, read_pool),
+            argon2: argon2::Argon2::new(
+                argon2::Algorithm::Argon2id,
+                argon2::Version::V0x13,
+                config.argon2_params().expect("Argon2 params are validated by Config::from_env"),
+            ),
+            jwt_secret: config.jwt_secret.clone(),
+            access_token_ttl: Duration::seconds(config.access_token_ttl_secs),
+            refresh_token_ttl: Duration::seconds(config.refresh_token_ttl_secs),
+            login_lockout_threshold: config.login_lockout_threshold,
+            login_lockout_secs: config.login_lockout_secs,
+            email_verification_url: config.email_verification_url.clone(),
+            email_verification_token_ttl: Duration::seconds(config.email_verification_token_ttl_secs),
+            email_verification_resend_cooldown: Duration::seconds(config.email_verification_resend_cooldown_secs),
+            password_reset_url: config.password_reset_url.clone(),
+            password_reset_token_ttl: Duration::seconds(config.password_reset_token_ttl_secs),
+            password_reset_cooldown: Duration::seconds(config.password_reset_cooldown_secs),
         }
     }
 
-    pub async fn register(&self, request: RegisterRequest) -> Result<AuthResponse, anyhow::Error> {
+    // The verification mail is sent before returning; a failed send only gets logged since
+    // the user can ask for another one
+    pub async fn register(
+        &self,
+        request: RegisterRequest,
+        email_sender: &dyn EmailSender,
+    ) -> Result<AuthResponse, anyhow::Error> {
         let start = std::time::Instant::now();
         // Check if user exists
-        if let Some(_) = self.user_repository.find_by_email(&request.email).await? {
+        if self.user_repository.find_by_email(&request.email).await?.is_some() {
             return Err(anyhow::anyhow!("User already exists"));
         }
 
@@ -41,9 +109,8 @@ impl AuthService {
         let start = std::time::Instant::now();
         // Hash password with Argon2
         let salt = SaltString::generate(&mut argon2::password_hash::rand_core::OsRng);
-        let argon2 = argon2::Argon2::default();
         let password_hash = PasswordHasher::hash_password(
-            &argon2,
+            &self.argon2,
             request.password.as_bytes(),
             &salt,
         ).map_err(|e| anyhow::anyhow!("Failed to hash password: {}", e))?;
@@ -61,18 +128,33 @@ impl AuthService {
         let duration = start.elapsed();
         log::info!("User creation process took: {:?}", duration);
 
-        // Generate token
-        self.generate_token(user.id)
+        let mut tx = self.email_verification_token_repository.begin().await?;
+        let token = self.create_verification_token(&mut tx, user.id).await?;
+        tx.commit().await?;
+
+        if let Err(e) = self.send_verification_email(&user, &token, email_sender).await {
+            log::error!("Failed to send verification email to user {}: {}", user.id, e);
+        }
+
+        // Generate tokens
+        self.generate_token_pair(user.id).await
     }
 
     pub async fn login(&self, request: LoginRequest) -> Result<AuthResponse, anyhow::Error> {
+        // Attempts are counted per email whether or not the account exists
+        let attempt_key = request.email.to_lowercase();
+        if self.login_lockout_threshold > 0 {
+            if let Some(until) = self.login_attempt_repository.find_locked_until(&attempt_key).await? {
+                return Err(AccountLocked { until }.into());
+            }
+        }
+
         let start = std::time::Instant::now();
         // Find user
-        let user = self
-            .user_repository
-            .find_by_email(&request.email)
-            .await?
-            .ok_or_else(|| anyhow::anyhow!("Invalid email or password"))?;
+        let user = match self.user_repository.find_by_email_from_replica(&request.email).await? {
+            Some(user) => user,
+            None => return Err(self.login_failed(&attempt_key).await),
+        };
 
         let duration = start.elapsed();
         log::info!("User find process took: {:?}", duration);
@@ -81,24 +163,309 @@ impl AuthService {
         // Verify password with Argon2
         let parsed_hash = PasswordHash::new(&user.password_hash)
             .map_err(|e| anyhow::anyhow!("Invalid password hash: {}", e))?;
-        let argon2 = argon2::Argon2::default();
-        if PasswordVerifier::verify_password(&argon2, request.password.as_bytes(), &parsed_hash).is_err() {
-            return Err(anyhow::anyhow!("Invalid email or password"));
+        if PasswordVerifier::verify_password(&self.argon2, request.password.as_bytes(), &parsed_hash).is_err() {
+            return Err(self.login_failed(&attempt_key).await);
         }
 
         let duration = start.elapsed();
         log::info!("Password verify process took: {:?}", duration);
 
-        // Generate token
-        self.generate_token(user.id)
+        if self.login_lockout_threshold > 0 {
+            self.login_attempt_repository.reset(&attempt_key).await?;
+        }
+
+        // Generate tokens
+        self.generate_token_pair(user.id).await
+    }
+
+    // Records the failure; the attempt that reaches the threshold is already answered as locked
+    async fn login_failed(&self, attempt_key: &str) -> anyhow::Error {
+        if self.login_lockout_threshold == 0 {
+            return anyhow::anyhow!("Invalid email or password");
+        }
+
+        match self
+            .login_attempt_repository
+            .record_failure(attempt_key, self.login_lockout_threshold, self.login_lockout_secs as f64)
+            .await
+        {
+            Ok(Some(until)) => AccountLocked { until }.into(),
+            Ok(None) => anyhow::anyhow!("Invalid email or password"),
+            Err(e) => e.into(),
+        }
+    }
+
+    pub async fn find_user(&self, user_id: i32) -> Result<Option<User>, anyhow::Error> {
+        Ok(self.user_repository.find_by_id(user_id).await?)
+    }
+
+    pub async fn change_password(&self, user_id: i32, request: ChangePasswordRequest) -> Result<(), anyhow::Error> {
+        let user = self
+            .user_repository
+            .find_by_id(user_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("User not found"))?;
+
+        // Verify current password with Argon2
+        let parsed_hash = PasswordHash::new(&user.password_hash)
+            .map_err(|e| anyhow::anyhow!("Invalid password hash: {}", e))?;
+        if PasswordVerifier::verify_password(&self.argon2, request.current_password.as_bytes(), &parsed_hash).is_err() {
+            return Err(anyhow::anyhow!("Invalid current password"));
+        }
+
+        let salt = SaltString::generate(&mut argon2::password_hash::rand_core::OsRng);
+        let password_hash = PasswordHasher::hash_password(
+            &self.argon2,
+            request.new_password.as_bytes(),
+            &salt,
+        ).map_err(|e| anyhow::anyhow!("Failed to hash password: {}", e))?;
+
+        // Other sessions end when their access token expires, since they can't be refreshed
+        let mut tx = self.user_repository.begin().await?;
+        UserRepository::set_password(&mut tx, user.id, &password_hash.to_string()).await?;
+        let revoked = RefreshTokenRepository::revoke_all_for_user(&mut tx, user.id).await?;
+
+        tx.commit().await?;
+        log::info!("Password changed for user {}, revoked {} refresh tokens", user.id, revoked);
+
+        Ok(())
+    }
+
+    // Mails a reset link when the email belongs to a user. Unknown emails and requests within
+    // the cooldown are silently ignored; callers answer the same either way.
+    pub async fn forgot_password(&self, email: &str, email_sender: &dyn EmailSender) -> Result<(), anyhow::Error> {
+        let Some(user) = self.user_repository.find_by_email(email).await? else {
+            return Ok(());
+        };
+
+        if let Some(last_sent) = self.user_repository.find_last_password_reset_at(user.id).await? {
+            if last_sent + self.password_reset_cooldown > Utc::now() {
+                log::info!("Password reset for user {} requested within the cooldown, not sent", user.id);
+                return Ok(());
+            }
+        }
+
+        let token = random_token();
+        self.user_repository
+            .create_password_reset(user.id, &hash_token(&token), Utc::now() + self.password_reset_token_ttl)
+            .await?;
+
+        let message = EmailMessage {
+            to: user.email.clone(),
+            subject: "Reset your password".to_string(),
+            body: format!(
+                "Hi {},\n\nOpen this link to choose a new password: {}?token={}\n\nThe link expires in {} minutes. \
+                If you didn't ask for this, ignore this email.",
+                user.name,
+                self.password_reset_url,
+                token,
+                self.password_reset_token_ttl.num_minutes()
+            ),
+        };
+
+        email_sender.send(&message).await
+    }
+
+    // Sets the new password and uses up every outstanding reset token of the user. Refresh
+    // tokens are revoked too, so sessions of whoever knew the old password end.
+    pub async fn reset_password(&self, request: ResetPasswordRequest) -> Result<(), anyhow::Error> {
+        let salt = SaltString::generate(&mut argon2::password_hash::rand_core::OsRng);
+        let password_hash = PasswordHasher::hash_password(
+            &self.argon2,
+            request.new_password.as_bytes(),
+            &salt,
+        ).map_err(|e| anyhow::anyhow!("Failed to hash password: {}", e))?;
+
+        let mut tx = self.user_repository.begin().await?;
+
+        let user_id = UserRepository::consume_password_reset(&mut tx, &hash_token(&request.token))
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Invalid reset token"))?;
+        UserRepository::set_password(&mut tx, user_id, &password_hash.to_string()).await?;
+        let revoked = RefreshTokenRepository::revoke_all_for_user(&mut tx, user_id).await?;
+
+        tx.commit().await?;
+        log::info!("Password reset for user {}, revoked {} refresh tokens", user_id, revoked);
+
+        Ok(())
+    }
+
+    // Consumes the token and marks the user's email verified. Each token works once.
+    pub async fn verify_email(&self, token: &str) -> Result<(), anyhow::Error> {
+        let mut tx = self.email_verification_token_repository.begin().await?;
+
+        let user_id = EmailVerificationTokenRepository::consume(&mut tx, &hash_token(token))
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Invalid verification token"))?;
+        UserRepository::mark_email_verified(&mut tx, user_id).await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    // Sends a fresh token, at most once per resend cooldown. Earlier tokens stay valid. The user's
+    // row stays locked from the cooldown check until the new token is stored, so concurrent
+    // requests can't both pass the check.
+    pub async fn resend_verification(&self, user_id: i32, email_sender: &dyn EmailSender) -> Result<(), anyhow::Error> {
+        let mut tx = self.user_repository.begin().await?;
+
+        let user = UserRepository::lock_by_id(&mut tx, user_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("User not found"))?;
+
+        if user.email_verified {
+            return Err(anyhow::anyhow!("Email already verified"));
+        }
+
+        if let Some(last_sent) = EmailVerificationTokenRepository::find_last_created_at(&mut tx, user.id).await? {
+            let next_allowed = last_sent + self.email_verification_resend_cooldown;
+            if next_allowed > Utc::now() {
+                // Rounded up so a client retrying on the value isn't refused again
+                let retry_after_secs = (next_allowed - Utc::now()).num_seconds() + 1;
+                return Err(VerificationThrottled { retry_after_secs }.into());
+            }
+        }
+
+        let token = self.create_verification_token(&mut tx, user.id).await?;
+        tx.commit().await?;
+
+        self.send_verification_email(&user, &token, email_sender).await
+    }
+
+    async fn create_verification_token(&self, conn: &mut PgConnection, user_id: i32) -> Result<String, anyhow::Error> {
+        let token = random_token();
+        EmailVerificationTokenRepository::create(
+            conn,
+            user_id,
+            &hash_token(&token),
+            Utc::now() + self.email_verification_token_ttl,
+        )
+        .await?;
+
+        Ok(token)
+    }
+
+    async fn send_verification_email(
+        &self,
+        user: &User,
+        token: &str,
+        email_sender: &dyn EmailSender,
+    ) -> Result<(), anyhow::Error> {
+        let message = EmailMessage {
+            to: user.email.clone(),
+            subject: "Verify your email".to_string(),
+            body: format!(
+                "Hi {},\n\nOpen this link to verify your email: {}?token={}\n\nThe link expires in {} hours.",
+                user.name,
+                self.email_verification_url,
+                token,
+                self.email_verification_token_ttl.num_hours()
+            ),
+        };
+
+        email_sender.send(&message).await
+    }
+
+    // Exchanges a refresh token for a new pair. The presented token is revoked, and presenting
+    // an already revoked one revokes every refresh token of the user since it has likely leaked.
+    pub async fn refresh(&self, refresh_token: &str) -> Result<AuthResponse, anyhow::Error> {
+        let mut tx = self.refresh_token_repository.begin().await?;
+
+        let stored = RefreshTokenRepository::lock_by_hash(&mut tx, &hash_token(refresh_token))
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Invalid refresh token"))?;
+
+        if stored.revoked_at.is_some() {
+            let revoked = RefreshTokenRepository::revoke_all_for_user(&mut tx, stored.user_id).await?;
+            tx.commit().await?;
+            log::warn!(
+                "Revoked refresh token reused for user {}, revoked {} active tokens",
+                stored.user_id,
+                revoked
+            );
+            return Err(anyhow::anyhow!("Invalid refresh token"));
+        }
+
+        if stored.expires_at <= Utc::now() {
+            return Err(anyhow::anyhow!("Invalid refresh token"));
+        }
+
+        let (refresh_token, refresh_token_id) = self.issue_refresh_token(&mut tx, stored.user_id).await?;
+        RefreshTokenRepository::revoke(&mut tx, stored.id, Some(refresh_token_id)).await?;
+        tx.commit().await?;
+
+        let (token, expired_at) = self.generate_access_token(stored.user_id)?;
+
+        Ok(AuthResponse {
+            token,
+            expired_at,
+            refresh_token,
+        })
+    }
+
+    // Denylists the access token until it expires, and revokes `refresh_token` if given
+    pub async fn revoke_token(
+        &self,
+        user_id: i32,
+        jti: &str,
+        expires_at: DateTime<Utc>,
+        refresh_token: Option<&str>,
+    ) -> Result<(), anyhow::Error> {
+        self.revoked_token_repository.create(jti, expires_at).await?;
+
+        if let Some(refresh_token) = refresh_token {
+            let mut tx = self.refresh_token_repository.begin().await?;
+            RefreshTokenRepository::revoke_by_hash(&mut tx, user_id, &hash_token(refresh_token)).await?;
+            tx.commit().await?;
+        }
+
+        Ok(())
     }
 
-    fn generate_token(&self, user_id: i32) -> Result<AuthResponse, anyhow::Error> {
+    pub async fn is_revoked(&self, jti: &str) -> Result<bool, anyhow::Error> {
+        Ok(self.revoked_token_repository.exists(jti).await?)
+    }
+
+    // Revoked tokens past their expiry are rejected by the signature check anyway
+    pub async fn cleanup_expired(&self) -> Result<u64, anyhow::Error> {
+        Ok(self.revoked_token_repository.delete_expired(Utc::now()).await?)
+    }
+
+    pub async fn generate_token_pair(&self, user_id: i32) -> Result<AuthResponse, anyhow::Error> {
+        let (token, expired_at) = self.generate_access_token(user_id)?;
+
+        let mut tx = self.refresh_token_repository.begin().await?;
+        let (refresh_token, _) = self.issue_refresh_token(&mut tx, user_id).await?;
+        tx.commit().await?;
+
+        Ok(AuthResponse {
+            token,
+            expired_at,
+            refresh_token,
+        })
+    }
+
+    async fn issue_refresh_token(&self, conn: &mut PgConnection, user_id: i32) -> Result<(String, i64), anyhow::Error> {
+        let refresh_token = random_token();
+
+        let id = RefreshTokenRepository::create(
+            conn,
+            user_id,
+            &hash_token(&refresh_token),
+            Utc::now() + self.refresh_token_ttl,
+        )
+        .await?;
+
+        Ok((refresh_token, id))
+    }
+
+    fn generate_access_token(&self, user_id: i32) -> Result<(String, DateTime<Utc>), anyhow::Error> {
         let start = std::time::Instant::now();
-        let expiration = Utc::now() + Duration::hours(24);
+        let expiration = Utc::now() + self.access_token_ttl;
         let claims = Claims {
             sub: user_id,
             exp: expiration.timestamp(),
+            jti: Uuid::new_v4().to_string(),
         };
 
         let token = encode(
@@ -110,9 +477,246 @@ impl AuthService {
         let duration = start.elapsed();
         log::info!("Token generate process took: {:?}", duration);
 
-        Ok(AuthResponse {
-            token,
-            expired_at: expiration,
-        })
+        Ok((token, expiration))
     }
-} 
\ No newline at end of file
+}
+
+// 256 random bits, URL safe
+fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+// Refresh, verification and reset tokens are random, so an unsalted hash is enough to keep them
+// unusable if the table leaks
+fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+// These run against a throwaway database created from the migrations, so they need DATABASE_URL
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use futures::future::BoxFuture;
+
+    use super::*;
+    use crate::config::tests::from_env_with;
+
+    // Keeps every message instead of sending it
+    #[derive(Default)]
+    struct StubSender {
+        sent: Mutex<Vec<(String, String)>>,
+    }
+
+    impl StubSender {
+        fn count(&self) -> usize {
+            self.sent.lock().unwrap().len()
+        }
+
+        // The token from the link in the last mail
+        fn last_token(&self) -> String {
+            let sent = self.sent.lock().unwrap();
+            let (_, body) = sent.last().expect("no mail sent");
+            let token = body.split("?token=").nth(1).expect("no link in mail");
+            token.split_whitespace().next().unwrap().to_string()
+        }
+    }
+
+    impl EmailSender for StubSender {
+        fn send<'a>(&'a self, message: &'a EmailMessage) -> BoxFuture<'a, anyhow::Result<()>> {
+            Box::pin(async move {
+                self.sent.lock().unwrap().push((message.to.clone(), message.body.clone()));
+                Ok(())
+            })
+        }
+    }
+
+    fn service(pool: PgPool, vars: &[(&str, &str)]) -> AuthService {
+        let config = from_env_with(vars).unwrap();
+        AuthService::new(pool.clone(), pool, &config)
+    }
+
+    fn cooldown(secs: &str) -> [(&str, &str); 1] {
+        [("EMAIL_VERIFICATION_RESEND_COOLDOWN_SECS", secs)]
+    }
+
+    fn register_request(email: &str) -> RegisterRequest {
+        RegisterRequest {
+            email: email.to_string(),
+            password: "secret123".to_string(),
+            name: "Ana".to_string(),
+        }
+    }
+
+    #[sqlx::test]
+    async fn register_mails_a_token_that_verifies_once(pool: PgPool) {
+        let auth = service(pool, &cooldown("60"));
+        let sender = StubSender::default();
+
+        auth.register(register_request("ana@example.com"), &sender).await.unwrap();
+        let user = auth.user_repository.find_by_email("ana@example.com").await.unwrap().unwrap();
+        assert!(!user.email_verified);
+        assert_eq!(sender.sent.lock().unwrap()[0].0, "ana@example.com");
+
+        let token = sender.last_token();
+        auth.verify_email(&token).await.unwrap();
+        assert_eq!(auth.user_repository.is_email_verified(user.id).await.unwrap(), Some(true));
+
+        let reused = auth.verify_email(&token).await.unwrap_err();
+        assert_eq!(reused.to_string(), "Invalid verification token");
+    }
+
+    #[sqlx::test]
+    async fn resend_waits_for_the_cooldown(pool: PgPool) {
+        let sender = StubSender::default();
+
+        let throttled = service(pool.clone(), &cooldown("60"));
+        throttled.register(register_request("ana@example.com"), &sender).await.unwrap();
+        let user = throttled.user_repository.find_by_email("ana@example.com").await.unwrap().unwrap();
+
+        let err = throttled.resend_verification(user.id, &sender).await.unwrap_err();
+        assert!(err.downcast_ref::<VerificationThrottled>().is_some());
+        assert_eq!(sender.count(), 1);
+
+        // Without a cooldown a new token goes out, and the earlier one still works
+        let first = sender.last_token();
+        let unthrottled = service(pool, &cooldown("0"));
+        unthrottled.resend_verification(user.id, &sender).await.unwrap();
+        assert_eq!(sender.count(), 2);
+        assert_ne!(sender.last_token(), first);
+
+        unthrottled.verify_email(&first).await.unwrap();
+        let err = unthrottled.resend_verification(user.id, &sender).await.unwrap_err();
+        assert_eq!(err.to_string(), "Email already verified");
+    }
+
+    #[sqlx::test]
+    async fn concurrent_resends_send_one_mail(pool: PgPool) {
+        let auth = service(pool, &cooldown("60"));
+        let sender = StubSender::default();
+        let user = auth.user_repository.create("Ana", "ana@example.com", "hash").await.unwrap();
+
+        let (first, second) = tokio::join!(
+            auth.resend_verification(user.id, &sender),
+            auth.resend_verification(user.id, &sender),
+        );
+
+        assert_eq!(first.is_ok() as u8 + second.is_ok() as u8, 1);
+        assert_eq!(sender.count(), 1);
+    }
+
+    fn login_request(email: &str, password: &str) -> LoginRequest {
+        LoginRequest {
+            email: email.to_string(),
+            password: password.to_string(),
+        }
+    }
+
+    #[sqlx::test]
+    async fn tuned_params_hash_new_passwords_and_verify_old_ones(pool: PgPool) {
+        let sender = StubSender::default();
+        let defaults = service(pool.clone(), &[]);
+        defaults.register(register_request("old@example.com"), &sender).await.unwrap();
+
+        let tuned = service(
+            pool,
+            &[("ARGON2_MEMORY_KIB", "8192"), ("ARGON2_ITERATIONS", "1"), ("ARGON2_PARALLELISM", "2")],
+        );
+        tuned.register(register_request("new@example.com"), &sender).await.unwrap();
+
+        let user = tuned.user_repository.find_by_email("new@example.com").await.unwrap().unwrap();
+        let params = argon2::Params::try_from(&PasswordHash::new(&user.password_hash).unwrap()).unwrap();
+        assert_eq!((params.m_cost(), params.t_cost(), params.p_cost()), (8192, 1, 2));
+
+        // The hash made with the default params keeps verifying after the change
+        tuned.login(login_request("old@example.com", "secret123")).await.unwrap();
+        tuned.login(login_request("new@example.com", "secret123")).await.unwrap();
+        assert!(tuned.login(login_request("old@example.com", "wrong-password")).await.is_err());
+    }
+
+    #[sqlx::test]
+    async fn found_user_serializes_timestamps_but_not_the_hash(pool: PgPool) {
+        let auth = service(pool, &[]);
+        let created = auth.user_repository.create("Ana", "ana@example.com", "hash").await.unwrap();
+
+        let user = serde_json::to_value(auth.find_user(created.id).await.unwrap().unwrap()).unwrap();
+        assert!(user["createdAt"].is_string() && user["updatedAt"].is_string());
+        assert!(user.get("passwordHash").is_none());
+
+        assert!(auth.find_user(created.id + 1).await.unwrap().is_none());
+    }
+
+    fn is_locked(result: Result<AuthResponse, anyhow::Error>) -> bool {
+        result.is_err_and(|e| e.downcast_ref::<AccountLocked>().is_some())
+    }
+
+    #[sqlx::test]
+    async fn repeated_failures_lock_the_account_until_the_lock_expires(pool: PgPool) {
+        let auth = service(pool.clone(), &[("LOGIN_LOCKOUT_THRESHOLD", "3"), ("LOGIN_LOCKOUT_SECS", "60")]);
+        auth.register(register_request("ana@example.com"), &StubSender::default()).await.unwrap();
+        let wrong = || login_request("ana@example.com", "wrong-password");
+        let right = || login_request("ana@example.com", "secret123");
+
+        // A successful login starts the count over
+        for _ in 0..2 {
+            assert!(!is_locked(auth.login(wrong()).await));
+        }
+        auth.login(right()).await.unwrap();
+        for _ in 0..2 {
+            assert!(!is_locked(auth.login(wrong()).await));
+        }
+
+        // The failure reaching the threshold is already answered as locked, and so is the
+        // right password after it. Attempts count per email in any case.
+        assert!(is_locked(auth.login(wrong()).await));
+        assert!(is_locked(auth.login(right()).await));
+        assert!(is_locked(auth.login(login_request("ANA@example.com", "secret123")).await));
+
+        sqlx::query("UPDATE login_attempts SET locked_until = NOW() - INTERVAL '1 second'")
+            .execute(&pool)
+            .await
+            .unwrap();
+        auth.login(right()).await.unwrap();
+    }
+
+    #[sqlx::test]
+    async fn unknown_emails_are_locked_too(pool: PgPool) {
+        let auth = service(pool, &[("LOGIN_LOCKOUT_THRESHOLD", "2")]);
+
+        assert!(!is_locked(auth.login(login_request("nobody@example.com", "secret123")).await));
+        assert!(is_locked(auth.login(login_request("nobody@example.com", "secret123")).await));
+    }
+
+    #[sqlx::test]
+    async fn a_threshold_of_0_never_locks(pool: PgPool) {
+        let auth = service(pool, &[("LOGIN_LOCKOUT_THRESHOLD", "0")]);
+
+        for _ in 0..10 {
+            assert!(!is_locked(auth.login(login_request("nobody@example.com", "secret123")).await));
+        }
+    }
+
+    #[sqlx::test]
+    async fn changing_the_password_needs_the_current_one_and_ends_other_sessions(pool: PgPool) {
+        let auth = service(pool, &[]);
+        let session = auth.register(register_request("ana@example.com"), &StubSender::default()).await.unwrap();
+        let user = auth.user_repository.find_by_email("ana@example.com").await.unwrap().unwrap();
+        let change = |current: &str| ChangePasswordRequest {
+            current_password: current.to_string(),
+            new_password: "new-secret".to_string(),
+        };
+
+        let err = auth.change_password(user.id, change("wrong-password")).await.unwrap_err();
+        assert_eq!(err.to_string(), "Invalid current password");
+        auth.login(login_request("ana@example.com", "secret123")).await.unwrap();
+
+        auth.change_password(user.id, change("secret123")).await.unwrap();
+        assert!(auth.login(login_request("ana@example.com", "secret123")).await.is_err());
+        auth.login(login_request("ana@example.com", "new-secret")).await.unwrap();
+
+        let err = auth.refresh(&session.refresh_token).await.unwrap_err();
+        assert_eq!(err.to_string(), "Invalid refresh token");
+    }
+}
@@ -0,0 +1,227 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::services::metrics_service::MetricsService;
+
+enum State {
+    // Counting consecutive failures since the first one in the current window
+    Closed { failures: u32, window_started: Option<Instant> },
+    Open { until: Instant },
+    // The cooldown is over and a single probe call decides whether to close again
+    HalfOpen { probe_started: Instant },
+}
+
+// Fails calls to a dependency fast while it is down. After `failure_threshold` consecutive
+// failures within `window` the circuit opens and calls are refused for `cooldown`; the first
+// call after that goes through as a probe, closing the circuit on success and reopening it on
+// failure. A threshold of 0 disables the breaker. Emits `{name}.circuit_open` each time the
+// circuit opens and `{name}.circuit_rejected` for every refused call.
+#[derive(Clone)]
+pub struct CircuitBreaker {
+    name: &'static str,
+    failure_threshold: u32,
+    window: Duration,
+    cooldown: Duration,
+    state: Arc<Mutex<State>>,
+    metrics: MetricsService,
+}
+
+impl CircuitBreaker {
+    pub fn new(
+        name: &'static str,
+        failure_threshold: u32,
+        window: Duration,
+        cooldown: Duration,
+        metrics: MetricsService,
+    ) -> Self {
+        Self {
+            name,
+            failure_threshold,
+            window,
+            cooldown,
+            state: Arc::new(Mutex::new(State::Closed { failures: 0, window_started: None })),
+            metrics,
+        }
+    }
+
+    // Err with how long until calls are let through again when the call must not be made
+    pub fn acquire(&self) -> Result<(), Duration> {
+        if self.failure_threshold == 0 {
+            return Ok(());
+        }
+
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        let refused = match *state {
+            State::Closed { .. } => None,
+            State::Open { until } if now < until => Some(until - now),
+            State::Open { .. } => {
+                log::info!("{} circuit half-open, probing", self.name);
+                *state = State::HalfOpen { probe_started: now };
+                None
+            }
+            // Another call is probing and its outcome decides for everyone. A probe that never
+            // reports back (its caller gave up) is replaced after one cooldown.
+            State::HalfOpen { probe_started } if now.duration_since(probe_started) < self.cooldown => {
+                Some(self.cooldown - now.duration_since(probe_started))
+            }
+            State::HalfOpen { .. } => {
+                *state = State::HalfOpen { probe_started: now };
+                None
+            }
+        };
+
+        match refused {
+            Some(retry_after) => {
+                self.metrics.increment(&format!("{}.circuit_rejected", self.name), None);
+                Err(retry_after)
+            }
+            None => Ok(()),
+        }
+    }
+
+    pub fn record_success(&self) {
+        if self.failure_threshold == 0 {
+            return;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        if matches!(*state, State::HalfOpen { .. }) {
+            log::info!("{} circuit closed", self.name);
+        }
+        *state = State::Closed { failures: 0, window_started: None };
+    }
+
+    pub fn record_failure(&self) {
+        if self.failure_threshold == 0 {
+            return;
+        }
+
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        let open = match *state {
+            State::Closed { failures, window_started } => {
+                // Failures spread out further than the window don't add up
+                let (failures, window_started) = match window_started {
+                    Some(started) if now.duration_since(started) < self.window => (failures + 1, started),
+                    _ => (1, now),
+                };
+                if failures < self.failure_threshold {
+                    *state = State::Closed { failures, window_started: Some(window_started) };
+                    false
+                } else {
+                    true
+                }
+            }
+            State::HalfOpen { .. } => true,
+            // A call admitted before the circuit opened finished late
+            State::Open { .. } => false,
+        };
+
+        if open {
+            *state = State::Open { until: now + self.cooldown };
+            self.metrics.increment(&format!("{}.circuit_open", self.name), None);
+            log::warn!("{} circuit open for {:?}", self.name, self.cooldown);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COOLDOWN: Duration = Duration::from_millis(50);
+
+    fn breaker(failure_threshold: u32) -> CircuitBreaker {
+        CircuitBreaker::new("test", failure_threshold, Duration::from_secs(60), COOLDOWN, MetricsService::noop())
+    }
+
+    fn open_breaker() -> CircuitBreaker {
+        let breaker = breaker(2);
+        breaker.record_failure();
+        breaker.record_failure();
+        breaker
+    }
+
+    #[test]
+    fn stays_closed_below_the_threshold() {
+        let breaker = breaker(3);
+        breaker.record_failure();
+        breaker.record_failure();
+        assert!(breaker.acquire().is_ok());
+
+        // A success resets the count
+        breaker.record_success();
+        breaker.record_failure();
+        breaker.record_failure();
+        assert!(breaker.acquire().is_ok());
+    }
+
+    #[test]
+    fn opens_at_the_threshold_and_refuses_until_the_cooldown_ends() {
+        let breaker = open_breaker();
+        let retry_after = breaker.acquire().unwrap_err();
+        assert!(retry_after <= COOLDOWN);
+    }
+
+    #[test]
+    fn failures_outside_the_window_do_not_add_up() {
+        let breaker = CircuitBreaker::new("test", 2, Duration::from_millis(20), COOLDOWN, MetricsService::noop());
+        breaker.record_failure();
+        std::thread::sleep(Duration::from_millis(30));
+        breaker.record_failure();
+        assert!(breaker.acquire().is_ok());
+    }
+
+    #[test]
+    fn lets_one_probe_through_after_the_cooldown() {
+        let breaker = open_breaker();
+        std::thread::sleep(COOLDOWN);
+
+        assert!(breaker.acquire().is_ok());
+        // Half-open: everyone else waits for the probe
+        assert!(breaker.acquire().is_err());
+    }
+
+    #[test]
+    fn successful_probe_closes_the_circuit() {
+        let breaker = open_breaker();
+        std::thread::sleep(COOLDOWN);
+        breaker.acquire().unwrap();
+
+        breaker.record_success();
+        assert!(breaker.acquire().is_ok());
+        assert!(breaker.acquire().is_ok());
+    }
+
+    #[test]
+    fn failed_probe_reopens_the_circuit() {
+        let breaker = open_breaker();
+        std::thread::sleep(COOLDOWN);
+        breaker.acquire().unwrap();
+
+        breaker.record_failure();
+        assert!(breaker.acquire().is_err());
+        std::thread::sleep(COOLDOWN);
+        assert!(breaker.acquire().is_ok());
+    }
+
+    #[test]
+    fn abandoned_probe_is_replaced_after_a_cooldown() {
+        let breaker = open_breaker();
+        std::thread::sleep(COOLDOWN);
+        breaker.acquire().unwrap();
+
+        std::thread::sleep(COOLDOWN);
+        assert!(breaker.acquire().is_ok());
+    }
+
+    #[test]
+    fn threshold_of_zero_never_opens() {
+        let breaker = breaker(0);
+        for _ in 0..10 {
+            breaker.record_failure();
+        }
+        assert!(breaker.acquire().is_ok());
+    }
+}
@@ -0,0 +1,346 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+
+use crate::services::{circuit_breaker::CircuitBreaker, elasticsearch_client::ElasticsearchClient};
+
+// Default range of the city counts, the last 100 weeks
+pub const DEFAULT_RANGE_FROM: &str = "now-100w/w";
+pub const DEFAULT_RANGE_TO: &str = "now/w";
+
+// Arbitrary city lists and ranges each get an entry, so the cache can't grow without bound
+const MAX_CACHED_QUERIES: usize = 1000;
+
+// Sorted, deduplicated cities plus the range as given
+type CacheKey = (Vec<String>, String, String);
+
+struct CachedCounts {
+    loaded_at: Instant,
+    cached_at: DateTime<Utc>,
+    cities: HashMap<String, i64>,
+}
+
+pub struct CityCounts {
+    pub cities: HashMap<String, i64>,
+    pub cached_at: DateTime<Utc>,
+    // Served past the TTL because Elasticsearch is unavailable
+    pub degraded: bool,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CityCountsError {
+    // ELASTICSEARCH_URL is not set, so there is no cluster to ask
+    #[error("ELASTICSEARCH_NOT_CONFIGURED")]
+    NotConfigured,
+    // The circuit is open and there are no earlier counts to fall back on
+    #[error("ELASTICSEARCH_UNAVAILABLE: retry in {}s", retry_after.as_secs() + 1)]
+    Unavailable { retry_after: Duration },
+    // Decode errors (`is_decode()`) mean the cluster answered with something other than JSON
+    #[error(transparent)]
+    Request(#[from] reqwest::Error),
+}
+
+// Counts of media articles per city. Results are cached per city list and range for `ttl`, so
+// repeated dashboard loads don't each run the aggregation; a TTL of 0 disables the cache.
+// While the circuit breaker has Elasticsearch marked down, expired cached counts are served
+// as degraded instead of waiting on the cluster. Without a client every request fails with
+// NotConfigured.
+#[derive(Clone)]
+pub struct DashboardService {
+    elasticsearch: Option<ElasticsearchClient>,
+    circuit_breaker: CircuitBreaker,
+    ttl: Duration,
+    cache: Arc<RwLock<HashMap<CacheKey, CachedCounts>>>,
+}
+
+impl DashboardService {
+    pub fn new(elasticsearch: Option<ElasticsearchClient>, circuit_breaker: CircuitBreaker, ttl: Duration) -> Self {
+        Self {
+            elasticsearch,
+            circuit_breaker,
+            ttl,
+            cache: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    pub fn is_configured(&self) -> bool {
+        self.elasticsearch.is_some()
+    }
+
+    // `refresh` skips the cache but still stores the fresh counts
+    pub async fn city_counts(
+        &self,
+        cities: &[String],
+        from: &str,
+        to: &str,
+        refresh: bool,
+    ) -> Result<CityCounts, CityCountsError> {
+        let Some(elasticsearch) = &self.elasticsearch else {
+            return Err(CityCountsError::NotConfigured);
+        };
+
+        let mut sorted = cities.to_vec();
+        sorted.sort();
+        sorted.dedup();
+        let key = (sorted, from.to_string(), to.to_string());
+
+        if !refresh {
+            if let Some(cached) = self.cached(&key, false) {
+                return Ok(cached);
+            }
+        }
+
+        if let Err(retry_after) = self.circuit_breaker.acquire() {
+            return self.cached(&key, true).ok_or(CityCountsError::Unavailable { retry_after });
+        }
+
+        let cities = match self.search_city_counts(elasticsearch, &key.0, from, to).await {
+            Ok(cities) => {
+                self.circuit_breaker.record_success();
+                cities
+            }
+            Err(e) => {
+                if is_unavailable(&e) {
+                    self.circuit_breaker.record_failure();
+                } else {
+                    self.circuit_breaker.record_success();
+                }
+                return Err(e.into());
+            }
+        };
+        let cached_at = Utc::now();
+        if !self.ttl.is_zero() {
+            let mut cache = self.cache.write().unwrap();
+            if cache.len() >= MAX_CACHED_QUERIES {
+                let ttl = self.ttl;
+                cache.retain(|_, cached| cached.loaded_at.elapsed() < ttl);
+            }
+            if cache.len() < MAX_CACHED_QUERIES {
+                cache.insert(key, CachedCounts { loaded_at: Instant::now(), cached_at, cities: cities.clone() });
+            }
+        }
+
+        Ok(CityCounts { cities, cached_at, degraded: false })
+    }
+
+    // `stale` also returns counts past the TTL, marked degraded
+    fn cached(&self, key: &CacheKey, stale: bool) -> Option<CityCounts> {
+        let cache = self.cache.read().unwrap();
+        cache
+            .get(key)
+            .filter(|cached| stale || cached.loaded_at.elapsed() < self.ttl)
+            .map(|cached| CityCounts {
+                cities: cached.cities.clone(),
+                cached_at: cached.cached_at,
+                degraded: cached.loaded_at.elapsed() >= self.ttl,
+            })
+    }
+
+    // Every requested city is in the result, with 0 when it has no articles in the range
+    async fn search_city_counts(
+        &self,
+        elasticsearch: &ElasticsearchClient,
+        cities: &[String],
+        from: &str,
+        to: &str,
+    ) -> Result<HashMap<String, i64>, reqwest::Error> {
+        let es_body = serde_json::json!({
+            "size": 0,
+            "query": {
+                "bool": {
+                    "filter": [
+                        { "range": { "published_at": { "gte": from, "lt": to } } }
+                    ]
+                }
+            },
+            "aggs": {
+                "cities_count": {
+                    "terms": {
+                        "field": "cities.keyword",
+                        "include": cities,
+                        "size": 10
+                    }
+                }
+            }
+        });
+
+        let val = elasticsearch.search("media-online-*", &es_body).await?;
+        let mut counts = HashMap::new();
+        if let Some(buckets) = val["aggregations"]["cities_count"]["buckets"].as_array() {
+            for bucket in buckets {
+                if let (Some(key), Some(doc_count)) = (bucket["key"].as_str(), bucket["doc_count"].as_i64()) {
+                    counts.insert(key.to_string(), doc_count);
+                }
+            }
+        }
+
+        Ok(cities
+            .iter()
+            .map(|city| (city.clone(), *counts.get(city).unwrap_or(&0)))
+            .collect())
+    }
+}
+
+// A cluster that is down, overloaded or throttling; any other error status is an answer to a
+// bad request and says nothing about its health
+fn is_unavailable(error: &reqwest::Error) -> bool {
+    match error.status() {
+        Some(status) => status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS,
+        None => true,
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::sync::atomic::{AtomicU16, AtomicUsize, Ordering};
+
+    use actix_web::{http::StatusCode, web, App, HttpResponse, HttpServer};
+    use serde_json::json;
+
+    use super::*;
+    use crate::services::metrics_service::MetricsService;
+
+    // An Elasticsearch that answers searches with `status`, counting the calls
+    pub(crate) struct FakeCluster {
+        status: Arc<AtomicU16>,
+        calls: Arc<AtomicUsize>,
+        pub(crate) url: String,
+    }
+
+    pub(crate) async fn fake_cluster(status: u16) -> FakeCluster {
+        let status = Arc::new(AtomicU16::new(status));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let (server_status, server_calls) = (status.clone(), calls.clone());
+        let server = HttpServer::new(move || {
+            let (status, calls) = (server_status.clone(), server_calls.clone());
+            App::new().route(
+                "/{index}/_search",
+                web::get().to(move || {
+                    let (status, calls) = (status.clone(), calls.clone());
+                    async move {
+                        calls.fetch_add(1, Ordering::SeqCst);
+                        let status = StatusCode::from_u16(status.load(Ordering::SeqCst)).unwrap();
+                        HttpResponse::build(status).json(json!({
+                            "aggregations": { "cities_count": { "buckets": [{ "key": "Jakarta", "doc_count": 3 }] } }
+                        }))
+                    }
+                }),
+            )
+        })
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+        let url = format!("http://{}", server.addrs()[0]);
+        tokio::spawn(server.run());
+        FakeCluster { status, calls, url }
+    }
+
+    pub(crate) fn service(url: &str, ttl: Duration) -> DashboardService {
+        let elasticsearch = ElasticsearchClient::new(url.to_string(), None, None, false, 5000);
+        let breaker = CircuitBreaker::new("elasticsearch", 2, Duration::from_secs(60), Duration::from_secs(60), MetricsService::noop());
+        DashboardService::new(Some(elasticsearch), breaker, ttl)
+    }
+
+    async fn counts(service: &DashboardService) -> Result<CityCounts, CityCountsError> {
+        service.city_counts(&["Jakarta".to_string()], DEFAULT_RANGE_FROM, DEFAULT_RANGE_TO, false).await
+    }
+
+    #[actix_web::test]
+    async fn server_errors_open_the_circuit_and_fail_fast() {
+        let cluster = fake_cluster(503).await;
+        let service = service(&cluster.url, Duration::ZERO);
+
+        for _ in 0..2 {
+            let error = counts(&service).await.err().unwrap();
+            assert!(matches!(error, CityCountsError::Request(e) if e.status() == Some(reqwest::StatusCode::SERVICE_UNAVAILABLE)));
+        }
+        assert!(matches!(counts(&service).await, Err(CityCountsError::Unavailable { .. })));
+        assert_eq!(cluster.calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[actix_web::test]
+    async fn throttling_counts_against_the_circuit() {
+        let cluster = fake_cluster(429).await;
+        let service = service(&cluster.url, Duration::ZERO);
+
+        let _ = counts(&service).await;
+        let _ = counts(&service).await;
+        assert!(matches!(counts(&service).await, Err(CityCountsError::Unavailable { .. })));
+    }
+
+    #[actix_web::test]
+    async fn rejected_queries_leave_the_circuit_closed() {
+        let cluster = fake_cluster(400).await;
+        let service = service(&cluster.url, Duration::ZERO);
+
+        for _ in 0..3 {
+            assert!(matches!(counts(&service).await, Err(CityCountsError::Request(_))));
+        }
+        assert_eq!(cluster.calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[actix_web::test]
+    async fn serves_cached_counts_until_the_ttl_runs_out() {
+        let cluster = fake_cluster(200).await;
+        let service = service(&cluster.url, Duration::from_millis(100));
+
+        let first = counts(&service).await.unwrap();
+        let cached = counts(&service).await.unwrap();
+        assert_eq!(cached.cached_at, first.cached_at);
+        assert_eq!(cluster.calls.load(Ordering::SeqCst), 1);
+
+        // An explicit refresh goes to the cluster even while the counts are fresh
+        let refreshed = service.city_counts(&["Jakarta".to_string()], DEFAULT_RANGE_FROM, DEFAULT_RANGE_TO, true).await.unwrap();
+        assert!(refreshed.cached_at > first.cached_at);
+        assert_eq!(cluster.calls.load(Ordering::SeqCst), 2);
+
+        tokio::time::sleep(Duration::from_millis(110)).await;
+        let expired = counts(&service).await.unwrap();
+        assert!(expired.cached_at > refreshed.cached_at);
+        assert!(!expired.degraded);
+        assert_eq!(cluster.calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[actix_web::test]
+    async fn refresh_job_precomputes_the_configured_cities() {
+        let cluster = fake_cluster(200).await;
+        let service = service(&cluster.url, Duration::from_secs(60));
+
+        crate::jobs::dashboard_cache::spawn(service.clone(), vec!["Jakarta".to_string()], Duration::from_secs(60));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(cluster.calls.load(Ordering::SeqCst), 1);
+
+        assert_eq!(counts(&service).await.unwrap().cities["Jakarta"], 3);
+        assert_eq!(cluster.calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[actix_web::test]
+    async fn open_circuit_serves_expired_counts_as_degraded() {
+        let cluster = fake_cluster(200).await;
+        let service = service(&cluster.url, Duration::from_millis(50));
+
+        let fresh = counts(&service).await.unwrap();
+        assert_eq!(fresh.cities["Jakarta"], 3);
+        assert!(!fresh.degraded);
+
+        cluster.status.store(500, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        let _ = counts(&service).await;
+        let _ = counts(&service).await;
+
+        let degraded = counts(&service).await.unwrap();
+        assert!(degraded.degraded);
+        assert_eq!(degraded.cities["Jakarta"], 3);
+        assert_eq!(degraded.cached_at, fresh.cached_at);
+        assert_eq!(cluster.calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[actix_web::test]
+    async fn without_a_cluster_counts_are_not_configured() {
+        let breaker = CircuitBreaker::new("elasticsearch", 2, Duration::from_secs(60), Duration::from_secs(60), MetricsService::noop());
+        let service = DashboardService::new(None, breaker, Duration::from_secs(60));
+        assert!(matches!(counts(&service).await, Err(CityCountsError::NotConfigured)));
+    }
+}
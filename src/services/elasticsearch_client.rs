@@ -0,0 +1,52 @@
+use std::time::Duration;
+
+use reqwest::Client;
+use serde_json::Value;
+
+// Shared Elasticsearch client, built once at startup from configuration
+#[derive(Clone)]
+pub struct ElasticsearchClient {
+    client: Client,
+    base_url: String,
+    user: Option<String>,
+    pass: Option<String>,
+}
+
+impl ElasticsearchClient {
+    pub fn new(
+        base_url: String,
+        user: Option<String>,
+        pass: Option<String>,
+        accept_invalid_certs: bool,
+        timeout_millis: u64,
+    ) -> Self {
+        // Only for clusters with self-signed certificates; off unless configured
+        let client = Client::builder()
+            .danger_accept_invalid_certs(accept_invalid_certs)
+            .timeout(Duration::from_millis(timeout_millis))
+            .build()
+            .expect("Failed to create HTTP client");
+
+        Self {
+            client,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            user,
+            pass,
+        }
+    }
+
+    // Non-2xx answers are errors carrying their `status()`. Decode errors (`is_decode()`) mean
+    // the cluster answered with something other than JSON.
+    pub async fn search(&self, index: &str, body: &Value) -> Result<Value, reqwest::Error> {
+        let mut request = self
+            .client
+            .get(format!("{}/{}/_search", self.base_url, index))
+            .json(body);
+
+        if let Some(user) = &self.user {
+            request = request.basic_auth(user, self.pass.as_ref());
+        }
+
+        request.send().await?.error_for_status()?.json::<Value>().await
+    }
+}
@@ -0,0 +1,84 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use serde::Serialize;
+
+use crate::config::Config;
+
+#[derive(Debug, Serialize)]
+pub struct EmailMessage {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+// Sends transactional mail. Implementations are swapped through config, and a stub can stand
+// in wherever the mail itself doesn't matter.
+pub trait EmailSender: Send + Sync {
+    fn send<'a>(&'a self, message: &'a EmailMessage) -> BoxFuture<'a, anyhow::Result<()>>;
+}
+
+// The HTTP relay when EMAIL_SENDER_URL is set, otherwise mail is only logged
+pub fn from_config(config: &Config) -> Arc<dyn EmailSender> {
+    match &config.email_sender_url {
+        Some(url) => Arc::new(HttpEmailSender::new(
+            url.clone(),
+            config.email_sender_api_key.clone(),
+            config.email_sender_timeout_millis,
+        )),
+        None => {
+            log::warn!("EMAIL_SENDER_URL not set, emails are logged instead of sent");
+            Arc::new(LogEmailSender)
+        }
+    }
+}
+
+// For local development. The body carries tokens, so it only goes out at debug level.
+pub struct LogEmailSender;
+
+impl EmailSender for LogEmailSender {
+    fn send<'a>(&'a self, message: &'a EmailMessage) -> BoxFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            log::info!("Email to {}: {}", message.to, message.subject);
+            log::debug!("Email body: {}", message.body);
+            Ok(())
+        })
+    }
+}
+
+// Posts the message as JSON to a mail relay, authenticated with a bearer key when one is set
+pub struct HttpEmailSender {
+    client: reqwest::Client,
+    url: String,
+    api_key: Option<String>,
+}
+
+impl HttpEmailSender {
+    pub fn new(url: String, api_key: Option<String>, timeout_millis: u64) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(timeout_millis))
+            .build()
+            .expect("Failed to create HTTP client");
+
+        Self { client, url, api_key }
+    }
+}
+
+impl EmailSender for HttpEmailSender {
+    fn send<'a>(&'a self, message: &'a EmailMessage) -> BoxFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            let mut request = self.client.post(&self.url).json(message);
+            if let Some(api_key) = &self.api_key {
+                request = request.bearer_auth(api_key);
+            }
+
+            let response = request.send().await?;
+            if !response.status().is_success() {
+                return Err(anyhow::anyhow!("Email relay responded with {}", response.status()));
+            }
+
+            Ok(())
+        })
+    }
+}
@@ -1,52 +1,192 @@
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use futures::future::{BoxFuture, FutureExt, Shared};
+use futures::stream::{self, StreamExt};
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 use anyhow::Result;
 use serde_json::json;
 use std::time::Duration;
 
-use crate::services::metrics_service::MetricsService;
+use crate::{
+    config::Config,
+    services::{circuit_breaker::CircuitBreaker, metrics_service::MetricsService},
+};
 
-#[derive(Debug, Serialize)]
+// Upper bound on pairs accepted by a single batch request
+pub const MAX_BATCH_SIZE: usize = 50;
+
+// Number of comparisons a batch runs against the face match API at once
+const BATCH_CONCURRENCY: usize = 5;
+
+#[derive(Debug, Clone, Serialize)]
 pub struct FaceMatchRequest {
     pub image1_url: String,
     pub image2_url: String,
     pub submission_id: String,
+    // Describes the submission, e.g. its type; only sent when FACE_MATCH_SEND_METADATA is set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<HashMap<String, String>>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct FaceMatchResponse {
+    // Not every provider echoes it back; filled in from the request when missing
+    #[serde(default)]
     pub submission_id: String,
     pub similarity_score: f64,
     pub is_match: bool,
     pub threshold: f64,
 }
 
+// Outcome of a health check against the face match provider
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FaceMatchHealth {
+    Healthy,
+    // Reachable, but the health endpoint answered with a non-2xx status
+    Unhealthy(u16),
+    // Connection failure or timeout
+    Unreachable(String),
+}
+
+impl FaceMatchHealth {
+    pub fn is_healthy(&self) -> bool {
+        *self == FaceMatchHealth::Healthy
+    }
+}
+
+impl std::fmt::Display for FaceMatchHealth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FaceMatchHealth::Healthy => write!(f, "healthy"),
+            FaceMatchHealth::Unhealthy(status) => write!(f, "health endpoint returned {}", status),
+            FaceMatchHealth::Unreachable(e) => write!(f, "unreachable: {}", e),
+        }
+    }
+}
+
+// Returned without calling the provider while its circuit breaker is open
+#[derive(Debug, thiserror::Error)]
+#[error("FACE_MATCH_UNAVAILABLE: retry in {}s", retry_after.as_secs() + 1)]
+pub struct FaceMatchUnavailable {
+    pub retry_after: Duration,
+}
+
+// Code for a failed comparison: 1021 while the circuit is open, 1006 otherwise
+pub fn error_code(err: &anyhow::Error) -> &'static str {
+    if err.is::<FaceMatchUnavailable>() { "1021" } else { "1006" }
+}
+
+// A 4xx from the provider: it is up, but refused this request
+#[derive(Debug, thiserror::Error)]
+#[error("Face match API returned error status: {0}")]
+struct ProviderRejected(reqwest::StatusCode);
+
+// Both image URLs without their presigning parameters, plus the threshold's bits
+type ComparisonKey = (String, String, u64);
+
+// A provider call every concurrent caller with the same key awaits. Errors are shared behind
+// an Arc since anyhow::Error can't be cloned.
+type Comparison = Shared<BoxFuture<'static, Result<FaceMatchResponse, Arc<anyhow::Error>>>>;
+
+// How to reach the provider, taken from the FACE_MATCH_* settings
+#[derive(Debug, Clone)]
+pub struct FaceMatchSettings {
+    pub base_url: String,
+    pub threshold: f64,
+    pub timeout: Duration,
+    // Bounds TCP/TLS setup on its own, within `timeout`
+    pub connect_timeout: Duration,
+    pub retry_attempts: u32,
+    pub retry_base_delay: Duration,
+    pub send_metadata: bool,
+    pub health_path: String,
+    pub health_timeout: Duration,
+}
+
+impl FaceMatchSettings {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            base_url: config.face_match_host.clone(),
+            threshold: config.face_match_threshold,
+            timeout: Duration::from_millis(config.face_match_timeout_millis),
+            connect_timeout: Duration::from_millis(config.face_match_connect_timeout_millis),
+            retry_attempts: config.face_match_retry_attempts,
+            retry_base_delay: Duration::from_millis(config.face_match_retry_base_delay_millis),
+            send_metadata: config.face_match_send_metadata,
+            health_path: config.face_match_health_path.clone(),
+            health_timeout: Duration::from_millis(config.face_match_health_timeout_millis),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct FaceMatchService {
     client: reqwest::Client,
     base_url: String,
     threshold: f64,
+    retry_attempts: u32,
+    retry_base_delay: Duration,
+    send_metadata: bool,
+    health_path: String,
+    health_timeout: Duration,
+    circuit_breaker: CircuitBreaker,
+    in_flight: Arc<Mutex<HashMap<ComparisonKey, Comparison>>>,
     metrics: MetricsService,
 }
 
 impl FaceMatchService {
-    pub fn new(
-        base_url: String,
-        threshold: f64,
-        timeout_millis: u64,
-        metrics: MetricsService,
-    ) -> Self {
+    pub fn new(settings: FaceMatchSettings, circuit_breaker: CircuitBreaker, metrics: MetricsService) -> Result<Self> {
+        let base_url = normalize_base_url(&settings.base_url)?;
+
+        // The connect timeout bounds TCP/TLS setup on its own, the total timeout the whole exchange
         let client = reqwest::Client::builder()
-            .timeout(Duration::from_millis(timeout_millis))
+            .connect_timeout(settings.connect_timeout)
+            .timeout(settings.timeout)
             .build()
             .expect("Failed to create HTTP client");
 
-        Self {
+        Ok(Self {
             client,
             base_url,
-            threshold,
+            threshold: settings.threshold,
+            retry_attempts: settings.retry_attempts,
+            retry_base_delay: settings.retry_base_delay,
+            send_metadata: settings.send_metadata,
+            health_path: settings.health_path,
+            health_timeout: settings.health_timeout,
+            circuit_breaker,
+            in_flight: Arc::new(Mutex::new(HashMap::new())),
             metrics,
-        }
+        })
+    }
+
+    // Any HTTP response counts as reachable; only connection failures and timeouts fail
+    pub async fn ping(&self) -> Result<()> {
+        self.client
+            .get(&self.base_url)
+            .send()
+            .await
+            .map_err(|e| anyhow::anyhow!("HTTP request failed: {}", e))?;
+        Ok(())
+    }
+
+    // Calls the provider's health endpoint with its own short timeout, independent of user
+    // traffic. Reports `face_match.health` as 1 when healthy and 0 otherwise.
+    pub async fn health_check(&self) -> FaceMatchHealth {
+        let start = std::time::Instant::now();
+        let url = format!("{}{}", self.base_url, self.health_path);
+
+        let health = match self.client.get(&url).timeout(self.health_timeout).send().await {
+            Ok(resp) if resp.status().is_success() => FaceMatchHealth::Healthy,
+            Ok(resp) => FaceMatchHealth::Unhealthy(resp.status().as_u16()),
+            Err(e) => FaceMatchHealth::Unreachable(e.to_string()),
+        };
+
+        self.metrics.gauge("face_match.health", if health.is_healthy() { 1.0 } else { 0.0 }, None);
+        self.metrics.timing("face_match.health.duration", start.elapsed(), None);
+
+        health
     }
 
     pub async fn compare_faces(
@@ -54,6 +194,109 @@ impl FaceMatchService {
         image1_url: String,
         image2_url: String,
         submission_id: String,
+        metadata: Option<HashMap<String, String>>,
+    ) -> Result<FaceMatchResponse> {
+        self.compare_faces_with_threshold(image1_url, image2_url, submission_id, self.threshold, metadata)
+            .await
+    }
+
+    // Concurrent comparisons of the same two images share one provider call. Presigned URLs
+    // differ in their signature only, so images are told apart by URL without the X-Amz-*
+    // presigning parameters. The call carries the first caller's submission id and metadata;
+    // every caller gets the result back under its own submission id.
+    pub async fn compare_faces_with_threshold(
+        &self,
+        image1_url: String,
+        image2_url: String,
+        submission_id: String,
+        threshold: f64,
+        metadata: Option<HashMap<String, String>>,
+    ) -> Result<FaceMatchResponse> {
+        let key = (image_key(&image1_url), image_key(&image2_url), threshold.to_bits());
+
+        let (comparison, shared) = {
+            let mut in_flight = self.in_flight.lock().unwrap();
+            match in_flight.get(&key) {
+                Some(comparison) => (comparison.clone(), true),
+                None => {
+                    let comparison = self.start_comparison(
+                        key.clone(),
+                        image1_url,
+                        image2_url,
+                        submission_id.clone(),
+                        threshold,
+                        metadata,
+                    );
+                    in_flight.insert(key, comparison.clone());
+                    (comparison, false)
+                }
+            }
+        };
+
+        if shared {
+            self.metrics.increment("face_match.deduplicated", None);
+        }
+
+        let mut response = comparison.await.map_err(|e| unshare_error(&e))?;
+        response.submission_id = submission_id;
+        Ok(response)
+    }
+
+    // The call removes itself from `in_flight` once done, so it's never reused after completing
+    fn start_comparison(
+        &self,
+        key: ComparisonKey,
+        image1_url: String,
+        image2_url: String,
+        submission_id: String,
+        threshold: f64,
+        metadata: Option<HashMap<String, String>>,
+    ) -> Comparison {
+        let service = self.clone();
+        async move {
+            let result = service
+                .guarded_comparison(image1_url, image2_url, submission_id, threshold, metadata)
+                .await
+                .map_err(Arc::new);
+            service.in_flight.lock().unwrap().remove(&key);
+            result
+        }
+        .boxed()
+        .shared()
+    }
+
+    async fn guarded_comparison(
+        &self,
+        image1_url: String,
+        image2_url: String,
+        submission_id: String,
+        threshold: f64,
+        metadata: Option<HashMap<String, String>>,
+    ) -> Result<FaceMatchResponse> {
+        // During an outage this saves every caller the full timeout and retries
+        if let Err(retry_after) = self.circuit_breaker.acquire() {
+            return Err(FaceMatchUnavailable { retry_after }.into());
+        }
+
+        let result = self
+            .request_comparison(image1_url, image2_url, submission_id, threshold, metadata)
+            .await;
+        // Only a provider that can't answer counts against the circuit; a 4xx is still an answer
+        match &result {
+            Ok(_) => self.circuit_breaker.record_success(),
+            Err(e) if e.downcast_ref::<ProviderRejected>().is_some() => self.circuit_breaker.record_success(),
+            Err(_) => self.circuit_breaker.record_failure(),
+        }
+        result
+    }
+
+    async fn request_comparison(
+        &self,
+        image1_url: String,
+        image2_url: String,
+        submission_id: String,
+        threshold: f64,
+        metadata: Option<HashMap<String, String>>,
     ) -> Result<FaceMatchResponse> {
         let start = std::time::Instant::now();
         let mut tags = HashMap::new();
@@ -63,48 +306,85 @@ impl FaceMatchService {
             "{}/compare-faces", self.base_url
         );
 
-        let body = json!({
+        let mut body = json!({
             "image1_url": image1_url,
             "image2_url": image2_url,
-            "threshold": self.threshold,
+            "threshold": threshold,
         });
+        // Providers that don't know the field may reject it, so the minimal body is the default
+        if let Some(metadata) = metadata.filter(|m| self.send_metadata && !m.is_empty()) {
+            body["metadata"] = json!(metadata);
+        }
 
-        let response = match self
-            .client
-            .post(&url)
-            .header("x-submission-id", &submission_id)
-            .body(body.to_string())
-            .send()
-            .await
-        {
+        // Connection failures and 5xx are retried with exponential backoff and jitter; 4xx,
+        // response timeouts and successes are final
+        let mut attempt = 1;
+        let result = loop {
+            let result = self
+                .client
+                .post(&url)
+                .header("x-submission-id", &submission_id)
+                .body(body.to_string())
+                .send()
+                .await;
+
+            let retryable = match &result {
+                Ok(resp) => resp.status().is_server_error(),
+                Err(e) => e.is_connect(),
+            };
+            if !retryable || attempt >= self.retry_attempts {
+                break result;
+            }
+
+            self.metrics.increment("face_match.retry", Some(tags.clone()));
+            let backoff = self.retry_base_delay * 2u32.pow(attempt - 1);
+            let jitter = rand::thread_rng().gen_range(0..=backoff.as_millis() as u64 / 2);
+            tokio::time::sleep(backoff + Duration::from_millis(jitter)).await;
+            attempt += 1;
+        };
+
+        let mut timing_tags = tags.clone();
+        timing_tags.insert("attempts".to_string(), attempt.to_string());
+
+        let response = match result {
             Ok(resp) => resp,
             Err(e) => {
+                self.record_timeout(&e, &tags);
                 self.metrics.increment("face_match.error", Some(tags.clone()));
-                self.metrics.timing("face_match.duration", start.elapsed(), Some(tags));
+                self.metrics.timing("face_match.duration", start.elapsed(), Some(timing_tags));
                 return Err(anyhow::anyhow!("HTTP request failed: {}", e));
             }
         };
 
         if !response.status().is_success() {
             self.metrics.increment("face_match.error", Some(tags.clone()));
-            self.metrics.timing("face_match.duration", start.elapsed(), Some(tags));
+            self.metrics.timing("face_match.duration", start.elapsed(), Some(timing_tags));
+            let status = response.status();
+            if status.is_client_error() {
+                return Err(ProviderRejected(status).into());
+            }
             return Err(anyhow::anyhow!(
                 "Face match API returned error status: {}",
-                response.status()
+                status
             ));
         }
 
-        let face_match_response: FaceMatchResponse = match response.json().await {
+        let mut face_match_response: FaceMatchResponse = match response.json().await {
             Ok(resp) => resp,
             Err(e) => {
+                self.record_timeout(&e, &tags);
                 self.metrics.increment("face_match.error", Some(tags.clone()));
-                self.metrics.timing("face_match.duration", start.elapsed(), Some(tags));
+                self.metrics.timing("face_match.duration", start.elapsed(), Some(timing_tags));
                 return Err(anyhow::anyhow!("Failed to parse response: {}", e));
             }
         };
 
+        if face_match_response.submission_id.is_empty() {
+            face_match_response.submission_id = submission_id;
+        }
+
         // Check if the match meets our threshold
-        let is_above_threshold = face_match_response.similarity_score >= self.threshold;
+        let is_above_threshold = face_match_response.similarity_score >= threshold;
         
         if is_above_threshold {
             self.metrics.increment("face_match.success", Some(tags.clone()));
@@ -112,12 +392,393 @@ impl FaceMatchService {
             self.metrics.increment("face_match.failure", Some(tags.clone()));
         }
 
-        self.metrics.timing("face_match.duration", start.elapsed(), Some(tags));
+        self.metrics.timing("face_match.duration", start.elapsed(), Some(timing_tags));
 
         Ok(face_match_response)
     }
 
+    pub async fn compare_faces_batch(
+        &self,
+        requests: Vec<FaceMatchRequest>,
+    ) -> Vec<(String, Result<FaceMatchResponse>)> {
+        let mut tags = HashMap::new();
+        tags.insert("endpoint".to_string(), "face_match_batch".to_string());
+        self.metrics.gauge("face_match.batch.size", requests.len() as f64, Some(tags));
+
+        // Results keep the order of the incoming pairs
+        stream::iter(requests)
+            .map(|request| async move {
+                let submission_id = request.submission_id.clone();
+                let result = self
+                    .compare_faces(request.image1_url, request.image2_url, request.submission_id, request.metadata)
+                    .await;
+                (submission_id, result)
+            })
+            .buffered(BATCH_CONCURRENCY)
+            .collect()
+            .await
+    }
+
+    // Separates slow connection setup from a slow provider response
+    fn record_timeout(&self, error: &reqwest::Error, tags: &HashMap<String, String>) {
+        if !error.is_timeout() {
+            return;
+        }
+
+        if error.is_connect() {
+            self.metrics.increment("face_match.connect_timeout", Some(tags.clone()));
+        } else {
+            self.metrics.increment("face_match.response_timeout", Some(tags.clone()));
+        }
+    }
+
     pub fn get_threshold(&self) -> f64 {
         self.threshold
     }
-} 
\ No newline at end of file
+} 
+
+// Any other query parameter can select a different object or version, so it stays in the key
+fn image_key(url: &str) -> String {
+    let Ok(mut parsed) = reqwest::Url::parse(url) else {
+        return url.to_string();
+    };
+
+    let kept: Vec<(String, String)> = parsed
+        .query_pairs()
+        .filter(|(name, _)| !name.to_ascii_lowercase().starts_with("x-amz-"))
+        .map(|(name, value)| (name.into_owned(), value.into_owned()))
+        .collect();
+    if kept.is_empty() {
+        parsed.set_query(None);
+    } else {
+        parsed.query_pairs_mut().clear().extend_pairs(kept);
+    }
+    parsed.to_string()
+}
+
+// Each caller gets its own copy of a shared failure, keeping FaceMatchUnavailable recognizable
+fn unshare_error(err: &anyhow::Error) -> anyhow::Error {
+    match err.downcast_ref::<FaceMatchUnavailable>() {
+        Some(unavailable) => FaceMatchUnavailable { retry_after: unavailable.retry_after }.into(),
+        None => anyhow::anyhow!("{}", err),
+    }
+}
+
+// Paths are appended to the base URL as strings, so it must be an absolute http(s) URL and
+// loses any trailing slash; a path prefix like `http://host/face-match` is kept
+fn normalize_base_url(base_url: &str) -> Result<String> {
+    let url = reqwest::Url::parse(base_url.trim())
+        .map_err(|e| anyhow::anyhow!("Invalid face match base URL '{}': {}", base_url, e))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(anyhow::anyhow!("Invalid face match base URL '{}': scheme must be http or https", base_url));
+    }
+    if url.host_str().is_none() || url.query().is_some() || url.fragment().is_some() {
+        return Err(anyhow::anyhow!(
+            "Invalid face match base URL '{}': must be a host and optional path, without query or fragment",
+            base_url
+        ));
+    }
+    Ok(base_url.trim().trim_end_matches('/').to_string())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Instant;
+
+    use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+    use serde_json::Value;
+
+    use super::*;
+    use crate::services::metrics_service::tests::{agent, dogstatsd, received};
+
+    #[derive(Default)]
+    pub(crate) struct Provider {
+        calls: AtomicUsize,
+        // Comparison request bodies in the order they arrived; sent without a JSON content type
+        bodies: Mutex<Vec<Value>>,
+        arrivals: Mutex<Vec<Instant>>,
+    }
+
+    impl Provider {
+        pub(crate) fn calls(&self) -> usize {
+            self.calls.load(Ordering::SeqCst)
+        }
+
+        pub(crate) fn bodies(&self) -> Vec<Value> {
+            self.bodies.lock().unwrap().clone()
+        }
+
+        // When each comparison request arrived
+        pub(crate) fn arrivals(&self) -> Vec<Instant> {
+            self.arrivals.lock().unwrap().clone()
+        }
+    }
+
+    // A provider that answers the nth comparison (from 0) with `respond(n, request)` after
+    // `delay`, recording what it was sent in `provider`
+    pub(crate) async fn fake_provider(
+        provider: Arc<Provider>,
+        delay: Duration,
+        respond: fn(usize, &HttpRequest) -> HttpResponse,
+    ) -> String {
+        let server = HttpServer::new(move || {
+            let provider = provider.clone();
+            App::new().route(
+                "/compare-faces",
+                web::post().to(move |request: HttpRequest, body: web::Bytes| {
+                    let provider = provider.clone();
+                    async move {
+                        let call = provider.calls.fetch_add(1, Ordering::SeqCst);
+                        provider.arrivals.lock().unwrap().push(Instant::now());
+                        provider.bodies.lock().unwrap().push(serde_json::from_slice(&body).unwrap());
+                        tokio::time::sleep(delay).await;
+                        respond(call, &request)
+                    }
+                }),
+            )
+        })
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+        let addr = server.addrs()[0];
+        tokio::spawn(server.run());
+        format!("http://{}", addr)
+    }
+
+    pub(crate) fn matched(_: usize, _: &HttpRequest) -> HttpResponse {
+        HttpResponse::Ok().json(json!({ "similarity_score": 0.9, "is_match": true, "threshold": 0.8 }))
+    }
+
+    pub(crate) fn settings(base_url: String, retry_attempts: u32) -> FaceMatchSettings {
+        FaceMatchSettings {
+            base_url,
+            threshold: 0.8,
+            timeout: Duration::from_secs(5),
+            connect_timeout: Duration::from_secs(1),
+            retry_attempts,
+            retry_base_delay: Duration::from_millis(10),
+            send_metadata: false,
+            health_path: "/health".to_string(),
+            health_timeout: Duration::from_secs(1),
+        }
+    }
+
+    fn service(base_url: String, retry_attempts: u32) -> FaceMatchService {
+        let metrics = MetricsService::noop();
+        let breaker = CircuitBreaker::new("face_match", 0, Duration::from_secs(60), Duration::from_secs(30), metrics.clone());
+        FaceMatchService::new(settings(base_url, retry_attempts), breaker, metrics).unwrap()
+    }
+
+    fn presigned(name: &str, signature: &str) -> String {
+        format!(
+            "http://minio:9000/documents/{}?response-content-type=image%2Fjpeg&X-Amz-Date=20250101T000000Z&X-Amz-Signature={}",
+            name, signature
+        )
+    }
+
+    #[actix_web::test]
+    async fn concurrent_identical_comparisons_call_the_provider_once() {
+        let provider = Arc::new(Provider::default());
+        let service = service(fake_provider(provider.clone(), Duration::from_millis(200), matched).await, 1);
+
+        let (first, second) = tokio::join!(
+            service.compare_faces(presigned("a_SELFIE", "111"), presigned("b_SELFIE", "222"), "s1".to_string(), None),
+            service.compare_faces(presigned("a_SELFIE", "333"), presigned("b_SELFIE", "444"), "s2".to_string(), None),
+        );
+
+        assert_eq!(provider.calls(), 1);
+        assert_eq!(first.unwrap().submission_id, "s1");
+        assert_eq!(second.unwrap().submission_id, "s2");
+    }
+
+    #[actix_web::test]
+    async fn comparisons_of_different_objects_are_not_shared() {
+        let provider = Arc::new(Provider::default());
+        let service = service(fake_provider(provider.clone(), Duration::from_millis(200), matched).await, 1);
+        let other_version = format!("{}&versionId=2", presigned("a_SELFIE", "111"));
+
+        let (first, second) = tokio::join!(
+            service.compare_faces(presigned("a_SELFIE", "111"), presigned("b_SELFIE", "222"), "s1".to_string(), None),
+            service.compare_faces(other_version, presigned("b_SELFIE", "222"), "s2".to_string(), None),
+        );
+
+        assert!(first.is_ok() && second.is_ok());
+        assert_eq!(provider.calls(), 2);
+    }
+
+    #[actix_web::test]
+    async fn retries_server_errors_until_the_provider_recovers() {
+        let provider = Arc::new(Provider::default());
+        let unavailable_twice = |call, request: &HttpRequest| match call {
+            0 | 1 => HttpResponse::ServiceUnavailable().finish(),
+            _ => matched(call, request),
+        };
+        let service = service(fake_provider(provider.clone(), Duration::ZERO, unavailable_twice).await, 3);
+
+        let result = service
+            .compare_faces(presigned("a_SELFIE", "111"), presigned("b_SELFIE", "222"), "s1".to_string(), None)
+            .await;
+
+        assert!(result.unwrap().is_match);
+        assert_eq!(provider.calls(), 3);
+    }
+
+    #[actix_web::test]
+    async fn client_errors_are_not_retried() {
+        let provider = Arc::new(Provider::default());
+        let service = service(fake_provider(provider.clone(), Duration::ZERO, |_, _| HttpResponse::BadRequest().finish()).await, 3);
+
+        let result = service
+            .compare_faces(presigned("a_SELFIE", "111"), presigned("b_SELFIE", "222"), "s1".to_string(), None)
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(provider.calls(), 1);
+    }
+
+    #[actix_web::test]
+    async fn connect_and_response_timeouts_are_applied_on_their_own() {
+        let agent = agent();
+        let metrics = dogstatsd(&agent, None, HashMap::new());
+        let with_timeouts = |base_url: String| {
+            let breaker = CircuitBreaker::new("face_match", 0, Duration::from_secs(60), Duration::from_secs(30), metrics.clone());
+            let settings = FaceMatchSettings {
+                timeout: Duration::from_millis(400),
+                connect_timeout: Duration::from_millis(100),
+                ..settings(base_url, 1)
+            };
+            FaceMatchService::new(settings, breaker, metrics.clone()).unwrap()
+        };
+        let compare = |service: FaceMatchService| async move {
+            let started = Instant::now();
+            let result = service
+                .compare_faces(presigned("a_SELFIE", "111"), presigned("b_SELFIE", "222"), "s1".to_string(), None)
+                .await;
+            assert!(result.is_err());
+            started.elapsed()
+        };
+
+        // Accepts connections but never answers the TLS handshake, so setup stalls
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let stalled = format!("https://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let mut held = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                held.push(socket);
+            }
+        });
+        let elapsed = compare(with_timeouts(stalled)).await;
+        assert!(elapsed < Duration::from_millis(400), "{elapsed:?}");
+
+        // Connects at once but answers after the total timeout
+        let slow = fake_provider(Arc::new(Provider::default()), Duration::from_secs(2), matched).await;
+        let elapsed = compare(with_timeouts(slow)).await;
+        assert!((Duration::from_millis(400)..Duration::from_secs(2)).contains(&elapsed), "{elapsed:?}");
+
+        let lines = received(&agent);
+        assert_eq!(lines.iter().filter(|line| line.starts_with("face_match.connect_timeout:")).count(), 1, "{lines:?}");
+        assert_eq!(lines.iter().filter(|line| line.starts_with("face_match.response_timeout:")).count(), 1, "{lines:?}");
+    }
+
+    #[test]
+    fn base_urls_are_validated_and_lose_their_trailing_slash() {
+        assert_eq!(normalize_base_url("http://face-match:8080").unwrap(), "http://face-match:8080");
+        assert_eq!(normalize_base_url("https://face-match.internal/v2/").unwrap(), "https://face-match.internal/v2");
+        assert_eq!(normalize_base_url(" http://face-match:8080// ").unwrap(), "http://face-match:8080");
+
+        for invalid in ["face-match:8080", "ftp://face-match", "not a url", "http://face-match?key=1", "http://face-match#top", ""] {
+            let err = normalize_base_url(invalid).unwrap_err();
+            assert!(err.to_string().starts_with("Invalid face match base URL"), "{invalid}: {err}");
+        }
+    }
+
+    #[actix_web::test]
+    async fn a_trailing_slash_base_url_still_reaches_compare_faces() {
+        let provider = Arc::new(Provider::default());
+        let base_url = format!("{}/", fake_provider(provider.clone(), Duration::ZERO, matched).await);
+
+        let result = service(base_url, 1)
+            .compare_faces(presigned("a_SELFIE", "111"), presigned("b_SELFIE", "222"), "s1".to_string(), None)
+            .await;
+
+        assert!(result.unwrap().is_match);
+        assert_eq!(provider.calls(), 1);
+    }
+
+    // `matched` answers without echoing the submission id
+    #[actix_web::test]
+    async fn missing_submission_id_is_taken_from_the_request() {
+        let provider = Arc::new(Provider::default());
+        let service = service(fake_provider(provider, Duration::ZERO, matched).await, 1);
+
+        let response = service
+            .request_comparison(presigned("a_SELFIE", "111"), presigned("b_SELFIE", "222"), "s1".to_string(), 0.8, None)
+            .await
+            .unwrap();
+
+        assert_eq!(response.submission_id, "s1");
+        assert!(response.is_match);
+    }
+
+    // The provider refuses pairs of submissions named "rejected..."
+    #[actix_web::test]
+    async fn batch_reports_every_pair_on_its_own() {
+        let provider = Arc::new(Provider::default());
+        let reject_some = |call, request: &HttpRequest| {
+            match request.headers().get("x-submission-id").and_then(|id| id.to_str().ok()) {
+                Some(id) if id.starts_with("rejected") => HttpResponse::BadRequest().finish(),
+                _ => matched(call, request),
+            }
+        };
+        let service = service(fake_provider(provider.clone(), Duration::ZERO, reject_some).await, 1);
+        let pair = |n: usize, submission_id: &str| FaceMatchRequest {
+            image1_url: presigned(&format!("{}_SELFIE", n), "111"),
+            image2_url: presigned(&format!("{}_KTP", n), "222"),
+            submission_id: submission_id.to_string(),
+            metadata: None,
+        };
+
+        let results = service
+            .compare_faces_batch(vec![pair(0, "s0"), pair(1, "rejected1"), pair(2, "s2"), pair(3, "rejected3")])
+            .await;
+
+        let outcomes: Vec<_> = results.iter().map(|(id, result)| (id.as_str(), result.is_ok())).collect();
+        assert_eq!(outcomes, [("s0", true), ("rejected1", false), ("s2", true), ("rejected3", false)]);
+        assert_eq!(provider.calls(), 4);
+    }
+
+    #[actix_web::test]
+    async fn metadata_is_sent_only_when_enabled() {
+        let provider = Arc::new(Provider::default());
+        let base_url = fake_provider(provider.clone(), Duration::ZERO, matched).await;
+        let metadata = || Some(HashMap::from([("submission_type".to_string(), "KYC".to_string())]));
+
+        for (n, send_metadata) in [false, true].into_iter().enumerate() {
+            let mut settings = settings(base_url.clone(), 1);
+            settings.send_metadata = send_metadata;
+            let metrics = MetricsService::noop();
+            let breaker = CircuitBreaker::new("face_match", 0, Duration::from_secs(60), Duration::from_secs(30), metrics.clone());
+            let service = FaceMatchService::new(settings, breaker, metrics).unwrap();
+
+            let selfie = presigned(&format!("{}_SELFIE", n), "111");
+            service.compare_faces(selfie, presigned("b_KTP", "222"), "s1".to_string(), metadata()).await.unwrap();
+        }
+
+        let bodies = provider.bodies.lock().unwrap();
+        assert!(bodies[0].get("metadata").is_none(), "{}", bodies[0]);
+        assert_eq!(bodies[1]["metadata"], json!({ "submission_type": "KYC" }));
+    }
+
+    #[test]
+    fn image_key_drops_only_presigning_parameters() {
+        assert_eq!(
+            image_key(&presigned("a_SELFIE", "111")),
+            "http://minio:9000/documents/a_SELFIE?response-content-type=image%2Fjpeg"
+        );
+        assert_eq!(
+            image_key("http://minio:9000/documents/a_SELFIE?X-Amz-Signature=1"),
+            "http://minio:9000/documents/a_SELFIE"
+        );
+    }
+}
@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use serde_json::json;
+use sqlx::PgPool;
+
+use crate::repositories::{
+    audit_log_repository::AuditLogRepository,
+    feature_flag_repository::{FeatureFlag, FeatureFlagRepository},
+};
+
+pub const ON_DEMAND_ENABLED: &str = "on_demand_enabled";
+
+struct CachedFlags {
+    loaded_at: Instant,
+    flags: HashMap<String, bool>,
+}
+
+// Runtime toggles stored in the feature_flags table. Flags are cached for `ttl`, so a change
+// made on another instance takes effect here within one TTL; unset flags use the caller's default.
+#[derive(Clone)]
+pub struct FeatureFlagsService {
+    pool: PgPool,
+    repository: FeatureFlagRepository,
+    ttl: Duration,
+    cache: Arc<RwLock<Option<CachedFlags>>>,
+}
+
+impl FeatureFlagsService {
+    pub fn new(pool: PgPool, ttl: Duration) -> Self {
+        Self {
+            repository: FeatureFlagRepository::new(pool.clone()),
+            pool,
+            ttl,
+            cache: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    pub async fn is_enabled(&self, name: &str, default: bool) -> bool {
+        if let Some(enabled) = self.cached(name, true) {
+            return enabled.unwrap_or(default);
+        }
+
+        match self.repository.list().await {
+            Ok(flags) => {
+                let flags: HashMap<String, bool> = flags.into_iter().map(|f| (f.name, f.enabled)).collect();
+                let enabled = flags.get(name).copied();
+                *self.cache.write().unwrap() = Some(CachedFlags { loaded_at: Instant::now(), flags });
+                enabled.unwrap_or(default)
+            }
+            Err(e) => {
+                // Keep serving the last known values while the database is unavailable
+                log::error!("Failed to load feature flags: {}", e);
+                self.cached(name, false).flatten().unwrap_or(default)
+            }
+        }
+    }
+
+    pub async fn list(&self) -> Result<Vec<FeatureFlag>, sqlx::Error> {
+        self.repository.list().await
+    }
+
+    pub async fn set(&self, name: &str, enabled: bool, actor: &str) -> Result<FeatureFlag, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let flag = FeatureFlagRepository::upsert(&mut tx, name, enabled, actor).await?;
+        AuditLogRepository::create(
+            &mut tx,
+            "FEATURE_FLAG_UPDATED",
+            "FEATURE_FLAG",
+            name,
+            actor,
+            json!({ "enabled": enabled }),
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        // This instance sees the change right away; others within the TTL
+        *self.cache.write().unwrap() = None;
+
+        Ok(flag)
+    }
+
+    // None when there is no usable cache; Some(None) when cached but the flag is unset
+    fn cached(&self, name: &str, fresh_only: bool) -> Option<Option<bool>> {
+        let cache = self.cache.read().unwrap();
+        let cached = cache.as_ref()?;
+        if fresh_only && cached.loaded_at.elapsed() >= self.ttl {
+            return None;
+        }
+        Some(cached.flags.get(name).copied())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A change made through another instance only shows here once the cached flags expire
+    #[sqlx::test]
+    async fn cached_flags_are_served_until_the_ttl_expires(pool: PgPool) {
+        let ttl = Duration::from_millis(300);
+        let instance = FeatureFlagsService::new(pool.clone(), ttl);
+        let other_instance = FeatureFlagsService::new(pool, ttl);
+
+        assert!(!instance.is_enabled(ON_DEMAND_ENABLED, false).await);
+
+        other_instance.set(ON_DEMAND_ENABLED, true, "admin").await.unwrap();
+        assert!(!instance.is_enabled(ON_DEMAND_ENABLED, false).await);
+        assert!(other_instance.is_enabled(ON_DEMAND_ENABLED, false).await);
+
+        tokio::time::sleep(ttl).await;
+        assert!(instance.is_enabled(ON_DEMAND_ENABLED, false).await);
+    }
+}
@@ -1,54 +1,445 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::net::UdpSocket;
+use std::str::FromStr;
+use rand::Rng;
+use serde::Serialize;
 use statsd::Client;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
-#[derive(Clone)]
-pub struct MetricsService {
-    client: Arc<Client>,
+// How tags are attached to emitted metrics
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TagFormat {
+    // `metric#k=v,k=v` folded into the metric name
+    Legacy,
+    // `metric:value|type|#k:v,k:v` as parsed by DogStatsD-compatible agents
+    DogStatsd,
 }
 
-impl MetricsService {
-    pub fn new(host: &str, port: u16, prefix: &str) -> Self {
-        let client = Arc::new(Client::new(format!("{}:{}", host, port), prefix).unwrap());
-        Self { client }
+impl FromStr for TagFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "legacy" => Ok(TagFormat::Legacy),
+            "dogstatsd" => Ok(TagFormat::DogStatsd),
+            _ => Err("must be legacy or dogstatsd".to_string()),
+        }
     }
+}
 
-    pub fn increment(&self, metric: &str, tags: Option<HashMap<String, String>>) {
-        let mut metric_name = metric.to_string();
+// The statsd client can't put tags after the metric type, so DogStatsD lines are sent directly
+struct DogStatsdSink {
+    socket: UdpSocket,
+    prefix: Option<String>,
+}
+
+impl DogStatsdSink {
+    fn send(&self, metric: &str, value: &str, kind: &str, rate: f64, tags: Option<HashMap<String, String>>) {
+        let mut line = match &self.prefix {
+            Some(prefix) => format!("{}.{}:{}|{}", prefix, metric, value, kind),
+            None => format!("{}:{}|{}", metric, value, kind),
+        };
+        if rate < 1.0 {
+            line = format!("{}|@{}", line, rate);
+        }
         if let Some(tags) = tags {
             let tag_string = tags
                 .iter()
-                .map(|(k, v)| format!("{}={}", k, v))
+                .map(|(k, v)| format!("{}:{}", k, v))
                 .collect::<Vec<String>>()
                 .join(",");
-            metric_name = format!("{}#{}", metric_name, tag_string);
+            line = format!("{}|#{}", line, tag_string);
+        }
+        // Metrics are best effort, same as the statsd client
+        let _ = self.socket.send(line.as_bytes());
+    }
+}
+
+// Upper bounds, in seconds, of the histogram buckets timings are counted into
+const PROMETHEUS_BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+enum PrometheusValue {
+    Counter(f64),
+    Gauge(f64),
+    Histogram { buckets: Vec<u64>, sum: f64, count: u64 },
+}
+
+// Keeps every series in memory and renders them in the Prometheus text format on scrape.
+// Series are keyed by metric name, then by their rendered labels.
+#[derive(Default)]
+struct PrometheusSink {
+    families: Mutex<BTreeMap<String, BTreeMap<String, PrometheusValue>>>,
+}
+
+impl PrometheusSink {
+    fn record(
+        &self,
+        name: String,
+        tags: Option<&HashMap<String, String>>,
+        initial: impl FnOnce() -> PrometheusValue,
+        update: impl FnOnce(&mut PrometheusValue),
+    ) {
+        let mut families = self.families.lock().unwrap();
+        let value = families
+            .entry(name)
+            .or_default()
+            .entry(prometheus_labels(tags))
+            .or_insert_with(initial);
+        update(value);
+    }
+
+    fn increment(&self, metric: &str, tags: Option<&HashMap<String, String>>) {
+        self.record(format!("{}_total", prometheus_name(metric)), tags, || PrometheusValue::Counter(0.0), |value| {
+            if let PrometheusValue::Counter(count) = value {
+                *count += 1.0;
+            }
+        });
+    }
+
+    fn gauge(&self, metric: &str, gauge: f64, tags: Option<&HashMap<String, String>>) {
+        self.record(prometheus_name(metric), tags, || PrometheusValue::Gauge(gauge), |value| {
+            *value = PrometheusValue::Gauge(gauge);
+        });
+    }
+
+    fn timing(&self, metric: &str, seconds: f64, tags: Option<&HashMap<String, String>>) {
+        let initial = || PrometheusValue::Histogram {
+            buckets: vec![0; PROMETHEUS_BUCKETS.len()],
+            sum: 0.0,
+            count: 0,
+        };
+        self.record(format!("{}_seconds", prometheus_name(metric)), tags, initial, |value| {
+            if let PrometheusValue::Histogram { buckets, sum, count } = value {
+                for (bucket, le) in buckets.iter_mut().zip(PROMETHEUS_BUCKETS) {
+                    if seconds <= *le {
+                        *bucket += 1;
+                    }
+                }
+                *sum += seconds;
+                *count += 1;
+            }
+        });
+    }
+
+    fn render(&self) -> String {
+        let families = self.families.lock().unwrap();
+        let mut out = String::new();
+
+        for (name, series) in families.iter() {
+            let kind = match series.values().next() {
+                Some(PrometheusValue::Counter(_)) => "counter",
+                Some(PrometheusValue::Gauge(_)) => "gauge",
+                Some(PrometheusValue::Histogram { .. }) => "histogram",
+                None => continue,
+            };
+            let _ = writeln!(out, "# TYPE {} {}", name, kind);
+
+            for (labels, value) in series {
+                match value {
+                    PrometheusValue::Counter(v) | PrometheusValue::Gauge(v) => {
+                        let _ = writeln!(out, "{}{} {}", name, braced(labels), v);
+                    }
+                    PrometheusValue::Histogram { buckets, sum, count } => {
+                        for (bucket, le) in buckets.iter().zip(PROMETHEUS_BUCKETS) {
+                            let _ = writeln!(out, "{}_bucket{} {}", name, braced(&with_le(labels, &le.to_string())), bucket);
+                        }
+                        let _ = writeln!(out, "{}_bucket{} {}", name, braced(&with_le(labels, "+Inf")), count);
+                        let _ = writeln!(out, "{}_sum{} {}", name, braced(labels), sum);
+                        let _ = writeln!(out, "{}_count{} {}", name, braced(labels), count);
+                    }
+                }
+            }
+        }
+
+        out
+    }
+}
+
+// Prometheus names only allow [a-zA-Z0-9_:], so `face_match.duration` becomes `face_match_duration`
+fn prometheus_name(metric: &str) -> String {
+    metric
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '_' })
+        .collect()
+}
+
+// `k="v",k="v"` sorted by key, so the same tags always land in the same series
+fn prometheus_labels(tags: Option<&HashMap<String, String>>) -> String {
+    let Some(tags) = tags else {
+        return String::new();
+    };
+    let sorted: BTreeMap<&String, &String> = tags.iter().collect();
+    sorted
+        .into_iter()
+        .map(|(k, v)| {
+            let value = v.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n");
+            format!("{}=\"{}\"", prometheus_name(k), value)
+        })
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+fn with_le(labels: &str, le: &str) -> String {
+    if labels.is_empty() {
+        format!("le=\"{}\"", le)
+    } else {
+        format!("{},le=\"{}\"", labels, le)
+    }
+}
+
+fn braced(labels: &str) -> String {
+    if labels.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", labels)
+    }
+}
+
+// With neither a client nor a DogStatsD sink nor Prometheus every call is a no-op.
+//
+// Counters and timers are sampled before they reach statsd; gauges and Prometheus always see
+// every value. Rates are looked up by the metric name before tags are added, so every tag
+// combination of a metric is sampled alike. Sampled counters carry `@rate` so statsd scales
+// them back up; in the legacy format that follows the folded name (`metric#k=v:1|c|@0.1`).
+// The statsd client can't attach a rate to timers, so legacy timers are only thinned out and
+// their counts read low, while DogStatsD timers carry the rate like counters do.
+#[derive(Clone)]
+pub struct MetricsService {
+    client: Option<Arc<Client>>,
+    dogstatsd: Option<Arc<DogStatsdSink>>,
+    prometheus: Option<Arc<PrometheusSink>>,
+    sample_rate: f64,
+    sample_rate_overrides: Arc<HashMap<String, f64>>,
+}
+
+impl MetricsService {
+    pub fn new(
+        host: &str,
+        port: u16,
+        prefix: Option<&str>,
+        tag_format: TagFormat,
+        sample_rate: f64,
+        sample_rate_overrides: HashMap<String, f64>,
+    ) -> Self {
+        let client = Arc::new(Client::new(format!("{}:{}", host, port), prefix.unwrap_or_default()).unwrap());
+
+        let dogstatsd = match tag_format {
+            TagFormat::Legacy => None,
+            TagFormat::DogStatsd => {
+                let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+                socket.connect(format!("{}:{}", host, port)).unwrap();
+                socket.set_nonblocking(true).unwrap();
+                Some(Arc::new(DogStatsdSink { socket, prefix: prefix.map(str::to_string) }))
+            }
+        };
+
+        Self {
+            client: Some(client),
+            dogstatsd,
+            prometheus: None,
+            sample_rate,
+            sample_rate_overrides: Arc::new(sample_rate_overrides),
+        }
+    }
+
+    pub fn noop() -> Self {
+        Self {
+            client: None,
+            dogstatsd: None,
+            prometheus: None,
+            sample_rate: 1.0,
+            sample_rate_overrides: Arc::new(HashMap::new()),
+        }
+    }
+
+    // Also keeps every metric for the Prometheus endpoint, next to whatever statsd does
+    pub fn with_prometheus(mut self) -> Self {
+        self.prometheus = Some(Arc::new(PrometheusSink::default()));
+        self
+    }
+
+    // None when Prometheus isn't enabled
+    pub fn render_prometheus(&self) -> Option<String> {
+        self.prometheus.as_ref().map(|sink| sink.render())
+    }
+
+    pub fn increment(&self, metric: &str, tags: Option<HashMap<String, String>>) {
+        if let Some(prometheus) = &self.prometheus {
+            prometheus.increment(metric, tags.as_ref());
+        }
+        let rate = self.sample_rate(metric);
+        if let Some(sink) = &self.dogstatsd {
+            if sampled(rate) {
+                sink.send(metric, "1", "c", rate, tags);
+            }
+        } else if let Some(client) = &self.client {
+            if rate < 1.0 {
+                // Makes its own sampling decision
+                client.sampled_count(&legacy_name(metric, tags), 1.0, rate);
+            } else {
+                client.incr(&legacy_name(metric, tags));
+            }
         }
-        self.client.incr(&metric_name);
     }
 
     pub fn gauge(&self, metric: &str, value: f64, tags: Option<HashMap<String, String>>) {
-        let mut metric_name = metric.to_string();
-        if let Some(tags) = tags {
-            let tag_string = tags
-                .iter()
-                .map(|(k, v)| format!("{}={}", k, v))
-                .collect::<Vec<String>>()
-                .join(",");
-            metric_name = format!("{}#{}", metric_name, tag_string);
+        if let Some(prometheus) = &self.prometheus {
+            prometheus.gauge(metric, value, tags.as_ref());
+        }
+        if let Some(sink) = &self.dogstatsd {
+            sink.send(metric, &value.to_string(), "g", 1.0, tags);
+        } else if let Some(client) = &self.client {
+            client.gauge(&legacy_name(metric, tags), value);
         }
-        self.client.gauge(&metric_name, value);
     }
 
     pub fn timing(&self, metric: &str, duration: std::time::Duration, tags: Option<HashMap<String, String>>) {
-        let mut metric_name = metric.to_string();
-        if let Some(tags) = tags {
-            let tag_string = tags
-                .iter()
-                .map(|(k, v)| format!("{}={}", k, v))
-                .collect::<Vec<String>>()
-                .join(",");
-            metric_name = format!("{}#{}", metric_name, tag_string);
+        if let Some(prometheus) = &self.prometheus {
+            prometheus.timing(metric, duration.as_secs_f64(), tags.as_ref());
+        }
+        let rate = self.sample_rate(metric);
+        if !sampled(rate) {
+            return;
         }
-        self.client.timer(&metric_name, duration.as_millis() as f64);
+        let millis = duration.as_millis() as f64;
+        if let Some(sink) = &self.dogstatsd {
+            sink.send(metric, &millis.to_string(), "ms", rate, tags);
+        } else if let Some(client) = &self.client {
+            client.timer(&legacy_name(metric, tags), millis);
+        }
+    }
+
+    fn sample_rate(&self, metric: &str) -> f64 {
+        self.sample_rate_overrides.get(metric).copied().unwrap_or(self.sample_rate)
+    }
+}
+
+fn sampled(rate: f64) -> bool {
+    rate >= 1.0 || rand::thread_rng().gen_bool(rate)
+}
+
+fn legacy_name(metric: &str, tags: Option<HashMap<String, String>>) -> String {
+    let mut metric_name = metric.to_string();
+    if let Some(tags) = tags {
+        let tag_string = tags
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect::<Vec<String>>()
+            .join(",");
+        metric_name = format!("{}#{}", metric_name, tag_string);
     }
-} 
\ No newline at end of file
+    metric_name
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    // A statsd agent on a free local port
+    pub(crate) fn agent() -> UdpSocket {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.set_read_timeout(Some(Duration::from_millis(200))).unwrap();
+        socket
+    }
+
+    // Every line the agent got, until none arrives for a while
+    pub(crate) fn received(agent: &UdpSocket) -> Vec<String> {
+        let mut lines = Vec::new();
+        let mut buf = [0; 1024];
+        while let Ok(len) = agent.recv(&mut buf) {
+            lines.push(String::from_utf8_lossy(&buf[..len]).into_owned());
+        }
+        lines
+    }
+
+    pub(crate) fn dogstatsd(agent: &UdpSocket, prefix: Option<&str>, sample_rate_overrides: HashMap<String, f64>) -> MetricsService {
+        let port = agent.local_addr().unwrap().port();
+        MetricsService::new("127.0.0.1", port, prefix, TagFormat::DogStatsd, 1.0, sample_rate_overrides)
+    }
+
+    fn tags(pairs: &[(&str, &str)]) -> Option<HashMap<String, String>> {
+        Some(pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect())
+    }
+
+    #[test]
+    fn dogstatsd_lines_put_tags_after_the_type() {
+        let agent = agent();
+        let metrics = dogstatsd(&agent, Some("socio_echo_be"), HashMap::new());
+
+        metrics.increment("api_error", tags(&[("endpoint", "presigned_urls")]));
+        metrics.timing("api_latency", Duration::from_millis(12), tags(&[("endpoint", "login")]));
+        metrics.gauge("face_match.circuit_open", 1.0, None);
+
+        assert_eq!(
+            received(&agent),
+            [
+                "socio_echo_be.api_error:1|c|#endpoint:presigned_urls",
+                "socio_echo_be.api_latency:12|ms|#endpoint:login",
+                "socio_echo_be.face_match.circuit_open:1|g",
+            ]
+        );
+
+        // Without a prefix the metric name starts the line
+        let unprefixed = dogstatsd(&agent, None, HashMap::new());
+        unprefixed.increment("api_success", None);
+        assert_eq!(received(&agent), ["api_success:1|c"]);
+    }
+
+    #[test]
+    fn legacy_lines_fold_tags_into_the_name() {
+        let agent = agent();
+        let port = agent.local_addr().unwrap().port();
+        let metrics = MetricsService::new("127.0.0.1", port, Some("socio_echo_be"), TagFormat::Legacy, 1.0, HashMap::new());
+
+        metrics.increment("api_error", tags(&[("endpoint", "presigned_urls")]));
+        metrics.timing("api_latency", Duration::from_millis(12), None);
+
+        assert_eq!(
+            received(&agent),
+            ["socio_echo_be.api_error#endpoint=presigned_urls:1|c", "socio_echo_be.api_latency:12|ms"]
+        );
+    }
+
+    #[test]
+    fn noop_accepts_every_call_without_a_collector() {
+        let metrics = MetricsService::noop();
+        assert!(metrics.client.is_none() && metrics.dogstatsd.is_none());
+
+        metrics.increment("api_error", tags(&[("endpoint", "login")]));
+        metrics.gauge("face_match.circuit_open", 1.0, None);
+        metrics.timing("api_latency", Duration::from_millis(12), None);
+        assert_eq!(metrics.render_prometheus(), None);
+
+        // Prometheus still works without statsd
+        let metrics = MetricsService::noop().with_prometheus();
+        metrics.increment("api_error", None);
+        assert!(metrics.render_prometheus().unwrap().contains("api_error_total 1"));
+    }
+
+    #[test]
+    fn overridden_metrics_are_sampled_at_their_own_rate() {
+        let agent = agent();
+        let overrides = HashMap::from([("api_latency".to_string(), 0.5)]);
+        let metrics = dogstatsd(&agent, None, overrides);
+        assert_eq!(metrics.sample_rate("api_latency"), 0.5);
+        assert_eq!(metrics.sample_rate("api_error"), 1.0);
+
+        for _ in 0..200 {
+            metrics.timing("api_latency", Duration::from_millis(12), None);
+        }
+        for _ in 0..5 {
+            metrics.increment("api_error", None);
+        }
+
+        let lines = received(&agent);
+        let (latency, other): (Vec<_>, Vec<_>) = lines.iter().partition(|line| line.starts_with("api_latency"));
+        assert!((1..200).contains(&latency.len()), "{} of 200 sent", latency.len());
+        assert!(latency.iter().all(|line| *line == "api_latency:12|ms|@0.5"));
+        // Metrics without an override keep the default rate and carry no `@rate`
+        assert_eq!(other, ["api_error:1|c"; 5]);
+    }
+}
@@ -1,3 +1,11 @@
+pub mod app_services;
 pub mod auth_service;
+pub mod circuit_breaker;
+pub mod dashboard_service;
+pub mod elasticsearch_client;
+pub mod email_sender;
 pub mod metrics_service;
-pub mod face_match_service; 
\ No newline at end of file
+pub mod face_match_service;
+pub mod feature_flags_service;
+pub mod user_erasure_service;
+pub mod webhook_service;
@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use serde::Serialize;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    commons::minio_service::MinioService,
+    repositories::{audit_log_repository::AuditLogRepository, user_repository::UserRepository},
+    services::metrics_service::MetricsService,
+    submissions::submission_repository::SubmissionRepository,
+};
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserErasureSummary {
+    pub user_id: i32,
+    pub submissions_deleted: usize,
+    pub objects_deleted: usize,
+    pub objects_failed: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubmissionErasureSummary {
+    pub submission_id: Uuid,
+    pub objects_deleted: usize,
+    pub objects_failed: Vec<String>,
+}
+
+pub struct UserErasureService {
+    pool: PgPool,
+    minio_service: MinioService,
+    metrics: MetricsService,
+}
+
+impl UserErasureService {
+    pub fn new(pool: PgPool, minio_service: MinioService, metrics: MetricsService) -> Self {
+        Self {
+            pool,
+            minio_service,
+            metrics,
+        }
+    }
+
+    // Deletes the user, soft-deletes their submissions and records the erasure in one
+    // transaction, then removes the stored objects. Object removal can't be rolled back,
+    // so failures are reported in the summary instead of failing the erasure. None when there
+    // is no such user.
+    pub async fn erase_user(&self, user_id: i32, actor: &str) -> Result<Option<UserErasureSummary>, anyhow::Error> {
+        let mut tags = HashMap::new();
+        tags.insert("endpoint".to_string(), "erase_user".to_string());
+
+        let mut tx = self.pool.begin().await?;
+
+        let submissions_data = SubmissionRepository::soft_delete_by_user(&mut tx, &user_id.to_string()).await?;
+
+        if !UserRepository::delete(&mut tx, user_id).await? {
+            return Ok(None);
+        }
+
+        let objects: Vec<StoredObject> = submissions_data.iter().flat_map(document_objects).collect();
+
+        AuditLogRepository::create(
+            &mut tx,
+            "USER_ERASED",
+            "user",
+            &user_id.to_string(),
+            actor,
+            json!({
+                "submissionsDeleted": submissions_data.len(),
+                "objects": object_names(&objects),
+            }),
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        let (objects_deleted, objects_failed) = self.delete_objects(objects, &format!("user {}", user_id)).await;
+
+        if !objects_failed.is_empty() {
+            self.metrics.increment("erase_user.object_error", Some(tags.clone()));
+        }
+        self.metrics.increment("erase_user.success", Some(tags));
+
+        Ok(Some(UserErasureSummary {
+            user_id,
+            submissions_deleted: submissions_data.len(),
+            objects_deleted,
+            objects_failed,
+        }))
+    }
+
+    // Erasure of a single submission, requested by the user who owns it. Same order as
+    // erase_user: the row is scrubbed and the erasure recorded first, then objects are removed.
+    // None when the user has no such submission.
+    pub async fn erase_submission(
+        &self,
+        user_id: i32,
+        submission_id: Uuid,
+    ) -> Result<Option<SubmissionErasureSummary>, anyhow::Error> {
+        let mut tags = HashMap::new();
+        tags.insert("endpoint".to_string(), "erase_submission".to_string());
+
+        let mut tx = self.pool.begin().await?;
+
+        // Someone else's submission is reported as missing, so ids can't be probed
+        let Some(submission_data) = SubmissionRepository::soft_delete(&mut tx, submission_id, &user_id.to_string()).await? else {
+            return Ok(None);
+        };
+
+        let objects = document_objects(&submission_data);
+
+        AuditLogRepository::create(
+            &mut tx,
+            "SUBMISSION_ERASED",
+            "SUBMISSION",
+            &submission_id.to_string(),
+            &format!("user:{}", user_id),
+            json!({ "objects": object_names(&objects) }),
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        let (objects_deleted, objects_failed) = self.delete_objects(objects, &submission_id.to_string()).await;
+
+        if !objects_failed.is_empty() {
+            self.metrics.increment("erase_submission.object_error", Some(tags.clone()));
+        }
+        self.metrics.increment("erase_submission.success", Some(tags));
+
+        Ok(Some(SubmissionErasureSummary {
+            submission_id,
+            objects_deleted,
+            objects_failed,
+        }))
+    }
+
+    // Returns how many objects were deleted and the names of those that couldn't be
+    async fn delete_objects(&self, objects: Vec<StoredObject>, owner: &str) -> (usize, Vec<String>) {
+        let mut objects_deleted = 0;
+        let mut objects_failed = Vec::new();
+        for object in objects {
+            let result = match &object.archived_bucket {
+                Some(bucket) => self.minio_service.delete_file_in(bucket, object.name.clone()).await,
+                None => self.minio_service.delete_file(object.name.clone()).await,
+            };
+            match result {
+                Ok(()) => objects_deleted += 1,
+                Err(e) => {
+                    log::error!("Failed to delete object {} of erased {}: {}", object, owner, e);
+                    objects_failed.push(object.to_string());
+                }
+            }
+        }
+        (objects_deleted, objects_failed)
+    }
+}
+
+// A stored document object, in the archive bucket once the submission was archived
+struct StoredObject {
+    name: String,
+    archived_bucket: Option<String>,
+}
+
+impl fmt::Display for StoredObject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.archived_bucket {
+            Some(bucket) => write!(f, "{}/{}", bucket, self.name),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+// Stored objects referenced by a submission's documents
+fn document_objects(submission_data: &Value) -> Vec<StoredObject> {
+    submission_data
+        .as_object()
+        .map(|documents| {
+            documents
+                .values()
+                .filter_map(|document| {
+                    let name = document["documentName"].as_str().filter(|name| !name.is_empty())?;
+                    Some(StoredObject {
+                        name: name.to_string(),
+                        archived_bucket: document["archivedBucket"].as_str().map(str::to_string),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn object_names(objects: &[StoredObject]) -> Vec<String> {
+    objects.iter().map(StoredObject::to_string).collect()
+}
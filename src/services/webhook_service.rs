@@ -0,0 +1,397 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use hmac::{Hmac, Mac};
+use serde_json::Value;
+use sha2::Sha256;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    config::Config,
+    repositories::webhook_delivery_repository::{NewWebhookDelivery, WebhookDeliveryRepository},
+    services::metrics_service::MetricsService,
+};
+
+pub const SUBMISSION_DECIDED: &str = "SUBMISSION_DECIDED";
+
+// Receivers recompute the signature over `{timestamp}.{body}` with the shared secret and
+// should reject timestamps too far in the past, so a captured delivery can't be replayed.
+// While a secret is being rotated the header holds one signature per secret, comma separated.
+pub const SIGNATURE_HEADER: &str = "x-webhook-signature";
+pub const TIMESTAMP_HEADER: &str = "x-webhook-timestamp";
+
+// How deliveries are signed and sent, taken from the WEBHOOK_* settings
+#[derive(Clone)]
+pub struct WebhookSettings {
+    pub secret: Option<String>,
+    pub previous_secret: Option<String>,
+    pub allowed_hosts: Vec<String>,
+    pub timeout: Duration,
+    pub retry_attempts: u32,
+    pub retry_base_delay: Duration,
+}
+
+impl WebhookSettings {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            secret: config.webhook_secret.clone(),
+            previous_secret: config.webhook_previous_secret.clone(),
+            allowed_hosts: config.webhook_allowed_hosts.clone(),
+            timeout: Duration::from_millis(config.webhook_timeout_millis),
+            retry_attempts: config.webhook_retry_attempts,
+            retry_base_delay: Duration::from_millis(config.webhook_retry_base_delay_millis),
+        }
+    }
+}
+
+// Posts signed events to the callback URL a client gave for its submission. Deliveries run in
+// the background, so the request that triggered them never waits on the receiver. Connection
+// failures, timeouts, 429 and 5xx are retried with exponential backoff; every attempt is
+// recorded in webhook_deliveries. Without a secret webhooks are disabled.
+#[derive(Clone)]
+pub struct WebhookService {
+    timeout: Duration,
+    secret: Option<String>,
+    previous_secret: Option<String>,
+    allowed_hosts: Vec<String>,
+    retry_attempts: u32,
+    retry_base_delay: Duration,
+    repository: WebhookDeliveryRepository,
+    metrics: MetricsService,
+}
+
+impl WebhookService {
+    pub fn new(pool: PgPool, settings: WebhookSettings, metrics: MetricsService) -> Self {
+        Self {
+            timeout: settings.timeout,
+            secret: settings.secret,
+            previous_secret: settings.previous_secret,
+            allowed_hosts: settings.allowed_hosts,
+            retry_attempts: settings.retry_attempts,
+            retry_base_delay: settings.retry_base_delay,
+            repository: WebhookDeliveryRepository::new(pool),
+            metrics,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.secret.is_some()
+    }
+
+    pub fn deliver(&self, submission_id: Uuid, url: String, event: &'static str, payload: Value) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let service = self.clone();
+        tokio::spawn(async move {
+            service.send(submission_id, &url, event, payload.to_string()).await;
+        });
+    }
+
+    // `sha256=<current>`, followed by `, sha256=<previous>` while a previous secret is set
+    fn signature(&self, timestamp: &str, body: &str) -> String {
+        self.secret
+            .iter()
+            .chain(&self.previous_secret)
+            .map(|secret| sign(secret, timestamp, body))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    async fn send(&self, submission_id: Uuid, url: &str, event: &str, body: String) {
+        let mut tags = HashMap::new();
+        tags.insert("event".to_string(), event.to_string());
+
+        // Resolved again at delivery and pinned for every attempt, so a host whose DNS changed
+        // to an internal address since the submission was created is still refused
+        let client = match resolve_callback_url(url, &self.allowed_hosts).await {
+            Ok((host, addrs)) => self.client_for(&host, &addrs),
+            Err(reason) => {
+                log::warn!("Refusing {} webhook for {}: {}", event, submission_id, reason);
+                self.metrics.increment("webhook.refused", Some(tags));
+                return;
+            }
+        };
+        let client = match client {
+            Ok(client) => client,
+            Err(e) => {
+                log::error!("Failed to create webhook client for {}: {}", submission_id, e);
+                self.metrics.increment("webhook.failed", Some(tags));
+                return;
+            }
+        };
+
+        for attempt in 1..=self.retry_attempts {
+            // Signed per attempt so the timestamp reflects when it was sent
+            let timestamp = chrono::Utc::now().timestamp().to_string();
+            let result = client
+                .post(url)
+                .header("content-type", "application/json")
+                .header(TIMESTAMP_HEADER, &timestamp)
+                .header(SIGNATURE_HEADER, self.signature(&timestamp, &body))
+                .body(body.clone())
+                .send()
+                .await;
+
+            let (status_code, error, retryable) = match &result {
+                Ok(resp) if resp.status().is_success() => (Some(resp.status().as_u16()), None, false),
+                Ok(resp) => {
+                    let status = resp.status();
+                    let retryable = status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS;
+                    (Some(status.as_u16()), Some(format!("HTTP {}", status)), retryable)
+                }
+                Err(e) => (None, Some(e.to_string()), e.is_connect() || e.is_timeout()),
+            };
+            let delivered = error.is_none();
+
+            if let Err(e) = self
+                .repository
+                .create(NewWebhookDelivery {
+                    submission_id,
+                    url,
+                    event,
+                    attempt: attempt as i32,
+                    status_code: status_code.map(i32::from),
+                    error: error.as_deref(),
+                    delivered,
+                })
+                .await
+            {
+                log::error!("Failed to record webhook delivery for {}: {}", submission_id, e);
+            }
+
+            if delivered {
+                self.metrics.increment("webhook.delivered", Some(tags));
+                return;
+            }
+            if !retryable || attempt >= self.retry_attempts {
+                break;
+            }
+
+            self.metrics.increment("webhook.retry", Some(tags.clone()));
+            tokio::time::sleep(self.retry_base_delay * 2u32.pow(attempt - 1)).await;
+        }
+
+        self.metrics.increment("webhook.failed", Some(tags));
+        log::warn!("Giving up delivering {} webhook for {}", event, submission_id);
+    }
+}
+
+impl WebhookService {
+    fn client_for(&self, host: &str, addrs: &[SocketAddr]) -> reqwest::Result<reqwest::Client> {
+        reqwest::Client::builder()
+            .timeout(self.timeout)
+            // A redirect would send the signed payload somewhere the client didn't register
+            .redirect(reqwest::redirect::Policy::none())
+            .resolve_to_addrs(host, addrs)
+            .build()
+    }
+}
+
+// Resolves the callback host and refuses it when any address is internal (loopback, private,
+// link-local, unique-local, ...), so webhooks can't be aimed at the service's own network.
+// Hosts listed in WEBHOOK_ALLOWED_HOSTS skip the address check; when the list is set, other
+// hosts are refused outright. Returns the host and the addresses to pin the delivery to.
+pub async fn resolve_callback_url(url: &str, allowed_hosts: &[String]) -> Result<(String, Vec<SocketAddr>), String> {
+    let url = reqwest::Url::parse(url).map_err(|e| e.to_string())?;
+    let host = url.host_str().ok_or("missing host")?.to_ascii_lowercase();
+    let port = url.port_or_known_default().ok_or("missing port")?;
+    // IPv6 literals come with brackets, which lookup_host doesn't take
+    let lookup_host = host.trim_start_matches('[').trim_end_matches(']').to_string();
+
+    let allowlisted = allowed_hosts.iter().any(|allowed| allowed.eq_ignore_ascii_case(&host));
+    if !allowed_hosts.is_empty() && !allowlisted {
+        return Err(format!("host {} is not allowed", host));
+    }
+
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((lookup_host.as_str(), port))
+        .await
+        .map_err(|e| format!("host {} does not resolve: {}", host, e))?
+        .collect();
+    if addrs.is_empty() {
+        return Err(format!("host {} does not resolve", host));
+    }
+    if !allowlisted {
+        if let Some(addr) = addrs.iter().find(|addr| !is_public(addr.ip())) {
+            return Err(format!("host {} resolves to internal address {}", host, addr.ip()));
+        }
+    }
+
+    Ok((lookup_host, addrs))
+}
+
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                // Carrier-grade NAT, 100.64.0.0/10
+                || (a == 100 && (64..128).contains(&b))
+                // 0.0.0.0/8
+                || a == 0)
+        }
+        IpAddr::V6(ip) => {
+            if let Some(v4) = ip.to_ipv4_mapped() {
+                return is_public(IpAddr::V4(v4));
+            }
+            let first = ip.segments()[0];
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                // Unique local, fc00::/7
+                || (first & 0xfe00) == 0xfc00
+                // Link local, fe80::/10
+                || (first & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+// `sha256=<hex HMAC-SHA256 of "{timestamp}.{body}">`
+fn sign(secret: &str, timestamp: &str, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(timestamp.as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    format!("sha256={:x}", mac.finalize().into_bytes())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+
+    use super::*;
+
+    // Deliveries to 127.0.0.1 are allowed, over http, and never retried
+    pub(crate) fn settings(secret: Option<&str>, previous_secret: Option<&str>) -> WebhookSettings {
+        WebhookSettings {
+            secret: secret.map(str::to_string),
+            previous_secret: previous_secret.map(str::to_string),
+            allowed_hosts: vec!["127.0.0.1".to_string()],
+            timeout: Duration::from_millis(1000),
+            retry_attempts: 1,
+            retry_base_delay: Duration::from_millis(10),
+        }
+    }
+
+    // Timestamp and signature header of each delivery received
+    type Deliveries = Arc<Mutex<Vec<(String, String)>>>;
+
+    // The deliveries a local receiver got, and its URL
+    fn receiver() -> (Deliveries, String) {
+        let received = Deliveries::default();
+        let headers = received.clone();
+        let server = HttpServer::new(move || {
+            let headers = headers.clone();
+            App::new().default_service(web::to(move |request: HttpRequest| {
+                let headers = headers.clone();
+                async move {
+                    let header = |name: &str| request.headers().get(name).unwrap().to_str().unwrap().to_string();
+                    headers.lock().unwrap().push((header(TIMESTAMP_HEADER), header(SIGNATURE_HEADER)));
+                    HttpResponse::Ok().finish()
+                }
+            }))
+        })
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+        let addr = server.addrs()[0];
+        tokio::spawn(server.run());
+        (received, format!("http://{}/hook", addr))
+    }
+
+    // Timestamp and signature header of the one delivery of `payload` through `webhooks`
+    async fn delivered_signature(webhooks: WebhookService, payload: Value) -> (String, String) {
+        let (received, url) = receiver();
+        webhooks.deliver(Uuid::new_v4(), url, SUBMISSION_DECIDED, payload);
+        for _ in 0..100 {
+            if let Some(delivery) = received.lock().unwrap().first() {
+                return delivery.clone();
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("webhook was not delivered");
+    }
+
+    async fn refused(url: &str) -> bool {
+        resolve_callback_url(url, &[]).await.is_err()
+    }
+
+    #[tokio::test]
+    async fn refuses_internal_addresses() {
+        assert!(refused("http://127.0.0.1/hook").await);
+        assert!(refused("http://localhost:8080/hook").await);
+        assert!(refused("https://10.1.2.3/hook").await);
+        assert!(refused("https://172.16.0.1/hook").await);
+        assert!(refused("https://192.168.1.1/hook").await);
+        assert!(refused("http://169.254.169.254/latest/meta-data").await);
+        assert!(refused("https://100.64.0.1/hook").await);
+        assert!(refused("https://0.0.0.0/hook").await);
+        assert!(refused("https://[::1]/hook").await);
+        assert!(refused("https://[fd00::1]/hook").await);
+        assert!(refused("https://[fe80::1]/hook").await);
+        assert!(refused("https://[::ffff:127.0.0.1]/hook").await);
+    }
+
+    #[tokio::test]
+    async fn accepts_public_addresses() {
+        let (host, addrs) = resolve_callback_url("https://93.184.216.34/hook", &[]).await.unwrap();
+        assert_eq!(host, "93.184.216.34");
+        assert_eq!(addrs, vec!["93.184.216.34:443".parse().unwrap()]);
+
+        assert!(resolve_callback_url("https://[2606:2800:220:1::1]/hook", &[]).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn allowlist_overrides_address_check_and_refuses_other_hosts() {
+        let allowed = vec!["127.0.0.1".to_string()];
+        assert!(resolve_callback_url("http://127.0.0.1:9000/hook", &allowed).await.is_ok());
+        assert!(resolve_callback_url("https://93.184.216.34/hook", &allowed).await.is_err());
+    }
+
+    #[test]
+    fn signature_covers_timestamp_and_body() {
+        let signature = sign("secret", "1700000000", "{}");
+        assert!(signature.starts_with("sha256="));
+        assert_ne!(signature, sign("secret", "1700000001", "{}"));
+        assert_ne!(signature, sign("other", "1700000000", "{}"));
+    }
+
+    #[sqlx::test]
+    async fn signs_with_the_current_secret(pool: PgPool) {
+        let webhooks = WebhookService::new(pool, settings(Some("new"), None), MetricsService::noop());
+        let payload = serde_json::json!({ "status": "APPROVED" });
+
+        let (timestamp, signature) = delivered_signature(webhooks, payload.clone()).await;
+        assert_eq!(signature, sign("new", &timestamp, &payload.to_string()));
+    }
+
+    // Receivers still holding the previous secret keep verifying deliveries during a rotation
+    #[sqlx::test]
+    async fn signs_with_both_secrets_while_rotating(pool: PgPool) {
+        let webhooks = WebhookService::new(pool, settings(Some("new"), Some("old")), MetricsService::noop());
+        let payload = serde_json::json!({ "status": "APPROVED" });
+
+        let (timestamp, signature) = delivered_signature(webhooks, payload.clone()).await;
+        let body = payload.to_string();
+        assert_eq!(signature, format!("{}, {}", sign("new", &timestamp, &body), sign("old", &timestamp, &body)));
+    }
+
+    // Only the previous secret is no secret to sign with
+    #[tokio::test]
+    async fn a_previous_secret_alone_leaves_webhooks_disabled() {
+        let pool = sqlx::PgPool::connect_lazy("postgres://127.0.0.1:1/unused").unwrap();
+        let webhooks = WebhookService::new(pool, settings(None, Some("old")), MetricsService::noop());
+        assert!(!webhooks.is_enabled());
+    }
+}
@@ -0,0 +1,156 @@
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+// Documents a submission can hold; the name is also the key in the stored submission data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum DocumentType {
+    Ktp,
+    Passport,
+    DrivingLicense,
+    Selfie,
+    Nfc,
+}
+
+impl DocumentType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DocumentType::Ktp => "KTP",
+            DocumentType::Passport => "PASSPORT",
+            DocumentType::DrivingLicense => "DRIVING_LICENSE",
+            DocumentType::Selfie => "SELFIE",
+            DocumentType::Nfc => "NFC",
+        }
+    }
+}
+
+impl std::fmt::Display for DocumentType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for DocumentType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "KTP" => Ok(DocumentType::Ktp),
+            "PASSPORT" => Ok(DocumentType::Passport),
+            "DRIVING_LICENSE" => Ok(DocumentType::DrivingLicense),
+            "SELFIE" => Ok(DocumentType::Selfie),
+            "NFC" => Ok(DocumentType::Nfc),
+            _ => Err(format!("UNKNOWN_DOCUMENT_TYPE: {}", s)),
+        }
+    }
+}
+
+// Upload state of a client-uploaded document, as last observed in storage
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum UploadStatus {
+    Pending,
+    Uploaded,
+}
+
+// Image formats accepted for documents the service stores itself, told apart by their leading bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageFormat {
+    Jpeg,
+    Png,
+}
+
+impl ImageFormat {
+    pub fn detect(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            Some(ImageFormat::Jpeg)
+        } else if bytes.starts_with(&[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]) {
+            Some(ImageFormat::Png)
+        } else {
+            None
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg => "image/jpeg",
+            ImageFormat::Png => "image/png",
+        }
+    }
+
+    // Parameters and case are ignored, e.g. "image/PNG; q=1" is a PNG
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        let essence = content_type.split(';').next().unwrap_or("").trim();
+        if essence.eq_ignore_ascii_case("image/jpeg") {
+            Some(ImageFormat::Jpeg)
+        } else if essence.eq_ignore_ascii_case("image/png") {
+            Some(ImageFormat::Png)
+        } else {
+            None
+        }
+    }
+
+    // File name extension for downloads
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg => "jpg",
+            ImageFormat::Png => "png",
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg => "jpeg",
+            ImageFormat::Png => "png",
+        }
+    }
+}
+
+impl FromStr for ImageFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "jpeg" => Ok(ImageFormat::Jpeg),
+            "png" => Ok(ImageFormat::Png),
+            _ => Err("must be jpeg or png".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [DocumentType; 5] = [
+        DocumentType::Ktp,
+        DocumentType::Passport,
+        DocumentType::DrivingLicense,
+        DocumentType::Selfie,
+        DocumentType::Nfc,
+    ];
+
+    #[test]
+    fn names_round_trip_through_strings_and_json() {
+        let names: Vec<_> = ALL.iter().map(DocumentType::as_str).collect();
+        assert_eq!(names, ["KTP", "PASSPORT", "DRIVING_LICENSE", "SELFIE", "NFC"]);
+
+        for document_type in ALL {
+            let name = document_type.as_str();
+            assert_eq!(document_type.to_string(), name);
+            assert_eq!(name.parse::<DocumentType>(), Ok(document_type));
+
+            let json = serde_json::to_value(document_type).unwrap();
+            assert_eq!(json, name);
+            assert_eq!(serde_json::from_value::<DocumentType>(json).unwrap(), document_type);
+        }
+    }
+
+    #[test]
+    fn unknown_names_are_rejected() {
+        assert_eq!("Selfie".parse::<DocumentType>(), Err("UNKNOWN_DOCUMENT_TYPE: Selfie".to_string()));
+        assert!(serde_json::from_value::<DocumentType>(serde_json::json!("Selfie")).is_err());
+    }
+}
@@ -0,0 +1,16 @@
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkStatusItem {
+    pub submission_id: String,
+    pub previous_status: String,
+    pub changed: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkStatusResponse {
+    pub submission_status: String,
+    pub submissions: Vec<BulkStatusItem>,
+}
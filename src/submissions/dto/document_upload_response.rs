@@ -0,0 +1,12 @@
+use serde::Serialize;
+
+use crate::submissions::document_type::{DocumentType, UploadStatus};
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentUploadResponse {
+    pub submission_id: String,
+    pub document_type: DocumentType,
+    pub document_reference: String,
+    pub upload_status: UploadStatus,
+}
@@ -0,0 +1,18 @@
+use serde::Serialize;
+
+use crate::{models::user::ApiError, services::face_match_service::FaceMatchResponse};
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FaceMatchBatchItem {
+    pub submission_id: String,
+    pub success: bool,
+    pub data: Option<FaceMatchResponse>,
+    pub error: Option<ApiError>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FaceMatchBatchResponse {
+    pub results: Vec<FaceMatchBatchItem>,
+}
@@ -1 +1,8 @@
+pub mod bulk_status_response;
+pub mod document_upload_response;
+pub mod face_match_batch_response;
+pub mod nfc_summary_response;
 pub mod presigned_urls_response;
+pub mod recompute_status_response;
+pub mod submission_summary;
+pub mod submission_type_documents_response;
@@ -0,0 +1,14 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NfcSummaryResponse {
+    pub total: i64,
+    // Submission count per status; statuses the identifier never had are left out
+    pub status_counts: BTreeMap<String, i64>,
+    pub latest_status: String,
+    pub latest_updated_at: DateTime<Utc>,
+}
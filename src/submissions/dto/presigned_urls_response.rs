@@ -2,6 +2,8 @@ use std::collections::HashMap;
 
 use serde::Serialize;
 
+use crate::submissions::document_type::{DocumentType, UploadStatus};
+
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Document {
@@ -14,7 +16,19 @@ pub struct Document {
 #[serde(rename_all = "camelCase")]
 pub struct PresignedUrlsResponse {
     pub submission_id: String,
-    pub documents: HashMap<String, Document>,
+    pub documents: HashMap<DocumentType, Document>,
+}
+
+// What a presigned URL request would create, returned instead of it in dry-run mode
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PresignedUrlsDryRunResponse {
+    pub dry_run: bool,
+    pub submission_type: String,
+    pub upload_documents: Vec<DocumentType>,
+    pub stored_documents: Vec<DocumentType>,
+    pub nfc_size_bytes: usize,
+    pub external_reference: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -22,4 +36,5 @@ pub struct PresignedUrlsResponse {
 pub struct SubmissionData {
     pub document_name: String,
     pub document_reference: String,
+    pub upload_status: UploadStatus,
 }
\ No newline at end of file
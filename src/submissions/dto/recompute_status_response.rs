@@ -0,0 +1,10 @@
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecomputeStatusResponse {
+    pub submission_id: String,
+    pub previous_status: String,
+    pub submission_status: String,
+    pub changed: bool,
+}
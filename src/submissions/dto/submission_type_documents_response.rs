@@ -0,0 +1,13 @@
+use serde::Serialize;
+
+use crate::submissions::document_type::DocumentType;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubmissionTypeDocumentsResponse {
+    pub submission_type: String,
+    // Captured by the client and uploaded through presigned URLs
+    pub upload_documents: Vec<DocumentType>,
+    // Taken from the presigned URL request and stored by the backend
+    pub stored_documents: Vec<DocumentType>,
+}
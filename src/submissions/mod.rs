@@ -1,4 +1,7 @@
+pub mod document_type;
 pub mod dto;
 pub mod submission_controller;
 pub mod submission_service;
+pub mod submission_status;
 pub mod submission_repository;
+pub mod submission_type_registry;
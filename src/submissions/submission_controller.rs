@@ -1,22 +1,54 @@
-use actix_web::{web, HttpResponse};
+use std::collections::HashMap;
+
+use actix_multipart::{Multipart, MultipartError};
+use actix_web::{http::header, web, HttpRequest, HttpResponse};
+use futures::StreamExt;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::{
-    commons::minio_service::MinioService,
-    models::user::{ApiResponse, ApiError},
-    services::{metrics_service::MetricsService, face_match_service::FaceMatchService},
+    middleware::auth::{AuthenticatedUser, VerifiedUser},
+    commons::{
+        app_error::{errors, AppError},
+        etag,
+        json_body,
+    },
+    models::{
+        pagination::{Page, PaginatedResponse},
+        user::{ApiResponse, ApiError},
+    },
+    repositories::user_repository::UserRepository,
+    services::{
+        app_services::AppServices,
+        face_match_service::{self, FaceMatchRequest, FaceMatchService, FaceMatchUnavailable, MAX_BATCH_SIZE},
+    },
     submissions::{
-        submission_repository::SubmissionRepository,
-        submission_service::SubmissionService,
+        document_type::{DocumentType, UploadStatus},
+        dto::{
+            face_match_batch_response::{FaceMatchBatchItem, FaceMatchBatchResponse},
+            submission_type_documents_response::SubmissionTypeDocumentsResponse,
+        },
+        submission_service::{PresignedUrlsRequest, MAX_EXTERNAL_REFERENCE_LENGTH, MAX_IDEMPOTENCY_KEY_LENGTH},
+        submission_status::SubmissionStatus,
+        submission_type_registry,
     },
 };
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PresignedUrlsBody {
-    pub submission_type: SubmissionType,
+    // Falls back to DEFAULT_SUBMISSION_TYPE when omitted
+    pub submission_type: Option<SubmissionType>,
     pub nfc_identifier: String,
+    // Client's own id for the submission, for correlating with their systems
+    pub external_reference: Option<String>,
+    // Validate the request and report what would be created, without creating it
+    #[serde(default)]
+    pub dry_run: bool,
+    // Overrides the default upload URL lifetime, up to PRESIGN_UPLOAD_MAX_TTL_SECS
+    pub expiry_in_seconds: Option<u64>,
+    // Receives a signed POST once the submission is approved or rejected
+    pub callback_url: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -27,6 +59,12 @@ pub struct FaceMatchBody {
     pub submission_id: String,
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FaceMatchBatchBody {
+    pub pairs: Vec<FaceMatchBody>,
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ProcessSubmissionBody {
@@ -40,84 +78,170 @@ pub struct GetSubmissionStatusQuery {
     pub nfc_identifier: String,
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FindByExternalReferenceQuery {
+    pub external_reference: String,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ProcessSubmissionResponse {
     pub submission_status: String,
+    pub similarity_score: f64,
+    pub threshold: f64,
 }
 
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GetSubmissionStatusResponse {
-    pub submission_status: String,
+    // The stored status of the latest submission for the identifier
+    pub status: SubmissionStatus,
+    // Legacy KYC/NOT_KYC projection of `status`, omitted when SUBMISSION_STATUS_LEGACY_FIELD is false
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub submission_status: Option<&'static str>,
+    pub documents: HashMap<DocumentType, UploadStatus>,
+}
+
+// Existing clients only know KYC and NOT_KYC, so anything short of approved is NOT_KYC there;
+// the other states are only reported in `status`
+fn legacy_submission_status(status: SubmissionStatus) -> &'static str {
+    match status {
+        SubmissionStatus::Approved => "KYC",
+        _ => "NOT_KYC",
+    }
 }
 
 #[derive(Debug, Deserialize, Clone, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum SubmissionType {
-    KYC,
-    ON_DEMAND,
+    Kyc,
+    KycPassport,
+    KycDrivingLicense,
+    OnDemand,
 }
 
 impl std::fmt::Display for SubmissionType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            SubmissionType::KYC => write!(f, "KYC"),
-            SubmissionType::ON_DEMAND => write!(f, "ON_DEMAND"),
+            SubmissionType::Kyc => write!(f, "KYC"),
+            SubmissionType::KycPassport => write!(f, "KYC_PASSPORT"),
+            SubmissionType::KycDrivingLicense => write!(f, "KYC_DRIVING_LICENSE"),
+            SubmissionType::OnDemand => write!(f, "ON_DEMAND"),
         }
     }
 }
 
-#[actix_web::post("/submissions/urls")]
-async fn presigned_urls(
-    pool: web::Data<sqlx::PgPool>,
-    minio_service: web::Data<MinioService>,
-    metrics: web::Data<MetricsService>,
+impl std::str::FromStr for SubmissionType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        submission_type_registry::find(s)
+            .map(|config| config.submission_type.clone())
+            .ok_or_else(|| format!("unknown submission type {}", s))
+    }
+}
+
+// Rejects malformed ids up front instead of letting them surface as not found from the database
+fn invalid_submission_id() -> AppError {
+    AppError::Validation(errors("1003", "INVALID_SUBMISSION_ID"))
+}
+
+// Registered in main with the larger submission body limit, since it carries the NFC image
+pub async fn presigned_urls(
+    req: HttpRequest,
+    user: VerifiedUser,
+    services: web::Data<AppServices>,
     body: Result<web::Json<PresignedUrlsBody>, actix_web::Error>,
-) -> HttpResponse {
+) -> Result<HttpResponse, AppError> {
     let body = match body {
         Ok(b) => b,
-        Err(e) => {
-            return HttpResponse::BadRequest().json(ApiResponse::<()> {
-                success: false,
-                data: None,
-                errors: Some(vec![ApiError {
-                    entity: "SOCIO_ECHO_BE".to_string(),
-                    code: "1003".to_string(),
-                    cause: format!("INVALID_REQUEST_BODY: {}", e),
-                }]),
-            });
-        }
+        Err(e) => return Ok(json_body::rejection(e)),
     };
 
-    // TODO: Get these from auth middleware
-    let session_id = Uuid::new_v4().to_string();
-    let user_id = "1".to_string();
-
-    let submission_service = SubmissionService::new(
-        minio_service.as_ref().clone(),
-        SubmissionRepository::new(pool.as_ref().clone()),
-        metrics.get_ref().clone()
-    );
-
-    match submission_service
-        .generate_presigned_urls(
-            session_id,
-            user_id,
-            body.submission_type.clone(),
-            body.nfc_identifier.clone(),
-        )
-        .await
-    {
-        Ok(response) => HttpResponse::Ok().json(ApiResponse {
+    let submission_type = body
+        .submission_type
+        .clone()
+        .or_else(|| services.config.default_submission_type.clone())
+        .ok_or_else(|| AppError::Validation(errors("1003", "INVALID_REQUEST_BODY: missing field `submissionType`")))?;
+
+    let external_reference = body.external_reference.clone().filter(|r| !r.is_empty());
+    if external_reference.as_ref().is_some_and(|r| r.chars().count() > MAX_EXTERNAL_REFERENCE_LENGTH) {
+        return Err(AppError::Validation(errors(
+            "1003",
+            format!("INVALID_EXTERNAL_REFERENCE: max {} characters", MAX_EXTERNAL_REFERENCE_LENGTH),
+        )));
+    }
+
+    // Retries carrying the same key get the first attempt's submission back
+    let idempotency_key = match req.headers().get("Idempotency-Key") {
+        None => None,
+        Some(value) => match value.to_str() {
+            Ok(key) if !key.trim().is_empty() && key.chars().count() <= MAX_IDEMPOTENCY_KEY_LENGTH => Some(key.to_string()),
+            _ => {
+                return Err(AppError::Validation(errors(
+                    "1003",
+                    format!("INVALID_IDEMPOTENCY_KEY: 1 to {} visible ASCII characters", MAX_IDEMPOTENCY_KEY_LENGTH),
+                )));
+            }
+        },
+    };
+
+    let submission_service = services.submission_service();
+    let request = PresignedUrlsRequest {
+        submission_type,
+        nfc_identifier: body.nfc_identifier.clone(),
+        external_reference,
+        expiry_in_seconds: body.expiry_in_seconds,
+        callback_url: body.callback_url.clone(),
+        idempotency_key,
+    };
+
+    if body.dry_run {
+        let response = submission_service
+            .validate_presigned_urls_request(request)
+            .await
+            .map_err(presigned_urls_error)?;
+        return Ok(HttpResponse::Ok().json(ApiResponse {
             success: true,
             data: Some(response),
             errors: None,
-        }),
-        Err(errors) => HttpResponse::InternalServerError().json(ApiResponse::<()> {
-            success: false,
-            data: None,
-            errors: Some(errors),
-        }),
+        }));
+    }
+
+    // Higher-risk users are matched more strictly. The tier is set by an admin, never by the client.
+    let risk_tier = UserRepository::new(services.pool.clone(), services.read_pool.0.clone())
+        .find_risk_tier(user.user_id)
+        .await
+        .map_err(|e| AppError::Internal(errors("1002", e.to_string())))?;
+
+    // Each presigned URL request starts its own session
+    let session_id = Uuid::new_v4().to_string();
+    let user_id = user.user_id.to_string();
+
+    let response = submission_service
+        .generate_presigned_urls(session_id, user_id, risk_tier, request)
+        .await
+        .map_err(presigned_urls_error)?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse {
+        success: true,
+        data: Some(response),
+        errors: None,
+    }))
+}
+
+fn presigned_urls_error(errors: Vec<ApiError>) -> AppError {
+    if errors.iter().any(|e| e.code == "1003" || e.code == "1007") {
+        AppError::Validation(errors)
+    } else if errors.iter().any(|e| e.code == "1005" || e.code == "1016") {
+        AppError::Unprocessable(errors)
+    } else if errors.iter().any(|e| e.code == "1018") {
+        AppError::Conflict(errors)
+    } else {
+        AppError::Internal(errors)
     }
 }
 
@@ -125,145 +249,747 @@ async fn presigned_urls(
 async fn face_match(
     face_match_service: web::Data<FaceMatchService>,
     body: Result<web::Json<FaceMatchBody>, actix_web::Error>,
-) -> HttpResponse {
+) -> Result<HttpResponse, AppError> {
     let body = match body {
         Ok(b) => b,
-        Err(e) => {
-            return HttpResponse::BadRequest().json(ApiResponse::<()> {
-                success: false,
-                data: None,
-                errors: Some(vec![ApiError {
-                    entity: "SOCIO_ECHO_BE".to_string(),
-                    code: "1003".to_string(),
-                    cause: format!("INVALID_REQUEST_BODY: {}", e),
-                }]),
-            });
-        }
+        Err(e) => return Ok(json_body::rejection(e)),
     };
 
-    match face_match_service
+    if Uuid::parse_str(&body.submission_id).is_err() {
+        return Err(invalid_submission_id());
+    }
+
+    let response = face_match_service
         .compare_faces(
             body.image1_url.clone(),
             body.image2_url.clone(),
             body.submission_id.clone(),
+            None,
         )
         .await
-    {
-        Ok(response) => HttpResponse::Ok().json(ApiResponse {
-            success: true,
-            data: Some(response),
-            errors: None,
-        }),
-        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::<()> {
-            success: false,
-            data: None,
-            errors: Some(vec![ApiError {
-                entity: "SOCIO_ECHO_BE".to_string(),
-                code: "1006".to_string(),
-                cause: e.to_string(),
-            }]),
-        }),
+        .map_err(|e| {
+            let errors = errors(face_match_service::error_code(&e), e.to_string());
+            match e.downcast_ref::<FaceMatchUnavailable>() {
+                Some(unavailable) => AppError::Upstream {
+                    errors,
+                    retry_after_secs: Some(unavailable.retry_after.as_secs() + 1),
+                },
+                None => AppError::Internal(errors),
+            }
+        })?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse {
+        success: true,
+        data: Some(response),
+        errors: None,
+    }))
+}
+
+// Registered in main with the larger submission body limit
+pub async fn face_match_batch(
+    _user: VerifiedUser,
+    face_match_service: web::Data<FaceMatchService>,
+    body: Result<web::Json<FaceMatchBatchBody>, actix_web::Error>,
+) -> Result<HttpResponse, AppError> {
+    let body = match body {
+        Ok(b) => b.into_inner(),
+        Err(e) => return Ok(json_body::rejection(e)),
+    };
+
+    if body.pairs.len() > MAX_BATCH_SIZE {
+        return Err(AppError::Validation(errors("1003", format!("BATCH_SIZE_EXCEEDED: max {}", MAX_BATCH_SIZE))));
+    }
+
+    if body.pairs.iter().any(|pair| Uuid::parse_str(&pair.submission_id).is_err()) {
+        return Err(invalid_submission_id());
     }
+
+    let requests = body
+        .pairs
+        .into_iter()
+        .map(|pair| FaceMatchRequest {
+            image1_url: pair.image1_url,
+            image2_url: pair.image2_url,
+            submission_id: pair.submission_id,
+            metadata: None,
+        })
+        .collect();
+
+    // A failing pair only fails its own entry, never the whole batch
+    let results = face_match_service
+        .compare_faces_batch(requests)
+        .await
+        .into_iter()
+        .map(|(submission_id, result)| match result {
+            Ok(response) => FaceMatchBatchItem {
+                submission_id,
+                success: true,
+                data: Some(response),
+                error: None,
+            },
+            Err(e) => FaceMatchBatchItem {
+                submission_id,
+                success: false,
+                data: None,
+                error: Some(ApiError {
+                    entity: "SOCIO_ECHO_BE".to_string(),
+                    code: face_match_service::error_code(&e).to_string(),
+                    cause: e.to_string(),
+                }),
+            },
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(ApiResponse {
+        success: true,
+        data: Some(FaceMatchBatchResponse { results }),
+        errors: None,
+    }))
 }
 
 #[actix_web::put("/submissions/urls")]
 async fn process_submission(
-    pool: web::Data<sqlx::PgPool>,
-    minio_service: web::Data<MinioService>,
-    face_match_service: web::Data<FaceMatchService>,
-    metrics: web::Data<MetricsService>,
+    user: VerifiedUser,
+    services: web::Data<AppServices>,
     body: Result<web::Json<ProcessSubmissionBody>, actix_web::Error>,
-) -> HttpResponse {
+) -> Result<HttpResponse, AppError> {
     let body = match body {
         Ok(b) => b,
-        Err(e) => {
-            return HttpResponse::BadRequest().json(ApiResponse::<()> {
-                success: false,
-                data: None,
-                errors: Some(vec![ApiError {
-                    entity: "SOCIO_ECHO_BE".to_string(),
-                    code: "1003".to_string(),
-                    cause: format!("INVALID_REQUEST_BODY: {}", e),
-                }]),
-            });
-        }
+        Err(e) => return Ok(json_body::rejection(e)),
     };
 
-    let submission_service = SubmissionService::new(
-        minio_service.as_ref().clone(),
-        SubmissionRepository::new(pool.as_ref().clone()),
-        metrics.as_ref().clone()
-    );
+    if Uuid::parse_str(&body.submission_id).is_err() {
+        return Err(invalid_submission_id());
+    }
 
-    match submission_service
+    let submission_service = services.submission_service();
+
+    let response = submission_service
         .process_submission(
+            Some(&user.user_id.to_string()),
             body.submission_id.clone(),
-            face_match_service.as_ref().clone()
+            services.face_match.clone(),
+            services.webhooks.clone(),
         )
         .await
-    {
-        Ok(response) => HttpResponse::Ok().json(ApiResponse {
+        .map_err(|errors| {
+            if errors.iter().any(|e| e.cause == "SUBMISSION_NOT_FOUND") {
+                AppError::NotFound(errors)
+            } else if errors.iter().any(|e| e.code == "1004" || e.code == "1005") {
+                AppError::Unprocessable(errors)
+            } else if errors.iter().any(|e| e.code == "1013") {
+                AppError::Conflict(errors)
+            } else if errors.iter().any(|e| e.code == "1021") {
+                AppError::Upstream { errors, retry_after_secs: None }
+            } else {
+                AppError::Internal(errors)
+            }
+        })?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse {
+        success: true,
+        data: Some(response),
+        errors: None,
+    }))
+}
+
+#[actix_web::get("/submissions/status")]
+async fn get_submission_status(
+    req: HttpRequest,
+    services: web::Data<AppServices>,
+    query: web::Query<GetSubmissionStatusQuery>,
+) -> Result<HttpResponse, AppError> {
+    let submission_type = match submission_type_registry::find(&query.submission_type) {
+        Some(config) if config.status_queryable => config.submission_type.clone(),
+        _ => return Err(AppError::Validation(errors("1003", "INVALID_SUBMISSION_TYPE"))),
+    };
+
+    let nfc_identifier = query.nfc_identifier.clone();
+
+    let submission_service = services.submission_service();
+
+    // No submission yet for the identifier is an expected answer for pollers, not a failure
+    let (status, documents, updated_at) = submission_service
+        .get_submission_status(submission_type, nfc_identifier)
+        .await
+        .map_err(|errors| {
+            if errors.iter().any(|e| e.code == "1004") {
+                AppError::NotFound(errors)
+            } else {
+                AppError::Internal(errors)
+            }
+        })?;
+
+    // Pollers send back the ETag they have, so an unchanged submission costs no body
+    let etag = etag::from_updated_at(updated_at);
+    if etag::matches_if_none_match(&req, &etag) {
+        return Ok(HttpResponse::NotModified()
+            .insert_header((header::ETAG, etag))
+            .finish());
+    }
+
+    let response = GetSubmissionStatusResponse {
+        status,
+        submission_status: services.config.submission_status_legacy_field.then(|| legacy_submission_status(status)),
+        documents,
+    };
+    Ok(HttpResponse::Ok()
+        .insert_header((header::ETAG, etag))
+        .json(ApiResponse {
             success: true,
             data: Some(response),
             errors: None,
-        }),
-        Err(errors) => {
-            let status_code = if errors.iter().any(|e| e.code == "1004") {
-                HttpResponse::UnprocessableEntity
+        }))
+}
+
+// Erases one of the caller's own submissions and its stored documents
+#[actix_web::delete("/submissions/{id}")]
+async fn erase_submission(
+    user: AuthenticatedUser,
+    services: web::Data<AppServices>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    let Ok(submission_id) = Uuid::parse_str(&path.into_inner()) else {
+        return Err(invalid_submission_id());
+    };
+
+    let erasure_service = services.erasure_service();
+
+    let summary = erasure_service
+        .erase_submission(user.user_id, submission_id)
+        .await
+        .map_err(|e| AppError::Internal(errors("1002", e.to_string())))?
+        .ok_or_else(|| AppError::NotFound(errors("1004", "SUBMISSION_NOT_FOUND")))?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse {
+        success: true,
+        data: Some(summary),
+        errors: None,
+    }))
+}
+
+// Alternative to the presigned upload URL for clients that can't reach storage directly.
+// Takes a multipart body whose `file` part is the document image; other parts are ignored.
+#[actix_web::post("/submissions/{id}/documents/{kind}")]
+async fn upload_document(
+    user: VerifiedUser,
+    services: web::Data<AppServices>,
+    path: web::Path<(String, String)>,
+    payload: Multipart,
+) -> Result<HttpResponse, AppError> {
+    let (submission_id, kind) = path.into_inner();
+    let Ok(submission_id) = Uuid::parse_str(&submission_id) else {
+        return Err(invalid_submission_id());
+    };
+    let Ok(document) = kind.parse::<DocumentType>() else {
+        return Err(AppError::Validation(errors("1003", format!("INVALID_DOCUMENT_TYPE: {}", kind))));
+    };
+
+    let (content_type, content) = read_file_part(payload, services.config.max_document_upload_bytes).await?;
+
+    let submission_service = services.submission_service();
+
+    let response = submission_service
+        .upload_document(&user.user_id.to_string(), submission_id, document, content_type.as_deref(), content)
+        .await
+        .map_err(|errors| {
+            if errors.iter().any(|e| e.code == "1003" || e.code == "1007") {
+                AppError::Validation(errors)
+            } else if errors.iter().any(|e| e.code == "1004") {
+                AppError::NotFound(errors)
+            } else if errors.iter().any(|e| e.code == "1020") {
+                AppError::Conflict(errors)
             } else {
-                HttpResponse::InternalServerError
-            };
-            
-            status_code().json(ApiResponse::<()> {
-                success: false,
-                data: None,
-                errors: Some(errors),
-            })
+                AppError::Internal(errors)
+            }
+        })?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse {
+        success: true,
+        data: Some(response),
+        errors: None,
+    }))
+}
+
+// Content type and bytes of the `file` part, read chunk by chunk so an oversized upload is
+// cut off at `limit` instead of being buffered whole
+async fn read_file_part(mut payload: Multipart, limit: usize) -> Result<(Option<String>, Vec<u8>), AppError> {
+    let invalid = |e: MultipartError| AppError::Validation(errors("1003", format!("INVALID_MULTIPART_BODY: {}", e)));
+
+    while let Some(field) = payload.next().await {
+        let mut field = field.map_err(invalid)?;
+        if field.name() != Some("file") {
+            continue;
+        }
+
+        let content_type = field.content_type().map(|mime| mime.essence_str().to_string());
+        let mut content = Vec::new();
+        while let Some(chunk) = field.next().await {
+            let chunk = chunk.map_err(invalid)?;
+            if content.len() + chunk.len() > limit {
+                return Err(AppError::PayloadTooLarge(errors(
+                    "1007",
+                    format!("DOCUMENT_TOO_LARGE: max {} bytes", limit),
+                )));
+            }
+            content.extend_from_slice(&chunk);
         }
+        return Ok((content_type, content));
     }
+
+    Err(AppError::Validation(errors("1003", "INVALID_MULTIPART_BODY: missing file part")))
 }
 
-#[actix_web::get("/submissions/status")]
-async fn get_submission_status(
-    pool: web::Data<sqlx::PgPool>,
-    minio_service: web::Data<MinioService>,
-    metrics: web::Data<MetricsService>,
-    query: web::Query<GetSubmissionStatusQuery>,
-) -> HttpResponse {
+#[actix_web::get("/submissions/by-reference")]
+async fn find_by_external_reference(
+    user: AuthenticatedUser,
+    services: web::Data<AppServices>,
+    query: Result<web::Query<FindByExternalReferenceQuery>, actix_web::Error>,
+) -> Result<HttpResponse, AppError> {
+    let query = query.map_err(|e| AppError::Validation(errors("1003", format!("INVALID_QUERY_PARAMS: {}", e))))?;
+    let page = Page::from_query(query.limit, query.offset).map_err(|cause| AppError::Validation(errors("1003", cause)))?;
+
+    let submission_service = services.submission_service();
 
-    let submission_type = match query.submission_type.as_str() {
-        "KYC" => SubmissionType::KYC,
-        _ => return HttpResponse::BadRequest().json(ApiResponse::<()> {
+    // Errors keep the paginated shape, with a null meta
+    Ok(match submission_service
+        .find_by_external_reference(&query.external_reference, &user.user_id.to_string(), page)
+        .await {
+        Ok((submissions, total)) => HttpResponse::Ok().json(PaginatedResponse {
+            success: true,
+            meta: Some(page.meta(total, submissions.len())),
+            data: Some(submissions),
+            errors: None,
+        }),
+        Err(errors) => HttpResponse::InternalServerError().json(PaginatedResponse::<()> {
             success: false,
             data: None,
-            errors: Some(vec![ApiError {
-                entity: "SOCIO_ECHO_BE".to_string(),
-                code: "1003".to_string(),
-                cause: "INVALID_SUBMISSION_TYPE".to_string(),
-            }]),
+            errors: Some(errors),
+            meta: None,
+        }),
+    })
+}
+
+// Lets clients render the capture screens a submission type needs
+#[actix_web::get("/submission-types/{type}/documents")]
+async fn get_submission_type_documents(path: web::Path<String>) -> Result<HttpResponse, AppError> {
+    let type_config = submission_type_registry::find(&path.into_inner())
+        .ok_or_else(|| AppError::NotFound(errors("1004", "INVALID_SUBMISSION_TYPE")))?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse {
+        success: true,
+        data: Some(SubmissionTypeDocumentsResponse {
+            submission_type: type_config.submission_type.to_string(),
+            upload_documents: type_config.upload_documents.to_vec(),
+            stored_documents: submission_type_registry::STORED_DOCUMENTS.to_vec(),
         }),
+        errors: None,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        dev::HttpServiceFactory,
+        http::StatusCode,
+        test::{call_service, init_service, read_body_json, TestRequest},
+        App,
     };
+    use base64::{engine::general_purpose::STANDARD, Engine as _};
+    use serde_json::{json, Value};
+    use std::sync::Arc;
 
-    let nfc_identifier = query.nfc_identifier.clone();
+    use super::*;
+    use crate::{
+        commons::minio_service::tests::{fake_s3, FakeS3},
+        middleware::auth::tests::bearer,
+        services::app_services::{self, AppServices},
+        submissions::{
+            submission_repository::{NewSubmission, SubmissionRepository},
+            submission_status::tests::ALL,
+        },
+    };
 
-    let submission_service = SubmissionService::new(
-        minio_service.as_ref().clone(),
-        SubmissionRepository::new(pool.as_ref().clone()),
-        metrics.as_ref().clone()
-    );
+    // Status and JSON body of GET `uri` against `service`
+    async fn get_json<F: HttpServiceFactory + 'static>(service: F, uri: &str) -> (StatusCode, Value) {
+        let app = init_service(App::new().service(service)).await;
+        let response = call_service(&app, TestRequest::get().uri(uri).to_request()).await;
+        let status = response.status();
+        (status, read_body_json(response).await)
+    }
 
-    match submission_service.get_submission_status(submission_type, nfc_identifier).await {
-        Ok(response) => HttpResponse::Ok().json(ApiResponse {
-            success: true,
-            data: Some(response),
-            errors: None,
-        }),
-        Err(errors) => {
-            HttpResponse::InternalServerError().json(ApiResponse::<()> {
-                success: false,
-                data: None,
-                errors: Some(errors),
+    // Status and JSON body of `request`, sent as user 1, against `service`
+    async fn call<F: HttpServiceFactory + 'static>(services: AppServices, service: F, request: TestRequest) -> (StatusCode, Value) {
+        let authorization = bearer(&services.config, 1);
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(services.config.clone()))
+                .app_data(web::Data::new(services.pool.clone()))
+                .app_data(web::Data::new(services.read_pool.clone()))
+                .app_data(web::Data::new(services))
+                .service(service),
+        )
+        .await;
+        let request = request.insert_header((header::AUTHORIZATION, authorization));
+        let response = call_service(&app, request.to_request()).await;
+        let status = response.status();
+        (status, read_body_json(response).await)
+    }
+
+    async fn seed(services: &AppServices, submission_type: &str, nfc_identifier: &str, submission_data: Value) -> Uuid {
+        seed_as(services, "1", submission_type, nfc_identifier, submission_data).await
+    }
+
+    async fn seed_as(
+        services: &AppServices,
+        user_id: &str,
+        submission_type: &str,
+        nfc_identifier: &str,
+        submission_data: Value,
+    ) -> Uuid {
+        let submission_id = Uuid::new_v4();
+        SubmissionRepository::new(services.pool.clone(), services.pool.clone())
+            .create(NewSubmission {
+                submission_id,
+                submission_type,
+                session_id: &submission_id.to_string(),
+                user_id,
+                status: "INITIATED",
+                submission_data,
+                request_data: json!({}),
+                nfc_identifier: nfc_identifier.to_string(),
+                external_reference: None,
+                risk_tier: None,
+                idempotency_key: None,
+                idempotency_request_hash: None,
+                callback_url: None,
+            })
+            .await
+            .unwrap();
+        submission_id
+    }
+
+    // Data of a submission whose `documents` were uploaded
+    fn uploaded(documents: &[&str]) -> Value {
+        let documents: serde_json::Map<String, Value> = documents
+            .iter()
+            .map(|document| {
+                let reference = Uuid::new_v4();
+                let data = json!({
+                    "documentName": format!("{}_{}", reference, document),
+                    "documentReference": reference.to_string(),
+                    "uploadStatus": "UPLOADED",
+                });
+                (document.to_string(), data)
             })
+            .collect();
+        Value::Object(documents)
+    }
+
+    // Status and first error code of a status query for an identifier nothing was submitted
+    // for, answered from `pool`
+    async fn submission_status_error(pool: sqlx::PgPool) -> (StatusCode, Value) {
+        let services = app_services::tests::services(pool, &[]);
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(services))
+                .service(get_submission_status),
+        )
+        .await;
+        let request = TestRequest::get().uri("/submissions/status?submissionType=KYC&nfcIdentifier=unknown");
+        let response = call_service(&app, request.to_request()).await;
+        let status = response.status();
+        let body: Value = read_body_json(response).await;
+        (status, body["errors"][0]["code"].clone())
+    }
+
+    #[sqlx::test]
+    async fn unknown_identifier_is_not_found(pool: sqlx::PgPool) {
+        assert_eq!(submission_status_error(pool).await, (StatusCode::NOT_FOUND, json!("1004")));
+    }
+
+    #[sqlx::test]
+    async fn unchanged_status_is_not_modified_until_the_submission_changes(pool: sqlx::PgPool) {
+        let services = app_services::tests::services(pool, &[]);
+        let submission_id = seed(&services, "KYC", "nfc-1", uploaded(&["SELFIE"])).await;
+        let app = init_service(App::new().app_data(web::Data::new(services.clone())).service(get_submission_status)).await;
+        let status = |if_none_match: Option<&str>| {
+            let request = TestRequest::get().uri("/submissions/status?submissionType=KYC&nfcIdentifier=nfc-1");
+            match if_none_match {
+                Some(etag) => request.insert_header((header::IF_NONE_MATCH, etag.to_string())),
+                None => request,
+            }
+            .to_request()
+        };
+        let etag_of = |response: &actix_web::dev::ServiceResponse| {
+            response.headers().get(header::ETAG).unwrap().to_str().unwrap().to_string()
+        };
+
+        let response = call_service(&app, status(None)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let etag = etag_of(&response);
+
+        for if_none_match in [etag.clone(), format!("W/{}", etag), format!("\"other\", {}", etag)] {
+            let response = call_service(&app, status(Some(&if_none_match))).await;
+            assert_eq!(response.status(), StatusCode::NOT_MODIFIED, "{if_none_match}");
+            assert_eq!(etag_of(&response), etag);
+        }
+
+        SubmissionRepository::new(services.pool.clone(), services.pool.clone())
+            .update_submission_data(&submission_id.to_string(), &uploaded(&["KTP", "SELFIE"]))
+            .await
+            .unwrap();
+        let response = call_service(&app, status(Some(&etag))).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_ne!(etag_of(&response), etag);
+        let body: Value = read_body_json(response).await;
+        assert_eq!(body["data"]["documents"]["KTP"], "UPLOADED");
+    }
+
+    #[actix_web::test]
+    async fn database_failure_is_an_internal_error() {
+        let unreachable = sqlx::postgres::PgPoolOptions::new()
+            .acquire_timeout(std::time::Duration::from_millis(500))
+            .connect_lazy("postgres://127.0.0.1:1/unused")
+            .unwrap();
+        assert_eq!(
+            submission_status_error(unreachable).await,
+            (StatusCode::INTERNAL_SERVER_ERROR, json!("1002"))
+        );
+    }
+
+    #[actix_web::test]
+    async fn lists_the_documents_of_each_submission_type() {
+        let (status, body) = get_json(get_submission_type_documents, "/submission-types/KYC/documents").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"]["uploadDocuments"], json!(["KTP", "SELFIE"]));
+        assert_eq!(body["data"]["storedDocuments"], json!(["NFC"]));
+
+        let (status, body) = get_json(get_submission_type_documents, "/submission-types/ON_DEMAND/documents").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"]["uploadDocuments"], json!(["SELFIE"]));
+
+        let (status, _) = get_json(get_submission_type_documents, "/submission-types/UNKNOWN/documents").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    // Every pair is a call to the provider, so anonymous callers are turned away first
+    #[actix_web::test]
+    async fn face_match_batch_requires_a_logged_in_user() {
+        let app = init_service(App::new().route("/submissions/face-match/batch", web::post().to(face_match_batch))).await;
+        let pair = json!({
+            "image1_url": "http://example.com/a.jpg",
+            "image2_url": "http://example.com/b.jpg",
+            "submission_id": Uuid::new_v4().to_string(),
+        });
+        let request = TestRequest::post().uri("/submissions/face-match/batch").set_json(json!({ "pairs": [pair] }));
+
+        let response = call_service(&app, request.to_request()).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let body: Value = read_body_json(response).await;
+        assert_eq!(body["errors"][0]["code"], "1008");
+    }
+
+    #[test]
+    fn legacy_status_of_every_status() {
+        let legacy: Vec<_> = ALL.iter().map(|status| (*status, legacy_submission_status(*status))).collect();
+        assert_eq!(
+            legacy,
+            vec![
+                (SubmissionStatus::Initiated, "NOT_KYC"),
+                (SubmissionStatus::Processing, "NOT_KYC"),
+                (SubmissionStatus::PendingRetry, "NOT_KYC"),
+                (SubmissionStatus::RequiresReview, "NOT_KYC"),
+                (SubmissionStatus::Approved, "KYC"),
+                (SubmissionStatus::Rejected, "NOT_KYC"),
+                (SubmissionStatus::Deleted, "NOT_KYC"),
+            ]
+        );
+    }
+
+    // ON_DEMAND compares against the identifier's approved selfie, so with none there is
+    // nothing to compare to. That is not the submission going missing.
+    #[sqlx::test]
+    async fn on_demand_without_an_approved_reference_is_unprocessable(pool: sqlx::PgPool) {
+        let services = app_services::tests::services_with_minio(pool, &[], &fake_s3(Arc::new(FakeS3::default())));
+        let submission_id = seed(&services, "ON_DEMAND", "never-approved", uploaded(&["SELFIE", "NFC"])).await;
+
+        let request = TestRequest::put()
+            .uri("/submissions/urls")
+            .set_json(json!({ "submissionId": submission_id.to_string() }));
+        let (status, body) = call(services, process_submission, request).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["errors"][0]["code"], "1004");
+        assert_eq!(body["errors"][0]["cause"], "APPROVED_REFERENCE_NOT_FOUND");
+    }
+
+    #[sqlx::test]
+    async fn erases_only_the_callers_own_submission(pool: sqlx::PgPool) {
+        let s3 = Arc::new(FakeS3::default());
+        let services = app_services::tests::services_with_minio(pool, &[], &fake_s3(s3.clone()));
+        let own = seed(&services, "KYC", "own", uploaded(&["SELFIE", "NFC"])).await;
+        let someone_elses = seed_as(&services, "2", "KYC", "someone-elses", uploaded(&["SELFIE", "NFC"])).await;
+
+        let erase = |submission_id: Uuid| TestRequest::delete().uri(&format!("/submissions/{}", submission_id));
+        let (status, body) = call(services.clone(), erase_submission, erase(someone_elses)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["errors"][0]["cause"], "SUBMISSION_NOT_FOUND");
+        assert!(s3.requests("DELETE").is_empty());
+
+        let (status, body) = call(services.clone(), erase_submission, erase(own)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"]["objectsDeleted"], 2);
+        assert_eq!(s3.requests("DELETE").len(), 2);
+
+        // Already erased
+        let (status, _) = call(services, erase_submission, erase(own)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[sqlx::test]
+    async fn malformed_submission_ids_are_bad_requests(pool: sqlx::PgPool) {
+        let services = app_services::tests::services(pool, &[]);
+
+        let (status, body) = call(services.clone(), erase_submission, TestRequest::delete().uri("/submissions/42")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["errors"][0]["cause"], "INVALID_SUBMISSION_ID");
+
+        let request = TestRequest::put().uri("/submissions/urls").set_json(json!({ "submissionId": "not-a-uuid" }));
+        let (status, body) = call(services.clone(), process_submission, request).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["errors"][0]["cause"], "INVALID_SUBMISSION_ID");
+
+        // A well-formed id nobody has is still not found
+        let (status, _) = call(services, erase_submission, TestRequest::delete().uri(&format!("/submissions/{}", Uuid::new_v4()))).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    // Dry runs, so nothing is asked of MinIO
+    #[sqlx::test]
+    async fn an_omitted_submission_type_falls_back_to_the_configured_default(pool: sqlx::PgPool) {
+        let jpeg = STANDARD.encode([0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10, b'J', b'F', b'I', b'F']);
+        let presign = |body: Value| TestRequest::post().uri("/submissions/presigned-urls").set_json(body);
+        let resource = || web::resource("/submissions/presigned-urls").route(web::post().to(presigned_urls));
+        let omitted = json!({ "nfcIdentifier": jpeg, "dryRun": true });
+        let explicit = json!({ "submissionType": "KYC", "nfcIdentifier": jpeg, "dryRun": true });
+
+        let with_default = app_services::tests::services(pool.clone(), &[("DEFAULT_SUBMISSION_TYPE", "KYC_PASSPORT")]);
+        let (status, body) = call(with_default.clone(), resource(), presign(omitted.clone())).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"]["submissionType"], "KYC_PASSPORT");
+        let (status, body) = call(with_default, resource(), presign(explicit.clone())).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"]["submissionType"], "KYC");
+
+        // Without a default the type stays required
+        let without_default = app_services::tests::services(pool, &[]);
+        let (status, body) = call(without_default.clone(), resource(), presign(omitted)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["errors"][0]["cause"], "INVALID_REQUEST_BODY: missing field `submissionType`");
+        let (status, _) = call(without_default, resource(), presign(explicit)).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[sqlx::test]
+    async fn external_references_are_stored_and_found_per_user(pool: sqlx::PgPool) {
+        let services = app_services::tests::services_with_minio(pool, &[], &fake_s3(Arc::new(FakeS3::default())));
+        let jpeg = STANDARD.encode([0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10, b'J', b'F', b'I', b'F']);
+        let resource = || web::resource("/submissions/presigned-urls").route(web::post().to(presigned_urls));
+        let presign = |reference: &str| {
+            TestRequest::post()
+                .uri("/submissions/presigned-urls")
+                .set_json(json!({ "submissionType": "KYC", "nfcIdentifier": jpeg, "externalReference": reference }))
+        };
+
+        let (status, body) = call(services.clone(), resource(), presign("order-42")).await;
+        assert_eq!(status, StatusCode::OK);
+        let created = body["data"]["submissionId"].as_str().unwrap().to_string();
+        let stored: Option<String> = sqlx::query_scalar("SELECT external_reference FROM submissions WHERE submission_id = $1")
+            .bind(Uuid::parse_str(&created).unwrap())
+            .fetch_one(&services.pool)
+            .await
+            .unwrap();
+        assert_eq!(stored.as_deref(), Some("order-42"));
+
+        // Another user's submission with the same reference stays out of the results
+        let other = seed_as(&services, "2", "KYC", "nfc-2", json!({})).await;
+        sqlx::query("UPDATE submissions SET external_reference = 'order-42' WHERE submission_id = $1")
+            .bind(other)
+            .execute(&services.pool)
+            .await
+            .unwrap();
+        seed(&services, "KYC", "nfc-3", json!({})).await;
+
+        let find = |reference: &str| TestRequest::get().uri(&format!("/submissions/by-reference?externalReference={}", reference));
+        let (status, body) = call(services.clone(), find_by_external_reference, find("order-42")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["meta"]["total"], 1);
+        assert_eq!(body["data"][0]["submissionId"], created);
+        assert_eq!(body["data"][0]["externalReference"], "order-42");
+
+        let (_, body) = call(services.clone(), find_by_external_reference, find("order-43")).await;
+        assert_eq!(body["data"], json!([]));
+
+        let (status, _) = call(services.clone(), find_by_external_reference, TestRequest::get().uri("/submissions/by-reference")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, body) = call(services, resource(), presign(&"x".repeat(MAX_EXTERNAL_REFERENCE_LENGTH + 1))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body["errors"][0]["cause"].as_str().unwrap().starts_with("INVALID_EXTERNAL_REFERENCE"));
+    }
+
+    #[sqlx::test]
+    async fn a_dry_run_validates_without_creating_anything(pool: sqlx::PgPool) {
+        let s3 = Arc::new(FakeS3::default());
+        let services = app_services::tests::services_with_minio(pool, &[], &fake_s3(s3.clone()));
+        let jpeg = STANDARD.encode([0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10, b'J', b'F', b'I', b'F']);
+        let resource = || web::resource("/submissions/presigned-urls").route(web::post().to(presigned_urls));
+        let dry_run = |nfc_identifier: &str| {
+            TestRequest::post().uri("/submissions/presigned-urls").set_json(json!({
+                "submissionType": "KYC",
+                "nfcIdentifier": nfc_identifier,
+                "externalReference": "order-7",
+                "dryRun": true,
+            }))
+        };
+
+        let (status, body) = call(services.clone(), resource(), dry_run(&jpeg)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"]["dryRun"], true);
+        assert_eq!(body["data"]["uploadDocuments"], json!(["KTP", "SELFIE"]));
+        assert_eq!(body["data"]["storedDocuments"], json!(["NFC"]));
+        assert_eq!(body["data"]["nfcSizeBytes"], 10);
+        assert_eq!(body["data"]["externalReference"], "order-7");
+
+        // Validation still runs
+        let (status, _) = call(services.clone(), resource(), dry_run("not base64!")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let submissions: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM submissions").fetch_one(&services.pool).await.unwrap();
+        assert_eq!(submissions, 0);
+        assert!(s3.requests.lock().unwrap().is_empty());
+    }
+
+    #[sqlx::test]
+    async fn bad_nfc_payloads_and_minio_outages_are_errors_not_panics(pool: sqlx::PgPool) {
+        // Nothing listens on the MinIO endpoint
+        let services = app_services::tests::services(pool, &[]);
+        let resource = || web::resource("/submissions/presigned-urls").route(web::post().to(presigned_urls));
+        let presign = |nfc_identifier: String| {
+            TestRequest::post()
+                .uri("/submissions/presigned-urls")
+                .set_json(json!({ "submissionType": "KYC", "nfcIdentifier": nfc_identifier }))
+        };
+
+        for garbage in ["%%% not base64 %%%".to_string(), STANDARD.encode(b"not an image")] {
+            let (status, body) = call(services.clone(), resource(), presign(garbage.clone())).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{garbage}");
+            assert_eq!(body["success"], false);
+            assert_eq!(body["errors"][0]["code"], "1007");
         }
+
+        let jpeg = STANDARD.encode([0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10, b'J', b'F', b'I', b'F']);
+        let (status, body) = call(services, resource(), presign(jpeg)).await;
+        assert!(status.is_server_error(), "{status}");
+        assert_eq!(body["success"], false);
     }
 }
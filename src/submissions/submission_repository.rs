@@ -1,27 +1,38 @@
-use sqlx::PgPool;
+use chrono::{DateTime, Utc};
+use sqlx::{PgConnection, PgPool, Postgres, Transaction};
 use uuid::Uuid;
 use serde_json::{Value, json};
 
+use crate::submissions::dto::submission_summary::SubmissionSummary;
+
+// A submission row as created when upload URLs are handed out
+pub struct NewSubmission<'a> {
+    pub submission_id: Uuid,
+    pub submission_type: &'a str,
+    pub session_id: &'a str,
+    pub user_id: &'a str,
+    pub status: &'a str,
+    pub submission_data: Value,
+    pub request_data: Value,
+    pub nfc_identifier: String,
+    pub external_reference: Option<String>,
+    pub risk_tier: Option<String>,
+    pub idempotency_key: Option<&'a str>,
+    pub idempotency_request_hash: Option<&'a str>,
+    pub callback_url: Option<&'a str>,
+}
+
 pub struct SubmissionRepository {
     pool: PgPool,
+    read_pool: PgPool,
 }
 
 impl SubmissionRepository {
-    pub fn new(pool: PgPool) -> Self {
-        Self { pool }
+    pub fn new(pool: PgPool, read_pool: PgPool) -> Self {
+        Self { pool, read_pool }
     }
 
-    pub async fn create(
-        &self,
-        submission_id: Uuid,
-        submission_type: &str,
-        session_id: &str,
-        user_id: &str,
-        status: &str,
-        submission_data: Value,
-        request_data: Value,
-        nfc_identifier: String,
-    ) -> Result<(), sqlx::Error> {
+    pub async fn create(&self, new: NewSubmission<'_>) -> Result<(), sqlx::Error> {
         sqlx::query!(
             r#"
             INSERT INTO submissions (
@@ -32,18 +43,28 @@ impl SubmissionRepository {
                 status,
                 submission_data,
                 request_data,
-                nfc_identifier
+                nfc_identifier,
+                external_reference,
+                risk_tier,
+                idempotency_key,
+                idempotency_request_hash,
+                callback_url
             )
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
             "#,
-            submission_id,
-            submission_type,
-            session_id,
-            user_id,
-            status,
-            submission_data as _,
-            request_data as _,
-            nfc_identifier
+            new.submission_id,
+            new.submission_type,
+            new.session_id,
+            new.user_id,
+            new.status,
+            new.submission_data as _,
+            new.request_data as _,
+            new.nfc_identifier,
+            new.external_reference,
+            new.risk_tier,
+            new.idempotency_key,
+            new.idempotency_request_hash,
+            new.callback_url
         )
         .execute(&self.pool)
         .await?;
@@ -51,16 +72,94 @@ impl SubmissionRepository {
         Ok(())
     }
 
-    pub async fn find_submission_by_id(&self, submission_id: &str) -> Result<Option<(String, String, Value)>, sqlx::Error> {
+    pub async fn find_callback_url(&self, submission_id: Uuid) -> Result<Option<String>, sqlx::Error> {
+        let callback_url = sqlx::query_scalar!(
+            r#"
+            SELECT callback_url
+            FROM submissions
+            WHERE submission_id = $1
+            "#,
+            submission_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(callback_url.flatten())
+    }
+
+    // Submission type, status and submission data of a live submission the user owns.
+    // Read from the primary, since uploads can follow creation immediately.
+    pub async fn find_owned_submission(
+        &self,
+        submission_id: Uuid,
+        user_id: &str,
+    ) -> Result<Option<(String, String, Value)>, sqlx::Error> {
+        let row = sqlx::query!(
+            r#"
+            SELECT submission_type, status, submission_data
+            FROM submissions
+            WHERE submission_id = $1 AND user_id = $2 AND deleted_at IS NULL
+            "#,
+            submission_id,
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|r| {
+            let data = r.submission_data
+                .and_then(|s| serde_json::from_str(&s).ok())
+                .unwrap_or(json!({}));
+            (r.submission_type, r.status, data)
+        }))
+    }
+
+    // Submission the user created with this Idempotency-Key, with the hash of that request.
+    // Read from the primary so a retry right after the first request still finds it.
+    pub async fn find_by_idempotency_key(
+        &self,
+        user_id: &str,
+        idempotency_key: &str,
+    ) -> Result<Option<(Uuid, Option<String>, Value)>, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            SELECT submission_id, idempotency_request_hash, submission_data
+            FROM submissions
+            WHERE user_id = $1 AND idempotency_key = $2
+            "#,
+            user_id,
+            idempotency_key
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(result.map(|r| {
+            let data = r.submission_data
+                .and_then(|s| serde_json::from_str(&s).ok())
+                .unwrap_or(json!({}));
+            (r.submission_id, r.idempotency_request_hash, data)
+        }))
+    }
+
+    // Stays on the primary: it is read right before the status write in process_submission,
+    // so replica lag could hide a submission that was just created
+    // Returns the submission type, nfc identifier, risk tier and submission data
+    // With `user_id`, only a submission that user owns is found
+    pub async fn find_submission_by_id(
+        &self,
+        submission_id: &str,
+        user_id: Option<&str>,
+    ) -> Result<Option<(String, String, Option<String>, Value)>, sqlx::Error> {
         let submission_uuid = Uuid::parse_str(submission_id).map_err(|_| sqlx::Error::RowNotFound)?;
         
         let result = sqlx::query!(
             r#"
-            SELECT submission_data, submission_type, nfc_identifier
+            SELECT submission_data, submission_type, nfc_identifier, risk_tier
             FROM submissions
-            WHERE submission_id = $1
+            WHERE submission_id = $1 AND ($2::text IS NULL OR user_id = $2)
             "#,
-            submission_uuid
+            submission_uuid,
+            user_id
         )
         .fetch_optional(&self.pool)
         .await?;
@@ -71,7 +170,7 @@ impl SubmissionRepository {
             let data = r.submission_data
                 .and_then(|s| serde_json::from_str(&s).ok())
                 .unwrap_or(json!({}));
-            (submission_type, nfc_identifier, data)
+            (submission_type, nfc_identifier, r.risk_tier, data)
         }))
     }
 
@@ -93,11 +192,35 @@ impl SubmissionRepository {
         Ok(())
     }
 
-    pub async fn find_submission_by_nfc_identifier_and_status(&self, nfc_identifier: &str, status: &str) -> Result<Option<Value>, sqlx::Error> {
-        
+    pub async fn update_submission_data(&self, submission_id: &str, submission_data: &Value) -> Result<(), sqlx::Error> {
+        let submission_uuid = Uuid::parse_str(submission_id).map_err(|_| sqlx::Error::RowNotFound)?;
+
+        sqlx::query!(
+            r#"
+            UPDATE submissions
+            SET submission_data = $2, updated_at = NOW()
+            WHERE submission_id = $1
+            "#,
+            submission_uuid,
+            submission_data.to_string()
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    // The returned updated_at is when the submission last changed status, i.e. when it reached `status`.
+    // Read from the primary, since a lagging replica would hand out a reference the decision is
+    // then refused for.
+    pub async fn find_submission_by_nfc_identifier_and_status(
+        &self,
+        nfc_identifier: &str,
+        status: &str,
+    ) -> Result<Option<(Uuid, DateTime<Utc>, Value)>, sqlx::Error> {
         let result = sqlx::query!(
             r#"
-            SELECT submission_data
+            SELECT submission_id, updated_at, submission_data
             FROM submissions
             WHERE nfc_identifier = $1 AND status = $2
             order by id desc limit 1
@@ -112,25 +235,896 @@ impl SubmissionRepository {
             let data = r.submission_data
                 .and_then(|s| serde_json::from_str(&s).ok())
                 .unwrap_or(json!({}));
-            data
+            (r.submission_id, r.updated_at, data)
         }))
     }
 
-    pub async fn find_submission_by_nfc_identifier_and_submission_type(&self, submission_type: &str, nfc_identifier: &str) -> Result<Option<String>, sqlx::Error> {
-        
+    // Serializes decisions for one identifier until the transaction ends. Taken before reading
+    // the current reference, so a concurrent approval can't commit in between.
+    pub async fn lock_nfc_identifier(conn: &mut PgConnection, nfc_identifier: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("SELECT pg_advisory_xact_lock(hashtextextended($1, 0))")
+            .bind(nfc_identifier)
+            .execute(conn)
+            .await?;
+        Ok(())
+    }
+
+    // Same lookup as find_submission_by_nfc_identifier_and_status, inside a transaction
+    pub async fn find_latest_submission_by_status(
+        conn: &mut PgConnection,
+        nfc_identifier: &str,
+        status: &str,
+    ) -> Result<Option<Uuid>, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            SELECT submission_id
+            FROM submissions
+            WHERE nfc_identifier = $1 AND status = $2
+            order by id desc limit 1
+            "#,
+            nfc_identifier,
+            status
+        )
+        .fetch_optional(conn)
+        .await?;
+
+        Ok(result.map(|r| r.submission_id))
+    }
+
+    pub async fn lock_submission_status(conn: &mut PgConnection, submission_id: Uuid) -> Result<Option<String>, sqlx::Error> {
         let result = sqlx::query!(
             r#"
             SELECT status
             FROM submissions
-            WHERE submission_type = $1 AND nfc_identifier = $2
+            WHERE submission_id = $1
+            FOR UPDATE
+            "#,
+            submission_id
+        )
+        .fetch_optional(conn)
+        .await?;
+
+        Ok(result.map(|r| r.status))
+    }
+
+    // Locks in id order so overlapping bulk updates can't deadlock each other
+    pub async fn lock_submission_statuses(
+        conn: &mut PgConnection,
+        submission_ids: &[Uuid],
+    ) -> Result<Vec<(Uuid, String)>, sqlx::Error> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT submission_id, status
+            FROM submissions
+            WHERE submission_id = ANY($1)
+            order by id
+            FOR UPDATE
+            "#,
+            submission_ids
+        )
+        .fetch_all(conn)
+        .await?;
+
+        Ok(rows.into_iter().map(|r| (r.submission_id, r.status)).collect())
+    }
+
+    pub async fn create_status_history(
+        conn: &mut PgConnection,
+        submission_id: Uuid,
+        previous_status: &str,
+        status: &str,
+        reason: &str,
+        actor: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO submission_status_history (submission_id, previous_status, status, reason, actor)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+            submission_id,
+            previous_status,
+            status,
+            reason,
+            actor
+        )
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    // Match outcome of the most recent face match recorded for the submission
+    // Whether the latest face match matched, with its score and the threshold it was held to
+    pub async fn find_latest_face_match_outcome(
+        conn: &mut PgConnection,
+        submission_id: Uuid,
+    ) -> Result<Option<(bool, f64, f64)>, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            SELECT is_match, similarity_score, threshold
+            FROM face_match_audits
+            WHERE submission_id = $1
             order by id desc limit 1
             "#,
+            submission_id
+        )
+        .fetch_optional(conn)
+        .await?;
+
+        Ok(result.map(|r| (r.is_match, r.similarity_score, r.threshold)))
+    }
+
+    pub async fn begin(&self) -> Result<Transaction<'static, Postgres>, sqlx::Error> {
+        self.pool.begin().await
+    }
+
+    pub async fn set_submission_status(conn: &mut PgConnection, submission_id: &str, status: &str) -> Result<(), sqlx::Error> {
+        let submission_uuid = Uuid::parse_str(submission_id).map_err(|_| sqlx::Error::RowNotFound)?;
+
+        sqlx::query!(
+            r#"
+            UPDATE submissions
+            SET status = $2, updated_at = NOW()
+            WHERE submission_id = $1
+            "#,
+            submission_uuid,
+            status
+        )
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    // Score, threshold and outcome of the face match the current status was decided on
+    pub async fn update_face_match_result(
+        conn: &mut PgConnection,
+        submission_id: &str,
+        face_match_result: &Value,
+    ) -> Result<(), sqlx::Error> {
+        let submission_uuid = Uuid::parse_str(submission_id).map_err(|_| sqlx::Error::RowNotFound)?;
+
+        sqlx::query!(
+            r#"
+            UPDATE submissions
+            SET face_match_result = $2, updated_at = NOW()
+            WHERE submission_id = $1
+            "#,
+            submission_uuid,
+            face_match_result.to_string()
+        )
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    // Status and data of the latest decided submission, or of the latest one when none is
+    // decided yet, with its last update time for cache validation. Preferring a decision keeps
+    // the reported status from flapping while a newer submission is being processed.
+    pub async fn find_submission_by_nfc_identifier_and_submission_type(&self, submission_type: &str, nfc_identifier: &str) -> Result<Option<(String, Value, DateTime<Utc>)>, sqlx::Error> {
+        
+        let result = sqlx::query!(
+            r#"
+            SELECT status, submission_data, updated_at
+            FROM submissions
+            WHERE submission_type = $1 AND nfc_identifier = $2 AND deleted_at IS NULL
+            order by status IN ('APPROVED', 'REJECTED', 'REQUIRES_REVIEW') desc, id desc limit 1
+            "#,
             submission_type,
             nfc_identifier
         )
+        .fetch_optional(&self.read_pool)
+        .await?;
+
+        Ok(result.map(|r| {
+            let data = r.submission_data
+                .and_then(|s| serde_json::from_str(&s).ok())
+                .unwrap_or(json!({}));
+            (r.status, data, r.updated_at)
+        }))
+    }
+
+    // Page of live submissions tagged with the client's own reference, newest first, and the total count
+    pub async fn find_by_external_reference(
+        &self,
+        external_reference: &str,
+        user_id: &str,
+        limit: i64,
+        offset: i64,
+    ) -> Result<(Vec<SubmissionSummary>, i64), sqlx::Error> {
+        let total = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) as "count!"
+            FROM submissions
+            WHERE external_reference = $1 AND user_id = $2 AND deleted_at IS NULL
+            "#,
+            external_reference,
+            user_id
+        )
+        .fetch_one(&self.read_pool)
+        .await?;
+
+        let rows = sqlx::query!(
+            r#"
+            SELECT submission_id, submission_type, status, external_reference, created_at, updated_at
+            FROM submissions
+            WHERE external_reference = $1 AND user_id = $2 AND deleted_at IS NULL
+            order by id desc limit $3 offset $4
+            "#,
+            external_reference,
+            user_id,
+            limit,
+            offset
+        )
+        .fetch_all(&self.read_pool)
+        .await?;
+
+        let submissions = rows
+            .into_iter()
+            .map(|r| SubmissionSummary {
+                submission_id: r.submission_id,
+                submission_type: r.submission_type,
+                status: r.status,
+                external_reference: r.external_reference,
+                created_at: r.created_at,
+                updated_at: r.updated_at,
+            })
+            .collect();
+
+        Ok((submissions, total))
+    }
+
+    // Each filter is skipped when None
+    pub async fn list(
+        &self,
+        status: Option<&str>,
+        submission_type: Option<&str>,
+        user_id: Option<&str>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<(Vec<SubmissionSummary>, i64), sqlx::Error> {
+        let total = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) as "count!"
+            FROM submissions
+            WHERE deleted_at IS NULL
+                AND ($1::text IS NULL OR status = $1)
+                AND ($2::text IS NULL OR submission_type = $2)
+                AND ($3::text IS NULL OR user_id = $3)
+            "#,
+            status,
+            submission_type,
+            user_id
+        )
+        .fetch_one(&self.read_pool)
+        .await?;
+
+        let rows = sqlx::query!(
+            r#"
+            SELECT submission_id, submission_type, status, external_reference, created_at, updated_at
+            FROM submissions
+            WHERE deleted_at IS NULL
+                AND ($1::text IS NULL OR status = $1)
+                AND ($2::text IS NULL OR submission_type = $2)
+                AND ($3::text IS NULL OR user_id = $3)
+            order by id desc limit $4 offset $5
+            "#,
+            status,
+            submission_type,
+            user_id,
+            limit,
+            offset
+        )
+        .fetch_all(&self.read_pool)
+        .await?;
+
+        let submissions = rows
+            .into_iter()
+            .map(|r| SubmissionSummary {
+                submission_id: r.submission_id,
+                submission_type: r.submission_type,
+                status: r.status,
+                external_reference: r.external_reference,
+                created_at: r.created_at,
+                updated_at: r.updated_at,
+            })
+            .collect();
+
+        Ok((submissions, total))
+    }
+
+    // PROCESSING submissions not touched since `older_than`, oldest first. Reads the primary
+    // so submissions that have just been decided aren't reported from a lagging replica.
+    pub async fn find_stuck_processing(
+        &self,
+        older_than: DateTime<Utc>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<(Vec<SubmissionSummary>, i64), sqlx::Error> {
+        let total = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) as "count!"
+            FROM submissions
+            WHERE deleted_at IS NULL AND status = 'PROCESSING' AND updated_at < $1
+            "#,
+            older_than
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        let rows = sqlx::query!(
+            r#"
+            SELECT submission_id, submission_type, status, external_reference, created_at, updated_at
+            FROM submissions
+            WHERE deleted_at IS NULL AND status = 'PROCESSING' AND updated_at < $1
+            order by updated_at asc, id asc limit $2 offset $3
+            "#,
+            older_than,
+            limit,
+            offset
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let submissions = rows
+            .into_iter()
+            .map(|r| SubmissionSummary {
+                submission_id: r.submission_id,
+                submission_type: r.submission_type,
+                status: r.status,
+                external_reference: r.external_reference,
+                created_at: r.created_at,
+                updated_at: r.updated_at,
+            })
+            .collect();
+
+        Ok((submissions, total))
+    }
+
+    // Matches the expression indexed by submissions_document_references_idx
+    pub async fn find_by_document_reference(
+        &self,
+        document_reference: &str,
+    ) -> Result<Option<SubmissionSummary>, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            SELECT submission_id, submission_type, status, external_reference, created_at, updated_at
+            FROM submissions
+            WHERE jsonb_path_query_array(submission_data::jsonb, '$.*.documentReference') @> jsonb_build_array($1::text)
+                AND deleted_at IS NULL
+            order by id desc limit 1
+            "#,
+            document_reference
+        )
+        .fetch_optional(&self.read_pool)
+        .await?;
+
+        Ok(result.map(|r| SubmissionSummary {
+            submission_id: r.submission_id,
+            submission_type: r.submission_type,
+            status: r.status,
+            external_reference: r.external_reference,
+            created_at: r.created_at,
+            updated_at: r.updated_at,
+        }))
+    }
+
+    // Per-status counts for the identifier across all submission types
+    pub async fn count_by_status_for_nfc_identifier(&self, nfc_identifier: &str) -> Result<Vec<(String, i64)>, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            SELECT status, COUNT(*) as "count!"
+            FROM submissions
+            WHERE nfc_identifier = $1 AND deleted_at IS NULL
+            GROUP BY status
+            "#,
+            nfc_identifier
+        )
+        .fetch_all(&self.read_pool)
+        .await?;
+
+        Ok(result.into_iter().map(|r| (r.status, r.count)).collect())
+    }
+
+    pub async fn find_latest_status_for_nfc_identifier(
+        &self,
+        nfc_identifier: &str,
+    ) -> Result<Option<(String, DateTime<Utc>)>, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            SELECT status, updated_at
+            FROM submissions
+            WHERE nfc_identifier = $1 AND deleted_at IS NULL
+            order by id desc limit 1
+            "#,
+            nfc_identifier
+        )
+        .fetch_optional(&self.read_pool)
+        .await?;
+
+        Ok(result.map(|r| (r.status, r.updated_at)))
+    }
+
+    // Latest submission of `user_id` for the identifier/type/risk tier in `status` created at or
+    // after `since`
+    pub async fn find_recent_submission(
+        &self,
+        user_id: &str,
+        nfc_identifier: &str,
+        submission_type: &str,
+        risk_tier: Option<&str>,
+        status: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Option<(Uuid, Value)>, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            SELECT submission_id, submission_data
+            FROM submissions
+            WHERE nfc_identifier = $1 AND submission_type = $2 AND risk_tier IS NOT DISTINCT FROM $3
+                AND status = $4 AND created_at >= $5 AND user_id = $6
+            order by id desc limit 1
+            "#,
+            nfc_identifier,
+            submission_type,
+            risk_tier,
+            status,
+            since,
+            user_id
+        )
         .fetch_optional(&self.pool)
         .await?;
 
-        Ok(result.map(|r| r.status))
+        Ok(result.map(|r| {
+            let data = r.submission_data
+                .and_then(|s| serde_json::from_str(&s).ok())
+                .unwrap_or(json!({}));
+            (r.submission_id, data)
+        }))
+    }
+
+    // Next page of PENDING_RETRY/PROCESSING submissions not touched since `updated_before`,
+    // keyed on `id` so a submission failing again isn't picked up twice in the same run
+    pub async fn find_reprocessable_submissions(
+        &self,
+        updated_before: DateTime<Utc>,
+        after_id: i64,
+        limit: i64,
+    ) -> Result<Vec<(i64, Uuid)>, sqlx::Error> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT id, submission_id
+            FROM submissions
+            WHERE status IN ('PENDING_RETRY', 'PROCESSING') AND updated_at < $1 AND id > $2
+            order by id asc limit $3
+            "#,
+            updated_before,
+            after_id,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|r| (r.id, r.submission_id)).collect())
+    }
+
+    // Next page of decided submissions not touched since `updated_before` and not archived yet
+    pub async fn find_archivable_submissions(
+        &self,
+        updated_before: DateTime<Utc>,
+        after_id: i64,
+        limit: i64,
+    ) -> Result<Vec<(i64, Uuid, Value)>, sqlx::Error> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT id, submission_id, submission_data
+            FROM submissions
+            WHERE status IN ('APPROVED', 'REJECTED') AND updated_at < $1 AND id > $2
+                AND archived_at IS NULL AND deleted_at IS NULL
+            order by id asc limit $3
+            "#,
+            updated_before,
+            after_id,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|r| {
+                let data = r.submission_data
+                    .and_then(|s| serde_json::from_str(&s).ok())
+                    .unwrap_or(json!({}));
+                (r.id, r.submission_id, data)
+            })
+            .collect())
+    }
+
+    // Stores the archived document references. updated_at is left alone since it records when
+    // the submission was decided. False when the submission was archived or deleted since it was
+    // fetched.
+    pub async fn mark_archived(&self, submission_id: Uuid, submission_data: &Value) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE submissions
+            SET submission_data = $2, archived_at = NOW()
+            WHERE submission_id = $1 AND archived_at IS NULL AND deleted_at IS NULL
+            "#,
+            submission_id,
+            submission_data.to_string()
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    // Marks one live submission of the user DELETED and scrubs its identifying data, like
+    // soft_delete_by_user. None when the user has no such live submission.
+    pub async fn soft_delete(
+        conn: &mut PgConnection,
+        submission_id: Uuid,
+        user_id: &str,
+    ) -> Result<Option<Value>, sqlx::Error> {
+        let row = sqlx::query!(
+            r#"
+            WITH erased AS (
+                SELECT id, submission_data
+                FROM submissions
+                WHERE submission_id = $1 AND user_id = $2 AND deleted_at IS NULL
+                FOR UPDATE
+            )
+            UPDATE submissions
+            SET status = 'DELETED',
+                nfc_identifier = NULL,
+                submission_data = NULL,
+                request_data = NULL,
+                callback_url = NULL,
+                deleted_at = NOW(),
+                updated_at = NOW()
+            FROM erased
+            WHERE submissions.id = erased.id
+            RETURNING erased.submission_data
+            "#,
+            submission_id,
+            user_id
+        )
+        .fetch_optional(conn)
+        .await?;
+
+        Ok(row.map(|r| {
+            r.submission_data
+                .and_then(|s| serde_json::from_str(&s).ok())
+                .unwrap_or(json!({}))
+        }))
+    }
+
+    // Marks every live submission of the user DELETED and scrubs its identifying data.
+    // Returns the submission data as it was before scrubbing so stored objects can be removed.
+    pub async fn soft_delete_by_user(conn: &mut PgConnection, user_id: &str) -> Result<Vec<Value>, sqlx::Error> {
+        let rows = sqlx::query!(
+            r#"
+            WITH erased AS (
+                SELECT id, submission_data
+                FROM submissions
+                WHERE user_id = $1 AND deleted_at IS NULL
+                FOR UPDATE
+            )
+            UPDATE submissions
+            SET status = 'DELETED',
+                nfc_identifier = NULL,
+                submission_data = NULL,
+                request_data = NULL,
+                callback_url = NULL,
+                deleted_at = NOW(),
+                updated_at = NOW()
+            FROM erased
+            WHERE submissions.id = erased.id
+            RETURNING erased.submission_data
+            "#,
+            user_id
+        )
+        .fetch_all(conn)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|r| {
+                r.submission_data
+                    .and_then(|s| serde_json::from_str(&s).ok())
+                    .unwrap_or(json!({}))
+            })
+            .collect())
+    }
+
+    pub async fn create_face_match_audit(
+        conn: &mut PgConnection,
+        submission_id: &str,
+        submission_type: &str,
+        similarity_score: f64,
+        threshold: f64,
+        is_match: bool,
+    ) -> Result<(), sqlx::Error> {
+        let submission_uuid = Uuid::parse_str(submission_id).map_err(|_| sqlx::Error::RowNotFound)?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO face_match_audits (
+                submission_id,
+                submission_type,
+                similarity_score,
+                threshold,
+                is_match
+            )
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+            submission_uuid,
+            submission_type,
+            similarity_score,
+            threshold,
+            is_match
+        )
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn create_face_match_capture(
+        &self,
+        submission_id: &str,
+        submission_type: &str,
+        reference_object_url: &str,
+        selfie_object_url: &str,
+        threshold: f64,
+        response: &Value,
+    ) -> Result<(), sqlx::Error> {
+        let submission_uuid = Uuid::parse_str(submission_id).map_err(|_| sqlx::Error::RowNotFound)?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO face_match_captures (
+                submission_id,
+                submission_type,
+                reference_object_url,
+                selfie_object_url,
+                threshold,
+                response
+            )
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#,
+            submission_uuid,
+            submission_type,
+            reference_object_url,
+            selfie_object_url,
+            threshold,
+            response.to_string()
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn purge_face_match_captures(&self, cutoff: DateTime<Utc>) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM face_match_captures
+            WHERE created_at < $1
+            "#,
+            cutoff
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // Deletes audits created before `cutoff`, first folding them into the daily stats when
+    // `archive_stats` is set. Returns the number of deleted rows.
+    pub async fn purge_face_match_audits(&self, cutoff: DateTime<Utc>, archive_stats: bool) -> Result<u64, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        if archive_stats {
+            sqlx::query!(
+                r#"
+                INSERT INTO face_match_audit_daily_stats (day, total_count, match_count, similarity_score_sum)
+                SELECT
+                    (created_at AT TIME ZONE 'UTC')::date,
+                    COUNT(*),
+                    COUNT(*) FILTER (WHERE is_match),
+                    SUM(similarity_score)
+                FROM face_match_audits
+                WHERE created_at < $1
+                GROUP BY 1
+                ON CONFLICT (day) DO UPDATE SET
+                    total_count = face_match_audit_daily_stats.total_count + EXCLUDED.total_count,
+                    match_count = face_match_audit_daily_stats.match_count + EXCLUDED.match_count,
+                    similarity_score_sum = face_match_audit_daily_stats.similarity_score_sum + EXCLUDED.similarity_score_sum,
+                    updated_at = NOW()
+                "#,
+                cutoff
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM face_match_audits
+            WHERE created_at < $1
+            "#,
+            cutoff
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::postgres::PgPoolOptions;
+
+    use super::*;
+
+    // A pool whose `submissions` is a table of its own, standing in for a replica that hasn't
+    // caught up with the primary yet
+    async fn lagging_replica(pool: &PgPool) -> PgPool {
+        sqlx::query("CREATE SCHEMA replica").execute(pool).await.unwrap();
+        sqlx::query("CREATE TABLE replica.submissions (LIKE public.submissions INCLUDING ALL)")
+            .execute(pool)
+            .await
+            .unwrap();
+        let options = (*pool.connect_options()).clone().options([("search_path", "replica,public")]);
+        PgPoolOptions::new().max_connections(1).connect_with(options).await.unwrap()
+    }
+
+    #[sqlx::test]
+    async fn reads_go_to_the_replica_and_primary_only_reads_stay_on_the_pool(pool: PgPool) {
+        let repository = SubmissionRepository::new(pool.clone(), lagging_replica(&pool).await);
+        let submission_id = Uuid::new_v4();
+        repository
+            .create(NewSubmission {
+                submission_id,
+                submission_type: "KYC",
+                session_id: "session",
+                user_id: "1",
+                status: "APPROVED",
+                submission_data: json!({}),
+                request_data: json!({}),
+                nfc_identifier: "nfc-1".to_string(),
+                external_reference: None,
+                risk_tier: None,
+                idempotency_key: None,
+                idempotency_request_hash: None,
+                callback_url: None,
+            })
+            .await
+            .unwrap();
+
+        // Status queries read the replica, which doesn't have the submission yet
+        assert!(repository.find_submission_by_nfc_identifier_and_submission_type("KYC", "nfc-1").await.unwrap().is_none());
+        assert!(repository.count_by_status_for_nfc_identifier("nfc-1").await.unwrap().is_empty());
+
+        // Reads processing depends on see it right away
+        assert!(repository.find_submission_by_id(&submission_id.to_string(), None).await.unwrap().is_some());
+        assert!(repository.find_submission_by_nfc_identifier_and_status("nfc-1", "APPROVED").await.unwrap().is_some());
+
+        sqlx::query("INSERT INTO replica.submissions SELECT * FROM public.submissions").execute(&pool).await.unwrap();
+        assert!(repository.find_submission_by_nfc_identifier_and_submission_type("KYC", "nfc-1").await.unwrap().is_some());
+    }
+
+    async fn audit_aged(pool: &PgPool, days: i32, is_match: bool) {
+        let mut conn = pool.acquire().await.unwrap();
+        SubmissionRepository::create_face_match_audit(&mut conn, &Uuid::new_v4().to_string(), "KYC", 0.9, 0.8, is_match)
+            .await
+            .unwrap();
+        sqlx::query("UPDATE face_match_audits SET created_at = NOW() - make_interval(days => $1) WHERE id = (SELECT MAX(id) FROM face_match_audits)")
+            .bind(days)
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+
+    async fn audit_ages(pool: &PgPool) -> Vec<i32> {
+        sqlx::query_scalar("SELECT EXTRACT(DAY FROM NOW() - created_at)::int FROM face_match_audits ORDER BY created_at")
+            .fetch_all(pool)
+            .await
+            .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn purges_audits_past_the_retention_window_and_keeps_recent_ones(pool: PgPool) {
+        let repository = SubmissionRepository::new(pool.clone(), pool.clone());
+        audit_aged(&pool, 45, true).await;
+        audit_aged(&pool, 45, false).await;
+        audit_aged(&pool, 31, true).await;
+        audit_aged(&pool, 29, true).await;
+        audit_aged(&pool, 0, false).await;
+
+        let cutoff = Utc::now() - chrono::Duration::days(30);
+        assert_eq!(repository.purge_face_match_audits(cutoff, true).await.unwrap(), 3);
+        assert_eq!(audit_ages(&pool).await, [29, 0]);
+
+        // The purged rows live on as daily aggregates
+        let stats: Vec<(i64, i64)> = sqlx::query_as("SELECT total_count, match_count FROM face_match_audit_daily_stats ORDER BY day")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(stats, [(2, 1), (1, 1)]);
+
+        // Nothing left to purge, and without archiving the stats are left alone
+        assert_eq!(repository.purge_face_match_audits(cutoff, false).await.unwrap(), 0);
+        audit_aged(&pool, 40, false).await;
+        assert_eq!(repository.purge_face_match_audits(cutoff, false).await.unwrap(), 1);
+        let archived: i64 = sqlx::query_scalar("SELECT SUM(total_count)::bigint FROM face_match_audit_daily_stats")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(archived, 3);
+    }
+
+    async fn seed(repository: &SubmissionRepository, nfc_identifier: &str, status: &str) {
+        let submission_id = Uuid::new_v4();
+        repository
+            .create(NewSubmission {
+                submission_id,
+                submission_type: "KYC",
+                session_id: &submission_id.to_string(),
+                user_id: "1",
+                status,
+                submission_data: json!({}),
+                request_data: json!({}),
+                nfc_identifier: nfc_identifier.to_string(),
+                external_reference: None,
+                risk_tier: None,
+                idempotency_key: None,
+                idempotency_request_hash: None,
+                callback_url: None,
+            })
+            .await
+            .unwrap();
+    }
+
+    #[sqlx::test]
+    async fn status_prefers_the_latest_decision_over_newer_submissions_in_flight(pool: PgPool) {
+        let repository = SubmissionRepository::new(pool.clone(), pool);
+        let status = |nfc_identifier: &'static str| {
+            let repository = &repository;
+            async move {
+                let (status, _, _) = repository
+                    .find_submission_by_nfc_identifier_and_submission_type("KYC", nfc_identifier)
+                    .await
+                    .unwrap()
+                    .unwrap();
+                status
+            }
+        };
+
+        // Only in-flight submissions: the newest one
+        seed(&repository, "nfc-1", "INITIATED").await;
+        seed(&repository, "nfc-1", "PROCESSING").await;
+        assert_eq!(status("nfc-1").await, "PROCESSING");
+
+        // A decision wins over the retries overlapping it, whichever is newer
+        seed(&repository, "nfc-1", "APPROVED").await;
+        assert_eq!(status("nfc-1").await, "APPROVED");
+        seed(&repository, "nfc-1", "PROCESSING").await;
+        seed(&repository, "nfc-1", "INITIATED").await;
+        assert_eq!(status("nfc-1").await, "APPROVED");
+
+        // And the latest decision wins over earlier ones
+        seed(&repository, "nfc-1", "REJECTED").await;
+        seed(&repository, "nfc-1", "PROCESSING").await;
+        assert_eq!(status("nfc-1").await, "REJECTED");
+
+        // Other identifiers are their own
+        seed(&repository, "nfc-2", "PENDING_RETRY").await;
+        assert_eq!(status("nfc-2").await, "PENDING_RETRY");
     }
 }
@@ -1,50 +1,241 @@
-use std::{collections::HashMap, time::Duration};
+use std::{collections::{BTreeMap, HashMap}, time::Duration};
+use chrono::{DateTime, Utc};
 use uuid::Uuid;
-use serde_json::json;
+use serde_json::{json, Map, Value};
 use base64::{Engine as _, engine::general_purpose::STANDARD};
+use rand::Rng;
+use sha2::{Digest, Sha256};
 
 use crate::{
-    commons::minio_service::{self, MinioService},
-    models::user::ApiError,
-    services::{face_match_service::FaceMatchService, metrics_service::MetricsService},
+    config::Config,
+    commons::{
+        minio_service::{self, ContentDisposition, MinioService},
+        pii,
+    },
+    models::{pagination::Page, user::ApiError},
+    repositories::audit_log_repository::AuditLogRepository,
+    services::{
+        face_match_service::{self, FaceMatchResponse, FaceMatchService},
+        feature_flags_service::{self, FeatureFlagsService},
+        metrics_service::MetricsService,
+        webhook_service::{self, resolve_callback_url, WebhookService},
+    },
     submissions::{
-        dto::presigned_urls_response::{Document, PresignedUrlsResponse, SubmissionData}, 
-        submission_controller::{GetSubmissionStatusResponse, ProcessSubmissionResponse, SubmissionType}, 
-        submission_repository::SubmissionRepository
+        dto::{
+            bulk_status_response::{BulkStatusItem, BulkStatusResponse},
+            document_upload_response::DocumentUploadResponse,
+            nfc_summary_response::NfcSummaryResponse,
+            presigned_urls_response::{Document, PresignedUrlsDryRunResponse, PresignedUrlsResponse, SubmissionData},
+            recompute_status_response::RecomputeStatusResponse,
+            submission_summary::SubmissionSummary,
+        },
+        document_type::{DocumentType, ImageFormat, UploadStatus},
+        submission_controller::{ProcessSubmissionResponse, SubmissionType},
+        submission_repository::{NewSubmission, SubmissionRepository},
+        submission_status::SubmissionStatus,
+        submission_type_registry::{self, ProcessingStrategy},
     },
 };
 
+pub const MAX_EXTERNAL_REFERENCE_LENGTH: usize = 255;
+pub const MAX_BULK_STATUS_SIZE: usize = 100;
+pub const MAX_IDEMPOTENCY_KEY_LENGTH: usize = 255;
+// S3 refuses to presign anything valid for longer than 7 days
+pub const MAX_PRESIGN_TTL_SECS: u64 = 604800;
+pub const DEFAULT_RISK_TIER: &str = "DEFAULT";
+
+const MAX_CALLBACK_URL_LENGTH: usize = 2048;
+
+// How long a submission may stay PROCESSING before it's reported as stuck, unless overridden
+pub const DEFAULT_STUCK_PROCESSING_SECS: u64 = 900;
+// Keeps the cutoff within what chrono can represent
+const MAX_STUCK_PROCESSING_SECS: u64 = 365 * 24 * 3600;
+
+// Statuses a live submission can be in; deleted submissions are never listed
+const LISTABLE_STATUSES: &[&str] = &["INITIATED", "PROCESSING", "PENDING_RETRY", "REQUIRES_REVIEW", "APPROVED", "REJECTED"];
+
+// Corrections an operator may apply in bulk. DELETED and INITIATED are never targets, and
+// APPROVED is only reachable from statuses where a face match has or could have run. Manual
+// review is resolved the same way.
+fn is_allowed_correction(from: &str, to: &str) -> bool {
+    matches!(
+        (from, to),
+        ("APPROVED", "REJECTED")
+            | ("REJECTED", "APPROVED")
+            | ("APPROVED" | "REJECTED" | "PROCESSING", "PENDING_RETRY")
+            | ("PROCESSING" | "PENDING_RETRY", "APPROVED" | "REJECTED")
+            | ("INITIATED", "REJECTED")
+            | ("REQUIRES_REVIEW", "APPROVED" | "REJECTED")
+    )
+}
+
+// A non-match close enough to the threshold is left to a human when a review margin is set
+fn decide_status(is_match: bool, similarity_score: f64, threshold: f64, review_margin: f64) -> &'static str {
+    if is_match {
+        "APPROVED"
+    } else if review_margin > 0.0 && similarity_score >= threshold - review_margin {
+        "REQUIRES_REVIEW"
+    } else {
+        "REJECTED"
+    }
+}
+
+// A risk tier can only make the match stricter than the submission type's threshold
+fn risk_threshold(tier_thresholds: &HashMap<String, f64>, risk_tier: Option<&str>, threshold: f64) -> f64 {
+    let Some(risk_tier) = risk_tier else {
+        return threshold;
+    };
+
+    match tier_thresholds.get(risk_tier) {
+        Some(tier_threshold) => threshold.max(*tier_threshold),
+        None => {
+            log::warn!("Risk tier {} is no longer configured, using the default threshold", risk_tier);
+            threshold
+        }
+    }
+}
+
+// A reviewer, or a recompute, settled a submission processing left for review. Processing
+// holds back the webhook for these, so it goes out at this point instead.
+fn leaves_review(from: &str, to: &str) -> bool {
+    from == "REQUIRES_REVIEW" && matches!(to, "APPROVED" | "REJECTED")
+}
+
+// What the client asked presigned URLs for
+pub struct PresignedUrlsRequest {
+    pub submission_type: SubmissionType,
+    pub nfc_identifier: String,
+    pub external_reference: Option<String>,
+    pub expiry_in_seconds: Option<u64>,
+    pub callback_url: Option<String>,
+    pub idempotency_key: Option<String>,
+}
+
+// The status a face match led to and what it was decided on
+struct Decision<'a> {
+    face_match_result: &'a FaceMatchResponse,
+    threshold: f64,
+    status: &'a str,
+}
+
 pub struct SubmissionService {
     minio_service: MinioService,
     submission_repository: SubmissionRepository,
     metrics: MetricsService,
+    config: Config,
+    feature_flags: FeatureFlagsService,
 }
 
 impl SubmissionService {
     pub fn new(
         minio_service: MinioService, 
         submission_repository: SubmissionRepository, 
-        metrics: MetricsService
+        metrics: MetricsService,
+        config: Config,
+        feature_flags: FeatureFlagsService,
     ) -> Self {
         Self {
             minio_service,
             submission_repository,
             metrics,
+            config,
+            feature_flags,
         }
     }
 
+    // ON_DEMAND_ENABLED is only the default; the feature flag can toggle it at runtime
+    async fn is_submission_type_enabled(&self, submission_type: &str) -> bool {
+        submission_type != "ON_DEMAND"
+            || self.feature_flags.is_enabled(feature_flags_service::ON_DEMAND_ENABLED, self.config.on_demand_enabled).await
+    }
+
     pub async fn generate_presigned_urls(
         &self,
         session_id: String,
         user_id: String,
-        submission_type: SubmissionType,
-        nfc_identifier: String,
+        // The user's tier as set by an admin, never taken from the request
+        risk_tier: Option<String>,
+        request: PresignedUrlsRequest,
     ) -> Result<PresignedUrlsResponse, Vec<ApiError>> {
+        let PresignedUrlsRequest {
+            submission_type,
+            nfc_identifier,
+            external_reference,
+            expiry_in_seconds,
+            callback_url,
+            idempotency_key,
+        } = request;
         let start = std::time::Instant::now();
         let mut tags = HashMap::new();
         tags.insert("endpoint".to_string(), "presigned_urls".to_string());
         tags.insert("submission_type".to_string(), submission_type.to_string());
 
+        let upload_ttl_secs = match self.upload_ttl_secs(expiry_in_seconds) {
+            Ok(ttl) => ttl,
+            Err(e) => {
+                self.metrics.increment("api_error", Some(tags.clone()));
+                return Err(vec![e]);
+            }
+        };
+        let risk_tier = risk_tier.filter(|tier| tier.as_str() != DEFAULT_RISK_TIER);
+        if let Err(e) = self.check_callback_url(callback_url.as_deref()).await {
+            self.metrics.increment("api_error", Some(tags.clone()));
+            return Err(vec![e]);
+        }
+
+        let (upload_documents, nfc_identifier_clean, nfc_identifier_base64, nfc_image_format) =
+            match self.check_presigned_urls_request(&submission_type, &nfc_identifier).await {
+                Ok(checked) => checked,
+                Err(e) => {
+                    self.metrics.increment("api_error", Some(tags.clone()));
+                    return Err(vec![e]);
+                }
+            };
+
+        // A retried request gets the submission its first attempt created
+        let idempotency = idempotency_key.map(|key| {
+            let request = json!({
+                "submissionType": submission_type.to_string(),
+                "nfcIdentifier": nfc_identifier_clean,
+                "externalReference": external_reference,
+                "expiryInSeconds": expiry_in_seconds,
+                "callbackUrl": callback_url,
+            });
+            (key, format!("{:x}", Sha256::digest(request.to_string().as_bytes())))
+        });
+        if let Some((key, request_hash)) = &idempotency {
+            match self.replay_idempotent_request(&user_id, key, request_hash, &submission_type, upload_ttl_secs).await {
+                Ok(Some(response)) => {
+                    self.metrics.increment("presigned_urls.idempotent_replay", Some(tags.clone()));
+                    self.metrics.increment("api_success", Some(tags.clone()));
+                    self.metrics.timing("api_latency", start.elapsed(), Some(tags));
+                    return Ok(response);
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    self.metrics.increment("api_error", Some(tags.clone()));
+                    return Err(vec![e]);
+                }
+            }
+        }
+
+        // Hand back the caller's in-progress submission instead of creating a duplicate
+        if self.config.submission_dedupe_window_secs > 0 {
+            match self.find_in_progress_submission(&user_id, &submission_type, &nfc_identifier_clean, risk_tier.as_deref(), upload_ttl_secs).await {
+                Ok(Some(response)) => {
+                    self.metrics.increment("presigned_urls.dedupe_hit", Some(tags.clone()));
+                    self.metrics.increment("api_success", Some(tags.clone()));
+                    self.metrics.timing("api_latency", start.elapsed(), Some(tags));
+                    return Ok(response);
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    self.metrics.increment("api_error", Some(tags.clone()));
+                    return Err(vec![e]);
+                }
+            }
+        }
+
         // Generate a new submission ID
         let submission_id = Uuid::new_v4();
 
@@ -52,81 +243,53 @@ impl SubmissionService {
         let mut documents = HashMap::new();
 
         let mut documents_data = HashMap::new();
+        let (presign_ttl_secs, advertised_ttl_secs) = self.jittered_upload_ttl(upload_ttl_secs);
 
-        // KYC document
-        if submission_type.to_string() == "KYC" {
-            let ktp_uuid = Uuid::new_v4();
-            let ktp_filename = ktp_uuid.to_string() + "_KTP";
-            let ktp_url = match self.minio_service
-                .generate_upload_url(ktp_filename.clone(), Duration::from_secs(600))
+        for document in upload_documents {
+            let document_uuid = Uuid::new_v4();
+            let document_filename = format!("{}_{}", document_uuid, document);
+            let document_url = match self.minio_service
+                .generate_upload_url(document_filename.clone(), Duration::from_secs(presign_ttl_secs))
                 .await
             {
                 Ok(url) => url,
                 Err(e) => {
                     self.metrics.increment("api_error", Some(tags.clone()));
-                    return Err(vec![ApiError {
-                        entity: "SOCIO_ECHO_BE".to_string(),
-                        code: "1001".to_string(),
-                        cause: e.to_string(),
-                    }]);
+                    return Err(vec![minio_error(e)]);
                 }
             };
 
             documents.insert(
-                "KTP".to_string(),
+                *document,
                 Document {
-                    document_url: ktp_url,
-                    document_reference: ktp_uuid.to_string(),
-                    expiry_in_seconds: "600".to_string(),
+                    document_url,
+                    document_reference: document_uuid.to_string(),
+                    expiry_in_seconds: advertised_ttl_secs.to_string(),
                 },
             );
 
-            documents_data.insert("KTP", SubmissionData {
-                document_name: ktp_filename.clone(),
-                document_reference: ktp_uuid.to_string(),
+            documents_data.insert(*document, SubmissionData {
+                document_name: document_filename,
+                document_reference: document_uuid.to_string(),
+                upload_status: UploadStatus::Pending,
             });
         }
 
-        // Selfie document
-        let selfie_uuid: Uuid = Uuid::new_v4();
-        let selfie_filename = selfie_uuid.to_string() + "_SELFIE";
-        let selfie_url = match self.minio_service
-            .generate_upload_url(selfie_filename.clone(), Duration::from_secs(600))
-            .await
-        {
-            Ok(url) => url,
-            Err(e) => {
-                self.metrics.increment("api_error", Some(tags.clone()));
-                return Err(vec![ApiError {
-                    entity: "SOCIO_ECHO_BE".to_string(),
-                    code: "1001".to_string(),
-                    cause: e.to_string(),
-                }]);
-            }
-        };
-
-        documents.insert(
-            "SELFIE".to_string(),
-            Document {
-                document_url: selfie_url,
-                document_reference: selfie_uuid.to_string(),
-                expiry_in_seconds: "600".to_string(),
-            },
-        );
-        documents_data.insert("SELFIE", SubmissionData {
-            document_name: selfie_filename.clone(),
-            document_reference: selfie_uuid.to_string()
-        });
-
         // NFC document
-        let nfc_identifier_clean = nfc_identifier.replace("data:image/jpeg;base64,", "");
-        let nfc_identifier_base64 = STANDARD.decode(&nfc_identifier_clean).unwrap();
         let nfc_uuid = Uuid::new_v4();
         let nfc_identifier_filename = nfc_uuid.to_string() + "_NFC";
-        self.minio_service.upload_file(nfc_identifier_filename.clone(), nfc_identifier_base64, Some("image/jpeg".to_string())).await.unwrap();
-        documents_data.insert("NFC", SubmissionData {
+        if let Err(e) = self.minio_service.upload_file(
+            nfc_identifier_filename.clone(),
+            nfc_identifier_base64,
+            Some(nfc_image_format.content_type().to_string()),
+        ).await {
+            self.metrics.increment("api_error", Some(tags.clone()));
+            return Err(vec![minio_error(e)]);
+        }
+        documents_data.insert(DocumentType::Nfc, SubmissionData {
             document_name: nfc_identifier_filename.clone(),
             document_reference: nfc_uuid.to_string(),
+            upload_status: UploadStatus::Uploaded,
         });
 
         let response = PresignedUrlsResponse {
@@ -137,18 +300,45 @@ impl SubmissionService {
         // Save to database
         if let Err(e) = self
             .submission_repository
-            .create(
+            .create(NewSubmission {
                 submission_id,
-                &format!("{:?}", submission_type),
-                &session_id,
-                &user_id,
-                "INITIATED",
-                json!(documents_data),
-                json!({}),
-                nfc_identifier_clean.clone().chars().take(500).collect::<String>(),
-            )
+                submission_type: &submission_type.to_string(),
+                session_id: &session_id,
+                user_id: &user_id,
+                status: "INITIATED",
+                submission_data: json!(documents_data),
+                request_data: json!({}),
+                nfc_identifier: nfc_identifier_clean.clone().chars().take(500).collect::<String>(),
+                external_reference,
+                risk_tier,
+                idempotency_key: idempotency.as_ref().map(|(key, _)| key.as_str()),
+                idempotency_request_hash: idempotency.as_ref().map(|(_, request_hash)| request_hash.as_str()),
+                callback_url: callback_url.as_deref(),
+            })
             .await
         {
+            // No submission refers to the NFC image just stored, whichever way the insert failed
+            if let Err(delete_error) = self.minio_service.delete_file(nfc_identifier_filename.clone()).await {
+                log::warn!("Failed to delete NFC image {} of an uncreated submission: {}", nfc_identifier_filename, delete_error);
+            }
+
+            // A concurrent request with the same key created its submission first
+            if let Some((key, request_hash)) = idempotency.as_ref().filter(|_| is_idempotency_key_conflict(&e)) {
+                match self.replay_idempotent_request(&user_id, key, request_hash, &submission_type, upload_ttl_secs).await {
+                    Ok(Some(response)) => {
+                        self.metrics.increment("presigned_urls.idempotent_replay", Some(tags.clone()));
+                        self.metrics.increment("api_success", Some(tags.clone()));
+                        self.metrics.timing("api_latency", start.elapsed(), Some(tags));
+                        return Ok(response);
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        self.metrics.increment("api_error", Some(tags.clone()));
+                        return Err(vec![e]);
+                    }
+                }
+            }
+
             self.metrics.increment("api_error", Some(tags.clone()));
             return Err(vec![ApiError {
                 entity: "SOCIO_ECHO_BE".to_string(),
@@ -163,310 +353,1199 @@ impl SubmissionService {
         Ok(response)
     }
 
-    pub async fn process_submission(
+    // Runs the same checks and NFC decoding as generate_presigned_urls, but uploads nothing
+    // and creates no submission
+    pub async fn validate_presigned_urls_request(
         &self,
-        submission_id: String,
-        face_match_service: FaceMatchService,
-    ) -> Result<ProcessSubmissionResponse, Vec<ApiError>> {
-        let start = std::time::Instant::now();
+        request: PresignedUrlsRequest,
+    ) -> Result<PresignedUrlsDryRunResponse, Vec<ApiError>> {
+        let PresignedUrlsRequest {
+            submission_type,
+            nfc_identifier,
+            external_reference,
+            expiry_in_seconds,
+            callback_url,
+            ..
+        } = request;
         let mut tags = HashMap::new();
-        tags.insert("endpoint".to_string(), "process_submission".to_string());
+        tags.insert("endpoint".to_string(), "presigned_urls".to_string());
+        tags.insert("submission_type".to_string(), submission_type.to_string());
 
-        // 1. Check if submission exists in database
-        let (submission_type, nfc_identifier, submission_data) = match self.submission_repository.find_submission_by_id(&submission_id).await {
-            Ok(Some((submission_type, nfc_identifier, data))) => (submission_type, nfc_identifier, data),
-            Ok(None) => {
-                self.metrics.increment("process_submission.error", Some(tags.clone()));
-                self.metrics.timing("process_submission.duration", start.elapsed(), Some(tags));
-                return Err(vec![ApiError {
-                    entity: "SOCIO_ECHO_BE".to_string(),
-                    code: "1004".to_string(),
-                    cause: "SUBMISSION_NOT_FOUND".to_string(),
-                }]);
+        let checked = match self.upload_ttl_secs(expiry_in_seconds) {
+            Ok(_) => self.check_callback_url(callback_url.as_deref()).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = checked {
+            self.metrics.increment("api_error", Some(tags));
+            return Err(vec![e]);
+        }
+
+        match self.check_presigned_urls_request(&submission_type, &nfc_identifier).await {
+            Ok((upload_documents, _, nfc_identifier_base64, _)) => {
+                self.metrics.increment("presigned_urls.dry_run", Some(tags));
+                Ok(PresignedUrlsDryRunResponse {
+                    dry_run: true,
+                    submission_type: submission_type.to_string(),
+                    upload_documents: upload_documents.to_vec(),
+                    stored_documents: submission_type_registry::STORED_DOCUMENTS.to_vec(),
+                    nfc_size_bytes: nfc_identifier_base64.len(),
+                    external_reference,
+                })
             }
             Err(e) => {
-                self.metrics.increment("process_submission.error", Some(tags.clone()));
-                self.metrics.timing("process_submission.duration", start.elapsed(), Some(tags));
-                return Err(vec![ApiError {
-                    entity: "SOCIO_ECHO_BE".to_string(),
-                    code: "1002".to_string(),
-                    cause: e.to_string(),
-                }]);
+                self.metrics.increment("api_error", Some(tags));
+                Err(vec![e])
             }
-        };
+        }
+    }
 
+    // Upload URL lifetime: the client's requested one when within the configured max,
+    // otherwise the configured default
+    fn upload_ttl_secs(&self, requested: Option<u64>) -> Result<u64, ApiError> {
+        let max = self.config.presign_upload_max_ttl_secs;
+        match requested {
+            None => Ok(self.config.presign_upload_ttl_secs),
+            Some(ttl) if (1..=max).contains(&ttl) => Ok(ttl),
+            Some(_) => Err(ApiError {
+                entity: "SOCIO_ECHO_BE".to_string(),
+                code: "1003".to_string(),
+                cause: format!("INVALID_EXPIRY: must be between 1 and {} seconds", max),
+            }),
+        }
+    }
 
-        let mut image_url_1 = String::new();
-        let mut image_url_2 = String::new();
+    // Spreads the expiry of URLs created in a burst by up to PRESIGN_UPLOAD_TTL_JITTER_SECS either
+    // way. Returns the lifetime to presign with and the one to advertise, which is the low end
+    // of the band so a URL never expires before the client expects.
+    fn jittered_upload_ttl(&self, ttl_secs: u64) -> (u64, u64) {
+        let jitter = self.config.presign_upload_ttl_jitter_secs;
+        if jitter == 0 {
+            return (ttl_secs, ttl_secs);
+        }
 
-        // 2. Extract document names from submission data
-        let documents_data = match submission_data.as_object() {
-            Some(obj) => obj,
-            None => {
-                self.metrics.increment("process_submission.error", Some(tags.clone()));
-                self.metrics.timing("process_submission.duration", start.elapsed(), Some(tags));
-                return Err(vec![ApiError {
-                    entity: "SOCIO_ECHO_BE".to_string(),
-                    code: "1004".to_string(),
-                    cause: "INVALID_SUBMISSION_DATA".to_string(),
-                }]);
-            }
-        };
+        let low = ttl_secs.saturating_sub(jitter).max(1);
+        let high = (ttl_secs + jitter).min(MAX_PRESIGN_TTL_SECS);
+        (rand::thread_rng().gen_range(low..=high), low)
+    }
 
-        // 3. Get selfie document name
-        let selfie_doc = match documents_data.get("SELFIE") {
-            Some(doc) => doc,
-            None => {
-                self.metrics.increment("process_submission.error", Some(tags.clone()));
-                self.metrics.timing("process_submission.duration", start.elapsed(), Some(tags));
-                return Err(vec![ApiError {
-                    entity: "SOCIO_ECHO_BE".to_string(),
-                    code: "1004".to_string(),
-                    cause: "SELFIE_DOES_NOT_EXIST".to_string(),
-                }]);
-            }
+    // Absolute https URL of a public host, or any URL on an allowlisted host. Rejected while
+    // webhooks are disabled, so a client never waits for a callback that won't come.
+    async fn check_callback_url(&self, callback_url: Option<&str>) -> Result<(), ApiError> {
+        let Some(callback_url) = callback_url else {
+            return Ok(());
         };
-
-        let selfie_filename = match selfie_doc.get("documentName") {
-            Some(name) => name.as_str().unwrap_or(""),
-            None => {
-                self.metrics.increment("process_submission.error", Some(tags.clone()));
-                self.metrics.timing("process_submission.duration", start.elapsed(), Some(tags));
-                return Err(vec![ApiError {
-                    entity: "SOCIO_ECHO_BE".to_string(),
-                    code: "1004".to_string(),
-                    cause: "SELFIE_DOES_NOT_EXIST".to_string(),
-                }]);
-            }
+        let invalid = |reason: &str| ApiError {
+            entity: "SOCIO_ECHO_BE".to_string(),
+            code: "1003".to_string(),
+            cause: format!("INVALID_CALLBACK_URL: {}", reason),
         };
 
-        // 4. Check if selfie exists in MinIO
-        if !self.minio_service.file_exists(selfie_filename.to_string()).await.unwrap_or(false) {
-            self.metrics.increment("process_submission.error", Some(tags.clone()));
-            self.metrics.timing("process_submission.duration", start.elapsed(), Some(tags));
-            return Err(vec![ApiError {
+        if self.config.webhook_secret.is_none() {
+            return Err(invalid("webhooks are not enabled"));
+        }
+        if callback_url.len() > MAX_CALLBACK_URL_LENGTH {
+            return Err(invalid(&format!("longer than {} characters", MAX_CALLBACK_URL_LENGTH)));
+        }
+
+        let url = reqwest::Url::parse(callback_url).map_err(|e| invalid(&e.to_string()))?;
+        let allowlisted = url
+            .host_str()
+            .is_some_and(|host| self.config.webhook_allowed_hosts.iter().any(|allowed| allowed.eq_ignore_ascii_case(host)));
+        let scheme_allowed = url.scheme() == "https" || (allowlisted && url.scheme() == "http");
+        if !scheme_allowed || url.host().is_none() {
+            return Err(invalid("must be an absolute https URL"));
+        }
+
+        resolve_callback_url(callback_url, &self.config.webhook_allowed_hosts)
+            .await
+            .map_err(|reason| invalid(&reason))?;
+
+        Ok(())
+    }
+
+    // Each upload URL is a potential concurrent upload, so bound how many one submission gets
+    fn check_document_limit(&self, upload_documents: &[DocumentType]) -> Result<(), ApiError> {
+        if upload_documents.len() > self.config.max_documents_per_submission {
+            return Err(ApiError {
                 entity: "SOCIO_ECHO_BE".to_string(),
-                code: "1004".to_string(),
-                cause: "SELFIE_DOES_NOT_EXIST".to_string(),
-            }]);
+                code: "1016".to_string(),
+                cause: format!(
+                    "DOCUMENT_LIMIT_EXCEEDED: {} documents requested, max {}",
+                    upload_documents.len(),
+                    self.config.max_documents_per_submission
+                ),
+            });
         }
+        Ok(())
+    }
 
-        // 6. Generate URLs for face matching
-        let selfie_url = match self.minio_service.generate_view_url(selfie_filename.to_string()).await {
-            Ok(url) => url,
-            Err(e) => {
-                self.metrics.increment("process_submission.error", Some(tags.clone()));
-                self.metrics.timing("process_submission.duration", start.elapsed(), Some(tags));
-                return Err(vec![ApiError {
-                    entity: "SOCIO_ECHO_BE".to_string(),
-                    code: "1001".to_string(),
-                    cause: e.to_string(),
-                }]);
-            }
-        };
+    // Returns the documents to hand out upload URLs for, plus the NFC identifier without its
+    // data URL prefix, decoded, and the image format of the decoded bytes
+    async fn check_presigned_urls_request(
+        &self,
+        submission_type: &SubmissionType,
+        nfc_identifier: &str,
+    ) -> Result<(&'static [DocumentType], String, Vec<u8>, ImageFormat), ApiError> {
+        if !self.is_submission_type_enabled(&submission_type.to_string()).await {
+            return Err(ApiError {
+                entity: "SOCIO_ECHO_BE".to_string(),
+                code: "1005".to_string(),
+                cause: "SUBMISSION_TYPE_DISABLED".to_string(),
+            });
+        }
 
-        log::info!("selfie_url: {:?}", selfie_url);
+        let upload_documents = submission_type_registry::get(submission_type).upload_documents;
+        self.check_document_limit(upload_documents)?;
 
-        if submission_type == "KYC" {
+        let nfc_identifier_clean = nfc_identifier
+            .replace("data:image/jpeg;base64,", "")
+            .replace("data:image/png;base64,", "");
+        let nfc_identifier_base64 = STANDARD.decode(&nfc_identifier_clean).map_err(|e| ApiError {
+            entity: "SOCIO_ECHO_BE".to_string(),
+            code: "1007".to_string(),
+            cause: format!("INVALID_NFC_IDENTIFIER: {}", e),
+        })?;
+        if nfc_identifier_base64.is_empty() {
+            return Err(ApiError {
+                entity: "SOCIO_ECHO_BE".to_string(),
+                code: "1007".to_string(),
+                cause: "INVALID_NFC_IDENTIFIER: empty".to_string(),
+            });
+        }
+        if nfc_identifier_base64.len() > self.config.max_nfc_image_bytes {
+            return Err(ApiError {
+                entity: "SOCIO_ECHO_BE".to_string(),
+                code: "1007".to_string(),
+                cause: format!(
+                    "NFC_IMAGE_TOO_LARGE: {} bytes, max {}",
+                    nfc_identifier_base64.len(),
+                    self.config.max_nfc_image_bytes
+                ),
+            });
+        }
 
-            // 5. Get NFC document name
-            let nfc_doc = match documents_data.get("NFC") {
-                Some(doc) => doc,
-                None => {
-                    self.metrics.increment("process_submission.error", Some(tags.clone()));
-                    self.metrics.timing("process_submission.duration", start.elapsed(), Some(tags));
-                    return Err(vec![ApiError {
-                        entity: "SOCIO_ECHO_BE".to_string(),
-                        code: "1004".to_string(),
-                        cause: "NFC_DOES_NOT_EXIST".to_string(),
-                    }]);
-                }
-            };
+        // The data URL prefix is client supplied, so the format is read from the bytes themselves
+        let nfc_image_format = ImageFormat::detect(&nfc_identifier_base64)
+            .filter(|format| self.config.nfc_image_formats.contains(format))
+            .ok_or_else(|| ApiError {
+                entity: "SOCIO_ECHO_BE".to_string(),
+                code: "1007".to_string(),
+                cause: format!(
+                    "INVALID_NFC_IMAGE_FORMAT: must be one of {}",
+                    self.config
+                        .nfc_image_formats
+                        .iter()
+                        .map(|format| format.as_str())
+                        .collect::<Vec<&str>>()
+                        .join(", ")
+                ),
+            })?;
 
-            let nfc_filename = match nfc_doc.get("documentName") {
-                Some(name) => name.as_str().unwrap_or(""),
-                None => {
-                    self.metrics.increment("process_submission.error", Some(tags.clone()));
-                    self.metrics.timing("process_submission.duration", start.elapsed(), Some(tags));
-                    return Err(vec![ApiError {
-                        entity: "SOCIO_ECHO_BE".to_string(),
-                        code: "1004".to_string(),
-                        cause: "NFC_DOES_NOT_EXIST".to_string(),
-                    }]);
-                }
-            };
+        Ok((upload_documents, nfc_identifier_clean, nfc_identifier_base64, nfc_image_format))
+    }
 
-            let nfc_url = match self.minio_service.generate_view_url(nfc_filename.to_string()).await {
-                Ok(url) => url,
-                Err(e) => {
-                    self.metrics.increment("process_submission.error", Some(tags.clone()));
-                    self.metrics.timing("process_submission.duration", start.elapsed(), Some(tags));
-                    return Err(vec![ApiError {
-                        entity: "SOCIO_ECHO_BE".to_string(),
-                        code: "1001".to_string(),
-                        cause: e.to_string(),
-                    }]);
-                }
-            };
+    // INITIATED submission for the same identifier and type inside the dedupe window, with
+    // fresh upload URLs for its documents. Rejected or approved ones never match, so
+    // resubmitting after a rejection still creates a new submission.
+    async fn find_in_progress_submission(
+        &self,
+        user_id: &str,
+        submission_type: &SubmissionType,
+        nfc_identifier_clean: &str,
+        risk_tier: Option<&str>,
+        upload_ttl_secs: u64,
+    ) -> Result<Option<PresignedUrlsResponse>, ApiError> {
+        let since = chrono::Utc::now() - chrono::Duration::seconds(self.config.submission_dedupe_window_secs as i64);
+        let existing = self
+            .submission_repository
+            .find_recent_submission(
+                user_id,
+                &nfc_identifier_clean.chars().take(500).collect::<String>(),
+                &submission_type.to_string(),
+                risk_tier,
+                "INITIATED",
+                since,
+            )
+            .await
+            .map_err(|e| ApiError {
+                entity: "SOCIO_ECHO_BE".to_string(),
+                code: "1002".to_string(),
+                cause: e.to_string(),
+            })?;
 
-            log::info!("nfc_url: {:?}", nfc_url);
+        let (submission_id, submission_data) = match existing {
+            Some(existing) => existing,
+            None => return Ok(None),
+        };
 
-            image_url_1 = nfc_url;
-            image_url_2 = selfie_url;
+        self.existing_presigned_urls(submission_id, submission_type, &submission_data, upload_ttl_secs).await
+    }
 
-        } else if submission_type == "ON_DEMAND" {
+    // Submission created earlier with this Idempotency-Key, with fresh upload URLs. The key
+    // can't be reused for a different request (1018).
+    async fn replay_idempotent_request(
+        &self,
+        user_id: &str,
+        idempotency_key: &str,
+        request_hash: &str,
+        submission_type: &SubmissionType,
+        upload_ttl_secs: u64,
+    ) -> Result<Option<PresignedUrlsResponse>, ApiError> {
+        let error = |code: &str, cause: String| ApiError {
+            entity: "SOCIO_ECHO_BE".to_string(),
+            code: code.to_string(),
+            cause,
+        };
 
-            // 1. Check if submission exists in database
-            let submission_data_existing = match self.submission_repository.find_submission_by_nfc_identifier_and_status(&nfc_identifier, "APPROVED").await {
-                Ok(Some(submission_data_existing)) => submission_data_existing,
-                Ok(None) => {
-                    self.metrics.increment("process_submission.error", Some(tags.clone()));
-                    self.metrics.timing("process_submission.duration", start.elapsed(), Some(tags));
-                    return Err(vec![ApiError {
-                        entity: "SOCIO_ECHO_BE".to_string(),
-                        code: "1004".to_string(),
-                        cause: "SUBMISSION_NOT_FOUND".to_string(),
-                    }]);
-                }
-                Err(e) => {
-                    self.metrics.increment("process_submission.error", Some(tags.clone()));
-                    self.metrics.timing("process_submission.duration", start.elapsed(), Some(tags));
-                    return Err(vec![ApiError {
-                        entity: "SOCIO_ECHO_BE".to_string(),
-                        code: "1002".to_string(),
-                        cause: e.to_string(),
-                    }]);
-                }
-            };
+        let existing = self
+            .submission_repository
+            .find_by_idempotency_key(user_id, idempotency_key)
+            .await
+            .map_err(|e| error("1002", e.to_string()))?;
 
-            // 2. Extract document names from submission data
-            let documents_data_existing = match submission_data_existing.as_object() {
-                Some(obj) => obj,
-                None => {
-                    self.metrics.increment("process_submission.error", Some(tags.clone()));
-                    self.metrics.timing("process_submission.duration", start.elapsed(), Some(tags));
-                    return Err(vec![ApiError {
-                        entity: "SOCIO_ECHO_BE".to_string(),
-                        code: "1004".to_string(),
-                        cause: "INVALID_SUBMISSION_DATA".to_string(),
-                    }]);
-                }
-            };
+        let Some((submission_id, stored_request_hash, submission_data)) = existing else {
+            return Ok(None);
+        };
+        if stored_request_hash.as_deref() != Some(request_hash) {
+            return Err(error("1018", "IDEMPOTENCY_KEY_REUSED".to_string()));
+        }
 
-            // 3. Get selfie document name
-            let selfie_doc_existing = match documents_data_existing.get("SELFIE") {
-                Some(doc) => doc,
-                None => {
-                    self.metrics.increment("process_submission.error", Some(tags.clone()));
-                    self.metrics.timing("process_submission.duration", start.elapsed(), Some(tags));
-                    return Err(vec![ApiError {
-                        entity: "SOCIO_ECHO_BE".to_string(),
-                        code: "1004".to_string(),
-                        cause: "SELFIE_DOES_NOT_EXIST".to_string(),
-                    }]);
-                }
-            };
+        self.existing_presigned_urls(submission_id, submission_type, &submission_data, upload_ttl_secs)
+            .await?
+            .map(Some)
+            .ok_or_else(|| error("1000", "IDEMPOTENT_SUBMISSION_UNAVAILABLE".to_string()))
+    }
 
-            let selfie_filename_existing = match selfie_doc_existing.get("documentName") {
-                Some(name) => name.as_str().unwrap_or(""),
-                None => {
-                    self.metrics.increment("process_submission.error", Some(tags.clone()));
-                    self.metrics.timing("process_submission.duration", start.elapsed(), Some(tags));
-                    return Err(vec![ApiError {
-                        entity: "SOCIO_ECHO_BE".to_string(),
-                        code: "1004".to_string(),
-                        cause: "SELFIE_DOES_NOT_EXIST".to_string(),
-                    }]);
-                }
+    // Response for an existing submission with fresh upload URLs for its stored documents;
+    // None when the stored data doesn't match the type's current document set
+    async fn existing_presigned_urls(
+        &self,
+        submission_id: Uuid,
+        submission_type: &SubmissionType,
+        submission_data: &Value,
+        upload_ttl_secs: u64,
+    ) -> Result<Option<PresignedUrlsResponse>, ApiError> {
+        let upload_documents = submission_type_registry::get(submission_type).upload_documents;
+        self.check_document_limit(upload_documents)?;
+
+        let mut documents = HashMap::new();
+        let (presign_ttl_secs, advertised_ttl_secs) = self.jittered_upload_ttl(upload_ttl_secs);
+        for document in upload_documents {
+            let stored = &submission_data[document.as_str()];
+            let (document_name, document_reference) = match (stored["documentName"].as_str(), stored["documentReference"].as_str()) {
+                (Some(name), Some(reference)) => (name, reference),
+                _ => return Ok(None),
             };
 
-            // 4. Check if selfie exists in MinIO
-            if !self.minio_service.file_exists(selfie_filename_existing.to_string()).await.unwrap_or(false) {
-                self.metrics.increment("process_submission.error", Some(tags.clone()));
-                self.metrics.timing("process_submission.duration", start.elapsed(), Some(tags));
-                return Err(vec![ApiError {
-                    entity: "SOCIO_ECHO_BE".to_string(),
-                    code: "1004".to_string(),
-                    cause: "SELFIE_DOES_NOT_EXIST".to_string(),
-                }]);
-            }
+            let document_url = self.minio_service
+                .generate_upload_url(document_name.to_string(), Duration::from_secs(presign_ttl_secs))
+                .await
+                .map_err(minio_error)?;
 
-            // 6. Generate URLs for face matching
-            let selfie_url_existing = match self.minio_service.generate_view_url(selfie_filename_existing.to_string()).await {
-                Ok(url) => url,
-                Err(e) => {
-                    self.metrics.increment("process_submission.error", Some(tags.clone()));
-                    self.metrics.timing("process_submission.duration", start.elapsed(), Some(tags));
-                    return Err(vec![ApiError {
-                        entity: "SOCIO_ECHO_BE".to_string(),
-                        code: "1001".to_string(),
-                        cause: e.to_string(),
-                    }]);
-                }
-            };
+            documents.insert(
+                *document,
+                Document {
+                    document_url,
+                    document_reference: document_reference.to_string(),
+                    expiry_in_seconds: advertised_ttl_secs.to_string(),
+                },
+            );
+        }
 
-            log::info!("selfie_url_existing: {:?}", selfie_url_existing);
+        Ok(Some(PresignedUrlsResponse {
+            submission_id: submission_id.to_string(),
+            documents,
+        }))
+    }
 
-            image_url_1 = selfie_url_existing;
-            image_url_2 = selfie_url;
+    // Stores a document sent through the backend instead of to its presigned URL. It lands under
+    // the same object name, so processing can't tell the two apart. Only INITIATED submissions
+    // take uploads, and only for documents their type expects. The size limit is enforced by the
+    // caller while reading the body.
+    pub async fn upload_document(
+        &self,
+        user_id: &str,
+        submission_id: Uuid,
+        document: DocumentType,
+        declared_content_type: Option<&str>,
+        content: Vec<u8>,
+    ) -> Result<DocumentUploadResponse, Vec<ApiError>> {
+        let mut tags = HashMap::new();
+        tags.insert("document".to_string(), document.to_string());
+        let error = |code: &str, cause: String| vec![ApiError {
+            entity: "SOCIO_ECHO_BE".to_string(),
+            code: code.to_string(),
+            cause,
+        }];
 
-        } else {
-            return Err(vec![ApiError {
-                entity: "SOCIO_ECHO_BE".to_string(),
-                code: "1004".to_string(),
-                cause: "INVALID_SUBMISSION_TYPE".to_string(),
-            }]);
+        let (submission_type, status, submission_data) =
+            match self.submission_repository.find_owned_submission(submission_id, user_id).await {
+                Ok(Some(found)) => found,
+                Ok(None) => return Err(error("1004", "SUBMISSION_NOT_FOUND".to_string())),
+                Err(e) => return Err(error("1002", e.to_string())),
+            };
+        if status != "INITIATED" {
+            return Err(error("1020", format!("UPLOADS_CLOSED: submission is {}", status)));
         }
 
-        // 7. Perform face matching
-        let face_match_result = match face_match_service.compare_faces(
-            image_url_1,
-            image_url_2,
-            submission_id.clone(),
-        ).await {
-            Ok(result) => result,
-            Err(e) => {
-                self.metrics.increment("process_submission.error", Some(tags.clone()));
-                self.metrics.timing("process_submission.duration", start.elapsed(), Some(tags));
-                return Err(vec![ApiError {
-                    entity: "SOCIO_ECHO_BE".to_string(),
-                    code: "1006".to_string(),
-                    cause: e.to_string(),
-                }]);
-            }
-        };
+        let expected = submission_type_registry::find(&submission_type)
+            .map(|config| config.upload_documents.contains(&document))
+            .unwrap_or(false);
+        if !expected {
+            return Err(error("1003", format!("UNEXPECTED_DOCUMENT: {} is not uploaded for {}", document, submission_type)));
+        }
 
-        // 8. Update submission status based on face match result
-        let new_status = if face_match_result.is_match { "APPROVED" } else { "REJECTED" };
-        
-        if let Err(e) = self.submission_repository.update_submission_status(&submission_id, new_status).await {
-            self.metrics.increment("process_submission.error", Some(tags.clone()));
-            self.metrics.timing("process_submission.duration", start.elapsed(), Some(tags));
-            return Err(vec![ApiError {
-                entity: "SOCIO_ECHO_BE".to_string(),
-                code: "1002".to_string(),
-                cause: e.to_string(),
-            }]);
+        if content.is_empty() {
+            return Err(error("1007", "INVALID_DOCUMENT_FORMAT: empty".to_string()));
         }
+        // The bytes decide the format; a declared content type only has to agree with them
+        let format = ImageFormat::detect(&content)
+            .filter(|format| self.config.document_upload_formats.contains(format))
+            .filter(|format| declared_content_type.is_none_or(|declared| declared == format.content_type()))
+            .ok_or_else(|| {
+                error(
+                    "1007",
+                    format!(
+                        "INVALID_DOCUMENT_FORMAT: must be one of {}",
+                        self.config
+                            .document_upload_formats
+                            .iter()
+                            .map(ImageFormat::as_str)
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                )
+            })?;
 
-        // 9. Return response
-        let response = ProcessSubmissionResponse {
-            submission_status: new_status.to_string(),
+        let Value::Object(mut documents_data) = submission_data else {
+            return Err(error("1004", "INVALID_SUBMISSION_DATA".to_string()));
+        };
+        let (Some(filename), Some(document_reference)) = (
+            document_name(&documents_data, document),
+            documents_data
+                .get(document.as_str())
+                .and_then(|entry| entry.get("documentReference"))
+                .and_then(Value::as_str)
+                .map(str::to_string),
+        ) else {
+            return Err(error("1004", format!("{}_DOES_NOT_EXIST", document)));
         };
 
-        self.metrics.increment("process_submission.success", Some(tags.clone()));
-        self.metrics.timing("process_submission.duration", start.elapsed(), Some(tags));
-
-        Ok(response)
-    }
+        if let Err(e) = self
+            .minio_service
+            .upload_file(filename.clone(), content, Some(format.content_type().to_string()))
+            .await
+        {
+            self.metrics.increment("document_upload.error", Some(tags));
+            return Err(vec![minio_error(e)]);
+        }
 
-    pub async fn get_submission_status(
-        &self,
-        submission_type: SubmissionType,
-        nfc_identifier: String,
-    ) -> Result<GetSubmissionStatusResponse, Vec<ApiError>> {
-        let submission_data= match self.submission_repository.find_submission_by_nfc_identifier_and_submission_type(&submission_type.to_string(), &nfc_identifier.chars().take(500).collect::<String>()).await {
-            Ok(Some(status)) => status,
+        if let Some(entry) = documents_data.get_mut(document.as_str()).and_then(Value::as_object_mut) {
+            entry.insert("documentName".to_string(), json!(filename));
+            entry.insert("uploadStatus".to_string(), json!(UploadStatus::Uploaded));
+        }
+        if let Err(e) = self
+            .submission_repository
+            .update_submission_data(&submission_id.to_string(), &Value::Object(documents_data))
+            .await
+        {
+            // The object is stored either way; processing re-checks storage for upload statuses
+            self.metrics.increment("document_upload.error", Some(tags));
+            return Err(error("1002", e.to_string()));
+        }
+
+        self.metrics.increment("document_upload.success", Some(tags));
+        Ok(DocumentUploadResponse {
+            submission_id: submission_id.to_string(),
+            document_type: document,
+            document_reference,
+            upload_status: UploadStatus::Uploaded,
+        })
+    }
+
+    // With `user_id`, only that user's submission is processed and anyone else's is
+    // SUBMISSION_NOT_FOUND. Reprocessing runs without one.
+    pub async fn process_submission(
+        &self,
+        user_id: Option<&str>,
+        submission_id: String,
+        face_match_service: FaceMatchService,
+        webhooks: WebhookService,
+    ) -> Result<ProcessSubmissionResponse, Vec<ApiError>> {
+        let start = std::time::Instant::now();
+        let mut tags = HashMap::new();
+        tags.insert("endpoint".to_string(), "process_submission".to_string());
+
+        // 1. Check if submission exists in database
+        let (submission_type, nfc_identifier, risk_tier, submission_data) = match self.submission_repository.find_submission_by_id(&submission_id, user_id).await {
+            Ok(Some(found)) => found,
+            Ok(None) => return Err(self.process_error(tags, start, "1004", "SUBMISSION_NOT_FOUND".to_string())),
+            Err(e) => return Err(self.process_error(tags, start, "1002", e.to_string())),
+        };
+
+        let type_config = match submission_type_registry::find(&submission_type) {
+            Some(config) => config,
+            None => return Err(self.process_error(tags, start, "1004", "INVALID_SUBMISSION_TYPE".to_string())),
+        };
+
+        if !self.is_submission_type_enabled(&submission_type).await {
+            return Err(self.process_error(tags, start, "1005", "SUBMISSION_TYPE_DISABLED".to_string()));
+        }
+
+        // 2. Extract document names from submission data
+        let mut documents_data = match submission_data {
+            // Every submission stores at least its NFC document at creation, so an empty object
+            // (or unparsable data, read back as one) means the row is corrupt, not a partial upload
+            Value::Object(obj) if obj.is_empty() => {
+                return Err(self.process_error(tags, start, "1004", "NO_DOCUMENTS".to_string()));
+            }
+            Value::Object(obj) => obj,
+            _ => return Err(self.process_error(tags, start, "1004", "INVALID_SUBMISSION_DATA".to_string())),
+        };
+        self.refresh_upload_statuses(&submission_id, type_config.upload_documents, &mut documents_data).await;
+        let documents_data = &documents_data;
+
+        // 3. Generate the view URL of the selfie uploaded for this submission
+        let selfie_url = match self.uploaded_selfie_url(documents_data).await {
+            Ok(url) => url,
+            Err((code, cause)) => return Err(self.process_error(tags, start, code, cause)),
+        };
+
+        // 4. Resolve the reference image according to the submission type, keeping the
+        // approved submission it came from so the decision can be checked against it
+        let reference = match type_config.strategy {
+            ProcessingStrategy::CompareWithDocument(document) => {
+                self.stored_document_url(documents_data, document).await.map(|url| (url, None))
+            }
+            ProcessingStrategy::CompareWithApprovedSelfie => {
+                self.approved_selfie_url(&nfc_identifier)
+                    .await
+                    .map(|(url, id, approved_at)| (url, Some((id, approved_at))))
+            }
+        };
+        let (reference_url, reference) = match reference {
+            Ok(reference) => reference,
+            Err((code, cause)) => return Err(self.process_error(tags, start, code, cause)),
+        };
+        let reference_submission_id = reference.map(|(id, _)| id);
+
+        // 5. Perform face matching. PROCESSING and PENDING_RETRY let submissions caught
+        // by a provider outage be picked up again by the reprocessing job.
+        if let Err(e) = self.submission_repository.update_submission_status(&submission_id, "PROCESSING").await {
+            return Err(self.process_error(tags, start, "1002", e.to_string()));
+        }
+
+        let threshold = risk_threshold(
+            &self.config.risk_tier_thresholds,
+            risk_tier.as_deref(),
+            type_config.threshold.unwrap_or(face_match_service.get_threshold()),
+        );
+        let capture_inputs = self.sample_capture().then(|| (reference_url.clone(), selfie_url.clone()));
+        let metadata = face_match_metadata(&submission_type, type_config.strategy, risk_tier.as_deref());
+        let face_match_result = match face_match_service.compare_faces_with_threshold(
+            reference_url,
+            selfie_url,
+            submission_id.clone(),
+            threshold,
+            Some(metadata),
+        ).await {
+            Ok(result) => result,
+            Err(e) => {
+                if let Err(e) = self.submission_repository.update_submission_status(&submission_id, "PENDING_RETRY").await {
+                    log::error!("Failed to mark submission {} for retry: {}", submission_id, e);
+                }
+                return Err(self.process_error(tags, start, face_match_service::error_code(&e), e.to_string()));
+            }
+        };
+
+        if let Some((reference_submission_id, reference_approved_at)) = reference {
+            log::info!(
+                "{}",
+                reference_match_event(
+                    &submission_id,
+                    &nfc_identifier,
+                    reference_submission_id,
+                    reference_approved_at,
+                    threshold,
+                    &face_match_result,
+                )
+            );
+        }
+
+        if let Some((reference_url, selfie_url)) = capture_inputs {
+            self.capture_face_match(&submission_id, &submission_type, &reference_url, &selfie_url, threshold, &face_match_result).await;
+        }
+
+        // 6. Update submission status based on face match result
+        let new_status = self.decide(face_match_result.is_match, face_match_result.similarity_score, threshold);
+
+        let decision = Decision {
+            face_match_result: &face_match_result,
+            threshold,
+            status: new_status,
+        };
+        match self.record_decision(&submission_id, &submission_type, &nfc_identifier, reference_submission_id, decision).await {
+            Ok(true) => {}
+            Ok(false) => {
+                // Compared against a reference that is no longer current, so retry with the new one
+                if let Err(e) = self.submission_repository.update_submission_status(&submission_id, "PENDING_RETRY").await {
+                    log::error!("Failed to mark submission {} for retry: {}", submission_id, e);
+                }
+                return Err(self.process_error(tags, start, "1013", "REFERENCE_CHANGED".to_string()));
+            }
+            Err(e) => return Err(self.process_error(tags, start, "1002", e.to_string())),
+        }
+
+        // 7. Let the client know through its callback URL, if it gave one. Submissions waiting
+        // for review are decided later, by a human.
+        if webhooks.is_enabled() && new_status != "REQUIRES_REVIEW" {
+            let outcome = Some((face_match_result.similarity_score, threshold));
+            self.notify_decision(&webhooks, &submission_id, new_status, outcome).await;
+        }
+
+        // 8. Return response
+        let response = ProcessSubmissionResponse {
+            submission_status: new_status.to_string(),
+            similarity_score: face_match_result.similarity_score,
+            threshold,
+        };
+
+        self.metrics.increment("process_submission.success", Some(tags.clone()));
+        self.metrics.timing("process_submission.duration", start.elapsed(), Some(tags));
+
+        Ok(response)
+    }
+
+    // The decision is already recorded, so a failure here is logged and never fails processing.
+    // `outcome` is the similarity score and threshold the decision was made on, absent when a
+    // reviewer decided.
+    async fn notify_decision(
+        &self,
+        webhooks: &WebhookService,
+        submission_id: &str,
+        status: &str,
+        outcome: Option<(f64, f64)>,
+    ) {
+        let Ok(submission_uuid) = Uuid::parse_str(submission_id) else {
+            return;
+        };
+
+        let mut payload = json!({
+            "event": webhook_service::SUBMISSION_DECIDED,
+            "submissionId": submission_id,
+            "status": status,
+            "decidedAt": Utc::now(),
+        });
+        if let Some((similarity_score, threshold)) = outcome {
+            payload["similarityScore"] = json!(similarity_score);
+            payload["threshold"] = json!(threshold);
+        }
+
+        match self.submission_repository.find_callback_url(submission_uuid).await {
+            Ok(Some(callback_url)) => webhooks.deliver(
+                submission_uuid,
+                callback_url,
+                webhook_service::SUBMISSION_DECIDED,
+                payload,
+            ),
+            Ok(None) => {}
+            Err(e) => log::error!("Failed to load the callback URL of {}: {}", submission_id, e),
+        }
+    }
+
+    fn decide(&self, is_match: bool, similarity_score: f64, threshold: f64) -> &'static str {
+        decide_status(is_match, similarity_score, threshold, self.config.face_match_review_margin)
+    }
+
+    fn sample_capture(&self) -> bool {
+        let rate = self.config.face_match_capture_sample_rate;
+        rate > 0.0 && rand::thread_rng().gen_bool(rate)
+    }
+
+    // Keeps the compared objects and the provider response for offline evaluation. URLs are
+    // stored without their presigned query so they identify the object and hold no credentials.
+    // Capture is best effort and never affects the decision.
+    async fn capture_face_match(
+        &self,
+        submission_id: &str,
+        submission_type: &str,
+        reference_url: &str,
+        selfie_url: &str,
+        threshold: f64,
+        face_match_result: &FaceMatchResponse,
+    ) {
+        let unsigned = |url: &str| url.split('?').next().unwrap_or_default().to_string();
+
+        if let Err(e) = self.submission_repository.create_face_match_capture(
+            submission_id,
+            submission_type,
+            &unsigned(reference_url),
+            &unsigned(selfie_url),
+            threshold,
+            &json!(face_match_result),
+        ).await {
+            self.metrics.increment("face_match_capture.error", None);
+            log::error!("Failed to capture face match for {}: {}", submission_id, e);
+            return;
+        }
+
+        self.metrics.increment("face_match_capture.stored", None);
+    }
+
+    // Writes the audit and the status in one transaction, holding the identifier's lock so
+    // decisions for it are recorded one at a time. When the decision used an approved
+    // reference, the current one is read on the primary under that lock and nothing is written
+    // (returns false) if it is no longer the latest approved submission for the identifier.
+    async fn record_decision(
+        &self,
+        submission_id: &str,
+        submission_type: &str,
+        nfc_identifier: &str,
+        reference_submission_id: Option<Uuid>,
+        decision: Decision<'_>,
+    ) -> Result<bool, sqlx::Error> {
+        let Decision { face_match_result, threshold, status } = decision;
+        let mut tx = self.submission_repository.begin().await?;
+        if !nfc_identifier.is_empty() {
+            SubmissionRepository::lock_nfc_identifier(&mut tx, nfc_identifier).await?;
+        }
+
+        if let Some(reference_submission_id) = reference_submission_id {
+            let current = SubmissionRepository::find_latest_submission_by_status(&mut tx, nfc_identifier, "APPROVED").await?;
+            if current != Some(reference_submission_id) {
+                return Ok(false);
+            }
+        }
+
+        SubmissionRepository::create_face_match_audit(
+            &mut tx,
+            submission_id,
+            submission_type,
+            face_match_result.similarity_score,
+            threshold,
+            face_match_result.is_match,
+        ).await?;
+        SubmissionRepository::update_face_match_result(
+            &mut tx,
+            submission_id,
+            &json!({
+                "similarityScore": face_match_result.similarity_score,
+                "threshold": threshold,
+                "isMatch": face_match_result.is_match,
+            }),
+        ).await?;
+        SubmissionRepository::set_submission_status(&mut tx, submission_id, status).await?;
+        if status == "REQUIRES_REVIEW" {
+            if let Ok(submission_uuid) = Uuid::parse_str(submission_id) {
+                SubmissionRepository::create_status_history(
+                    &mut tx,
+                    submission_uuid,
+                    "PROCESSING",
+                    status,
+                    "FACE_MATCH_WITHIN_REVIEW_MARGIN",
+                    "system",
+                ).await?;
+            }
+        }
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    // Re-derives the status from the latest recorded face match, without calling the
+    // provider. Overwriting a different terminal decision needs `confirm`.
+    pub async fn recompute_status(
+        &self,
+        submission_id: &str,
+        confirm: bool,
+        actor: &str,
+        webhooks: &WebhookService,
+    ) -> Result<RecomputeStatusResponse, Vec<ApiError>> {
+        let db_error = |e: sqlx::Error| vec![ApiError {
+            entity: "SOCIO_ECHO_BE".to_string(),
+            code: "1002".to_string(),
+            cause: e.to_string(),
+        }];
+        let error = |code: &str, cause: &str| vec![ApiError {
+            entity: "SOCIO_ECHO_BE".to_string(),
+            code: code.to_string(),
+            cause: cause.to_string(),
+        }];
+
+        let submission_uuid = Uuid::parse_str(submission_id).map_err(|_| error("1003", "INVALID_SUBMISSION_ID"))?;
+        let mut tx = self.submission_repository.begin().await.map_err(db_error)?;
+
+        let previous_status = SubmissionRepository::lock_submission_status(&mut tx, submission_uuid)
+            .await
+            .map_err(db_error)?
+            .ok_or_else(|| error("1004", "SUBMISSION_NOT_FOUND"))?;
+        if previous_status == "DELETED" {
+            return Err(error("1004", "SUBMISSION_DELETED"));
+        }
+
+        let (is_match, similarity_score, threshold) = SubmissionRepository::find_latest_face_match_outcome(&mut tx, submission_uuid)
+            .await
+            .map_err(db_error)?
+            .ok_or_else(|| error("1004", "NO_FACE_MATCH_RECORD"))?;
+        let new_status = self.decide(is_match, similarity_score, threshold);
+
+        let changed = previous_status != new_status;
+        let terminal = matches!(previous_status.as_str(), "APPROVED" | "REJECTED" | "REQUIRES_REVIEW");
+        if changed && terminal && !confirm {
+            return Err(error("1015", "CONFIRMATION_REQUIRED"));
+        }
+
+        if changed {
+            SubmissionRepository::set_submission_status(&mut tx, submission_id, new_status)
+                .await
+                .map_err(db_error)?;
+            SubmissionRepository::create_status_history(
+                &mut tx,
+                submission_uuid,
+                &previous_status,
+                new_status,
+                "RECOMPUTED_FROM_FACE_MATCH",
+                actor,
+            )
+            .await
+            .map_err(db_error)?;
+            AuditLogRepository::create(
+                &mut tx,
+                "SUBMISSION_STATUS_RECOMPUTED",
+                "SUBMISSION",
+                submission_id,
+                actor,
+                json!({ "previousStatus": previous_status, "status": new_status }),
+            )
+            .await
+            .map_err(db_error)?;
+        }
+
+        tx.commit().await.map_err(db_error)?;
+
+        if webhooks.is_enabled() && leaves_review(&previous_status, new_status) {
+            self.notify_decision(webhooks, submission_id, new_status, Some((similarity_score, threshold))).await;
+        }
+
+        Ok(RecomputeStatusResponse {
+            submission_id: submission_id.to_string(),
+            previous_status,
+            submission_status: new_status.to_string(),
+            changed,
+        })
+    }
+
+    // All or nothing: any unknown id or disallowed transition fails the whole batch.
+    // Submissions already in the target status are left untouched.
+    pub async fn bulk_update_status(
+        &self,
+        submission_ids: &[String],
+        status: &str,
+        reason: &str,
+        actor: &str,
+        webhooks: &WebhookService,
+    ) -> Result<BulkStatusResponse, Vec<ApiError>> {
+        let db_error = |e: sqlx::Error| vec![ApiError {
+            entity: "SOCIO_ECHO_BE".to_string(),
+            code: "1002".to_string(),
+            cause: e.to_string(),
+        }];
+        let error = |code: &str, cause: String| ApiError {
+            entity: "SOCIO_ECHO_BE".to_string(),
+            code: code.to_string(),
+            cause,
+        };
+
+        if !matches!(status, "APPROVED" | "REJECTED" | "PENDING_RETRY") {
+            return Err(vec![error("1003", format!("INVALID_TARGET_STATUS: {}", status))]);
+        }
+
+        let mut submission_uuids = Vec::new();
+        let mut errors = Vec::new();
+        for submission_id in submission_ids {
+            match Uuid::parse_str(submission_id) {
+                Ok(uuid) if !submission_uuids.contains(&uuid) => submission_uuids.push(uuid),
+                Ok(_) => {}
+                Err(_) => errors.push(error("1003", format!("INVALID_SUBMISSION_ID: {}", submission_id))),
+            }
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        let mut tx = self.submission_repository.begin().await.map_err(db_error)?;
+
+        let locked: HashMap<Uuid, String> = SubmissionRepository::lock_submission_statuses(&mut tx, &submission_uuids)
+            .await
+            .map_err(db_error)?
+            .into_iter()
+            .collect();
+
+        for submission_uuid in &submission_uuids {
+            match locked.get(submission_uuid) {
+                None => errors.push(error("1004", format!("SUBMISSION_NOT_FOUND: {}", submission_uuid))),
+                Some(previous) if previous != status && !is_allowed_correction(previous, status) => {
+                    errors.push(error(
+                        "1017",
+                        format!("ILLEGAL_STATUS_TRANSITION: {} {} -> {}", submission_uuid, previous, status),
+                    ));
+                }
+                Some(_) => {}
+            }
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        let mut submissions = Vec::new();
+        for submission_uuid in submission_uuids {
+            let previous_status = locked[&submission_uuid].clone();
+            let changed = previous_status != status;
+
+            if changed {
+                SubmissionRepository::set_submission_status(&mut tx, &submission_uuid.to_string(), status)
+                    .await
+                    .map_err(db_error)?;
+                SubmissionRepository::create_status_history(
+                    &mut tx,
+                    submission_uuid,
+                    &previous_status,
+                    status,
+                    reason,
+                    actor,
+                )
+                .await
+                .map_err(db_error)?;
+            }
+
+            submissions.push(BulkStatusItem {
+                submission_id: submission_uuid.to_string(),
+                previous_status,
+                changed,
+            });
+        }
+
+        tx.commit().await.map_err(db_error)?;
+
+        if webhooks.is_enabled() {
+            for item in submissions.iter().filter(|item| item.changed && leaves_review(&item.previous_status, status)) {
+                self.notify_decision(webhooks, &item.submission_id, status, None).await;
+            }
+        }
+
+        Ok(BulkStatusResponse {
+            submission_status: status.to_string(),
+            submissions,
+        })
+    }
+
+    // Records the failed processing metrics and builds the error returned to the client
+    fn process_error(
+        &self,
+        tags: HashMap<String, String>,
+        start: std::time::Instant,
+        code: &str,
+        cause: String,
+    ) -> Vec<ApiError> {
+        self.metrics.increment("process_submission.error", Some(tags.clone()));
+        self.metrics.timing("process_submission.duration", start.elapsed(), Some(tags));
+        vec![ApiError {
+            entity: "SOCIO_ECHO_BE".to_string(),
+            code: code.to_string(),
+            cause,
+        }]
+    }
+
+    // Records which client-uploaded documents are in storage so status responses can tell
+    // what is still missing. A failed write only loses the report, so it doesn't block processing.
+    async fn refresh_upload_statuses(
+        &self,
+        submission_id: &str,
+        upload_documents: &[DocumentType],
+        documents_data: &mut Map<String, Value>,
+    ) {
+        let mut changed = false;
+
+        for document in upload_documents {
+            let Some(filename) = document_name(documents_data, *document) else {
+                continue;
+            };
+
+            // A failed check leaves the recorded status as it was
+            let status = match self.minio_service.file_exists(filename).await {
+                Ok(true) => UploadStatus::Uploaded,
+                Ok(false) => UploadStatus::Pending,
+                Err(e) => {
+                    log::warn!("Failed to check the upload of {} for {}: {}", document, submission_id, e);
+                    continue;
+                }
+            };
+
+            if let Some(entry) = documents_data.get_mut(document.as_str()).and_then(Value::as_object_mut) {
+                let status = json!(status);
+                if entry.get("uploadStatus") != Some(&status) {
+                    entry.insert("uploadStatus".to_string(), status);
+                    changed = true;
+                }
+            }
+        }
+
+        if changed {
+            let data = Value::Object(documents_data.clone());
+            if let Err(e) = self.submission_repository.update_submission_data(submission_id, &data).await {
+                log::error!("Failed to record upload statuses for {}: {}", submission_id, e);
+            }
+        }
+    }
+
+    // View URL of the selfie, after checking the client actually uploaded it
+    async fn uploaded_selfie_url(
+        &self,
+        documents_data: &Map<String, Value>,
+    ) -> Result<String, (&'static str, String)> {
+        let selfie_filename = document_name(documents_data, DocumentType::Selfie)
+            .ok_or(("1004", "SELFIE_DOES_NOT_EXIST".to_string()))?;
+
+        match self.minio_service.file_exists(selfie_filename.clone()).await {
+            Ok(true) => {}
+            Ok(false) => return Err(("1004", "SELFIE_DOES_NOT_EXIST".to_string())),
+            Err(e) => return Err((minio_code(&e), e.to_string())),
+        }
+
+        self.minio_service
+            .generate_view_url(selfie_filename, ContentDisposition::Inline, Duration::from_secs(self.config.presign_view_ttl_secs))
+            .await
+            .map_err(|e| (minio_code(&e), e.to_string()))
+    }
+
+    // View URL of a document stored on the submission by the backend itself
+    async fn stored_document_url(
+        &self,
+        documents_data: &Map<String, Value>,
+        document: DocumentType,
+    ) -> Result<String, (&'static str, String)> {
+        let filename = document_name(documents_data, document)
+            .ok_or(("1004", format!("{}_DOES_NOT_EXIST", document)))?;
+
+        self.minio_service
+            .generate_view_url(filename, ContentDisposition::Inline, Duration::from_secs(self.config.presign_view_ttl_secs))
+            .await
+            .map_err(|e| (minio_code(&e), e.to_string()))
+    }
+
+    // View URL of the selfie from the latest approved submission for the same identifier
+    async fn approved_selfie_url(&self, nfc_identifier: &str) -> Result<(String, Uuid, DateTime<Utc>), (&'static str, String)> {
+        let (reference_submission_id, approved_at, submission_data_existing) = match self.submission_repository.find_submission_by_nfc_identifier_and_status(nfc_identifier, "APPROVED").await {
+            Ok(Some(found)) => found,
+            Ok(None) => {
+                log::info!("No approved submission to compare identifier {} against", pii::nfc_identifier(nfc_identifier));
+                return Err(("1004", "APPROVED_REFERENCE_NOT_FOUND".to_string()));
+            }
+            Err(e) => return Err(("1002", e.to_string())),
+        };
+
+        let documents_data_existing = submission_data_existing
+            .as_object()
+            .ok_or(("1004", "INVALID_SUBMISSION_DATA".to_string()))?;
+
+        // Once archived, the reference selfie is only in the archive bucket
+        let archived_bucket = documents_data_existing
+            .get(DocumentType::Selfie.as_str())
+            .and_then(|entry| entry.get("archivedBucket"))
+            .and_then(Value::as_str);
+        let url = match archived_bucket {
+            Some(bucket) => {
+                let selfie_filename = document_name(documents_data_existing, DocumentType::Selfie)
+                    .ok_or(("1004", "SELFIE_DOES_NOT_EXIST".to_string()))?;
+                self.minio_service
+                    .generate_view_url_in(bucket, selfie_filename, ContentDisposition::Inline, Duration::from_secs(self.config.presign_view_ttl_secs))
+                    .await
+                    .map_err(|e| (minio_code(&e), e.to_string()))?
+            }
+            None => self.uploaded_selfie_url(documents_data_existing).await?,
+        };
+        Ok((url, reference_submission_id, approved_at))
+    }
+
+    // Presigned URL for a reviewer to open, or with `download` save, one of the submission's documents
+    pub async fn document_view_url(
+        &self,
+        submission_id: &str,
+        document: DocumentType,
+        download: bool,
+    ) -> Result<String, Vec<ApiError>> {
+        let error = |code: &str, cause: String| vec![ApiError {
+            entity: "SOCIO_ECHO_BE".to_string(),
+            code: code.to_string(),
+            cause,
+        }];
+
+        let submission_uuid = Uuid::parse_str(submission_id).map_err(|_| error("1003", "INVALID_SUBMISSION_ID".to_string()))?;
+        let (_, _, _, submission_data) = match self.submission_repository.find_submission_by_id(submission_id, None).await {
+            Ok(Some(found)) => found,
+            Ok(None) => return Err(error("1004", "SUBMISSION_NOT_FOUND".to_string())),
+            Err(e) => return Err(error("1002", e.to_string())),
+        };
+
+        let documents_data = submission_data.as_object();
+        let filename = documents_data
+            .and_then(|documents_data| document_name(documents_data, document))
+            .ok_or_else(|| error("1004", format!("{}_DOES_NOT_EXIST", document)))?;
+
+        // Archived documents sit in a bucket reviewers have no access to
+        let archived = documents_data
+            .and_then(|documents_data| documents_data.get(document.as_str()))
+            .is_some_and(|entry| entry.get("archivedBucket").is_some());
+        if archived {
+            return Err(error("1004", format!("{}_ARCHIVED", document)));
+        }
+
+        let disposition = if download {
+            ContentDisposition::Attachment(format!("{}_{}", submission_uuid, document))
+        } else {
+            ContentDisposition::Inline
+        };
+
+        self.minio_service
+            .generate_view_url(filename, disposition, Duration::from_secs(self.config.presign_view_ttl_secs))
+            .await
+            .map_err(|e| vec![minio_error(e)])
+    }
+
+    // Scoped to the caller, so references can't be probed across users
+    pub async fn find_by_external_reference(
+        &self,
+        external_reference: &str,
+        user_id: &str,
+        page: Page,
+    ) -> Result<(Vec<SubmissionSummary>, i64), Vec<ApiError>> {
+        self.submission_repository
+            .find_by_external_reference(external_reference, user_id, page.limit, page.offset)
+            .await
+            .map_err(|e| vec![ApiError {
+                entity: "SOCIO_ECHO_BE".to_string(),
+                code: "1002".to_string(),
+                cause: e.to_string(),
+            }])
+    }
+
+    pub async fn list_submissions(
+        &self,
+        status: Option<&str>,
+        submission_type: Option<&str>,
+        user_id: Option<&str>,
+        page: Page,
+    ) -> Result<(Vec<SubmissionSummary>, i64), Vec<ApiError>> {
+        let error = |code: &str, cause: String| vec![ApiError {
+            entity: "SOCIO_ECHO_BE".to_string(),
+            code: code.to_string(),
+            cause,
+        }];
+
+        if let Some(status) = status {
+            if !LISTABLE_STATUSES.contains(&status) {
+                return Err(error("1003", format!("INVALID_STATUS: {}", status)));
+            }
+        }
+        if let Some(submission_type) = submission_type {
+            if submission_type_registry::find(submission_type).is_none() {
+                return Err(error("1003", format!("INVALID_SUBMISSION_TYPE: {}", submission_type)));
+            }
+        }
+        if user_id.is_some_and(|user_id| user_id.trim().is_empty()) {
+            return Err(error("1003", "INVALID_USER_ID: must not be blank".to_string()));
+        }
+
+        self.submission_repository
+            .list(status, submission_type, user_id, page.limit, page.offset)
+            .await
+            .map_err(|e| error("1002", e.to_string()))
+    }
+
+    // Submissions left in PROCESSING for over `older_than_secs`, e.g. by a crash mid face match
+    pub async fn find_stuck_processing(
+        &self,
+        older_than_secs: u64,
+        page: Page,
+    ) -> Result<(Vec<SubmissionSummary>, i64), Vec<ApiError>> {
+        let error = |code: &str, cause: String| vec![ApiError {
+            entity: "SOCIO_ECHO_BE".to_string(),
+            code: code.to_string(),
+            cause,
+        }];
+
+        if !(1..=MAX_STUCK_PROCESSING_SECS).contains(&older_than_secs) {
+            return Err(error(
+                "1003",
+                format!("INVALID_OLDER_THAN_SECS: must be between 1 and {}", MAX_STUCK_PROCESSING_SECS),
+            ));
+        }
+
+        let older_than = Utc::now() - chrono::Duration::seconds(older_than_secs as i64);
+        self.submission_repository
+            .find_stuck_processing(older_than, page.limit, page.offset)
+            .await
+            .map_err(|e| error("1002", e.to_string()))
+    }
+
+    pub async fn find_by_document_reference(&self, document_reference: &str) -> Result<SubmissionSummary, Vec<ApiError>> {
+        let error = |code: &str, cause: String| vec![ApiError {
+            entity: "SOCIO_ECHO_BE".to_string(),
+            code: code.to_string(),
+            cause,
+        }];
+
+        // References are always generated as UUIDs, so anything else can't match
+        let document_reference = Uuid::parse_str(document_reference)
+            .map_err(|_| error("1003", "INVALID_DOCUMENT_REFERENCE".to_string()))?;
+
+        match self.submission_repository.find_by_document_reference(&document_reference.to_string()).await {
+            Ok(Some(submission)) => Ok(submission),
+            Ok(None) => Err(error("1004", "SUBMISSION_NOT_FOUND".to_string())),
+            Err(e) => Err(error("1002", e.to_string())),
+        }
+    }
+
+    // How an identifier has fared across all its submissions, for fraud review
+    pub async fn nfc_summary(&self, nfc_identifier: &str) -> Result<NfcSummaryResponse, Vec<ApiError>> {
+        let error = |code: &str, cause: String| vec![ApiError {
+            entity: "SOCIO_ECHO_BE".to_string(),
+            code: code.to_string(),
+            cause,
+        }];
+
+        // Stored identifiers are truncated the same way when the submission is created
+        let nfc_identifier = nfc_identifier.chars().take(500).collect::<String>();
+
+        let (latest_status, latest_updated_at) = match self.submission_repository.find_latest_status_for_nfc_identifier(&nfc_identifier).await {
+            Ok(Some(latest)) => latest,
+            Ok(None) => return Err(error("1004", "SUBMISSION_NOT_FOUND".to_string())),
+            Err(e) => return Err(error("1002", e.to_string())),
+        };
+
+        let status_counts: BTreeMap<String, i64> = self
+            .submission_repository
+            .count_by_status_for_nfc_identifier(&nfc_identifier)
+            .await
+            .map_err(|e| error("1002", e.to_string()))?
+            .into_iter()
+            .collect();
+
+        Ok(NfcSummaryResponse {
+            total: status_counts.values().sum(),
+            status_counts,
+            latest_status,
+            latest_updated_at,
+        })
+    }
+
+    pub async fn get_submission_status(
+        &self,
+        submission_type: SubmissionType,
+        nfc_identifier: String,
+    ) -> Result<(SubmissionStatus, HashMap<DocumentType, UploadStatus>, DateTime<Utc>), Vec<ApiError>> {
+        let (submission_status, submission_data, updated_at) = match self.submission_repository.find_submission_by_nfc_identifier_and_submission_type(&submission_type.to_string(), &nfc_identifier.chars().take(500).collect::<String>()).await {
+            Ok(Some(found)) => found,
             Ok(None) => {
                 return Err(vec![ApiError {
                     entity: "SOCIO_ECHO_BE".to_string(),
@@ -483,14 +1562,690 @@ impl SubmissionService {
             }
         };
 
-        let mut status: String = String::from("NOT_KYC");
-        if submission_data == "APPROVED" {
-            status = String::from("KYC");
+        let status = submission_status.parse::<SubmissionStatus>().map_err(|cause| vec![ApiError {
+            entity: "SOCIO_ECHO_BE".to_string(),
+            code: "1002".to_string(),
+            cause,
+        }])?;
+
+        // Documents without a recorded status predate tracking or were never checked
+        let documents = submission_type_registry::get(&submission_type)
+            .upload_documents
+            .iter()
+            .map(|document| {
+                let upload_status = serde_json::from_value(submission_data[document.as_str()]["uploadStatus"].clone())
+                    .unwrap_or(UploadStatus::Pending);
+                (*document, upload_status)
+            })
+            .collect();
+
+        Ok((status, documents, updated_at))
+    }
+
+}
+
+fn is_idempotency_key_conflict(e: &sqlx::Error) -> bool {
+    matches!(e, sqlx::Error::Database(db) if db.constraint() == Some("submissions_user_idempotency_key_idx"))
+}
+
+// MinIO failures are 1001, except a missing bucket which gets 1014 so operators see the
+// misconfiguration straight away
+fn minio_error(e: anyhow::Error) -> ApiError {
+    ApiError {
+        entity: "SOCIO_ECHO_BE".to_string(),
+        code: minio_code(&e).to_string(),
+        cause: e.to_string(),
+    }
+}
+
+fn minio_code(e: &anyhow::Error) -> &'static str {
+    if minio_service::is_no_such_bucket(e) { "1014" } else { "1001" }
+}
+
+// Stored object name of `document`; a non-string name yields an empty one so the
+// existence check downstream still fails cleanly
+fn document_name(documents_data: &Map<String, Value>, document: DocumentType) -> Option<String> {
+    documents_data
+        .get(document.as_str())?
+        .get("documentName")
+        .map(|name| name.as_str().unwrap_or("").to_string())
+}
+
+// Audit event for a decision made against an approved reference; carries ids and the
+// score only, never the presigned URLs. The identifier is always hashed, which still ties
+// events for the same person together.
+fn reference_match_event(
+    submission_id: &str,
+    nfc_identifier: &str,
+    reference_submission_id: Uuid,
+    reference_approved_at: DateTime<Utc>,
+    threshold: f64,
+    face_match_result: &FaceMatchResponse,
+) -> Value {
+    json!({
+        "event": "FACE_MATCH_REFERENCE_USED",
+        "submissionId": submission_id,
+        "nfcIdentifier": pii::hashed_nfc_identifier(nfc_identifier),
+        "referenceSubmissionId": reference_submission_id,
+        "referenceApprovedAt": reference_approved_at,
+        "similarityScore": face_match_result.similarity_score,
+        "threshold": threshold,
+        "isMatch": face_match_result.is_match,
+    })
+}
+
+// What the face match provider may use to tune matching: which submission the selfie belongs
+// to and what it is compared against. Never identifies the person.
+fn face_match_metadata(
+    submission_type: &str,
+    strategy: ProcessingStrategy,
+    risk_tier: Option<&str>,
+) -> HashMap<String, String> {
+    let reference = match strategy {
+        ProcessingStrategy::CompareWithDocument(document) => document.as_str(),
+        ProcessingStrategy::CompareWithApprovedSelfie => "APPROVED_SELFIE",
+    };
+
+    let mut metadata = HashMap::new();
+    metadata.insert("submission_type".to_string(), submission_type.to_string());
+    metadata.insert("reference_document".to_string(), reference.to_string());
+    metadata.insert("risk_tier".to_string(), risk_tier.unwrap_or(DEFAULT_RISK_TIER).to_string());
+    metadata
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use std::{collections::HashSet, sync::Arc};
+
+    use super::*;
+    use crate::{
+        commons::{
+            logging::tests::{capture_logs, logged_messages},
+            minio_service::tests::{fake_s3, FakeS3},
+        },
+        config::tests::from_env_with,
+        services::{
+            app_services,
+            circuit_breaker::CircuitBreaker,
+            face_match_service::tests::{fake_provider, matched, Provider},
+        },
+        submissions::submission_type_registry::SubmissionTypeConfig,
+    };
+
+    const JPEG: &[u8] = &[0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10, b'J', b'F', b'I', b'F'];
+
+    // Nothing listens on the MinIO endpoint; these tests never get as far as an actual request
+    fn service(pool: PgPool, vars: &[(&str, &str)]) -> SubmissionService {
+        let config = from_env_with(vars).unwrap();
+        let minio = MinioService::unchecked(
+            "http://127.0.0.1:1",
+            "minio",
+            "minio123",
+            &config.minio_bucket_name,
+            config.minio_view_key_suffixes.clone(),
+        );
+        let feature_flags = FeatureFlagsService::new(pool.clone(), Duration::ZERO);
+        SubmissionService::new(minio, SubmissionRepository::new(pool.clone(), pool), MetricsService::noop(), config, feature_flags)
+    }
+
+    // A provider nobody should call; any comparison fails to connect
+    fn unreachable_face_match() -> FaceMatchService {
+        face_match("http://127.0.0.1:1".to_string())
+    }
+
+    fn face_match(base_url: String) -> FaceMatchService {
+        let metrics = MetricsService::noop();
+        let breaker = CircuitBreaker::new("face_match", 0, Duration::from_secs(60), Duration::from_secs(30), metrics.clone());
+        FaceMatchService::new(face_match_service::tests::settings(base_url, 1), breaker, metrics).unwrap()
+    }
+
+    fn disabled_webhooks(pool: PgPool) -> WebhookService {
+        WebhookService::new(pool, webhook_service::tests::settings(None, None), MetricsService::noop())
+    }
+
+    async fn seed(service: &SubmissionService, submission_type: &str, status: &str, submission_data: Value) -> String {
+        seed_as(service, "1", submission_type, status, submission_data).await
+    }
+
+    async fn seed_as(
+        service: &SubmissionService,
+        user_id: &str,
+        submission_type: &str,
+        status: &str,
+        submission_data: Value,
+    ) -> String {
+        let submission_id = Uuid::new_v4();
+        service
+            .submission_repository
+            .create(NewSubmission {
+                submission_id,
+                submission_type,
+                session_id: &submission_id.to_string(),
+                user_id,
+                status,
+                submission_data,
+                request_data: json!({}),
+                nfc_identifier: STANDARD.encode(JPEG),
+                external_reference: None,
+                risk_tier: None,
+                idempotency_key: None,
+                idempotency_request_hash: None,
+                callback_url: None,
+            })
+            .await
+            .unwrap();
+        submission_id.to_string()
+    }
+
+    fn causes(errors: Vec<ApiError>) -> Vec<String> {
+        errors.into_iter().map(|e| e.cause).collect()
+    }
+
+    #[sqlx::test]
+    async fn on_demand_is_rejected_while_disabled(pool: PgPool) {
+        let service = service(pool.clone(), &[("ON_DEMAND_ENABLED", "false")]);
+
+        let err = service.check_presigned_urls_request(&SubmissionType::OnDemand, &STANDARD.encode(JPEG)).await.unwrap_err();
+        assert_eq!(err.cause, "SUBMISSION_TYPE_DISABLED");
+
+        // Already created submissions aren't processed either, and the provider is never called
+        let submission_id = seed(&service, "ON_DEMAND", "INITIATED", json!({ "SELFIE": {} })).await;
+        let errors = service
+            .process_submission(None, submission_id, unreachable_face_match(), disabled_webhooks(pool))
+            .await
+            .unwrap_err();
+        assert_eq!(causes(errors), ["SUBMISSION_TYPE_DISABLED"]);
+    }
+
+    #[sqlx::test]
+    async fn on_demand_is_accepted_while_enabled(pool: PgPool) {
+        let service = service(pool, &[("ON_DEMAND_ENABLED", "true")]);
+
+        let (documents, _, _, format) =
+            service.check_presigned_urls_request(&SubmissionType::OnDemand, &STANDARD.encode(JPEG)).await.unwrap();
+        assert_eq!(documents, [DocumentType::Selfie]);
+        assert_eq!(format, ImageFormat::Jpeg);
+    }
+
+    #[sqlx::test]
+    async fn empty_submission_data_is_reported_as_no_documents(pool: PgPool) {
+        let service = service(pool.clone(), &[]);
+
+        let empty = seed(&service, "KYC", "INITIATED", json!({})).await;
+        let errors = service
+            .process_submission(None, empty, unreachable_face_match(), disabled_webhooks(pool.clone()))
+            .await
+            .unwrap_err();
+        assert_eq!(causes(errors), ["NO_DOCUMENTS"]);
+
+        // A partial upload still names the document that's missing
+        let partial = seed(&service, "KYC", "INITIATED", json!({ "NFC": { "documentName": "nfc.jpg" } })).await;
+        let errors = service
+            .process_submission(None, partial, unreachable_face_match(), disabled_webhooks(pool))
+            .await
+            .unwrap_err();
+        assert_eq!(causes(errors), ["SELFIE_DOES_NOT_EXIST"]);
+    }
+
+    #[sqlx::test]
+    async fn status_reports_each_upload_document(pool: PgPool) {
+        let service = service(pool, &[]);
+        let submission_data = json!({
+            "KTP": { "documentName": "ktp.jpg", "uploadStatus": "UPLOADED" },
+            "SELFIE": { "documentName": "selfie.jpg", "uploadStatus": "PENDING" },
+            "NFC": { "documentName": "nfc.jpg", "uploadStatus": "UPLOADED" },
+        });
+        seed(&service, "KYC", "INITIATED", submission_data).await;
+
+        let (_, documents, _) = service.get_submission_status(SubmissionType::Kyc, STANDARD.encode(JPEG)).await.unwrap();
+        // The stored NFC image isn't one the client uploads
+        assert_eq!(
+            documents,
+            HashMap::from([(DocumentType::Ktp, UploadStatus::Uploaded), (DocumentType::Selfie, UploadStatus::Pending)])
+        );
+
+        // Documents without a recorded status count as pending
+        seed(&service, "KYC_PASSPORT", "INITIATED", json!({ "PASSPORT": { "documentName": "passport.jpg" } })).await;
+        let (_, documents, _) = service.get_submission_status(SubmissionType::KycPassport, STANDARD.encode(JPEG)).await.unwrap();
+        assert_eq!(
+            documents,
+            HashMap::from([(DocumentType::Passport, UploadStatus::Pending), (DocumentType::Selfie, UploadStatus::Pending)])
+        );
+    }
+
+    #[sqlx::test]
+    async fn in_progress_submissions_are_only_handed_back_to_their_owner(pool: PgPool) {
+        let service = service(pool, &[("SUBMISSION_DEDUPE_WINDOW_SECS", "600")]);
+        let uploads = || {
+            json!({
+                "KTP": { "documentName": format!("{}_KTP", Uuid::new_v4()), "documentReference": "ktp" },
+                "SELFIE": { "documentName": format!("{}_SELFIE", Uuid::new_v4()), "documentReference": "selfie" },
+            })
+        };
+        let first = seed_as(&service, "1", "KYC", "INITIATED", uploads()).await;
+        let second = seed_as(&service, "2", "KYC", "INITIATED", uploads()).await;
+
+        // Both users sent the same NFC identifier
+        let nfc = STANDARD.encode(JPEG);
+        let in_progress = |user_id: &'static str| {
+            let (service, nfc) = (&service, &nfc);
+            async move {
+                service
+                    .find_in_progress_submission(user_id, &SubmissionType::Kyc, nfc, None, 600)
+                    .await
+                    .unwrap()
+                    .map(|response| response.submission_id)
+            }
+        };
+        assert_eq!(in_progress("1").await, Some(first));
+        assert_eq!(in_progress("2").await, Some(second));
+        assert_eq!(in_progress("3").await, None);
+    }
+
+    const PNG: &[u8] = &[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, 0x00, 0x00];
+
+    // KYC requests are checked without reading feature flags, so no database is needed
+    async fn check_nfc(vars: &[(&str, &str)], nfc: &[u8]) -> Result<ImageFormat, String> {
+        let pool = PgPool::connect_lazy("postgres://127.0.0.1:1/unused").unwrap();
+        service(pool, vars)
+            .check_presigned_urls_request(&SubmissionType::Kyc, &STANDARD.encode(nfc))
+            .await
+            .map(|(_, _, _, format)| format)
+            .map_err(|e| e.cause)
+    }
+
+    #[tokio::test]
+    async fn upload_urls_beyond_the_document_limit_are_rejected() {
+        // KYC hands out upload URLs for KTP and SELFIE
+        assert_eq!(check_nfc(&[("MAX_DOCUMENTS_PER_SUBMISSION", "2")], JPEG).await, Ok(ImageFormat::Jpeg));
+        assert_eq!(
+            check_nfc(&[("MAX_DOCUMENTS_PER_SUBMISSION", "1")], JPEG).await,
+            Err("DOCUMENT_LIMIT_EXCEEDED: 2 documents requested, max 1".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn oversized_nfc_images_are_rejected() {
+        let limit = [("MAX_NFC_IMAGE_BYTES", "10")];
+        assert_eq!(check_nfc(&limit, JPEG).await, Ok(ImageFormat::Jpeg));
+
+        let oversized = [JPEG, &[0u8]].concat();
+        assert_eq!(check_nfc(&limit, &oversized).await, Err("NFC_IMAGE_TOO_LARGE: 11 bytes, max 10".to_string()));
+    }
+
+    #[tokio::test]
+    async fn nfc_images_must_be_in_an_allowed_format() {
+        assert_eq!(
+            check_nfc(&[], PNG).await,
+            Err("INVALID_NFC_IMAGE_FORMAT: must be one of jpeg".to_string())
+        );
+        assert_eq!(
+            check_nfc(&[], b"GIF89a").await,
+            Err("INVALID_NFC_IMAGE_FORMAT: must be one of jpeg".to_string())
+        );
+
+        let with_png = [("NFC_IMAGE_FORMATS", "jpeg,png")];
+        assert_eq!(check_nfc(&with_png, PNG).await, Ok(ImageFormat::Png));
+    }
+
+    fn presign_request(submission_type: SubmissionType, idempotency_key: Option<&str>) -> PresignedUrlsRequest {
+        PresignedUrlsRequest {
+            submission_type,
+            nfc_identifier: STANDARD.encode(JPEG),
+            external_reference: None,
+            expiry_in_seconds: None,
+            callback_url: None,
+            idempotency_key: idempotency_key.map(String::from),
+        }
+    }
+
+    async fn stored_type(pool: &PgPool, submission_id: &str) -> String {
+        sqlx::query_scalar("SELECT submission_type FROM submissions WHERE submission_id = $1")
+            .bind(Uuid::parse_str(submission_id).unwrap())
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn submissions_are_stored_under_their_wire_type(pool: PgPool) {
+        let s3 = Arc::new(FakeS3::default());
+        let services = app_services::tests::services_with_minio(pool.clone(), &[], &fake_s3(s3));
+        let service = services.submission_service();
+
+        for submission_type in [SubmissionType::Kyc, SubmissionType::KycPassport, SubmissionType::KycDrivingLicense] {
+            let name = submission_type.to_string();
+            let response = service
+                .generate_presigned_urls(Uuid::new_v4().to_string(), "1".to_string(), None, presign_request(submission_type, None))
+                .await
+                .unwrap();
+            assert_eq!(stored_type(&pool, &response.submission_id).await, name);
+        }
+    }
+
+    #[sqlx::test]
+    async fn concurrent_requests_with_one_idempotency_key_share_a_submission(pool: PgPool) {
+        let s3 = Arc::new(FakeS3::default());
+        let services = app_services::tests::services_with_minio(pool.clone(), &[], &fake_s3(s3.clone()));
+        let (first, second) = (services.submission_service(), services.submission_service());
+
+        let request = |service: SubmissionService| async move {
+            service
+                .generate_presigned_urls(Uuid::new_v4().to_string(), "1".to_string(), None, presign_request(SubmissionType::Kyc, Some("key-1")))
+                .await
+                .unwrap()
+        };
+        let (first, second) = tokio::join!(request(first), request(second));
+        assert_eq!(first.submission_id, second.submission_id);
+
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM submissions").fetch_one(&pool).await.unwrap();
+        assert_eq!(count, 1);
+
+        // Every NFC image stored for the request that lost is deleted again; the kept one isn't
+        let kept: String = sqlx::query_scalar("SELECT submission_data::jsonb->'NFC'->>'documentName' FROM submissions")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        let mut orphaned: Vec<_> = s3.requests("PUT").into_iter().filter(|path| !path.ends_with(&kept)).collect();
+        let mut deleted = s3.requests("DELETE");
+        orphaned.sort();
+        deleted.sort();
+        assert_eq!(deleted, orphaned);
+    }
+
+    #[sqlx::test]
+    async fn upload_url_expiries_vary_within_the_jitter_band(pool: PgPool) {
+        let vars = [("PRESIGN_UPLOAD_TTL_SECS", "600"), ("PRESIGN_UPLOAD_TTL_JITTER_SECS", "60")];
+        let services = app_services::tests::services_with_minio(pool, &vars, &fake_s3(Arc::new(FakeS3::default())));
+        let jittered = services.submission_service();
+
+        let mut expiries = HashSet::new();
+        for _ in 0..20 {
+            let response = jittered
+                .generate_presigned_urls(Uuid::new_v4().to_string(), "1".to_string(), None, presign_request(SubmissionType::Kyc, None))
+                .await
+                .unwrap();
+            for document in response.documents.values() {
+                // Clients are told the low end of the band
+                assert_eq!(document.expiry_in_seconds, "540");
+                let url = reqwest::Url::parse(&document.document_url).unwrap();
+                let (_, expires) = url.query_pairs().find(|(name, _)| name == "X-Amz-Expires").unwrap();
+                let expires: u64 = expires.parse().unwrap();
+                assert!((540..=660).contains(&expires), "{expires}");
+                expiries.insert(expires);
+            }
         }
+        assert!(expiries.len() > 1, "{expiries:?}");
+
+        // Without jitter every URL lives exactly the TTL
+        let without_jitter = service(services.pool.clone(), &[("PRESIGN_UPLOAD_TTL_SECS", "600")]);
+        assert!((0..20).all(|_| without_jitter.jittered_upload_ttl(600) == (600, 600)));
+    }
+
+    #[sqlx::test]
+    async fn a_reference_approved_during_the_comparison_is_not_decided_on(pool: PgPool) {
+        let services = app_services::tests::services_with_minio(
+            pool.clone(),
+            &[("ON_DEMAND_ENABLED", "true")],
+            &fake_s3(Arc::new(FakeS3::default())),
+        );
+        let service = services.submission_service();
+        let selfie = || json!({ "SELFIE": { "documentName": format!("{}_SELFIE", Uuid::new_v4()), "uploadStatus": "UPLOADED" } });
+        seed(&service, "KYC", "APPROVED", selfie()).await;
+        let submission_id = seed(&service, "ON_DEMAND", "INITIATED", selfie()).await;
+
+        // The provider takes long enough for a newer KYC to be approved meanwhile
+        let provider = Arc::new(Provider::default());
+        let face_match = face_match(fake_provider(provider.clone(), Duration::from_millis(500), matched).await);
+        let (result, _) = tokio::join!(
+            service.process_submission(None, submission_id.clone(), face_match, disabled_webhooks(pool.clone())),
+            async {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                seed(&service, "KYC", "APPROVED", selfie()).await
+            },
+        );
+
+        assert_eq!(provider.calls(), 1);
+        let errors = result.unwrap_err();
+        assert_eq!((errors[0].code.as_str(), errors[0].cause.as_str()), ("1013", "REFERENCE_CHANGED"));
+        // Left for the retry job to compare against the new reference
+        let status: String = sqlx::query_scalar("SELECT status FROM submissions WHERE submission_id = $1")
+            .bind(Uuid::parse_str(&submission_id).unwrap())
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(status, "PENDING_RETRY");
+    }
+
+    #[sqlx::test]
+    async fn on_demand_decisions_log_the_reference_they_used(pool: PgPool) {
+        let _logs = capture_logs().await;
+        let services = app_services::tests::services_with_minio(
+            pool.clone(),
+            &[("ON_DEMAND_ENABLED", "true")],
+            &fake_s3(Arc::new(FakeS3::default())),
+        );
+        let service = services.submission_service();
+        let selfie = || json!({ "SELFIE": { "documentName": format!("{}_SELFIE", Uuid::new_v4()), "uploadStatus": "UPLOADED" } });
+        let reference = seed(&service, "KYC", "APPROVED", selfie()).await;
+        let submission_id = seed(&service, "ON_DEMAND", "INITIATED", selfie()).await;
 
-        return Ok(GetSubmissionStatusResponse {
-            submission_status: status,
+        let face_match = face_match(fake_provider(Arc::new(Provider::default()), Duration::ZERO, matched).await);
+        service
+            .process_submission(None, submission_id.clone(), face_match, disabled_webhooks(pool))
+            .await
+            .unwrap();
+
+        let events: Vec<Value> = logged_messages()
+            .iter()
+            .filter_map(|message| serde_json::from_str::<Value>(message).ok())
+            .filter(|event| event["event"] == "FACE_MATCH_REFERENCE_USED" && event["submissionId"] == submission_id)
+            .collect();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0]["referenceSubmissionId"], reference);
+        assert!(events[0]["referenceApprovedAt"].is_string());
+        assert_eq!(events[0]["similarityScore"], 0.9);
+        // Ids only; no presigned URL with its credentials
+        assert!(!events[0].to_string().contains("X-Amz"), "{}", events[0]);
+    }
+
+    async fn captures(pool: &PgPool) -> Vec<(String, String)> {
+        sqlx::query_as("SELECT reference_object_url, selfie_object_url FROM face_match_captures")
+            .fetch_all(pool)
+            .await
+            .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn face_matches_are_captured_at_the_sample_rate(pool: PgPool) {
+        let provider = fake_provider(Arc::new(Provider::default()), Duration::ZERO, matched).await;
+        let endpoint = fake_s3(Arc::new(FakeS3::default()));
+        let documents = || {
+            let mut data = json!({});
+            for document in ["KTP", "SELFIE", "NFC"] {
+                data[document] = json!({ "documentName": format!("{}_{}", Uuid::new_v4(), document), "uploadStatus": "UPLOADED" });
+            }
+            data
+        };
+
+        for (rate, expected) in [("0", 0), ("1", 1)] {
+            let services = app_services::tests::services_with_minio(pool.clone(), &[("FACE_MATCH_CAPTURE_SAMPLE_RATE", rate)], &endpoint);
+            let service = services.submission_service();
+            let submission_id = seed(&service, "KYC", "INITIATED", documents()).await;
+            service
+                .process_submission(None, submission_id, face_match(provider.clone()), disabled_webhooks(pool.clone()))
+                .await
+                .unwrap();
+            assert_eq!(captures(&pool).await.len(), expected, "rate {rate}");
+        }
+
+        // Captured URLs name the objects without their presigned credentials
+        let (reference_url, selfie_url) = captures(&pool).await.remove(0);
+        assert!(reference_url.ends_with("_NFC") && selfie_url.ends_with("_SELFIE"), "{reference_url} {selfie_url}");
+
+        let sampled = service(pool, &[("FACE_MATCH_CAPTURE_SAMPLE_RATE", "0.5")]);
+        let captured = (0..1000).filter(|_| sampled.sample_capture()).count();
+        assert!((400..=600).contains(&captured), "{captured} of 1000");
+    }
+
+    #[sqlx::test]
+    async fn a_registered_type_config_drives_the_flow(pool: PgPool) {
+        // A hypothetical passport flow: a different upload set, a stricter threshold and the
+        // passport photo as reference, all declared only in the registry
+        submission_type_registry::register(SubmissionTypeConfig {
+            submission_type: SubmissionType::KycPassport,
+            upload_documents: &[DocumentType::DrivingLicense, DocumentType::Selfie],
+            threshold: Some(0.95),
+            strategy: ProcessingStrategy::CompareWithDocument(DocumentType::Passport),
+            status_queryable: true,
         });
+        let services = app_services::tests::services_with_minio(pool.clone(), &[], &fake_s3(Arc::new(FakeS3::default())));
+        let service = services.submission_service();
+
+        let dry_run = service.validate_presigned_urls_request(presign_request(SubmissionType::KycPassport, None)).await.unwrap();
+        assert_eq!(dry_run.upload_documents, [DocumentType::DrivingLicense, DocumentType::Selfie]);
+
+        let mut documents = json!({});
+        for document in ["DRIVING_LICENSE", "SELFIE", "PASSPORT", "NFC"] {
+            documents[document] = json!({ "documentName": format!("{}_{}", Uuid::new_v4(), document), "uploadStatus": "UPLOADED" });
+        }
+        let submission_id = seed(&service, "KYC_PASSPORT", "INITIATED", documents).await;
+        let provider = Arc::new(Provider::default());
+        let face_match = face_match(fake_provider(provider.clone(), Duration::ZERO, matched).await);
+        service
+            .process_submission(None, submission_id, face_match, disabled_webhooks(pool))
+            .await
+            .unwrap();
+
+        let request = &provider.bodies()[0];
+        let reference = request["image1_url"].as_str().unwrap();
+        assert!(reference.split('?').next().unwrap().ends_with("_PASSPORT"), "{reference}");
+        // The type's own threshold, not the service's 0.8
+        assert_eq!(request["threshold"], 0.95);
     }
 
+    async fn backdate(pool: &PgPool, submission_id: &str, secs: i64) {
+        sqlx::query("UPDATE submissions SET updated_at = NOW() - make_interval(secs => $2) WHERE submission_id = $1")
+            .bind(Uuid::parse_str(submission_id).unwrap())
+            .bind(secs as f64)
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+
+    #[sqlx::test]
+    async fn surfaces_only_submissions_stuck_in_processing(pool: PgPool) {
+        let service = service(pool.clone(), &[]);
+        let stuck = seed(&service, "KYC", "PROCESSING", json!({})).await;
+        // Still within the threshold
+        seed(&service, "KYC", "PROCESSING", json!({})).await;
+        let decided = seed(&service, "KYC", "APPROVED", json!({})).await;
+        backdate(&pool, &stuck, 3600).await;
+        backdate(&pool, &decided, 3600).await;
+
+        let page = Page::from_query(None, None).unwrap();
+        let (submissions, total) = service.find_stuck_processing(DEFAULT_STUCK_PROCESSING_SECS, page).await.unwrap();
+
+        let ids: Vec<String> = submissions.iter().map(|s| s.submission_id.to_string()).collect();
+        assert_eq!(ids, [stuck]);
+        assert_eq!(total, 1);
+    }
+
+    #[sqlx::test]
+    async fn margin_band_submission_reports_requires_review(pool: PgPool) {
+        let service = service(pool.clone(), &[("FACE_MATCH_REVIEW_MARGIN", "0.1")]);
+        let nfc_identifier = STANDARD.encode(JPEG);
+        let submission_id = seed(&service, "KYC", "PROCESSING", json!({})).await;
+
+        // Below the threshold, but within the margin
+        let result = FaceMatchResponse {
+            submission_id: submission_id.clone(),
+            similarity_score: 0.75,
+            is_match: false,
+            threshold: 0.8,
+        };
+        let status = service.decide(result.is_match, result.similarity_score, 0.8);
+        let decision = Decision { face_match_result: &result, threshold: 0.8, status };
+        assert!(service.record_decision(&submission_id, "KYC", &nfc_identifier, None, decision).await.unwrap());
+
+        let (status, _, _) = service.get_submission_status(SubmissionType::Kyc, nfc_identifier).await.unwrap();
+        assert_eq!(status, SubmissionStatus::RequiresReview);
+
+        let history: (String, String) =
+            sqlx::query_as("SELECT previous_status, status FROM submission_status_history WHERE submission_id = $1")
+                .bind(Uuid::parse_str(&submission_id).unwrap())
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(history, ("PROCESSING".to_string(), "REQUIRES_REVIEW".to_string()));
+
+        // Further below it is still a rejection
+        assert_eq!(service.decide(false, 0.65, 0.8), "REJECTED");
+    }
+
+    async fn record_face_match(service: &SubmissionService, submission_id: &str, similarity_score: f64, is_match: bool) {
+        let mut tx = service.submission_repository.begin().await.unwrap();
+        SubmissionRepository::create_face_match_audit(&mut tx, submission_id, "KYC", similarity_score, 0.8, is_match)
+            .await
+            .unwrap();
+        tx.commit().await.unwrap();
+    }
+
+    // recompute_status has no face-match provider to call; the stored audits are all it reads
+    #[sqlx::test]
+    async fn recompute_follows_the_latest_stored_face_match(pool: PgPool) {
+        let service = service(pool.clone(), &[]);
+        let webhooks = disabled_webhooks(pool);
+        let submission_id = seed(&service, "KYC", "PROCESSING", json!({})).await;
+
+        record_face_match(&service, &submission_id, 0.9, true).await;
+        let recomputed = service.recompute_status(&submission_id, false, "admin", &webhooks).await.unwrap();
+        assert_eq!((recomputed.previous_status.as_str(), recomputed.submission_status.as_str()), ("PROCESSING", "APPROVED"));
+        assert!(recomputed.changed);
+
+        // Overturning a decision needs the explicit confirmation
+        record_face_match(&service, &submission_id, 0.3, false).await;
+        let errors = service.recompute_status(&submission_id, false, "admin", &webhooks).await.unwrap_err();
+        assert_eq!(causes(errors), ["CONFIRMATION_REQUIRED"]);
+
+        let recomputed = service.recompute_status(&submission_id, true, "admin", &webhooks).await.unwrap();
+        assert_eq!((recomputed.previous_status.as_str(), recomputed.submission_status.as_str()), ("APPROVED", "REJECTED"));
+    }
+
+    #[test]
+    fn high_risk_tier_rejects_a_score_the_default_tier_approves() {
+        let tiers = HashMap::from([("HIGH".to_string(), 0.9)]);
+        let score = 0.85;
+
+        let default_threshold = risk_threshold(&tiers, None, 0.8);
+        assert_eq!(decide_status(score >= default_threshold, score, default_threshold, 0.0), "APPROVED");
+
+        let high_threshold = risk_threshold(&tiers, Some("HIGH"), 0.8);
+        assert_eq!(high_threshold, 0.9);
+        assert_eq!(decide_status(score >= high_threshold, score, high_threshold, 0.0), "REJECTED");
+    }
+
+    #[test]
+    fn risk_tier_never_lowers_the_threshold() {
+        let tiers = HashMap::from([("LOW".to_string(), 0.5)]);
+        assert_eq!(risk_threshold(&tiers, Some("LOW"), 0.8), 0.8);
+        assert_eq!(risk_threshold(&tiers, Some("REMOVED"), 0.8), 0.8);
+    }
+
+    #[test]
+    fn reference_match_event_never_carries_the_raw_identifier() {
+        let result = FaceMatchResponse {
+            submission_id: "s1".to_string(),
+            similarity_score: 0.91,
+            is_match: true,
+            threshold: 0.8,
+        };
+        let event = reference_match_event("s1", "04A1B2C3D4E5F6", Uuid::nil(), Utc::now(), 0.8, &result);
+
+        assert!(!event.to_string().contains("04A1B2C3D4E5F6"));
+        assert_eq!(event["nfcIdentifier"], pii::hashed_nfc_identifier("04A1B2C3D4E5F6"));
+        assert_eq!(event["event"], "FACE_MATCH_REFERENCE_USED");
+    }
 }
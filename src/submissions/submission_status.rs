@@ -0,0 +1,86 @@
+use std::str::FromStr;
+
+use serde::Serialize;
+
+// Lifecycle of a submission, as stored in submissions.status
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum SubmissionStatus {
+    Initiated,
+    Processing,
+    // The face match failed to run; picked up again by reprocessing
+    PendingRetry,
+    // Scored just below the threshold, waiting for a human decision
+    RequiresReview,
+    Approved,
+    Rejected,
+    Deleted,
+}
+
+impl SubmissionStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SubmissionStatus::Initiated => "INITIATED",
+            SubmissionStatus::Processing => "PROCESSING",
+            SubmissionStatus::PendingRetry => "PENDING_RETRY",
+            SubmissionStatus::RequiresReview => "REQUIRES_REVIEW",
+            SubmissionStatus::Approved => "APPROVED",
+            SubmissionStatus::Rejected => "REJECTED",
+            SubmissionStatus::Deleted => "DELETED",
+        }
+    }
+}
+
+impl std::fmt::Display for SubmissionStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for SubmissionStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "INITIATED" => Ok(SubmissionStatus::Initiated),
+            "PROCESSING" => Ok(SubmissionStatus::Processing),
+            "PENDING_RETRY" => Ok(SubmissionStatus::PendingRetry),
+            "REQUIRES_REVIEW" => Ok(SubmissionStatus::RequiresReview),
+            "APPROVED" => Ok(SubmissionStatus::Approved),
+            "REJECTED" => Ok(SubmissionStatus::Rejected),
+            "DELETED" => Ok(SubmissionStatus::Deleted),
+            _ => Err(format!("UNKNOWN_SUBMISSION_STATUS: {}", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) const ALL: [SubmissionStatus; 7] = [
+        SubmissionStatus::Initiated,
+        SubmissionStatus::Processing,
+        SubmissionStatus::PendingRetry,
+        SubmissionStatus::RequiresReview,
+        SubmissionStatus::Approved,
+        SubmissionStatus::Rejected,
+        SubmissionStatus::Deleted,
+    ];
+
+    #[test]
+    fn serializes_as_the_stored_string() {
+        for status in ALL {
+            assert_eq!(serde_json::to_value(status).unwrap(), status.as_str());
+        }
+    }
+
+    #[test]
+    fn parses_back_what_it_stores() {
+        for status in ALL {
+            assert_eq!(status.as_str().parse::<SubmissionStatus>(), Ok(status));
+        }
+        assert!("approved".parse::<SubmissionStatus>().is_err());
+        assert!("KYC".parse::<SubmissionStatus>().is_err());
+    }
+}
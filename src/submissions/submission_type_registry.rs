@@ -0,0 +1,116 @@
+use crate::submissions::{document_type::DocumentType, submission_controller::SubmissionType};
+
+// How process_submission picks the reference image the new selfie is compared against
+#[derive(Debug, Clone, Copy)]
+pub enum ProcessingStrategy {
+    // A document stored on the same submission (e.g. the NFC chip photo)
+    CompareWithDocument(DocumentType),
+    // The selfie of the latest APPROVED submission for the same nfc_identifier
+    CompareWithApprovedSelfie,
+}
+
+// Documents the backend stores itself from the presigned URL request, for every type
+pub const STORED_DOCUMENTS: &[DocumentType] = &[DocumentType::Nfc];
+
+#[derive(Debug)]
+pub struct SubmissionTypeConfig {
+    pub submission_type: SubmissionType,
+    // Documents the client uploads through presigned URLs
+    pub upload_documents: &'static [DocumentType],
+    // Overrides the face match service threshold when set
+    pub threshold: Option<f64>,
+    pub strategy: ProcessingStrategy,
+    // Whether GET /submissions/status accepts this type
+    pub status_queryable: bool,
+}
+
+// Single place to declare a submission type; the service and controllers iterate this
+static REGISTRY: &[SubmissionTypeConfig] = &[
+    SubmissionTypeConfig {
+        submission_type: SubmissionType::Kyc,
+        upload_documents: &[DocumentType::Ktp, DocumentType::Selfie],
+        threshold: None,
+        strategy: ProcessingStrategy::CompareWithDocument(DocumentType::Nfc),
+        status_queryable: true,
+    },
+    // Same flow as KYC with a different identity document; the selfie is still compared
+    // against the chip photo
+    SubmissionTypeConfig {
+        submission_type: SubmissionType::KycPassport,
+        upload_documents: &[DocumentType::Passport, DocumentType::Selfie],
+        threshold: None,
+        strategy: ProcessingStrategy::CompareWithDocument(DocumentType::Nfc),
+        status_queryable: true,
+    },
+    SubmissionTypeConfig {
+        submission_type: SubmissionType::KycDrivingLicense,
+        upload_documents: &[DocumentType::DrivingLicense, DocumentType::Selfie],
+        threshold: None,
+        strategy: ProcessingStrategy::CompareWithDocument(DocumentType::Nfc),
+        status_queryable: true,
+    },
+    SubmissionTypeConfig {
+        submission_type: SubmissionType::OnDemand,
+        upload_documents: &[DocumentType::Selfie],
+        threshold: None,
+        strategy: ProcessingStrategy::CompareWithApprovedSelfie,
+        status_queryable: false,
+    },
+];
+
+#[cfg(test)]
+thread_local! {
+    // Configs a test registered on its own thread, taking precedence over REGISTRY
+    static REGISTERED: std::cell::RefCell<Vec<&'static SubmissionTypeConfig>> = const { std::cell::RefCell::new(Vec::new()) };
+}
+
+// Lets a test exercise the flow with a type config that isn't shipped
+#[cfg(test)]
+pub(crate) fn register(config: SubmissionTypeConfig) {
+    REGISTERED.with(|registered| registered.borrow_mut().push(Box::leak(Box::new(config))));
+}
+
+pub fn find(name: &str) -> Option<&'static SubmissionTypeConfig> {
+    #[cfg(test)]
+    if let Some(config) = REGISTERED.with(|registered| {
+        registered.borrow().iter().rev().find(|config| config.submission_type.to_string() == name).copied()
+    }) {
+        return Some(config);
+    }
+
+    REGISTRY
+        .iter()
+        .find(|config| config.submission_type.to_string() == name)
+}
+
+pub fn get(submission_type: &SubmissionType) -> &'static SubmissionTypeConfig {
+    find(&submission_type.to_string()).expect("every SubmissionType must be registered")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_type_is_found_by_its_wire_name() {
+        for name in ["KYC", "KYC_PASSPORT", "KYC_DRIVING_LICENSE", "ON_DEMAND"] {
+            let submission_type: SubmissionType = serde_json::from_value(serde_json::json!(name)).unwrap();
+            assert_eq!(submission_type.to_string(), name);
+            assert_eq!(serde_json::to_value(&submission_type).unwrap(), name);
+
+            let config = find(name).unwrap_or_else(|| panic!("{name} is not registered"));
+            assert_eq!(config.submission_type.to_string(), name);
+            assert_eq!(name.parse::<SubmissionType>().unwrap().to_string(), name);
+        }
+        assert!(find("KycPassport").is_none());
+    }
+
+    #[test]
+    fn identity_document_types_upload_their_own_document() {
+        assert_eq!(get(&SubmissionType::KycPassport).upload_documents, [DocumentType::Passport, DocumentType::Selfie]);
+        assert_eq!(
+            get(&SubmissionType::KycDrivingLicense).upload_documents,
+            [DocumentType::DrivingLicense, DocumentType::Selfie]
+        );
+    }
+}
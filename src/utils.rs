@@ -5,6 +5,8 @@ use serde::{Deserialize, Serialize};
 pub struct Claims {
     pub sub: i32,
     pub exp: i64,
+    // Unique per token so it can be revoked on its own
+    pub jti: String,
 }
 
 pub fn validate_token(token: &str, secret: &str) -> Result<Claims, jsonwebtoken::errors::Error> {